@@ -0,0 +1,83 @@
+//! Drives one LED per player from the buzzer engine using [`DemuxOutput`].
+//!
+//! Run with `cargo run -p core --example led_demo`.
+
+use core::adapter::{self, DemuxOutput, GameInput, PlayerOutput, TimeSource};
+use core::game::{BuzzerGame, Config, PlayerId, player_mask};
+
+const PLAYERS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Led {
+    Off,
+    /// Solid on: this player has the floor.
+    On,
+    /// Blinking: locked out for the rest of the round.
+    Blink,
+}
+
+struct Leds([Led; PLAYERS]);
+
+impl PlayerOutput for Leds {
+    fn accepted(&mut self, player: PlayerId, deadline_ms: u64) {
+        println!("player {player} has the floor until {deadline_ms}ms");
+        self.0[player] = Led::On;
+    }
+
+    fn locked(&mut self, player: PlayerId) {
+        self.0[player] = Led::Blink;
+    }
+
+    fn round_reset(&mut self) {
+        self.0 = [Led::Off; PLAYERS];
+    }
+}
+
+/// Button presses queued by a (pretend) GPIO interrupt, with when each was made.
+struct Buttons(Vec<(PlayerId, u64)>);
+
+impl GameInput for Buttons {
+    fn next_buzz(&mut self) -> Option<(PlayerId, u64)> {
+        self.0.pop()
+    }
+
+    fn active_players(&self) -> u128 {
+        player_mask(0..PLAYERS)
+    }
+}
+
+struct Clock(u64);
+
+impl TimeSource for Clock {
+    fn now_ms(&self) -> u64 {
+        self.0
+    }
+}
+
+fn main() {
+    let mut game = BuzzerGame::new(Config {
+        answer_window_in_ms: 3000,
+        max_players: PLAYERS,
+    });
+    let mut buttons = Buttons(Vec::new());
+    let mut leds = DemuxOutput::new(Leds([Led::Off; PLAYERS]), PLAYERS);
+    let mut clock = Clock(0);
+
+    adapter::start_round(&mut game, buttons.active_players(), &mut leds);
+
+    // Player 2 presses first and runs out of time.
+    buttons.0.push((2, clock.0));
+    adapter::step(&mut game, &clock, &mut buttons, &mut leds);
+    clock.0 = 3000;
+    adapter::step(&mut game, &clock, &mut buttons, &mut leds);
+    leds.sync(&game);
+    println!("{:?}", leds.inner().0);
+
+    // Player 0 answers wrong; the host moves on.
+    buttons.0.push((0, clock.0));
+    adapter::step(&mut game, &clock, &mut buttons, &mut leds);
+    println!("{:?}", leds.inner().0);
+    adapter::continue_round(&mut game, &mut leds);
+    leds.sync(&game);
+    println!("{:?}", leds.inner().0);
+}
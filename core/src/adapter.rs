@@ -9,10 +9,18 @@ pub trait TimeSource {
     fn now_ms(&self) -> u64;
 }
 
+impl<T: TimeSource + ?Sized> TimeSource for &T {
+    fn now_ms(&self) -> u64 {
+        (**self).now_ms()
+    }
+}
+
 pub trait GameInput {
-    /// Return the next buzzing player, or None if no pending buzzes.
-    fn next_buzz(&mut self) -> Option<PlayerId>;
-    fn current_player_count(&self) -> PlayerId;
+    /// Return the next buzzing player and when they buzzed, on the game's
+    /// [`TimeSource`], or None if no pending buzzes.
+    fn next_buzz(&mut self) -> Option<(PlayerId, u64)>;
+    /// Bitmask of the players currently seated (see [`player_mask`](crate::game::player_mask)).
+    fn active_players(&self) -> u128;
 }
 
 /// Output sink for game actions (LEDs, sounds, UI updates, etc.).
@@ -30,19 +38,31 @@ where
 {
     let now = time.now_ms();
 
-    while let Some(player) = input.next_buzz() {
-        let event = game.buzz(player, now);
+    // Each buzz counts from when it was made, not from this step, so buzzes
+    // between two steps are not all treated as simultaneous.
+    while let Some((player, buzzed_at)) = input.next_buzz() {
+        let event = game.buzz(player, buzzed_at.min(now));
         output.on_event(event);
     }
 
-    if let Some(event) = game.tick(now) {
-        output.on_event(event);
+    game.advance_to(now, &mut Emit(output));
+}
+
+/// Forwards events collected by [`BuzzerGame::advance_to`] straight to an output.
+struct Emit<'a, O: GameOutput>(&'a mut O);
+
+impl<O: GameOutput> Extend<OutputEvent> for Emit<'_, O> {
+    fn extend<T: IntoIterator<Item = OutputEvent>>(&mut self, events: T) {
+        for event in events {
+            self.0.on_event(event);
+        }
     }
 }
 
-/// Reset the game state (clears locks and returns to idle).
-pub fn start_round<I: GameInput, O: GameOutput>(game: &mut BuzzerGame, input: &I, output: &mut O) {
-    game.set_curr_player_id(input.current_player_count());
+/// Reset the game state (clears locks and returns to idle). Only players in
+/// `active_players` may buzz during the round.
+pub fn start_round<O: GameOutput>(game: &mut BuzzerGame, active_players: u128, output: &mut O) {
+    game.set_active_players(active_players);
     let event = game.start_round();
     output.on_event(event);
 }
@@ -51,3 +71,430 @@ pub fn continue_round<O: GameOutput>(game: &mut BuzzerGame, output: &mut O) {
     let event = game.continue_round();
     output.on_event(event);
 }
+
+/// Award the answering player; no-op when nobody holds the floor.
+pub fn correct_answer<O: GameOutput>(game: &mut BuzzerGame, output: &mut O) {
+    if let Some(event) = game.correct_answer() {
+        output.on_event(event);
+    }
+}
+
+/// Counters collected by [`MetricsOutput`]. Durations are whole milliseconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub accepted: u64,
+    pub rejected: u64,
+    pub timed_out: u64,
+    pub correct: u64,
+    pub round_started: u64,
+    pub round_continued: u64,
+    /// Answers that ended (timed out, correct, or continued past) after an `Accepted`.
+    pub answers: u64,
+    pub min_answer_ms: Option<u64>,
+    pub max_answer_ms: Option<u64>,
+    pub total_answer_ms: u64,
+}
+
+impl MetricsSnapshot {
+    /// Mean time from `Accepted` to the answer ending, if any answer ended.
+    pub fn mean_answer_ms(&self) -> Option<u64> {
+        self.total_answer_ms.checked_div(self.answers)
+    }
+}
+
+/// Wraps another [`GameOutput`], counting events by type and timing how long
+/// each accepted player held the floor, then forwards every event unchanged.
+pub struct MetricsOutput<O: GameOutput, T: TimeSource> {
+    inner: O,
+    time: T,
+    answering_since_ms: Option<u64>,
+    metrics: MetricsSnapshot,
+}
+
+impl<O: GameOutput, T: TimeSource> MetricsOutput<O, T> {
+    pub fn new(inner: O, time: T) -> Self {
+        Self {
+            inner,
+            time,
+            answering_since_ms: None,
+            metrics: MetricsSnapshot::default(),
+        }
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.metrics
+    }
+
+    pub fn inner(&self) -> &O {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> O {
+        self.inner
+    }
+
+    fn finish_answer(&mut self) {
+        let Some(since) = self.answering_since_ms.take() else {
+            return;
+        };
+        let elapsed = self.time.now_ms().saturating_sub(since);
+        let metrics = &mut self.metrics;
+        metrics.answers += 1;
+        metrics.total_answer_ms += elapsed;
+        metrics.min_answer_ms = Some(metrics.min_answer_ms.map_or(elapsed, |m| m.min(elapsed)));
+        metrics.max_answer_ms = Some(metrics.max_answer_ms.map_or(elapsed, |m| m.max(elapsed)));
+    }
+}
+
+impl<O: GameOutput, T: TimeSource> GameOutput for MetricsOutput<O, T> {
+    fn on_event(&mut self, event: OutputEvent) {
+        match event {
+            OutputEvent::Accepted(..) => {
+                self.metrics.accepted += 1;
+                self.answering_since_ms = Some(self.time.now_ms());
+            }
+            OutputEvent::Rejected(_) => self.metrics.rejected += 1,
+            OutputEvent::TimedOut(_) => {
+                self.metrics.timed_out += 1;
+                self.finish_answer();
+            }
+            OutputEvent::Correct(_) => {
+                self.metrics.correct += 1;
+                self.finish_answer();
+            }
+            OutputEvent::RoundStarted => {
+                self.metrics.round_started += 1;
+                // A restart abandons the answer rather than resolving it.
+                self.answering_since_ms = None;
+            }
+            OutputEvent::RoundContinued => {
+                self.metrics.round_continued += 1;
+                self.finish_answer();
+            }
+        }
+        self.inner.on_event(event);
+    }
+}
+
+/// Per-player output callbacks, for hardware that drives one indicator per player
+/// (e.g. an LED per buzzer). Wrap an implementation in [`DemuxOutput`].
+pub trait PlayerOutput {
+    /// `player` won the buzz and must answer before `deadline_ms`.
+    fn accepted(&mut self, player: PlayerId, deadline_ms: u64);
+    /// `player` became locked out for the rest of the round.
+    fn locked(&mut self, player: PlayerId);
+    /// A new round started; every player is unlocked.
+    fn round_reset(&mut self);
+    /// `player` answered correctly, ending the round.
+    fn correct(&mut self, _player: PlayerId) {}
+    /// `player` buzzed while buzzing was closed to them.
+    fn rejected(&mut self, _player: PlayerId) {}
+    /// The answering player's window ended and buzzing reopened.
+    fn round_continued(&mut self) {}
+}
+
+/// Adapts a [`PlayerOutput`] to [`GameOutput`] by translating global events into
+/// per-player calls. Lock-outs are not carried by every event (e.g. continuing a
+/// round locks the answering player silently), so call [`DemuxOutput::sync`]
+/// after each `step`/`continue_round` to emit `locked` for newly locked players.
+pub struct DemuxOutput<P: PlayerOutput> {
+    inner: P,
+    player_count: PlayerId,
+    reported_locks: u128,
+}
+
+impl<P: PlayerOutput> DemuxOutput<P> {
+    /// `player_count` bounds which ids are reported; ids at or above it are
+    /// unused seats and never produce callbacks.
+    pub fn new(inner: P, player_count: PlayerId) -> Self {
+        Self {
+            inner,
+            player_count,
+            reported_locks: 0,
+        }
+    }
+
+    /// Emit `locked` for every player locked out since the last sync.
+    pub fn sync(&mut self, game: &BuzzerGame) {
+        let locks = game.locked_out_players() & self.seat_mask();
+        let newly_locked = locks & !self.reported_locks;
+        self.reported_locks = locks;
+        for player in 0..self.player_count.min(128) {
+            if newly_locked & (1u128 << player) != 0 {
+                self.inner.locked(player);
+            }
+        }
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+
+    fn seat_mask(&self) -> u128 {
+        if self.player_count >= 128 {
+            u128::MAX
+        } else {
+            (1u128 << self.player_count) - 1
+        }
+    }
+}
+
+impl<P: PlayerOutput> GameOutput for DemuxOutput<P> {
+    fn on_event(&mut self, event: OutputEvent) {
+        match event {
+            OutputEvent::Accepted(player, deadline_ms) => self.inner.accepted(player, deadline_ms),
+            OutputEvent::Rejected(player) => self.inner.rejected(player),
+            OutputEvent::TimedOut(player) => {
+                if player < self.player_count && self.reported_locks & (1u128 << player) == 0 {
+                    self.reported_locks |= 1u128 << player;
+                    self.inner.locked(player);
+                }
+            }
+            OutputEvent::Correct(player) => self.inner.correct(player),
+            OutputEvent::RoundStarted => {
+                self.reported_locks = 0;
+                self.inner.round_reset();
+            }
+            OutputEvent::RoundContinued => self.inner.round_continued(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::cell::Cell;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::game::{Config, player_mask};
+
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Accepted(PlayerId, u64),
+        Locked(PlayerId),
+        Reset,
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        calls: Vec<Call>,
+    }
+
+    impl PlayerOutput for Recorder {
+        fn accepted(&mut self, player: PlayerId, deadline_ms: u64) {
+            self.calls.push(Call::Accepted(player, deadline_ms));
+        }
+
+        fn locked(&mut self, player: PlayerId) {
+            self.calls.push(Call::Locked(player));
+        }
+
+        fn round_reset(&mut self) {
+            self.calls.push(Call::Reset);
+        }
+    }
+
+    /// Pending buzzes with when each was made, taken from the back.
+    struct Buzzes(Vec<(PlayerId, u64)>, u128);
+
+    impl GameInput for Buzzes {
+        fn next_buzz(&mut self) -> Option<(PlayerId, u64)> {
+            self.0.pop()
+        }
+
+        fn active_players(&self) -> u128 {
+            self.1
+        }
+    }
+
+    struct FixedTime(u64);
+
+    impl TimeSource for FixedTime {
+        fn now_ms(&self) -> u64 {
+            self.0
+        }
+    }
+
+    struct ManualTime(Cell<u64>);
+
+    impl TimeSource for ManualTime {
+        fn now_ms(&self) -> u64 {
+            self.0.get()
+        }
+    }
+
+    struct Discard;
+
+    impl GameOutput for Discard {
+        fn on_event(&mut self, _event: OutputEvent) {}
+    }
+
+    fn setup(players: PlayerId) -> (BuzzerGame, Buzzes, DemuxOutput<Recorder>) {
+        let mut game = BuzzerGame::new(Config {
+            answer_window_in_ms: 100,
+            max_players: players,
+        });
+        let input = Buzzes(Vec::new(), player_mask(0..players));
+        let mut output = DemuxOutput::new(Recorder::default(), players);
+        start_round(&mut game, input.active_players(), &mut output);
+        output.sync(&game);
+        (game, input, output)
+    }
+
+    #[test]
+    fn reports_each_lock_once_when_several_players_lock_in_one_round() {
+        let (mut game, mut input, mut output) = setup(4);
+
+        // Player 1 times out.
+        input.0.push((1, 0));
+        step(&mut game, &FixedTime(0), &mut input, &mut output);
+        step(&mut game, &FixedTime(100), &mut input, &mut output);
+        output.sync(&game);
+
+        // Player 3 answers wrong and the admin continues.
+        input.0.push((3, 150));
+        step(&mut game, &FixedTime(150), &mut input, &mut output);
+        continue_round(&mut game, &mut output);
+        output.sync(&game);
+        output.sync(&game);
+
+        assert_eq!(
+            output.inner().calls,
+            [
+                Call::Reset,
+                Call::Accepted(1, 100),
+                Call::Locked(1),
+                Call::Accepted(3, 250),
+                Call::Locked(3),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_reset_clears_reported_locks() {
+        let (mut game, mut input, mut output) = setup(2);
+        input.0.push((0, 0));
+        step(&mut game, &FixedTime(0), &mut input, &mut output);
+        continue_round(&mut game, &mut output);
+        output.sync(&game);
+
+        start_round(&mut game, input.active_players(), &mut output);
+        output.sync(&game);
+        input.0.push((0, 10));
+        step(&mut game, &FixedTime(10), &mut input, &mut output);
+        step(&mut game, &FixedTime(110), &mut input, &mut output);
+        output.sync(&game);
+
+        assert_eq!(
+            output.into_inner().calls,
+            [
+                Call::Reset,
+                Call::Accepted(0, 100),
+                Call::Locked(0),
+                Call::Reset,
+                Call::Accepted(0, 110),
+                Call::Locked(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn buzzes_keep_their_own_time_between_ticks() {
+        let (mut game, mut input, mut output) = setup(3);
+
+        // Stepped every 10ms. Player 0 buzzed at 1, player 1 at 9: the first
+        // buzz wins and its answer counts from 1, not from the tick at 10.
+        input.0.extend([(1, 9), (0, 1)]);
+        for now in (10..=100).step_by(10) {
+            step(&mut game, &FixedTime(now), &mut input, &mut output);
+        }
+        assert_eq!(game.answering_player(), Some(0));
+
+        // Player 2 buzzed at 105, after the deadline at 101 but before the
+        // step that times player 0 out: buzzing had not reopened yet.
+        input.0.push((2, 105));
+        step(&mut game, &FixedTime(110), &mut input, &mut output);
+        assert_eq!(game.answering_player(), None);
+        input.0.push((2, 115));
+        step(&mut game, &FixedTime(120), &mut input, &mut output);
+
+        assert_eq!(
+            output.inner().calls,
+            [
+                Call::Reset,
+                Call::Accepted(0, 101),
+                Call::Locked(0),
+                Call::Accepted(2, 215),
+            ]
+        );
+    }
+
+    #[test]
+    fn ids_past_player_count_are_never_reported() {
+        // The engine locks every id outside the roster.
+        let (game, _, mut output) = setup(2);
+        assert_ne!(game.locked_out_players(), 0);
+        output.sync(&game);
+        assert_eq!(output.inner().calls, [Call::Reset]);
+    }
+
+    #[test]
+    fn metrics_match_scripted_game() {
+        let time = ManualTime(Cell::new(0));
+        let mut game = BuzzerGame::new(Config {
+            answer_window_in_ms: 1000,
+            max_players: 3,
+        });
+        let mut input = Buzzes(Vec::new(), player_mask(0..3));
+        let mut output = MetricsOutput::new(Discard, &time);
+
+        start_round(&mut game, input.active_players(), &mut output);
+
+        // Player 0 buzzes at t=100; player 1 is rejected in the same step.
+        time.0.set(100);
+        input.0.extend([(1, 100), (0, 100)]);
+        step(&mut game, &time, &mut input, &mut output);
+        // Admin moves on after 400ms.
+        time.0.set(500);
+        continue_round(&mut game, &mut output);
+
+        // Player 1 buzzes and times out after the full window.
+        time.0.set(600);
+        input.0.push((1, 600));
+        step(&mut game, &time, &mut input, &mut output);
+        time.0.set(1600);
+        step(&mut game, &time, &mut input, &mut output);
+
+        // Player 2 buzzes but the round restarts before they answer.
+        input.0.push((2, 1600));
+        step(&mut game, &time, &mut input, &mut output);
+        start_round(&mut game, input.active_players(), &mut output);
+        // Continuing an idle round records no answer.
+        continue_round(&mut game, &mut output);
+
+        let snapshot = output.snapshot();
+        assert_eq!(
+            snapshot,
+            MetricsSnapshot {
+                accepted: 3,
+                rejected: 1,
+                timed_out: 1,
+                correct: 0,
+                round_started: 2,
+                round_continued: 2,
+                answers: 2,
+                min_answer_ms: Some(400),
+                max_answer_ms: Some(1000),
+                total_answer_ms: 1400,
+            }
+        );
+        assert_eq!(snapshot.mean_answer_ms(), Some(700));
+        assert_eq!(MetricsSnapshot::default().mean_answer_ms(), None);
+    }
+}
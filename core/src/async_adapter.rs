@@ -0,0 +1,105 @@
+//! Async counterparts of the [`adapter`](crate::adapter) traits, enabled with the
+//! `async` feature. They let an executor-driven platform await buzzes and output
+//! sinks (e.g. a bounded channel) instead of polling. The sync traits are unchanged.
+
+// Auto-trait bounds on the returned futures are left to the implementor; the
+// engine is single-task and never needs to send these futures across threads.
+#![allow(async_fn_in_trait)]
+
+use crate::adapter::TimeSource;
+use crate::game::{BuzzerGame, OutputEvent, PlayerId};
+
+pub trait GameInputAsync {
+    /// Wait for the next buzzing player and when they buzzed, or None if the
+    /// input is closed.
+    async fn next_buzz(&mut self) -> Option<(PlayerId, u64)>;
+    /// Bitmask of the players currently seated.
+    fn active_players(&self) -> u128;
+}
+
+/// Async output sink for game actions.
+pub trait GameOutputAsync {
+    async fn on_event(&mut self, event: OutputEvent);
+}
+
+/// Async counterpart of [`adapter::step`](crate::adapter::step): awaits one buzz,
+/// feeds it to the game, then ticks. Pair with [`tick_async`] on a timer so
+/// deadlines still fire while no buzzes arrive.
+pub async fn step_async<T, I, O>(game: &mut BuzzerGame, time: &T, input: &mut I, output: &mut O)
+where
+    T: TimeSource,
+    I: GameInputAsync,
+    O: GameOutputAsync,
+{
+    if let Some((player, buzzed_at)) = input.next_buzz().await {
+        let event = game.buzz(player, buzzed_at.min(time.now_ms()));
+        output.on_event(event).await;
+    }
+
+    tick_async(game, time, output).await;
+}
+
+/// Advance the game clock without waiting for input.
+pub async fn tick_async<T, O>(game: &mut BuzzerGame, time: &T, output: &mut O)
+where
+    T: TimeSource,
+    O: GameOutputAsync,
+{
+    let mut due = Due::default();
+    game.advance_to(time.now_ms(), &mut due);
+    for event in due.events.into_iter().flatten() {
+        output.on_event(event).await;
+    }
+}
+
+/// How many events one [`BuzzerGame::advance_to`] may yield; the answer
+/// deadline is the only timed transition so far.
+const MAX_DUE: usize = 4;
+
+/// Collects what [`BuzzerGame::advance_to`] finds due, so the events can be
+/// awaited one by one afterwards.
+struct Due {
+    events: [Option<OutputEvent>; MAX_DUE],
+    len: usize,
+}
+
+impl Default for Due {
+    fn default() -> Self {
+        Self {
+            events: [const { None }; MAX_DUE],
+            len: 0,
+        }
+    }
+}
+
+impl Extend<OutputEvent> for Due {
+    fn extend<T: IntoIterator<Item = OutputEvent>>(&mut self, events: T) {
+        for event in events {
+            assert!(self.len < MAX_DUE, "more events due at once than MAX_DUE");
+            self.events[self.len] = Some(event);
+            self.len += 1;
+        }
+    }
+}
+
+/// Reset the game state (clears locks and returns to idle).
+pub async fn start_round_async<O: GameOutputAsync>(
+    game: &mut BuzzerGame,
+    active_players: u128,
+    output: &mut O,
+) {
+    game.set_active_players(active_players);
+    let event = game.start_round();
+    output.on_event(event).await;
+}
+
+pub async fn continue_round_async<O: GameOutputAsync>(game: &mut BuzzerGame, output: &mut O) {
+    let event = game.continue_round();
+    output.on_event(event).await;
+}
+
+pub async fn correct_answer_async<O: GameOutputAsync>(game: &mut BuzzerGame, output: &mut O) {
+    if let Some(event) = game.correct_answer() {
+        output.on_event(event).await;
+    }
+}
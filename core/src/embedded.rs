@@ -0,0 +1,286 @@
+//! Reference [`embedded-hal`](embedded_hal) adapter, enabled with the
+//! `embedded-hal` feature: one push button and one LED per player.
+//!
+//! A typical main loop reads the clock once, then calls
+//! [`ButtonInput::scan`], [`adapter::step`](crate::adapter::step) and
+//! [`LedOutput::update`] with that time.
+
+use embedded_hal::digital::{InputPin, OutputPin, PinState};
+
+use crate::adapter::{GameInput, GameOutput, TimeSource};
+use crate::game::{MAX_PLAYER_ID, OutputEvent, PlayerId};
+
+/// A free-running hardware counter (e.g. a timer peripheral or SysTick count).
+pub trait MonotonicCounter {
+    fn ticks(&self) -> u64;
+}
+
+/// [`TimeSource`] over a [`MonotonicCounter`] ticking `ticks_per_ms` times per
+/// millisecond.
+pub struct CounterTime<C: MonotonicCounter> {
+    counter: C,
+    ticks_per_ms: u64,
+}
+
+impl<C: MonotonicCounter> CounterTime<C> {
+    pub fn new(counter: C, ticks_per_ms: u64) -> Self {
+        Self {
+            counter,
+            ticks_per_ms: ticks_per_ms.max(1),
+        }
+    }
+}
+
+impl<C: MonotonicCounter> TimeSource for CounterTime<C> {
+    fn now_ms(&self) -> u64 {
+        self.counter.ticks() / self.ticks_per_ms
+    }
+}
+
+/// Scans `N` buttons (player `i` on pin `i`) with per-pin debouncing. A press is
+/// queued once its pin has read the same level for `debounce_ms`.
+pub struct ButtonInput<P: InputPin, const N: usize> {
+    pins: [P; N],
+    active_low: bool,
+    debounce_ms: u64,
+    raw: [bool; N],
+    changed_at_ms: [u64; N],
+    pressed: [bool; N],
+    pending: u128,
+    /// When each pending press was queued.
+    pressed_at_ms: [u64; N],
+}
+
+impl<P: InputPin, const N: usize> ButtonInput<P, N> {
+    /// `active_low` is the usual wiring with a pull-up: pressed reads low.
+    pub fn new(pins: [P; N], active_low: bool, debounce_ms: u64) -> Self {
+        assert!(N <= MAX_PLAYER_ID + 1, "at most 128 buttons");
+        Self {
+            pins,
+            active_low,
+            debounce_ms,
+            raw: [false; N],
+            changed_at_ms: [0; N],
+            pressed: [false; N],
+            pending: 0,
+            pressed_at_ms: [0; N],
+        }
+    }
+
+    /// Samples every pin. Pins that fail to read count as released.
+    pub fn scan(&mut self, now_ms: u64) {
+        for i in 0..N {
+            let level = self.pins[i].is_high().unwrap_or(self.active_low);
+            let reading = level != self.active_low;
+            if reading != self.raw[i] {
+                self.raw[i] = reading;
+                self.changed_at_ms[i] = now_ms;
+                continue;
+            }
+            let settled = now_ms.saturating_sub(self.changed_at_ms[i]) >= self.debounce_ms;
+            if settled && reading != self.pressed[i] {
+                self.pressed[i] = reading;
+                if reading {
+                    self.pending |= 1u128 << i;
+                    self.pressed_at_ms[i] = now_ms;
+                }
+            }
+        }
+    }
+
+    pub fn release(self) -> [P; N] {
+        self.pins
+    }
+}
+
+impl<P: InputPin, const N: usize> GameInput for ButtonInput<P, N> {
+    /// Presses are stamped with the scan that settled them and reported
+    /// earliest first, lowest pin first within a scan.
+    fn next_buzz(&mut self) -> Option<(PlayerId, u64)> {
+        let player = (0..N)
+            .filter(|&i| self.pending & (1u128 << i) != 0)
+            .min_by_key(|&i| self.pressed_at_ms[i])?;
+        self.pending &= !(1u128 << player);
+        Some((player, self.pressed_at_ms[player]))
+    }
+
+    fn active_players(&self) -> u128 {
+        if N >= 128 {
+            u128::MAX
+        } else {
+            (1u128 << N) - 1
+        }
+    }
+}
+
+/// Drives one LED per player (player `i` on pin `i`): solid while a player holds
+/// the floor or after a correct answer, blinking after they time out.
+pub struct LedOutput<P: OutputPin, const N: usize> {
+    pins: [P; N],
+    blink_period_ms: u64,
+    blinking: u128,
+}
+
+impl<P: OutputPin, const N: usize> LedOutput<P, N> {
+    pub fn new(pins: [P; N], blink_period_ms: u64) -> Self {
+        Self {
+            pins,
+            blink_period_ms: blink_period_ms.max(1),
+            blinking: 0,
+        }
+    }
+
+    /// Advances blinking LEDs; call every loop iteration.
+    pub fn update(&mut self, now_ms: u64) {
+        let on = (now_ms / self.blink_period_ms).is_multiple_of(2);
+        for i in 0..N {
+            if self.blinking & (1u128 << i) != 0 {
+                self.set(i, on);
+            }
+        }
+    }
+
+    pub fn release(self) -> [P; N] {
+        self.pins
+    }
+
+    fn set(&mut self, player: PlayerId, on: bool) {
+        if let Some(pin) = self.pins.get_mut(player) {
+            // Nothing sensible to do about a failed LED write mid-game.
+            let _ = pin.set_state(PinState::from(on));
+        }
+    }
+
+    fn all_off(&mut self) {
+        for i in 0..N {
+            self.set(i, false);
+        }
+    }
+}
+
+impl<P: OutputPin, const N: usize> GameOutput for LedOutput<P, N> {
+    fn on_event(&mut self, event: OutputEvent) {
+        match event {
+            OutputEvent::Accepted(player, _) | OutputEvent::Correct(player) => {
+                self.set(player, true);
+            }
+            OutputEvent::TimedOut(player) if player < N => {
+                self.blinking |= 1u128 << player;
+            }
+            OutputEvent::RoundStarted => {
+                self.blinking = 0;
+                self.all_off();
+            }
+            OutputEvent::RoundContinued => {
+                for i in 0..N {
+                    if self.blinking & (1u128 << i) == 0 {
+                        self.set(i, false);
+                    }
+                }
+            }
+            OutputEvent::Rejected(_) | OutputEvent::TimedOut(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::cell::Cell;
+    use embedded_hal_mock::eh1::digital::{Mock, State, Transaction};
+    use std::vec;
+
+    use super::*;
+    use crate::adapter::step;
+    use crate::game::{BuzzerGame, Config};
+
+    struct Counter(Cell<u64>);
+
+    impl MonotonicCounter for &Counter {
+        fn ticks(&self) -> u64 {
+            self.0.get()
+        }
+    }
+
+    struct Recorder(std::vec::Vec<OutputEvent>);
+
+    impl GameOutput for Recorder {
+        fn on_event(&mut self, event: OutputEvent) {
+            self.0.push(event);
+        }
+    }
+
+    fn reads(states: &[State]) -> Mock {
+        let transactions: std::vec::Vec<_> = states.iter().map(|s| Transaction::get(*s)).collect();
+        Mock::new(&transactions)
+    }
+
+    #[test]
+    fn counter_time_converts_ticks_to_ms() {
+        let counter = Counter(Cell::new(48_000));
+        let time = CounterTime::new(&counter, 16);
+        assert_eq!(time.now_ms(), 3000);
+    }
+
+    #[test]
+    fn debounced_press_is_accepted_for_the_right_player() {
+        use State::{High, Low};
+        // Active-low buttons sampled at t = 0, 5, 10, 15.
+        // Player 0 bounces (low/high/low) and settles too late; player 1 holds low.
+        let pin0 = reads(&[High, Low, High, Low]);
+        let pin1 = reads(&[High, Low, Low, Low]);
+        let mut buttons = ButtonInput::new([pin0, pin1], true, 10);
+
+        let mut game = BuzzerGame::new(Config {
+            answer_window_in_ms: 1000,
+            max_players: 2,
+        });
+        let mut output = Recorder(vec![]);
+        crate::adapter::start_round(&mut game, buttons.active_players(), &mut output);
+
+        let counter = Counter(Cell::new(0));
+        let time = CounterTime::new(&counter, 1);
+        for now in [0, 5, 10, 15] {
+            counter.0.set(now);
+            buttons.scan(now);
+            step(&mut game, &time, &mut buttons, &mut output);
+        }
+
+        assert!(matches!(
+            output.0[..],
+            [OutputEvent::RoundStarted, OutputEvent::Accepted(1, 1015)]
+        ));
+        for mut pin in buttons.release() {
+            pin.done();
+        }
+    }
+
+    #[test]
+    fn leds_light_the_winner_and_blink_on_timeout() {
+        use State::{High, Low};
+        let pin0 = Mock::new(&[
+            Transaction::set(Low),  // round start
+            Transaction::set(High), // accepted
+            Transaction::set(Low),  // blink off at t=250
+            Transaction::set(High), // blink on at t=500
+            Transaction::set(Low),  // next round
+        ]);
+        let pin1 = Mock::new(&[
+            Transaction::set(Low), // round start
+            Transaction::set(Low), // next round
+        ]);
+        let mut leds = LedOutput::new([pin0, pin1], 250);
+
+        leds.on_event(OutputEvent::RoundStarted);
+        leds.on_event(OutputEvent::Accepted(0, 1000));
+        leds.on_event(OutputEvent::TimedOut(0));
+        leds.update(250);
+        leds.update(500);
+        leds.on_event(OutputEvent::RoundStarted);
+
+        for mut pin in leds.release() {
+            pin.done();
+        }
+    }
+}
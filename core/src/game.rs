@@ -1,9 +1,25 @@
+#[cfg(any(test, feature = "alloc"))]
+use alloc::{vec, vec::Vec};
+
 pub type PlayerId = usize;
 
 pub const MAX_PLAYER_ID: PlayerId = 127;
+/// Players a bitmask game can seat: ids `0..=MAX_PLAYER_ID`.
+const MASK_SEATS: usize = MAX_PLAYER_ID + 1;
+
+/// Builds an active-player bitmask; ids above [`MAX_PLAYER_ID`] are ignored.
+pub fn player_mask<I: IntoIterator<Item = PlayerId>>(players: I) -> u128 {
+    players
+        .into_iter()
+        .filter(|&player| player <= MAX_PLAYER_ID)
+        .fold(0, |mask, player| mask | (1u128 << player))
+}
 
 pub struct Config {
     pub answer_window_in_ms: u64,
+    /// Seats to track lockouts for. Up to 128 fit the `u128` masks; more need
+    /// the `alloc` feature, without which the game still seats only 128.
+    pub max_players: usize,
 }
 
 #[derive(PartialEq, Eq)]
@@ -13,18 +29,159 @@ enum Phase {
         player: PlayerId,
         deadline_in_ms: u64,
     },
+    /// A correct answer ended the round; buzzing stays closed until the next one.
+    Resolved,
 }
 
 struct State {
     phase: Phase,
-    locked_out_players: u128, // only 128 players allowed
-    curr_player_id: PlayerId,
+    lockouts: Lockouts,
+    /// Deadline of the last answer that timed out: the floor was held until
+    /// then, whenever the timeout was noticed.
+    floor_freed_in_ms: u64,
+}
+
+/// Who is seated this round and who is locked out of it. Players outside the
+/// seats, or not seated this round, are always locked out.
+enum Lockouts {
+    /// Bit `n` stands for player `n`.
+    Mask { locked_out: u128, active: u128 },
+    /// One flag per seat, for games too big for a mask.
+    #[cfg(any(test, feature = "alloc"))]
+    Spill {
+        locked_out: Vec<bool>,
+        active: Vec<bool>,
+    },
+}
+
+impl Lockouts {
+    #[cfg(any(test, feature = "alloc"))]
+    fn new(max_players: usize) -> Self {
+        if max_players <= MASK_SEATS {
+            return Self::mask();
+        }
+        Self::Spill {
+            locked_out: vec![false; max_players],
+            active: vec![false; max_players],
+        }
+    }
+
+    #[cfg(not(any(test, feature = "alloc")))]
+    fn new(_max_players: usize) -> Self {
+        Self::mask()
+    }
+
+    fn mask() -> Self {
+        Self::Mask {
+            locked_out: 0,
+            active: 0,
+        }
+    }
+
+    fn seats(&self) -> usize {
+        match self {
+            Self::Mask { .. } => MASK_SEATS,
+            #[cfg(any(test, feature = "alloc"))]
+            Self::Spill { active, .. } => active.len(),
+        }
+    }
+
+    fn is_locked_out(&self, player: PlayerId) -> bool {
+        if player >= self.seats() {
+            return true;
+        }
+        match self {
+            Self::Mask { locked_out, active } => (locked_out | !active) & (1u128 << player) != 0,
+            #[cfg(any(test, feature = "alloc"))]
+            Self::Spill { locked_out, active } => locked_out[player] || !active[player],
+        }
+    }
+
+    fn set_locked_out(&mut self, player: PlayerId) {
+        if player >= self.seats() {
+            return;
+        }
+        match self {
+            Self::Mask { locked_out, .. } => *locked_out |= 1u128 << player,
+            #[cfg(any(test, feature = "alloc"))]
+            Self::Spill { locked_out, .. } => locked_out[player] = true,
+        }
+    }
+
+    fn clear_locked_out(&mut self, player: PlayerId) {
+        if player >= self.seats() {
+            return;
+        }
+        match self {
+            Self::Mask { locked_out, .. } => *locked_out &= !(1u128 << player),
+            #[cfg(any(test, feature = "alloc"))]
+            Self::Spill { locked_out, .. } => locked_out[player] = false,
+        }
+    }
+
+    fn reset_locked_out(&mut self) {
+        match self {
+            Self::Mask { locked_out, .. } => *locked_out = 0,
+            #[cfg(any(test, feature = "alloc"))]
+            Self::Spill { locked_out, .. } => locked_out.fill(false),
+        }
+    }
+
+    /// Seats exactly `players`; ids outside the seats are ignored.
+    fn set_active<I: IntoIterator<Item = PlayerId>>(&mut self, players: I) {
+        match self {
+            Self::Mask { active, .. } => *active = player_mask(players),
+            #[cfg(any(test, feature = "alloc"))]
+            Self::Spill { active, .. } => {
+                active.fill(false);
+                let seats = active.len();
+                for player in players.into_iter().filter(|&player| player < seats) {
+                    active[player] = true;
+                }
+            }
+        }
+    }
+
+    fn set_active_mask(&mut self, mask: u128) {
+        match self {
+            Self::Mask { active, .. } => *active = mask,
+            #[cfg(any(test, feature = "alloc"))]
+            Self::Spill { .. } => {
+                self.set_active((0..MASK_SEATS).filter(|&player| mask & (1u128 << player) != 0))
+            }
+        }
+    }
+
+    /// The seated players among ids `0..=MAX_PLAYER_ID`.
+    fn active_mask(&self) -> u128 {
+        match self {
+            Self::Mask { active, .. } => *active,
+            #[cfg(any(test, feature = "alloc"))]
+            Self::Spill { active, .. } => player_mask(
+                active
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, seated)| **seated)
+                    .map(|(player, _)| player),
+            ),
+        }
+    }
+
+    /// [`is_locked_out`](Self::is_locked_out) for ids `0..=MAX_PLAYER_ID`, as a mask.
+    fn locked_out_mask(&self) -> u128 {
+        match self {
+            Self::Mask { locked_out, active } => locked_out | !active,
+            #[cfg(any(test, feature = "alloc"))]
+            Self::Spill { .. } => player_mask((0..MASK_SEATS).filter(|&p| self.is_locked_out(p))),
+        }
+    }
 }
 
 pub enum OutputEvent {
     Accepted(PlayerId, u64), // deadline in ms
     Rejected(PlayerId),
     TimedOut(PlayerId), // timed out player
+    Correct(PlayerId),  // player whose answer was accepted as correct
     RoundStarted,
     RoundContinued,
 }
@@ -35,35 +192,115 @@ pub struct BuzzerGame {
 }
 
 impl BuzzerGame {
+    /// Games for up to 128 players track lockouts in `u128` masks; bigger ones
+    /// (with the `alloc` feature) in one flag per seat.
     pub fn new(config: Config) -> Self {
+        let lockouts = Lockouts::new(config.max_players);
         Self {
             config,
             state: State {
                 phase: Phase::Idle,
-                locked_out_players: 0,
-                curr_player_id: 0,
+                lockouts,
+                floor_freed_in_ms: 0,
             },
         }
     }
 
-    pub fn set_curr_player_id(&mut self, id: PlayerId) {
-        self.state.curr_player_id = id;
+    /// Ids below this can be seated.
+    pub fn seats(&self) -> usize {
+        self.state.lockouts.seats()
     }
 
-    pub fn locked_out_players(&self) -> u128 {
-        let explicit = self.state.locked_out_players;
-        if self.state.curr_player_id >= 128 {
-            return explicit;
+    /// Sets which players may buzz, as a bitmask (see [`player_mask`]). Ids need not
+    /// be contiguous; players outside the mask are always locked out. A game with
+    /// more than 128 seats takes them from
+    /// [`set_active_player_ids`](Self::set_active_player_ids) instead.
+    pub fn set_active_players(&mut self, mask: u128) {
+        self.state.lockouts.set_active_mask(mask);
+    }
+
+    /// Like [`set_active_players`](Self::set_active_players), for any number of seats.
+    pub fn set_active_player_ids<I: IntoIterator<Item = PlayerId>>(&mut self, players: I) {
+        self.state.lockouts.set_active(players);
+    }
+
+    /// Changes the answer window for buzzes accepted from now on; a deadline
+    /// already running keeps its original length.
+    pub fn set_answer_window(&mut self, answer_window_in_ms: u64) {
+        self.config.answer_window_in_ms = answer_window_in_ms;
+    }
+
+    /// The seated players among ids `0..=MAX_PLAYER_ID`, as a bitmask.
+    pub fn active_players(&self) -> u128 {
+        self.state.lockouts.active_mask()
+    }
+
+    /// The player currently holding the floor, if any.
+    pub fn answering_player(&self) -> Option<PlayerId> {
+        match self.state.phase {
+            Phase::Answering { player, .. } => Some(player),
+            _ => None,
+        }
+    }
+
+    /// Whether a correct answer ended the round; buzzing stays closed until
+    /// the next one starts.
+    pub fn is_resolved(&self) -> bool {
+        self.state.phase == Phase::Resolved
+    }
+
+    /// When the current answer times out, so a caller can sleep until then
+    /// instead of polling [`tick`](Self::tick).
+    pub fn deadline_in_ms(&self) -> Option<u64> {
+        match self.state.phase {
+            Phase::Answering { deadline_in_ms, .. } => Some(deadline_in_ms),
+            _ => None,
         }
-        let implicit = u128::MAX << self.state.curr_player_id;
-        explicit | implicit
     }
 
+    /// Lockouts of ids `0..=MAX_PLAYER_ID` as a bitmask; see
+    /// [`is_locked_out`](Self::is_locked_out) for the rest of a bigger game.
+    pub fn locked_out_players(&self) -> u128 {
+        self.state.lockouts.locked_out_mask()
+    }
+
+    /// Whether `player` is kept from buzzing this round: locked out after
+    /// answering, not seated, or beyond the game's [`seats`](Self::seats).
+    pub fn is_locked_out(&self, player: PlayerId) -> bool {
+        self.state.lockouts.is_locked_out(player)
+    }
+
+    /// Every seat [`is_locked_out`](Self::is_locked_out), in id order.
+    pub fn locked_out_ids(&self) -> impl Iterator<Item = PlayerId> + '_ {
+        (0..self.seats()).filter(|&player| self.is_locked_out(player))
+    }
+
+    /// `now_in_ms` is when the buzz was made, which may be a little before the
+    /// caller got to it. A buzz made while an answer was still running is
+    /// rejected even if that answer has since timed out.
     pub fn buzz(&mut self, player: PlayerId, now_in_ms: u64) -> OutputEvent {
-        if player > MAX_PLAYER_ID {
+        let floor_held = now_in_ms < self.state.floor_freed_in_ms;
+        if floor_held || !self.is_phase_idle() || self.is_locked_out(player) {
             return OutputEvent::Rejected(player);
         }
-        if !self.is_phase_idle() || self.is_locked_out(player) {
+
+        let deadline_in_ms = now_in_ms + self.config.answer_window_in_ms;
+        self.set_phase_answering(player, deadline_in_ms);
+        OutputEvent::Accepted(player, deadline_in_ms)
+    }
+
+    /// Hands `player` the floor as if they had buzzed first, with a fresh
+    /// deadline; whoever held it loses it without being locked out. A locked-out
+    /// or unseated player is rejected unless `force` is set. Rejected either way
+    /// once a correct answer has ended the round, and beyond the game's
+    /// [`seats`](Self::seats).
+    pub fn grant(&mut self, player: PlayerId, now_in_ms: u64, force: bool) -> OutputEvent {
+        let allowed = if force {
+            player < self.seats()
+        } else {
+            !self.is_locked_out(player)
+        };
+        if self.state.phase == Phase::Resolved || !allowed {
             return OutputEvent::Rejected(player);
         }
 
@@ -72,29 +309,87 @@ impl BuzzerGame {
         OutputEvent::Accepted(player, deadline_in_ms)
     }
 
+    /// Takes back a [`correct_answer`](Self::correct_answer) or
+    /// [`continue_round`](Self::continue_round) that judged `player`: their
+    /// lockout is lifted and, unless someone else holds the floor, they hold it
+    /// again with a fresh deadline. Returns the `Accepted` when they do.
+    pub fn reinstate(&mut self, player: PlayerId, now_in_ms: u64) -> Option<OutputEvent> {
+        self.state.lockouts.clear_locked_out(player);
+        if matches!(self.state.phase, Phase::Answering { .. }) || self.is_locked_out(player) {
+            return None;
+        }
+
+        let deadline_in_ms = now_in_ms + self.config.answer_window_in_ms;
+        self.set_phase_answering(player, deadline_in_ms);
+        Some(OutputEvent::Accepted(player, deadline_in_ms))
+    }
+
     pub fn start_round(&mut self) -> OutputEvent {
         self.reset_locked_players();
         self.set_phase_idle();
         OutputEvent::RoundStarted
     }
 
+    /// Returns to the state of a freshly created game: nobody is seated, locked
+    /// out, or answering. Unlike [`start_round`](Self::start_round) this does not
+    /// open buzzing; that waits for the next round.
+    pub fn new_game(&mut self) {
+        self.reset_locked_players();
+        self.set_phase_idle();
+        self.state.lockouts.set_active([]);
+    }
+
     pub fn continue_round(&mut self) -> OutputEvent {
+        if let Phase::Answering { player, .. } = self.state.phase {
+            self.set_locked_out(player);
+        }
+        self.set_phase_idle();
+        OutputEvent::RoundContinued
+    }
+
+    /// Takes the floor from whoever holds it without judging the answer: they
+    /// are not locked out and their deadline no longer applies. Returns who
+    /// held it.
+    pub fn abort_answer(&mut self) -> Option<PlayerId> {
+        let player = self.answering_player()?;
+        self.set_phase_idle();
+        Some(player)
+    }
+
+    /// Resolves the round in favor of the answering player. Returns None when
+    /// nobody holds the floor.
+    pub fn correct_answer(&mut self) -> Option<OutputEvent> {
         match self.state.phase {
             Phase::Answering { player, .. } => {
-                self.set_locked_out(player);
+                self.state.phase = Phase::Resolved;
+                Some(OutputEvent::Correct(player))
             }
-            _ => {}
+            _ => None,
         }
-        self.set_phase_idle();
-        OutputEvent::RoundContinued
     }
 
     pub fn tick(&mut self, now_in_ms: u64) -> Option<OutputEvent> {
+        self.next_due(now_in_ms)
+    }
+
+    /// Emits, in order, every event due by `now_in_ms`. The answer deadline is
+    /// the only timed transition today, so this yields at most one event, the
+    /// same one [`tick`](Self::tick) would; callers that go through it keep
+    /// working once more transitions are scheduled.
+    pub fn advance_to<E: Extend<OutputEvent>>(&mut self, now_in_ms: u64, events: &mut E) {
+        while let Some(event) = self.next_due(now_in_ms) {
+            events.extend(Some(event));
+        }
+    }
+
+    /// Applies the earliest scheduled transition due by `now_in_ms`, if any.
+    fn next_due(&mut self, now_in_ms: u64) -> Option<OutputEvent> {
         match self.state.phase {
             Phase::Answering {
                 player,
                 deadline_in_ms,
             } if now_in_ms >= deadline_in_ms => {
+                self.state.floor_freed_in_ms = deadline_in_ms;
                 self.set_phase_idle();
                 self.set_locked_out(player);
                 Some(OutputEvent::TimedOut(player))
@@ -103,29 +398,12 @@ impl BuzzerGame {
         }
     }
 
-    fn is_locked_out(&self, player: PlayerId) -> bool {
-        if player > MAX_PLAYER_ID {
-            return false;
-        }
-
-        if player >= self.state.curr_player_id {
-            return true;
-        }
-
-        let mask = 1u128 << player;
-        self.state.locked_out_players & mask != 0
-    }
-
     fn set_locked_out(&mut self, player: PlayerId) {
-        if player > MAX_PLAYER_ID {
-            return;
-        }
-        let mask = 1u128 << player;
-        self.state.locked_out_players |= mask;
+        self.state.lockouts.set_locked_out(player);
     }
 
     fn reset_locked_players(&mut self) {
-        self.state.locked_out_players = 0u128;
+        self.state.lockouts.reset_locked_out();
     }
 
     fn is_phase_idle(&self) -> bool {
@@ -143,3 +421,300 @@ impl BuzzerGame {
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    fn game() -> BuzzerGame {
+        BuzzerGame::new(Config {
+            answer_window_in_ms: 100,
+            max_players: 8,
+        })
+    }
+
+    #[test]
+    fn only_active_players_can_buzz() {
+        // Player 1 was removed; player 5 joined with a non-contiguous id.
+        let mut game = game();
+        game.set_active_players(player_mask([0, 2, 5]));
+        game.start_round();
+
+        assert!(matches!(game.buzz(1, 0), OutputEvent::Rejected(1)));
+        assert!(matches!(game.buzz(5, 0), OutputEvent::Accepted(5, 100)));
+        assert_eq!(
+            game.locked_out_players() & player_mask(0..6),
+            player_mask([1, 3, 4])
+        );
+    }
+
+    #[test]
+    fn player_mask_ignores_out_of_range_ids() {
+        assert_eq!(player_mask([0, 127, 128, 500]), 1 | (1u128 << 127));
+    }
+
+    struct Events(std::vec::Vec<OutputEvent>);
+
+    impl Extend<OutputEvent> for Events {
+        fn extend<T: IntoIterator<Item = OutputEvent>>(&mut self, events: T) {
+            self.0.extend(events);
+        }
+    }
+
+    #[test]
+    fn long_jump_past_deadline_times_out_once() {
+        let mut game = game();
+        game.set_active_players(player_mask([0, 1]));
+        game.start_round();
+        game.buzz(1, 0);
+
+        let mut events = Events(std::vec::Vec::new());
+        game.advance_to(30_000, &mut events);
+        game.advance_to(30_010, &mut events);
+
+        assert_eq!(events.0.len(), 1);
+        assert!(matches!(events.0[0], OutputEvent::TimedOut(1)));
+        assert_eq!(game.locked_out_players() & 0b11, 0b10);
+        assert!(matches!(
+            game.buzz(0, 30_010),
+            OutputEvent::Accepted(0, 30_110)
+        ));
+    }
+
+    #[test]
+    fn advance_before_deadline_emits_nothing() {
+        let mut game = game();
+        game.set_active_players(player_mask([0]));
+        game.start_round();
+        game.buzz(0, 0);
+
+        let mut events = Events(std::vec::Vec::new());
+        game.advance_to(99, &mut events);
+        // An earlier time has nothing due either.
+        game.advance_to(50, &mut events);
+        assert!(events.0.is_empty());
+    }
+
+    #[test]
+    fn correct_answer_closes_buzzing_until_next_round() {
+        let mut game = game();
+        game.set_active_players(player_mask([0, 1]));
+        game.start_round();
+        assert!(game.correct_answer().is_none());
+
+        game.buzz(0, 0);
+        assert!(matches!(
+            game.correct_answer(),
+            Some(OutputEvent::Correct(0))
+        ));
+        assert!(matches!(game.buzz(1, 10), OutputEvent::Rejected(1)));
+        // The deadline no longer applies once the answer is resolved.
+        assert!(game.tick(1_000).is_none());
+
+        game.start_round();
+        assert!(matches!(game.buzz(1, 20), OutputEvent::Accepted(1, 120)));
+    }
+
+    #[test]
+    fn aborted_answer_neither_times_out_nor_locks_out() {
+        let mut game = game();
+        game.set_active_players(player_mask([0]));
+        game.start_round();
+        assert_eq!(game.abort_answer(), None);
+
+        game.buzz(0, 0);
+        assert_eq!(game.abort_answer(), Some(0));
+        assert!(game.tick(1_000).is_none());
+        assert!(!game.is_locked_out(0));
+    }
+
+    #[test]
+    fn new_game_closes_buzzing_until_a_round_starts() {
+        let mut game = game();
+        game.set_active_players(player_mask([0, 1]));
+        game.start_round();
+        game.buzz(0, 0);
+
+        game.new_game();
+        assert_eq!(game.answering_player(), None);
+        assert!(matches!(game.buzz(1, 10), OutputEvent::Rejected(1)));
+        assert!(game.tick(1_000).is_none());
+
+        game.set_active_players(player_mask([0, 1]));
+        game.start_round();
+        assert!(matches!(game.buzz(0, 20), OutputEvent::Accepted(0, 120)));
+    }
+
+    #[test]
+    fn grant_takes_the_floor_from_whoever_holds_it() {
+        let mut game = game();
+        game.set_active_players(player_mask([0, 1, 2]));
+        game.start_round();
+
+        assert!(matches!(game.buzz(0, 10), OutputEvent::Accepted(0, 110)));
+        assert!(matches!(
+            game.grant(1, 50, false),
+            OutputEvent::Accepted(1, 150)
+        ));
+        assert_eq!(game.answering_player(), Some(1));
+        // Displaced, not locked out.
+        assert!(!game.is_locked_out(0));
+        assert!(game.tick(149).is_none());
+        assert!(matches!(game.tick(150), Some(OutputEvent::TimedOut(1))));
+    }
+
+    #[test]
+    fn grant_respects_lockouts_unless_forced() {
+        let mut game = game();
+        game.set_active_players(player_mask([0, 1]));
+        game.start_round();
+        game.buzz(0, 0);
+        game.continue_round();
+
+        // Locked out after answering, and never seated.
+        assert!(matches!(game.grant(0, 10, false), OutputEvent::Rejected(0)));
+        assert!(matches!(game.grant(5, 10, false), OutputEvent::Rejected(5)));
+        assert!(matches!(
+            game.grant(5, 10, true),
+            OutputEvent::Accepted(5, 110)
+        ));
+        assert!(matches!(
+            game.grant(0, 20, true),
+            OutputEvent::Accepted(0, 120)
+        ));
+        let beyond = MAX_PLAYER_ID + 1;
+        assert!(matches!(
+            game.grant(beyond, 20, true),
+            OutputEvent::Rejected(_)
+        ));
+
+        game.correct_answer();
+        assert!(game.is_resolved());
+        assert!(matches!(game.grant(1, 30, true), OutputEvent::Rejected(1)));
+    }
+
+    #[test]
+    fn buzz_made_before_a_timeout_is_rejected_after_it() {
+        let mut game = game();
+        game.set_active_players(player_mask([0, 1, 2]));
+        game.start_round();
+        game.buzz(0, 0);
+
+        // Player 1 buzzed at 95 while player 0 still held the floor; the buzz
+        // only reaches the game once the timeout at 100 has been handled.
+        assert!(matches!(game.tick(105), Some(OutputEvent::TimedOut(0))));
+        assert!(matches!(game.buzz(1, 95), OutputEvent::Rejected(1)));
+        assert!(matches!(game.buzz(2, 101), OutputEvent::Accepted(2, 201)));
+    }
+
+    #[test]
+    fn reinstate_takes_back_a_judgement() {
+        let mut game = game();
+        game.set_active_players(player_mask([0, 1]));
+        game.start_round();
+        game.buzz(0, 0);
+        game.correct_answer();
+
+        assert!(matches!(
+            game.reinstate(0, 50),
+            Some(OutputEvent::Accepted(0, 150))
+        ));
+        assert_eq!(game.deadline_in_ms(), Some(150));
+
+        game.continue_round();
+        assert!(game.is_locked_out(0));
+        game.buzz(1, 60);
+        // Player 1 keeps the floor; player 0 may only buzz again.
+        assert!(game.reinstate(0, 70).is_none());
+        assert_eq!(game.answering_player(), Some(1));
+        assert!(!game.is_locked_out(0));
+    }
+
+    #[test]
+    fn new_answer_window_spares_running_deadline() {
+        let mut game = game();
+        game.set_active_players(player_mask([0, 1]));
+        game.start_round();
+        game.buzz(0, 0);
+
+        game.set_answer_window(500);
+        assert!(matches!(game.tick(100), Some(OutputEvent::TimedOut(0))));
+        assert!(matches!(game.buzz(1, 100), OutputEvent::Accepted(1, 600)));
+    }
+
+    #[test]
+    fn deadline_is_known_only_while_answering() {
+        let mut game = game();
+        game.set_active_players(player_mask([0, 1]));
+        game.start_round();
+        assert_eq!(game.deadline_in_ms(), None);
+
+        game.buzz(0, 30);
+        assert_eq!(game.deadline_in_ms(), Some(130));
+        game.continue_round();
+        assert_eq!(game.deadline_in_ms(), None);
+
+        game.buzz(1, 40);
+        game.correct_answer();
+        assert_eq!(game.deadline_in_ms(), None);
+    }
+
+    #[test]
+    fn two_hundred_players_spill_past_the_mask() {
+        let mut game = BuzzerGame::new(Config {
+            answer_window_in_ms: 100,
+            max_players: 200,
+        });
+        assert_eq!(game.seats(), 200);
+        game.set_active_player_ids(0..200);
+        game.start_round();
+
+        assert!(matches!(game.buzz(150, 0), OutputEvent::Accepted(150, 100)));
+        game.continue_round();
+        assert!(game.is_locked_out(150));
+        assert!(matches!(game.buzz(150, 10), OutputEvent::Rejected(150)));
+
+        assert!(matches!(
+            game.buzz(199, 20),
+            OutputEvent::Accepted(199, 120)
+        ));
+        assert!(matches!(game.tick(120), Some(OutputEvent::TimedOut(199))));
+        assert!(game.locked_out_ids().eq([150, 199]));
+        assert_eq!(game.locked_out_players(), 0);
+        assert!(matches!(game.buzz(200, 130), OutputEvent::Rejected(200)));
+        assert!(matches!(game.buzz(5, 130), OutputEvent::Accepted(5, 230)));
+    }
+
+    #[test]
+    fn two_hundred_players_only_seat_the_active_ones() {
+        let mut game = BuzzerGame::new(Config {
+            answer_window_in_ms: 100,
+            max_players: 200,
+        });
+        game.set_active_players(player_mask([0, 127]));
+        game.start_round();
+        assert!(game.is_locked_out(130));
+        assert_eq!(game.active_players(), player_mask([0, 127]));
+
+        game.set_active_player_ids([3, 180]);
+        assert!(matches!(game.buzz(0, 0), OutputEvent::Rejected(0)));
+        assert!(matches!(game.buzz(180, 0), OutputEvent::Accepted(180, 100)));
+
+        game.new_game();
+        assert!(game.locked_out_ids().eq(0..200));
+    }
+
+    #[test]
+    fn small_games_keep_the_bitmask() {
+        for max_players in [0, 8, 128] {
+            let game = BuzzerGame::new(Config {
+                answer_window_in_ms: 100,
+                max_players,
+            });
+            assert_eq!(game.seats(), 128);
+            assert_eq!(game.locked_out_players(), u128::MAX);
+        }
+    }
+}
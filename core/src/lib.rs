@@ -1,3 +1,11 @@
 #![no_std]
+#[cfg(any(test, feature = "alloc", feature = "protocol"))]
+extern crate alloc;
 pub mod adapter;
+#[cfg(feature = "async")]
+pub mod async_adapter;
+#[cfg(feature = "embedded-hal")]
+pub mod embedded;
 pub mod game;
+#[cfg(feature = "protocol")]
+pub mod protocol;
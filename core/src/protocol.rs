@@ -0,0 +1,398 @@
+//! The websocket protocol shared by the server and its clients, browser
+//! builds included: plain serde types that need only `alloc`.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+/// Websocket protocol versions this server speaks. Every message carries its
+/// version as `v`; client messages without one are taken as version 1.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[u64] = &[1];
+/// The version stamped on every server message.
+pub const PROTOCOL_VERSION: u64 = 1;
+/// Longest `id` a client may tag a message with, in bytes.
+pub const MAX_ACK_ID_LEN: usize = 64;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Admin,
+    Player,
+    /// Receives every broadcast but never buzzes, scores or takes a player slot.
+    Spectator,
+}
+
+/// Fields this server does not know are ignored, so newer clients can add them.
+/// Any message may carry a string `id` of up to [`MAX_ACK_ID_LEN`] bytes; it is
+/// echoed on the `action_ok`, `action_denied` or `rejected` sent back for it,
+/// never on broadcasts.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Buzz,
+    StartRound {
+        /// Optional "get ready" delay before buzzing opens.
+        countdown_ms: Option<u64>,
+        /// Shown to everyone once the round starts.
+        question: Option<String>,
+    },
+    ContinueRound,
+    MarkCorrect,
+    Pause,
+    Resume,
+    Kick {
+        name: String,
+    },
+    /// Admin-only: the player stays but cannot buzz, chat or react.
+    Mute {
+        name: String,
+    },
+    Unmute {
+        name: String,
+    },
+    /// Admin-only: a kicked player's name may join again.
+    Unban {
+        name: String,
+    },
+    /// Keeps your seat, score and lockout; the reply is `renamed` with a new token.
+    Rename {
+        /// Older clients send `name`.
+        #[serde(alias = "name")]
+        new_name: String,
+    },
+    /// Admin-only: the named player takes the floor as if they had buzzed
+    /// first, e.g. to settle a tie. With `force`, a player who is locked out or
+    /// joined mid-round may take it too.
+    Grant {
+        name: String,
+        #[serde(default)]
+        force: bool,
+    },
+    /// Take back the last answer marked correct or wrong.
+    Undo,
+    /// Start over: scores, lockouts and round numbering are reset.
+    NewGame,
+    /// Leave the room for good; the session token stops working.
+    Leave,
+    SetReady {
+        ready: bool,
+    },
+    /// Admin-only: clear everyone's ready flag and ask them to confirm again.
+    RequestReady,
+    /// Admin-only: close the room for everyone.
+    CloseRoom,
+    /// Admin-only: reject every buzz with `room_locked` until `unlock_room` or
+    /// the next `start_round`, e.g. between questions.
+    LockRoom,
+    UnlockRoom,
+    /// Admin-only: clamped like the room's setting and applied from the next
+    /// round; everyone is told with `settings_changed`.
+    SetAnswerWindow {
+        answer_window_in_ms: u64,
+    },
+    /// A single emoji, e.g. "👏".
+    React {
+        emoji: String,
+    },
+    Chat {
+        text: String,
+        /// Admin-only: announce as the room rather than as yourself.
+        #[serde(default)]
+        system: bool,
+    },
+    /// Answered at once with the server's wall clock, to measure the offset
+    /// between the two clocks; has its own small quota.
+    TimeSync {
+        client_ts_ms: u64,
+    },
+    /// Hands over a token refreshed over HTTP, so this socket stays open past
+    /// the expiry of the one it was opened with. Must be the same player's.
+    UpdateToken {
+        token: String,
+    },
+    /// Answered with `round_state`, to the sender only.
+    GetRoundState,
+    /// Sent after noticing a jump in `seq`; the reply is the same `welcome` and
+    /// `question` a fresh attach gets, stamped with the latest `seq`.
+    Resync {
+        /// The last `seq` received before the jump.
+        last_seq: Option<u64>,
+    },
+}
+
+/// Every message also carries the protocol version `v` and a per-connection
+/// `seq`, see `Route` in the room adapter. Times ending in `_ms` without
+/// `in` are server wall-clock epoch milliseconds; a client corrects them by the
+/// offset it measures with `time_sync`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// `remaining_ms` is the answer time left when this was sent, for clients
+    /// that have not synced their clock.
+    Accepted {
+        name: String,
+        round: u64,
+        deadline_ms: u64,
+        remaining_ms: u64,
+    },
+    Participants {
+        participants: Vec<ParticipantInfo>,
+    },
+    /// Buzzing opens `starts_in_ms` after `ts_ms`, at `starts_at_ms`; earlier
+    /// buzzes are false starts.
+    Countdown {
+        starts_in_ms: u64,
+        starts_at_ms: u64,
+        ts_ms: u64,
+    },
+    /// The reply to the client's `time_sync`, sent straight back without a
+    /// `seq` and never replayed.
+    TimeSync {
+        client_ts_ms: u64,
+        server_ts_ms: u64,
+    },
+    RoundStarted {
+        round: u64,
+    },
+    /// The current round's question; also sent to clients attaching mid-round.
+    Question {
+        text: String,
+        round: u64,
+        ts_ms: u64,
+    },
+    /// The first message on every fresh attach and resync: enough to rebuild
+    /// the whole view without waiting for the next round event. Its `seq` is
+    /// where a reconnect resumes from. The round's `question`, if one is open,
+    /// follows it.
+    Welcome {
+        you: ParticipantInfo,
+        participants: Vec<ParticipantInfo>,
+        game: GameStateInfo,
+        settings: RoomSettingsInfo,
+    },
+    /// Buzzing was locked or unlocked for the whole room.
+    RoomLock {
+        locked: bool,
+        ts_ms: u64,
+    },
+    /// The reply to `get_round_state`.
+    RoundState {
+        round: u64,
+        /// Who holds the floor, with `deadline_ms` and `remaining_ms` as in
+        /// [`ServerMessage::Accepted`].
+        answering: Option<String>,
+        deadline_ms: Option<u64>,
+        remaining_ms: Option<u64>,
+        /// Everyone who has held the floor this round, first buzz first.
+        attempted: Vec<String>,
+        locked_out: Vec<String>,
+    },
+    RoundContinued,
+    /// The answer clock is frozen and buzzes are rejected until `Resumed`.
+    Paused,
+    Resumed,
+    Rejected {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+    },
+    /// Sent to the admin only, for every buzz the room loop accepts or rejects.
+    /// `reaction_ms` counts from when buzzing last opened and is `None` for a
+    /// buzz while it was closed, e.g. a false start during the countdown.
+    BuzzDetail {
+        name: String,
+        accepted: bool,
+        reaction_ms: Option<u64>,
+        ts_ms: u64,
+    },
+    TimedOut {
+        name: String,
+    },
+    Correct {
+        name: String,
+    },
+    /// Sums up how an answer ended, once per answer, alongside the message
+    /// for that ending; `winner` is set for a correct one.
+    RoundResolved {
+        round: u64,
+        outcome: RoundOutcome,
+        winner: Option<String>,
+        ts_ms: u64,
+    },
+    /// The admin took back the last `correct` or wrong answer they judged;
+    /// corrected scores follow, and an `accepted` if `name` holds the floor again.
+    Undone {
+        name: String,
+        round: u64,
+    },
+    Scoreboard {
+        entries: Vec<ScoreEntry>,
+        ts_ms: u64,
+        round: u64,
+    },
+    /// Scores and round numbering were reset; the next round is round 1.
+    GameReset,
+    /// The admin asked everyone to confirm they are ready.
+    ReadyCheck,
+    /// A new answer window takes effect from the next round.
+    SettingsChanged {
+        answer_window_in_ms: u64,
+        max_players: usize,
+        requires_password: bool,
+    },
+    Chat {
+        from: String,
+        text: String,
+        ts_ms: u64,
+        system: bool,
+    },
+    Reaction {
+        from: String,
+        emoji: String,
+        ts_ms: u64,
+    },
+    ActionDenied {
+        reason: String,
+        /// For `rate_limited`, the kind of message whose quota ran out.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        category: Option<RateCategory>,
+        /// The `id` of the message denied, if it had one.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+    },
+    /// Sent only for a message that carried an `id`, once it has been carried
+    /// out; a buzz gets it when accepted, or when it only repeats the player's
+    /// last one within the room's debounce.
+    ActionOk {
+        id: String,
+    },
+    /// A frame that could not be read as a client message; nothing was done.
+    ProtocolError {
+        detail: String,
+        supported_versions: Vec<u64>,
+    },
+    Kicked,
+    /// The room is gone; the server closes the socket right after this.
+    RoomClosed {
+        reason: String,
+    },
+    /// Another connection took over this session; the server closes this socket
+    /// right after. Carries no `seq`.
+    Replaced,
+    /// The socket's token ran out without an `update_token`; the server closes
+    /// it with 4401 right after. Carries no `seq`. Reconnect with a fresh token.
+    SessionExpired,
+    /// Someone else runs the room now; `reason` is `admin_disconnected` when
+    /// the admin was away past the grace period.
+    AdminChanged {
+        name: String,
+        reason: String,
+    },
+    /// Sent to the renamed player only; the token replaces their old one.
+    Renamed {
+        old_name: String,
+        new_name: String,
+        token: String,
+    },
+}
+
+impl ServerMessage {
+    /// The answer to a player's own message: a denial always, success only
+    /// when the message carried an `id` to confirm.
+    pub fn acknowledgement(ack_id: Option<String>, result: Result<(), &str>) -> Option<Self> {
+        match (result, ack_id) {
+            (Err(reason), id) => Some(ServerMessage::ActionDenied {
+                reason: reason.to_string(),
+                category: None,
+                id,
+            }),
+            (Ok(()), Some(id)) => Some(ServerMessage::ActionOk { id }),
+            (Ok(()), None) => None,
+        }
+    }
+}
+
+/// Each connection has a quota per kind of message, so using up one never
+/// holds up the others.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateCategory {
+    Buzz,
+    /// Messages that run the game rather than play it.
+    Control,
+    /// Chat and reactions.
+    Chat,
+    /// `time_sync`, `resync` and `get_round_state`.
+    Sync,
+    /// Everything else, unreadable frames included; set per room.
+    General,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ParticipantInfo {
+    pub name: String,
+    pub role: Role,
+    pub locked_out: bool,
+    pub ready: bool,
+    pub muted: bool,
+    /// Whether they have a live socket; a dropped player keeps their seat.
+    pub connected: bool,
+    /// Index into the clients' palette of `PLAYER_COLORS` colors; stable
+    /// while the participant stays in the room.
+    pub color: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GamePhase {
+    /// Before the first round, or buzzing is open.
+    #[default]
+    Idle,
+    Countdown,
+    Answering,
+    /// A correct answer ended the round.
+    Resolved,
+}
+
+/// How the answer that held the floor ended.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundOutcome {
+    Correct,
+    /// The admin judged it wrong and reopened buzzing.
+    Wrong,
+    TimedOut,
+    /// A new round or game began before it was judged.
+    Aborted,
+}
+
+/// The game as of a [`ServerMessage::Welcome`].
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GameStateInfo {
+    pub phase: GamePhase,
+    pub round: u64,
+    pub paused: bool,
+    /// Buzzing is locked, see [`ServerMessage::RoomLock`].
+    #[serde(default)]
+    pub locked: bool,
+    /// Who holds the floor, with `deadline_ms` and `remaining_ms` as in
+    /// [`ServerMessage::Accepted`]. While paused, `remaining_ms` stays put.
+    pub answering: Option<String>,
+    pub deadline_ms: Option<u64>,
+    pub remaining_ms: Option<u64>,
+    /// Players kept from buzzing this round.
+    pub locked_out: Vec<String>,
+    pub scores: Vec<ScoreEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RoomSettingsInfo {
+    pub answer_window_in_ms: u64,
+    pub max_players: usize,
+    pub requires_password: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ScoreEntry {
+    pub name: String,
+    pub score: u32,
+}
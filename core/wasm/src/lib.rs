@@ -0,0 +1,111 @@
+//! JS bindings for the buzzer engine, for running a round locally without a
+//! server. Build with `wasm-pack build core/wasm`.
+//!
+//! Events are returned as plain objects tagged with `type`, using the same
+//! snake_case names as the server's `ServerMessage`, so frontend handlers can be
+//! shared. Players are identified by id rather than name. Times are JS numbers
+//! in milliseconds.
+
+use buzzer_core::game::{BuzzerGame, Config, OutputEvent, PlayerId};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Accepted { player: PlayerId, deadline_ms: f64 },
+    Rejected { player: PlayerId },
+    TimedOut { player: PlayerId },
+    Correct { player: PlayerId },
+    RoundStarted,
+    RoundContinued,
+}
+
+impl From<OutputEvent> for Event {
+    fn from(event: OutputEvent) -> Self {
+        match event {
+            OutputEvent::Accepted(player, deadline_ms) => Event::Accepted {
+                player,
+                deadline_ms: deadline_ms as f64,
+            },
+            OutputEvent::Rejected(player) => Event::Rejected { player },
+            OutputEvent::TimedOut(player) => Event::TimedOut { player },
+            OutputEvent::Correct(player) => Event::Correct { player },
+            OutputEvent::RoundStarted => Event::RoundStarted,
+            OutputEvent::RoundContinued => Event::RoundContinued,
+        }
+    }
+}
+
+#[wasm_bindgen(js_name = BuzzerGame)]
+pub struct WasmBuzzerGame {
+    game: BuzzerGame,
+}
+
+#[wasm_bindgen(js_class = BuzzerGame)]
+impl WasmBuzzerGame {
+    /// Creates a game where players `0..playerCount` may buzz.
+    #[wasm_bindgen(constructor)]
+    pub fn new(answer_window_ms: f64, player_count: usize) -> Self {
+        let mut game = BuzzerGame::new(Config {
+            answer_window_in_ms: to_ms(answer_window_ms),
+            max_players: player_count,
+        });
+        game.set_active_player_ids(0..player_count);
+        Self { game }
+    }
+
+    /// Replaces the roster with the given player ids.
+    #[wasm_bindgen(js_name = setActivePlayers)]
+    pub fn set_active_players(&mut self, players: Vec<usize>) {
+        self.game.set_active_player_ids(players);
+    }
+
+    pub fn buzz(&mut self, player: usize, now_ms: f64) -> JsValue {
+        to_js(&Event::from(self.game.buzz(player, to_ms(now_ms))))
+    }
+
+    /// Advances the clock, returning every event that fell due (usually none).
+    pub fn tick(&mut self, now_ms: f64) -> JsValue {
+        to_js(&self.advance(now_ms))
+    }
+
+    #[wasm_bindgen(js_name = startRound)]
+    pub fn start_round(&mut self) -> JsValue {
+        to_js(&Event::from(self.game.start_round()))
+    }
+
+    #[wasm_bindgen(js_name = continueRound)]
+    pub fn continue_round(&mut self) -> JsValue {
+        to_js(&Event::from(self.game.continue_round()))
+    }
+
+    /// Marks the answering player correct; returns `null` when nobody answers.
+    #[wasm_bindgen(js_name = markCorrect)]
+    pub fn mark_correct(&mut self) -> JsValue {
+        match self.game.correct_answer() {
+            Some(event) => to_js(&Event::from(event)),
+            None => JsValue::NULL,
+        }
+    }
+}
+
+impl WasmBuzzerGame {
+    fn advance(&mut self, now_ms: f64) -> Vec<Event> {
+        let mut events = Vec::new();
+        self.game.advance_to(to_ms(now_ms), &mut events);
+        events.into_iter().map(Event::from).collect()
+    }
+}
+
+fn to_ms(value: f64) -> u64 {
+    if value.is_finite() && value > 0.0 {
+        value as u64
+    } else {
+        0
+    }
+}
+
+fn to_js<T: Serialize>(value: &T) -> JsValue {
+    serde_wasm_bindgen::to_value(value).expect("events serialize to JS values")
+}
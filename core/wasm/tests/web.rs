@@ -0,0 +1,63 @@
+//! Headless browser test: `wasm-pack test --headless --firefox core/wasm`.
+//! CI does not run it; it builds core's `protocol` for wasm32 instead (see `.github/workflows/ci.yml`).
+
+use buzzer_core::protocol::{ClientMessage, ServerMessage};
+use core_wasm::WasmBuzzerGame;
+use js_sys::{Array, Reflect};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn field(event: &JsValue, key: &str) -> JsValue {
+    Reflect::get(event, &JsValue::from_str(key)).expect("event is an object")
+}
+
+fn kind(event: &JsValue) -> String {
+    field(event, "type").as_string().expect("event has a type")
+}
+
+fn number(event: &JsValue, key: &str) -> f64 {
+    field(event, key).as_f64().expect("numeric field")
+}
+
+#[wasm_bindgen_test]
+fn plays_a_full_round() {
+    let mut game = WasmBuzzerGame::new(1000.0, 3);
+    assert_eq!(kind(&game.start_round()), "round_started");
+
+    let accepted = game.buzz(1, 100.0);
+    assert_eq!(kind(&accepted), "accepted");
+    assert_eq!(number(&accepted, "player"), 1.0);
+    assert_eq!(number(&accepted, "deadline_ms"), 1100.0);
+    assert_eq!(kind(&game.buzz(2, 150.0)), "rejected");
+
+    assert_eq!(Array::from(&game.tick(1099.0)).length(), 0);
+    let due = Array::from(&game.tick(1100.0));
+    assert_eq!(due.length(), 1);
+    assert_eq!(kind(&due.get(0)), "timed_out");
+    assert_eq!(number(&due.get(0), "player"), 1.0);
+
+    assert_eq!(kind(&game.continue_round()), "round_continued");
+    assert_eq!(kind(&game.buzz(1, 1200.0)), "rejected");
+    assert_eq!(kind(&game.buzz(2, 1200.0)), "accepted");
+
+    let correct = game.mark_correct();
+    assert_eq!(kind(&correct), "correct");
+    assert_eq!(number(&correct, "player"), 2.0);
+    assert!(game.mark_correct().is_null());
+}
+
+/// The server's protocol types build for the browser and cross into JS as the
+/// same objects the server sends.
+#[wasm_bindgen_test]
+fn protocol_messages_cross_into_js() {
+    let started = serde_wasm_bindgen::to_value(&ServerMessage::RoundStarted { round: 3 }).unwrap();
+    assert_eq!(kind(&started), "round_started");
+    assert_eq!(number(&started, "round"), 3.0);
+
+    let buzz = js_sys::Object::new();
+    Reflect::set(&buzz, &"type".into(), &"buzz".into()).unwrap();
+    let buzz: ClientMessage = serde_wasm_bindgen::from_value(buzz.into()).unwrap();
+    assert!(matches!(buzz, ClientMessage::Buzz));
+}
@@ -0,0 +1,64 @@
+//! Creates a room, joins it as a player and buzzes in the first round.
+//!
+//! Start the server, then: `cargo run -p server --example bot [http://127.0.0.1:3000]`
+
+use futures::StreamExt;
+use server::client::Client;
+use server::dtos::{ClientMessage, CreateRoomRequest, JoinRoomRequest, ServerMessage};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let base_url = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "http://127.0.0.1:3000".to_string());
+    let client = Client::new(base_url);
+
+    let room = client
+        .create_room(&CreateRoomRequest {
+            name: "Quizmaster".to_string(),
+            ..Default::default()
+        })
+        .await?;
+    println!("Created room {}", room.room_id);
+    let player = client
+        .join(
+            &room.room_id,
+            &JoinRoomRequest {
+                name: "Bot".to_string(),
+                ..Default::default()
+            },
+            None,
+        )
+        .await?;
+
+    let (mut admin, _) = client.connect(&room.room_id, &room.token).await?;
+    let (mut bot, mut messages) = client.connect(&room.room_id, &player.token).await?;
+
+    while let Some(message) = messages.next().await {
+        match message? {
+            // The bot is seated; start the round as the admin.
+            ServerMessage::Welcome { .. } => {
+                admin
+                    .send(&ClientMessage::StartRound {
+                        countdown_ms: None,
+                        question: Some("Who wrote this bot?".to_string()),
+                    })
+                    .await?;
+            }
+            ServerMessage::RoundStarted { round } => {
+                println!("Round {round} is open, buzzing");
+                bot.send(&ClientMessage::Buzz).await?;
+            }
+            ServerMessage::Accepted {
+                name, remaining_ms, ..
+            } => {
+                println!("{name} has the floor for {remaining_ms} ms");
+                break;
+            }
+            other => println!("{other:?}"),
+        }
+    }
+    admin.close().await?;
+    bot.close().await?;
+    Ok(())
+}
@@ -1,124 +1,1101 @@
 use std::{
-    sync::{
-        Arc, Mutex,
-        atomic::{AtomicBool, Ordering},
-    },
-    time::Instant,
+    collections::VecDeque,
+    ops::Deref,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
 };
 
+use axum::{body::Bytes, extract::ws::Utf8Bytes};
 use dashmap::DashMap;
-use tokio::{sync::mpsc, time};
+use tokio::{
+    sync::{broadcast, mpsc, oneshot, watch},
+    time::{self, Instant},
+};
+
+use core::adapter::{GameInput, GameOutput, TimeSource};
+use core::async_adapter::{self, GameInputAsync, GameOutputAsync};
+use core::game::{BuzzerGame, Config, MAX_PLAYER_ID, OutputEvent, PlayerId, player_mask};
 
-use core::adapter::{self, GameInput, GameOutput, TimeSource};
-use core::game::{BuzzerGame, Config, OutputEvent, PlayerId};
+use crate::dtos::{GamePhase, PROTOCOL_VERSION, RoundOutcome, ServerMessage};
+use crate::state::room_state::{AnswerResult, RoundHistory, build_scoreboard};
+use crate::utils::time::now_millis;
+use crate::webhook::RoomWebhook;
+
+/// Instructions for the room loop, applied strictly in arrival order.
+pub enum RoomControl {
+    StartRound {
+        countdown_ms: u64,
+        question: Option<String>,
+    },
+    ContinueRound,
+    MarkCorrect,
+    /// See [`BuzzerGame::grant`]; the outcome is acknowledged to `requester_id`.
+    Grant {
+        player_id: PlayerId,
+        force: bool,
+        requester_id: PlayerId,
+        ack_id: Option<String>,
+    },
+    /// Takes back the latest of the last [`UNDO_DEPTH`] judgements; acknowledged
+    /// to `requester_id`.
+    Undo {
+        requester_id: PlayerId,
+        ack_id: Option<String>,
+    },
+    NewGame,
+    /// Takes effect when the next round starts, never mid-answer.
+    SetAnswerWindow {
+        answer_window_in_ms: u64,
+    },
+    /// Freeze the answer clock and reject buzzes; later controls wait for `Resume`.
+    Pause,
+    Resume,
+    /// Sets the round counter of a room rebuilt from a
+    /// [`RoomSnapshot`](crate::dtos::RoomSnapshot), before anyone has joined.
+    RestoreRound {
+        round: u64,
+    },
+    /// Tell every connection why the room is closing, drop their routes, and stop.
+    Shutdown {
+        reason: String,
+    },
+    Query(oneshot::Sender<GameView>),
+}
 
-use crate::dtos::ServerMessage;
+/// Snapshot of the game published by the room loop after every iteration.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameView {
+    pub phase: GamePhase,
+    pub locked_out: u128,
+    pub answering: Option<PlayerId>,
+    /// When the answer in progress times out, on the room clock.
+    pub deadline_in_ms: Option<u64>,
+    pub paused: bool,
+    pub question: Option<ActiveQuestion>,
+    /// Rounds started in the current game, counting the one in play.
+    pub round: u64,
+    /// Players who have held the floor this round, in the order they took it.
+    pub attempted: Vec<PlayerId>,
+}
 
+/// Question attached to the round currently in play.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActiveQuestion {
+    pub text: String,
+    pub round: u64,
+    pub ts_ms: u64,
+}
+
+/// Judgements the admin can take back, most recent first.
+pub const UNDO_DEPTH: usize = 8;
+
+/// Messages kept per route for replay after a reconnect, oldest dropped first.
+/// The room keeps as many broadcasts for connections that were away or fell behind.
+pub const REPLAY_BUFFER_LEN: usize = 64;
+pub const REPLAY_BUFFER_BYTES: usize = 64 * 1024;
+/// Broadcasts a subscriber may fall behind by before it starts missing them.
+pub const BROADCAST_CAPACITY: usize = 256;
+/// Messages queued for one socket before its client counts as stuck.
+pub const DEFAULT_OUTBOUND_CAPACITY: usize = 256;
+/// Room for a full replay of both buffers on resume, so a reconnect always fits.
+pub const MIN_OUTBOUND_CAPACITY: usize = 2 * REPLAY_BUFFER_LEN;
+
+/// A serialized message on its way to one socket. Cloning shares the buffer, so
+/// the channel, the replay buffer and the websocket frame all hold one copy.
+/// Its MessagePack form is encoded the first time a socket asks for it and kept
+/// alongside, so a replay does not encode it again.
+#[derive(Clone, Debug)]
+pub struct Outbound {
+    json: Utf8Bytes,
+    msgpack: Arc<OnceLock<Option<Bytes>>>,
+}
+
+impl Outbound {
+    pub fn as_str(&self) -> &str {
+        &self.json
+    }
+
+    pub fn json(&self) -> Utf8Bytes {
+        self.json.clone()
+    }
+
+    /// `None` only if the JSON does not convert, which a server message always does.
+    pub fn msgpack(&self) -> Option<Bytes> {
+        self.msgpack
+            .get_or_init(|| {
+                let value: serde_json::Value = serde_json::from_str(&self.json).ok()?;
+                rmp_serde::to_vec_named(&value).ok().map(Bytes::from)
+            })
+            .clone()
+    }
+}
+
+impl From<String> for Outbound {
+    fn from(json: String) -> Self {
+        Self {
+            json: json.into(),
+            msgpack: Arc::default(),
+        }
+    }
+}
+
+impl From<&str> for Outbound {
+    fn from(json: &str) -> Self {
+        json.to_string().into()
+    }
+}
+
+impl Deref for Outbound {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.json
+    }
+}
+
+impl PartialEq for Outbound {
+    fn eq(&self, other: &Self) -> bool {
+        self.json == other.json
+    }
+}
+
+impl PartialEq<&str> for Outbound {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+/// A room-wide message, serialized once for every connection. `index` counts
+/// the room's broadcasts so a route can tell which ones it already has.
+#[derive(Clone, Debug)]
+pub struct Broadcast {
+    index: u64,
+    payload: Arc<str>,
+}
+
+/// Fans room-wide messages out over a `broadcast` channel. Every connection
+/// subscribes and copies what it receives onto its own [`Route`], so a slow
+/// client only ever holds up itself.
+pub struct Broadcaster {
+    /// Held while sending so `index` order matches channel order.
+    inner: Mutex<BroadcasterInner>,
+}
+
+struct BroadcasterInner {
+    tx: broadcast::Sender<Broadcast>,
+    next_index: u64,
+    recent: VecDeque<Broadcast>,
+}
+
+impl Default for Broadcaster {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(BROADCAST_CAPACITY);
+        Self {
+            inner: Mutex::new(BroadcasterInner {
+                tx,
+                next_index: 0,
+                recent: VecDeque::new(),
+            }),
+        }
+    }
+}
+
+impl Broadcaster {
+    pub fn send(&self, msg: &ServerMessage) {
+        let payload = serde_json::to_string(msg).expect("serialize server message");
+        self.send_serialized(payload.into());
+    }
+
+    /// Like [`send`](Self::send), for a message serialized ahead of time.
+    pub fn send_serialized(&self, payload: Arc<str>) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        let broadcast = Broadcast {
+            index: inner.next_index,
+            payload,
+        };
+        inner.next_index += 1;
+        if inner.recent.len() == REPLAY_BUFFER_LEN {
+            inner.recent.pop_front();
+        }
+        inner.recent.push_back(broadcast.clone());
+        // Nobody connected is fine; the recent buffer still has it.
+        let _ = inner.tx.send(broadcast);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Broadcast> {
+        self.inner.lock().expect("lock broadcaster").tx.subscribe()
+    }
+
+    fn next_index(&self) -> u64 {
+        self.inner.lock().map_or(0, |inner| inner.next_index)
+    }
+
+    /// Every broadcast from `index` on, or `None` if some were already dropped.
+    fn since(&self, index: u64) -> Option<Vec<Broadcast>> {
+        let inner = self.inner.lock().ok()?;
+        let oldest = inner.recent.front().map_or(inner.next_index, |b| b.index);
+        if oldest > index {
+            return None;
+        }
+        Some(
+            inner
+                .recent
+                .iter()
+                .filter(|b| b.index >= index)
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// One client's outbound channel. Every message carries a `seq` that starts at 0
+/// and increases by one. A fresh attach creates a new route, so it starts over;
+/// a reconnect that can be replayed from the buffer keeps counting instead.
+///
+/// The channel to the socket is bounded. A client that stops reading until it
+/// fills up is cut off rather than buffered for: the route drops its sender,
+/// which closes the socket, and keeps buffering as if the client had
+/// disconnected, so a reconnect with `since_seq` resumes as usual.
+pub struct Route {
+    /// Held while sending so concurrent senders cannot reorder `seq` on the wire.
+    inner: Mutex<RouteInner>,
+}
+
+struct RouteInner {
+    next_seq: u64,
+    /// Index of the next room broadcast this route has not delivered yet.
+    next_broadcast: u64,
+    /// `None` while the client is disconnected; messages are still buffered.
+    tx: Option<mpsc::Sender<Outbound>>,
+    recent: VecDeque<(u64, Outbound)>,
+    recent_bytes: usize,
+}
+
+impl RouteInner {
+    /// Stamps the next `seq` onto a serialized message, then sends and buffers it.
+    fn push(&mut self, payload: &str) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        // Every server message is a JSON object, so the envelope goes in front
+        // of its fields.
+        let payload = Outbound::from(format!(
+            "{{\"v\":{PROTOCOL_VERSION},\"seq\":{seq},{}",
+            &payload[1..]
+        ));
+        if let Some(tx) = &self.tx
+            && tx.try_send(payload.clone()).is_err()
+        {
+            // Full means the client stopped reading; closed means it is gone.
+            self.tx = None;
+        }
+        self.recent_bytes += payload.len();
+        self.recent.push_back((seq, payload));
+        while self.recent.len() > REPLAY_BUFFER_LEN || self.recent_bytes > REPLAY_BUFFER_BYTES {
+            let Some((_, dropped)) = self.recent.pop_front() else {
+                break;
+            };
+            self.recent_bytes -= dropped.len();
+        }
+    }
+
+    /// Tells the current socket it has been superseded and lets go of it. Sent
+    /// outside the `seq` stream: it is that socket's last message and must never
+    /// be replayed to the connection taking over.
+    fn displace(&mut self) {
+        if let Some(old) = self.tx.take() {
+            let replaced =
+                serde_json::to_string(&ServerMessage::Replaced).expect("serialize server message");
+            let _ = old.try_send(replaced.into());
+        }
+    }
+
+    fn deliver(&mut self, broadcast: &Broadcast) {
+        if broadcast.index < self.next_broadcast {
+            return;
+        }
+        self.next_broadcast = broadcast.index + 1;
+        self.push(&broadcast.payload);
+    }
+}
+
+impl Route {
+    /// Starts with the broadcasts sent from now on; earlier ones are covered by
+    /// the welcome a fresh attach sends.
+    pub fn new(tx: mpsc::Sender<Outbound>, broadcasts: &Broadcaster) -> Self {
+        Self {
+            inner: Mutex::new(RouteInner {
+                next_seq: 0,
+                next_broadcast: broadcasts.next_index(),
+                tx: Some(tx),
+                recent: VecDeque::new(),
+                recent_bytes: 0,
+            }),
+        }
+    }
+
+    /// Sends to this client only.
+    pub fn send(&self, msg: &ServerMessage) {
+        let payload = serde_json::to_string(msg).expect("serialize server message");
+        self.send_serialized(&payload);
+    }
+
+    /// Like [`send`](Self::send), for a message serialized ahead of time.
+    pub fn send_serialized(&self, payload: &str) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.push(payload);
+        }
+    }
+
+    /// Passes on a room broadcast, unless this route already has it.
+    pub fn deliver(&self, broadcast: &Broadcast) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.deliver(broadcast);
+        }
+    }
+
+    /// Delivers the broadcasts this route missed, e.g. after its subscriber
+    /// lagged. Returns `false` when some are no longer available.
+    pub fn catch_up(&self, broadcasts: &Broadcaster) -> bool {
+        let Ok(mut inner) = self.inner.lock() else {
+            return false;
+        };
+        let Some(missed) = broadcasts.since(inner.next_broadcast) else {
+            return false;
+        };
+        for broadcast in &missed {
+            inner.deliver(broadcast);
+        }
+        true
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.inner
+            .lock()
+            .is_ok_and(|inner| inner.tx.as_ref().is_some_and(|tx| !tx.is_closed()))
+    }
+
+    /// Stop delivering but keep counting and buffering, so a reconnect can resume.
+    /// Only applies while `tx` is still the route's socket; one that was already
+    /// replaced has nothing left to detach.
+    pub fn detach(&self, tx: &mpsc::WeakSender<Outbound>) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        let current = inner
+            .tx
+            .as_ref()
+            .zip(tx.upgrade())
+            .is_some_and(|(current, tx)| current.same_channel(&tx));
+        if current {
+            inner.tx = None;
+        }
+    }
+
+    /// Sends `replaced` to the connected socket, if any, and closes it.
+    pub fn displace(&self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.displace();
+        }
+    }
+
+    /// Replays every buffered message after `since_seq` to `tx`, followed by the
+    /// room broadcasts sent while disconnected, and delivers live traffic there
+    /// from now on. Returns `false`, leaving the route untouched, when any of that
+    /// has already been dropped or would not fit in `tx`.
+    pub fn resume(
+        &self,
+        tx: &mpsc::Sender<Outbound>,
+        since_seq: u64,
+        broadcasts: &Broadcaster,
+    ) -> bool {
+        let Ok(mut inner) = self.inner.lock() else {
+            return false;
+        };
+        if since_seq >= inner.next_seq {
+            return false;
+        }
+        let oldest = inner.recent.front().map_or(inner.next_seq, |(seq, _)| *seq);
+        if oldest > since_seq + 1 {
+            return false;
+        }
+        let Some(missed) = broadcasts.since(inner.next_broadcast) else {
+            return false;
+        };
+        let replayed = inner
+            .recent
+            .iter()
+            .filter(|(seq, _)| *seq > since_seq)
+            .count();
+        if replayed + missed.len() > tx.capacity() {
+            return false;
+        }
+        inner.displace();
+        for (_, payload) in inner.recent.iter().filter(|(seq, _)| *seq > since_seq) {
+            let _ = tx.try_send(payload.clone());
+        }
+        inner.tx = Some(tx.clone());
+        for broadcast in &missed {
+            inner.deliver(broadcast);
+        }
+        true
+    }
+}
+
+impl From<ActiveQuestion> for ServerMessage {
+    fn from(question: ActiveQuestion) -> Self {
+        ServerMessage::Question {
+            text: question.text,
+            round: question.round,
+            ts_ms: question.ts_ms,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn spawn_room_loop(
-    tick_in_ms: u64,
+    time: InstantTime,
     answer_window_in_ms: u64,
-    buzz_rx: mpsc::UnboundedReceiver<PlayerId>,
-    reset_flag: Arc<AtomicBool>,
-    continue_flag: Arc<AtomicBool>,
-    shutdown: Arc<AtomicBool>,
-    locked_out_mask: Arc<Mutex<u128>>,
-    routes: Arc<DashMap<PlayerId, mpsc::UnboundedSender<String>>>,
+    buzz_rx: mpsc::UnboundedReceiver<Buzz>,
+    control_rx: mpsc::UnboundedReceiver<RoomControl>,
+    view_tx: watch::Sender<GameView>,
+    routes: Arc<DashMap<PlayerId, Route>>,
+    broadcaster: Arc<Broadcaster>,
     names_by_id: Arc<DashMap<PlayerId, String>>,
-    next_id: Arc<Mutex<PlayerId>>,
+    scores: Arc<DashMap<PlayerId, u32>>,
+    history: Arc<Mutex<RoundHistory>>,
+    admin_id: Arc<Mutex<PlayerId>>,
+    webhook: Option<RoomWebhook>,
 ) {
-    tokio::spawn(async move {
-        let mut game = BuzzerGame::new(Config {
-            answer_window_in_ms,
-        });
-        let mut interval = time::interval(time::Duration::from_millis(tick_in_ms));
-        let time = InstantTime {
-            start: Instant::now(),
-        };
-        let mut input = ChannelInput {
-            rx: buzz_rx,
-            next_player_id: next_id,
-        };
-        let mut output = RoutedOutput {
-            routes,
-            names_by_id,
-        };
+    let mut room = RoomLoop::new(
+        time,
+        answer_window_in_ms,
+        buzz_rx,
+        routes,
+        broadcaster,
+        names_by_id,
+        scores,
+        history,
+        admin_id,
+    );
+    room.output.webhook = webhook;
+    tokio::spawn(run_room_loop(room, control_rx, view_tx));
+}
 
-        loop {
-            interval.tick().await;
-            if shutdown.load(Ordering::SeqCst) {
-                break;
+/// Drives `room` until it is shut down or dropped. The loop only wakes for a
+/// buzz, a control or the next countdown/answer deadline, so an idle room costs
+/// nothing between events.
+async fn run_room_loop<C: RoomClock>(
+    mut room: RoomLoop<C>,
+    mut control_rx: mpsc::UnboundedReceiver<RoomControl>,
+    view_tx: watch::Sender<GameView>,
+) {
+    loop {
+        // Re-armed every iteration: any event may have moved the next deadline.
+        let wake_in_ms = room
+            .next_wakeup_ms()
+            .map(|at| at.saturating_sub(room.time.now_ms()));
+        // Buzzes are handled as soon as they arrive.
+        tokio::select! {
+            _ = sleep_for(wake_in_ms) => room.on_tick().await,
+            control = control_rx.recv() => match control {
+                Some(RoomControl::Shutdown { reason }) => {
+                    room.close(reason);
+                    break;
+                }
+                Some(control) => room.on_control(control).await,
+                None => break,
+            },
+            _ = async_adapter::step_async(&mut room.game, &room.time, &mut room.input, &mut room.output) => {
+                // Every sender is gone: the room was dropped.
+                if room.input.rx.is_closed() && room.input.rx.is_empty() {
+                    break;
+                }
             }
-            if reset_flag.swap(false, Ordering::SeqCst) {
-                adapter::start_round(&mut game, &input, &mut output);
+        }
+        view_tx.send_replace(room.view());
+    }
+}
+
+/// Sleeps `ms`, or forever when nothing is scheduled.
+async fn sleep_for(ms: Option<u64>) {
+    match ms {
+        Some(ms) => time::sleep(Duration::from_millis(ms)).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// The room's game clock; it stands still while the room is paused. Clones
+/// share one clock, so the output reads the same time the game runs on.
+trait RoomClock: TimeSource + Clone + Send + 'static {
+    fn is_paused(&self) -> bool;
+    fn pause(&mut self);
+    fn resume(&mut self);
+}
+
+struct RoomLoop<C: RoomClock> {
+    game: BuzzerGame,
+    time: C,
+    input: ChannelInput,
+    output: RoutedOutput,
+    /// While a countdown runs, nobody is active so every buzz is rejected as a
+    /// false start; the round starts for real at this time.
+    arm_at_ms: Option<u64>,
+    /// Question for a round still counting down.
+    pending_question: Option<String>,
+    question: Option<ActiveQuestion>,
+    pending_answer_window: Option<u64>,
+    /// Controls received while paused, replayed on resume.
+    deferred: VecDeque<RoomControl>,
+    active_before_pause: u128,
+    /// The last [`UNDO_DEPTH`] answers the admin judged, newest at the back.
+    judgements: VecDeque<Judgement>,
+}
+
+/// An answer marked correct or wrong, kept so it can be taken back.
+struct Judgement {
+    player_id: PlayerId,
+    result: AnswerResult,
+    round: u64,
+    /// When buzzing had opened before the answer was judged.
+    open_since_ms: Option<u64>,
+}
+
+impl<C: RoomClock> RoomLoop<C> {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        time: C,
+        answer_window_in_ms: u64,
+        buzz_rx: mpsc::UnboundedReceiver<Buzz>,
+        routes: Arc<DashMap<PlayerId, Route>>,
+        broadcaster: Arc<Broadcaster>,
+        names_by_id: Arc<DashMap<PlayerId, String>>,
+        scores: Arc<DashMap<PlayerId, u32>>,
+        history: Arc<Mutex<RoundHistory>>,
+        admin_id: Arc<Mutex<PlayerId>>,
+    ) -> Self {
+        let pending_ack = PendingAck::default();
+        Self {
+            game: BuzzerGame::new(Config {
+                answer_window_in_ms,
+                // Deliberately the bitmask's 128 seats, never the spill path:
+                // player and spectator ids and the room's `u128` views of the
+                // game all assume rooms that small (see `MAX_PLAYERS`).
+                max_players: MAX_PLAYER_ID + 1,
+            }),
+            input: ChannelInput {
+                rx: buzz_rx,
+                names_by_id: Arc::clone(&names_by_id),
+                pending_ack: Arc::clone(&pending_ack),
+            },
+            output: RoutedOutput {
+                routes,
+                broadcaster,
+                names_by_id,
+                scores,
+                history,
+                admin_id,
+                clock: Box::new(time.clone()),
+                round: 0,
+                open_since_ms: None,
+                attempted: Vec::new(),
+                webhook: None,
+                pending_ack,
+            },
+            time,
+            arm_at_ms: None,
+            pending_question: None,
+            question: None,
+            pending_answer_window: None,
+            deferred: VecDeque::new(),
+            active_before_pause: 0,
+            judgements: VecDeque::new(),
+        }
+    }
+
+    /// Handles every buzz already queued, without waiting for more.
+    #[cfg(test)]
+    fn step(&mut self) {
+        core::adapter::step(
+            &mut self.game,
+            &self.time,
+            &mut self.input,
+            &mut self.output,
+        );
+    }
+
+    /// Room-clock time of the next countdown end or answer deadline. Nothing
+    /// falls due while the clock is paused.
+    fn next_wakeup_ms(&self) -> Option<u64> {
+        if self.time.is_paused() {
+            return None;
+        }
+        [self.arm_at_ms, self.game.deadline_in_ms()]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    async fn on_tick(&mut self) {
+        if self.arm_at_ms.is_some_and(|at| self.time.now_ms() >= at) {
+            self.arm_at_ms = None;
+            let question = self.pending_question.take();
+            self.start_round(question).await;
+        }
+        async_adapter::tick_async(&mut self.game, &self.time, &mut self.output).await;
+    }
+
+    async fn on_control(&mut self, control: RoomControl) {
+        match control {
+            RoomControl::Query(resp) => {
+                let _ = resp.send(self.view());
+            }
+            RoomControl::Pause => {
+                if !self.time.is_paused() {
+                    self.time.pause();
+                    self.active_before_pause = self.game.active_players();
+                    self.game.set_active_players(0);
+                    self.output.broadcast(ServerMessage::Paused);
+                }
+            }
+            RoomControl::Resume => {
+                if self.time.is_paused() {
+                    self.time.resume();
+                    self.game.set_active_players(self.active_before_pause);
+                    self.output.broadcast(ServerMessage::Resumed);
+                    while let Some(control) = self.deferred.pop_front() {
+                        Box::pin(self.on_control(control)).await;
+                    }
+                }
+            }
+            RoomControl::RestoreRound { round } => {
+                self.output.round = round;
+            }
+            control if self.time.is_paused() => self.deferred.push_back(control),
+            RoomControl::StartRound {
+                countdown_ms,
+                question,
+            } => {
+                if countdown_ms > 0 {
+                    self.abort_answer();
+                    self.game.set_active_players(0);
+                    self.output.open_since_ms = None;
+                    self.arm_at_ms = Some(self.time.now_ms() + countdown_ms);
+                    self.pending_question = question;
+                    let ts_ms = now_millis();
+                    self.output.broadcast(ServerMessage::Countdown {
+                        starts_in_ms: countdown_ms,
+                        starts_at_ms: ts_ms + countdown_ms,
+                        ts_ms,
+                    });
+                } else {
+                    self.arm_at_ms = None;
+                    self.pending_question = None;
+                    self.start_round(question).await;
+                }
+            }
+            RoomControl::ContinueRound => {
+                if self.game.answering_player().is_some() {
+                    self.output.resolve_round(RoundOutcome::Wrong, None);
+                }
+                self.remember_judgement(AnswerResult::Wrong);
+                async_adapter::continue_round_async(&mut self.game, &mut self.output).await;
             }
-            if continue_flag.swap(false, Ordering::SeqCst) {
-                adapter::continue_round(&mut game, &mut output);
+            RoomControl::MarkCorrect => {
+                self.remember_judgement(AnswerResult::Correct);
+                async_adapter::correct_answer_async(&mut self.game, &mut self.output).await;
             }
-            adapter::step(&mut game, &time, &mut input, &mut output);
-            if let Ok(mut mask) = locked_out_mask.lock() {
-                *mask = game.locked_out_players();
+            RoomControl::Undo {
+                requester_id,
+                ack_id,
+            } => {
+                let result = match self.judgements.pop_back() {
+                    Some(judgement) => {
+                        self.undo(judgement);
+                        Ok(())
+                    }
+                    None => Err("nothing_to_undo"),
+                };
+                if let Some(msg) = ServerMessage::acknowledgement(ack_id, result) {
+                    self.output.send_to(requester_id, &msg);
+                }
+            }
+            RoomControl::Grant {
+                player_id,
+                force,
+                requester_id,
+                ack_id,
+            } => {
+                let result = if self.arm_at_ms.is_some() {
+                    Err("round_not_open")
+                } else {
+                    let allowed = if force {
+                        player_id < self.game.seats()
+                    } else {
+                        !self.game.is_locked_out(player_id)
+                    };
+                    // Whoever held the floor loses it unjudged.
+                    if allowed {
+                        self.abort_answer();
+                    }
+                    match self.game.grant(player_id, self.time.now_ms(), force) {
+                        event @ OutputEvent::Accepted(..) => {
+                            GameOutputAsync::on_event(&mut self.output, event).await;
+                            Ok(())
+                        }
+                        _ if !force && self.game.is_locked_out(player_id) => Err("locked_out"),
+                        _ => Err("round_over"),
+                    }
+                };
+                if let Some(msg) = ServerMessage::acknowledgement(ack_id, result) {
+                    self.output.send_to(requester_id, &msg);
+                }
+            }
+            RoomControl::SetAnswerWindow {
+                answer_window_in_ms,
+            } => {
+                self.pending_answer_window = Some(answer_window_in_ms);
+            }
+            RoomControl::NewGame => {
+                self.abort_answer();
+                self.game.new_game();
+                self.arm_at_ms = None;
+                self.pending_question = None;
+                self.question = None;
+                self.output.round = 0;
+                self.output.open_since_ms = None;
+                self.output.scores.clear();
+                self.output.attempted.clear();
+                self.judgements.clear();
+                self.output.broadcast(ServerMessage::GameReset);
+            }
+            // Handled by the loop itself.
+            RoomControl::Shutdown { .. } => {}
+        }
+    }
+
+    /// Ends the answer in progress, if any, unjudged, e.g. because a new round
+    /// is on its way.
+    fn abort_answer(&mut self) {
+        if self.game.abort_answer().is_some() {
+            self.output.record(RoundHistory::discard);
+            self.output.resolve_round(RoundOutcome::Aborted, None);
+        }
+    }
+
+    /// Notes who the admin is about to judge; nothing is noted when nobody
+    /// holds the floor.
+    fn remember_judgement(&mut self, result: AnswerResult) {
+        let Some(player_id) = self.game.answering_player() else {
+            return;
+        };
+        if self.judgements.len() == UNDO_DEPTH {
+            self.judgements.pop_front();
+        }
+        self.judgements.push_back(Judgement {
+            player_id,
+            result,
+            round: self.output.round,
+            open_since_ms: self.output.open_since_ms,
+        });
+    }
+
+    /// Reverts the score a correct answer earned and, while its round is still
+    /// in play, the lockout and the floor it cost.
+    fn undo(&mut self, judgement: Judgement) {
+        let name = self.output.name_for(judgement.player_id);
+        let in_play = judgement.round == self.output.round && self.arm_at_ms.is_none();
+        let deadline = in_play
+            .then(|| self.game.reinstate(judgement.player_id, self.time.now_ms()))
+            .flatten()
+            .and_then(|event| match event {
+                OutputEvent::Accepted(_, deadline) => Some(deadline),
+                _ => None,
+            });
+        let reinstated = deadline.is_some();
+        if in_play {
+            self.output.record(|history| {
+                history.retract(&name, judgement.result, reinstated);
+            });
+        }
+        self.output.broadcast(ServerMessage::Undone {
+            name: name.clone(),
+            round: judgement.round,
+        });
+        if judgement.result == AnswerResult::Correct {
+            if let Some(mut score) = self.output.scores.get_mut(&judgement.player_id) {
+                *score = score.saturating_sub(1);
             }
+            self.output.broadcast(ServerMessage::Scoreboard {
+                entries: build_scoreboard(&self.output.names_by_id, &self.output.scores),
+                ts_ms: now_millis(),
+                round: self.output.round,
+            });
+        }
+        if let Some(deadline) = deadline {
+            self.output.open_since_ms = judgement.open_since_ms;
+            let remaining_ms = deadline.saturating_sub(self.time.now_ms());
+            self.output.broadcast(ServerMessage::Accepted {
+                name,
+                round: self.output.round,
+                deadline_ms: now_millis() + remaining_ms,
+                remaining_ms,
+            });
+        }
+    }
+
+    async fn start_round(&mut self, question: Option<String>) {
+        if let Some(answer_window_in_ms) = self.pending_answer_window.take() {
+            self.game.set_answer_window(answer_window_in_ms);
         }
-    });
+        self.abort_answer();
+        let active_players = GameInput::active_players(&self.input);
+        async_adapter::start_round_async(&mut self.game, active_players, &mut self.output).await;
+        self.question = question.map(|text| ActiveQuestion {
+            text,
+            round: self.output.round,
+            ts_ms: now_millis(),
+        });
+        if let Some(question) = &self.question {
+            self.output.broadcast(question.clone().into());
+        }
+    }
+
+    fn close(&self, reason: String) {
+        // Sent on each route directly: a broadcast could still be in flight when
+        // the routes are dropped below.
+        let msg = ServerMessage::RoomClosed { reason };
+        for entry in self.output.routes.iter() {
+            entry.value().send(&msg);
+        }
+        // Dropping the senders ends each socket's outbound stream.
+        self.output.routes.clear();
+    }
+
+    fn view(&self) -> GameView {
+        let phase = if self.arm_at_ms.is_some() {
+            GamePhase::Countdown
+        } else if self.game.answering_player().is_some() {
+            GamePhase::Answering
+        } else if self.game.is_resolved() {
+            GamePhase::Resolved
+        } else {
+            GamePhase::Idle
+        };
+        GameView {
+            phase,
+            locked_out: self.game.locked_out_players(),
+            answering: self.game.answering_player(),
+            deadline_in_ms: self.game.deadline_in_ms(),
+            paused: self.time.is_paused(),
+            question: self.question.clone(),
+            round: self.output.round,
+            attempted: self.output.attempted.clone(),
+        }
+    }
 }
 
-struct InstantTime {
+/// Monotonic room clock that stands still while the room is paused, so answer
+/// deadlines and countdowns resume where they left off. Clones share one clock,
+/// so a buzz can be stamped on it as soon as it is received.
+#[derive(Clone)]
+pub struct InstantTime {
+    inner: Arc<Mutex<ClockState>>,
+}
+
+struct ClockState {
     start: Instant,
+    paused_at: Option<Instant>,
+    paused_total: Duration,
+}
+
+impl InstantTime {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(ClockState {
+                start: Instant::now(),
+                paused_at: None,
+                paused_total: Duration::ZERO,
+            })),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ClockState> {
+        self.inner.lock().expect("lock room clock")
+    }
+}
+
+impl RoomClock for InstantTime {
+    fn is_paused(&self) -> bool {
+        self.state().paused_at.is_some()
+    }
+
+    fn pause(&mut self) {
+        self.state().paused_at.get_or_insert_with(Instant::now);
+    }
+
+    fn resume(&mut self) {
+        let mut state = self.state();
+        if let Some(paused_at) = state.paused_at.take() {
+            state.paused_total += paused_at.elapsed();
+        }
+    }
 }
 
 impl TimeSource for InstantTime {
     fn now_ms(&self) -> u64 {
-        self.start.elapsed().as_millis() as u64
+        let state = self.state();
+        let now = state.paused_at.unwrap_or_else(Instant::now);
+        now.saturating_duration_since(state.start)
+            .saturating_sub(state.paused_total)
+            .as_millis() as u64
     }
 }
 
+/// A buzz on its way to the room loop.
+pub struct Buzz {
+    pub player_id: PlayerId,
+    /// The `id` the client tagged it with, echoed on the answer.
+    pub ack_id: Option<String>,
+    /// When it was received, on the room clock; the game counts from here
+    /// rather than from when the loop gets to it.
+    pub received_ms: u64,
+}
+
+/// The `ack_id` of the buzz the game is handling, left by [`ChannelInput`] for
+/// [`RoutedOutput`]: the game answers every buzz before it takes the next one.
+type PendingAck = Arc<Mutex<Option<(PlayerId, String)>>>;
+
 struct ChannelInput {
-    rx: mpsc::UnboundedReceiver<PlayerId>,
-    next_player_id: Arc<Mutex<PlayerId>>,
+    rx: mpsc::UnboundedReceiver<Buzz>,
+    names_by_id: Arc<DashMap<PlayerId, String>>,
+    pending_ack: PendingAck,
+}
+
+impl ChannelInput {
+    fn take(&self, buzz: Buzz) -> (PlayerId, u64) {
+        let pending = buzz.ack_id.map(|id| (buzz.player_id, id));
+        *self.pending_ack.lock().expect("lock pending ack") = pending;
+        (buzz.player_id, buzz.received_ms)
+    }
 }
 
 impl GameInput for ChannelInput {
-    fn next_buzz(&mut self) -> Option<PlayerId> {
-        self.rx.try_recv().ok()
+    fn next_buzz(&mut self) -> Option<(PlayerId, u64)> {
+        let buzz = self.rx.try_recv().ok()?;
+        Some(self.take(buzz))
     }
 
-    fn current_player_count(&self) -> PlayerId {
-        *self.next_player_id.lock().expect("next_id lock")
+    fn active_players(&self) -> u128 {
+        player_mask(self.names_by_id.iter().map(|entry| *entry.key()))
+    }
+}
+
+impl GameInputAsync for ChannelInput {
+    async fn next_buzz(&mut self) -> Option<(PlayerId, u64)> {
+        let buzz = self.rx.recv().await?;
+        Some(self.take(buzz))
+    }
+
+    fn active_players(&self) -> u128 {
+        GameInput::active_players(self)
     }
 }
 
 struct RoutedOutput {
-    routes: Arc<DashMap<PlayerId, mpsc::UnboundedSender<String>>>,
+    /// For messages meant for one player only.
+    routes: Arc<DashMap<PlayerId, Route>>,
+    broadcaster: Arc<Broadcaster>,
     names_by_id: Arc<DashMap<PlayerId, String>>,
+    scores: Arc<DashMap<PlayerId, u32>>,
+    history: Arc<Mutex<RoundHistory>>,
+    /// Receives a `buzz_detail` for every buzz.
+    admin_id: Arc<Mutex<PlayerId>>,
+    /// The room clock the game's deadlines are on.
+    clock: Box<dyn TimeSource + Send>,
+    /// Bumped on every `RoundStarted` and stamped on round-scoped messages.
+    round: u64,
+    /// When buzzing last opened (round start or continue), epoch ms; `None`
+    /// while it is closed.
+    open_since_ms: Option<u64>,
+    /// Who has held the floor since the round started, first first.
+    attempted: Vec<PlayerId>,
+    webhook: Option<RoomWebhook>,
+    pending_ack: PendingAck,
 }
 
 impl GameOutput for RoutedOutput {
     fn on_event(&mut self, event: OutputEvent) {
         match event {
-            OutputEvent::Accepted(player_id, _) => {
+            OutputEvent::Accepted(player_id, deadline) => {
+                if !self.attempted.contains(&player_id) {
+                    self.attempted.push(player_id);
+                }
                 let name = self.name_for(player_id);
-                let msg = ServerMessage::Accepted { name };
+                self.record(|history| history.accepted(name.clone(), now_millis()));
+                // The deadline runs from when the buzz was received, which may
+                // be a little before the loop got to it; clients get wall time.
+                let remaining_ms = deadline.saturating_sub(self.clock.now_ms());
+                let msg = ServerMessage::Accepted {
+                    name,
+                    round: self.round,
+                    deadline_ms: now_millis() + remaining_ms,
+                    remaining_ms,
+                };
                 self.broadcast(msg);
+                if let Some(id) = self.take_ack(player_id) {
+                    self.send_to(player_id, &ServerMessage::ActionOk { id });
+                }
+                self.send_buzz_detail(player_id, true);
             }
             OutputEvent::Rejected(player_id) => {
-                if let Some(tx) = self
-                    .routes
-                    .get(&player_id)
-                    .map(|entry| entry.value().clone())
-                {
-                    let _ = tx.send(serialize(ServerMessage::Rejected));
-                }
+                let id = self.take_ack(player_id);
+                self.send_to(player_id, &ServerMessage::Rejected { id });
+                self.send_buzz_detail(player_id, false);
             }
             OutputEvent::TimedOut(player_id) => {
+                self.record(|history| history.resolve(AnswerResult::TimedOut));
                 let name = self.name_for(player_id);
                 let msg = ServerMessage::TimedOut { name };
                 self.broadcast(msg);
+                self.resolve_round(RoundOutcome::TimedOut, None);
+            }
+            OutputEvent::Correct(player_id) => {
+                let score = {
+                    let mut score = self.scores.entry(player_id).or_insert(0);
+                    *score += 1;
+                    *score
+                };
+                self.record(|history| history.resolve(AnswerResult::Correct));
+                self.open_since_ms = None;
+                let name = self.name_for(player_id);
+                if let Some(webhook) = &self.webhook {
+                    webhook.round_resolved(self.round, name.clone(), score);
+                }
+                self.broadcast(ServerMessage::Correct { name: name.clone() });
+                self.resolve_round(RoundOutcome::Correct, Some(name));
+                self.broadcast(ServerMessage::Scoreboard {
+                    entries: build_scoreboard(&self.names_by_id, &self.scores),
+                    ts_ms: now_millis(),
+                    round: self.round,
+                });
             }
             OutputEvent::RoundStarted => {
-                let msg = ServerMessage::RoundStarted;
+                self.round += 1;
+                self.attempted.clear();
+                self.open_since_ms = Some(now_millis());
+                self.record(|history| history.start_round(now_millis()));
+                let msg = ServerMessage::RoundStarted { round: self.round };
                 self.broadcast(msg);
             }
             OutputEvent::RoundContinued => {
+                // Continuing past an answer means the host judged it wrong.
+                self.open_since_ms = Some(now_millis());
+                self.record(|history| {
+                    history.resolve(AnswerResult::Wrong);
+                    history.reopen(now_millis());
+                });
                 let msg = ServerMessage::RoundContinued;
                 self.broadcast(msg);
             }
@@ -126,7 +1103,30 @@ impl GameOutput for RoutedOutput {
     }
 }
 
+impl GameOutputAsync for RoutedOutput {
+    async fn on_event(&mut self, event: OutputEvent) {
+        // Sending never has to wait: routes hand messages over with `try_send`,
+        // and a client whose queue is full is cut off instead of waited for.
+        GameOutput::on_event(self, event);
+    }
+}
+
 impl RoutedOutput {
+    fn send_to(&self, player: PlayerId, msg: &ServerMessage) {
+        if let Some(route) = self.routes.get(&player) {
+            route.send(msg);
+        }
+    }
+
+    /// The `ack_id` of `player`'s buzz, the one just answered.
+    fn take_ack(&self, player: PlayerId) -> Option<String> {
+        let mut pending = self.pending_ack.lock().expect("lock pending ack");
+        match pending.take() {
+            Some((buzzer, id)) if buzzer == player => Some(id),
+            _ => None,
+        }
+    }
+
     fn name_for(&self, player: PlayerId) -> String {
         self.names_by_id
             .get(&player)
@@ -134,14 +1134,543 @@ impl RoutedOutput {
             .unwrap_or_else(|| "unknown".to_string())
     }
 
-    fn broadcast(&self, msg: ServerMessage) {
-        let payload = serialize(msg);
-        for entry in self.routes.iter() {
-            let _ = entry.value().send(payload.clone());
+    fn record(&self, update: impl FnOnce(&mut RoundHistory)) {
+        if let Ok(mut history) = self.history.lock() {
+            update(&mut history);
         }
     }
+
+    fn broadcast(&self, msg: ServerMessage) {
+        self.broadcaster.send(&msg);
+    }
+
+    /// Announces how the answer holding the floor ended; called once per answer.
+    fn resolve_round(&self, outcome: RoundOutcome, winner: Option<String>) {
+        self.broadcast(ServerMessage::RoundResolved {
+            round: self.round,
+            outcome,
+            winner,
+            ts_ms: now_millis(),
+        });
+    }
+
+    /// Tells the admin, and nobody else, who buzzed and how fast.
+    fn send_buzz_detail(&self, player: PlayerId, accepted: bool) {
+        let admin_id = *self.admin_id.lock().expect("lock admin id");
+        let Some(route) = self.routes.get(&admin_id) else {
+            return;
+        };
+        let ts_ms = now_millis();
+        route.send(&ServerMessage::BuzzDetail {
+            name: self.name_for(player),
+            accepted,
+            reaction_ms: self.open_since_ms.map(|since| ts_ms.saturating_sub(since)),
+            ts_ms,
+        });
+    }
 }
 
-fn serialize(msg: ServerMessage) -> String {
-    serde_json::to_string(&msg).expect("serialize server message")
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::block_on;
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+    /// A clock that only moves when the test says so.
+    #[derive(Clone, Default)]
+    struct MockTime {
+        now_ms: Arc<AtomicU64>,
+        paused: Arc<AtomicBool>,
+    }
+
+    impl MockTime {
+        fn advance(&mut self, ms: u64) {
+            if !self.is_paused() {
+                self.now_ms.fetch_add(ms, Ordering::SeqCst);
+            }
+        }
+    }
+
+    impl TimeSource for MockTime {
+        fn now_ms(&self) -> u64 {
+            self.now_ms.load(Ordering::SeqCst)
+        }
+    }
+
+    impl RoomClock for MockTime {
+        fn is_paused(&self) -> bool {
+            self.paused.load(Ordering::SeqCst)
+        }
+
+        fn pause(&mut self) {
+            self.paused.store(true, Ordering::SeqCst);
+        }
+
+        fn resume(&mut self) {
+            self.paused.store(false, Ordering::SeqCst);
+        }
+    }
+
+    const ADMIN: PlayerId = 0;
+    const BOB: PlayerId = 1;
+
+    fn untagged(player_id: PlayerId, received_ms: u64) -> Buzz {
+        Buzz {
+            player_id,
+            ack_id: None,
+            received_ms,
+        }
+    }
+
+    /// A room with Bob seated, plus a subscription to everything it broadcasts.
+    fn room_loop<C: RoomClock>(
+        time: C,
+        answer_window_in_ms: u64,
+    ) -> (
+        RoomLoop<C>,
+        mpsc::UnboundedSender<Buzz>,
+        broadcast::Receiver<Broadcast>,
+    ) {
+        let (buzz_tx, buzz_rx) = mpsc::unbounded_channel();
+        let broadcaster = Arc::new(Broadcaster::default());
+        let broadcasts = broadcaster.subscribe();
+        let names_by_id = Arc::new(DashMap::new());
+        names_by_id.insert(BOB, "Bob".to_string());
+        let room = RoomLoop::new(
+            time,
+            answer_window_in_ms,
+            buzz_rx,
+            Arc::new(DashMap::new()),
+            broadcaster,
+            names_by_id,
+            Arc::new(DashMap::new()),
+            Arc::new(Mutex::new(RoundHistory::new(10))),
+            Arc::new(Mutex::new(ADMIN)),
+        );
+        (room, buzz_tx, broadcasts)
+    }
+
+    fn mock_room(
+        answer_window_in_ms: u64,
+    ) -> (
+        RoomLoop<MockTime>,
+        mpsc::UnboundedSender<Buzz>,
+        broadcast::Receiver<Broadcast>,
+    ) {
+        room_loop(MockTime::default(), answer_window_in_ms)
+    }
+
+    fn message_type(broadcast: &Broadcast) -> String {
+        let msg: serde_json::Value = serde_json::from_str(&broadcast.payload).unwrap();
+        msg["type"].as_str().unwrap().to_string()
+    }
+
+    fn drain_types(rx: &mut broadcast::Receiver<Broadcast>) -> Vec<String> {
+        let mut types = Vec::new();
+        while let Ok(broadcast) = rx.try_recv() {
+            types.push(message_type(&broadcast));
+        }
+        types
+    }
+
+    #[test]
+    fn advancing_past_the_deadline_times_out() {
+        block_on(async {
+            let (mut room, buzz_tx, mut rx) = mock_room(1000);
+            room.on_control(RoomControl::StartRound {
+                countdown_ms: 0,
+                question: None,
+            })
+            .await;
+            room.time.advance(250);
+            buzz_tx.send(untagged(BOB, room.time.now_ms())).unwrap();
+            room.step();
+            assert_eq!(drain_types(&mut rx), ["round_started", "accepted"]);
+
+            room.time.advance(999);
+            room.on_tick().await;
+            assert!(drain_types(&mut rx).is_empty());
+
+            room.time.advance(1);
+            room.on_tick().await;
+            assert_eq!(drain_types(&mut rx), ["timed_out", "round_resolved"]);
+            assert_ne!(room.view().locked_out & player_mask([BOB]), 0);
+        });
+    }
+
+    #[test]
+    fn countdown_takes_the_floor_from_an_answer_in_progress() {
+        block_on(async {
+            let (mut room, buzz_tx, mut rx) = mock_room(1000);
+            room.on_control(RoomControl::StartRound {
+                countdown_ms: 0,
+                question: None,
+            })
+            .await;
+            buzz_tx.send(untagged(BOB, room.time.now_ms())).unwrap();
+            room.step();
+            assert_eq!(drain_types(&mut rx), ["round_started", "accepted"]);
+
+            room.on_control(RoomControl::StartRound {
+                countdown_ms: 3000,
+                question: None,
+            })
+            .await;
+            assert_eq!(drain_types(&mut rx), ["round_resolved", "countdown"]);
+            assert_eq!(room.view().answering, None);
+
+            // Bob's deadline passes during the countdown without a word.
+            room.time.advance(1000);
+            room.on_tick().await;
+            assert!(drain_types(&mut rx).is_empty());
+            room.time.advance(2000);
+            room.on_tick().await;
+            assert_eq!(drain_types(&mut rx), ["round_started"]);
+            // Never judged, so never locked out.
+            buzz_tx.send(untagged(BOB, room.time.now_ms())).unwrap();
+            room.step();
+            assert_eq!(drain_types(&mut rx), ["accepted"]);
+        });
+    }
+
+    #[test]
+    fn paused_clock_holds_the_deadline() {
+        block_on(async {
+            let (mut room, buzz_tx, mut rx) = mock_room(1000);
+            room.on_control(RoomControl::StartRound {
+                countdown_ms: 0,
+                question: None,
+            })
+            .await;
+            buzz_tx.send(untagged(BOB, room.time.now_ms())).unwrap();
+            room.step();
+            room.on_control(RoomControl::Pause).await;
+            room.time.advance(5000);
+            room.on_tick().await;
+            assert_eq!(
+                drain_types(&mut rx),
+                ["round_started", "accepted", "paused"]
+            );
+
+            room.on_control(RoomControl::Resume).await;
+            room.time.advance(1000);
+            room.on_tick().await;
+            assert_eq!(
+                drain_types(&mut rx),
+                ["resumed", "timed_out", "round_resolved"]
+            );
+        });
+    }
+
+    /// The `(outcome, winner)` of every `round_resolved` broadcast so far.
+    fn drain_resolutions(rx: &mut broadcast::Receiver<Broadcast>) -> Vec<(String, Option<String>)> {
+        let mut resolutions = Vec::new();
+        while let Ok(broadcast) = rx.try_recv() {
+            let msg: serde_json::Value = serde_json::from_str(&broadcast.payload).unwrap();
+            if msg["type"] == "round_resolved" {
+                resolutions.push((
+                    msg["outcome"].as_str().unwrap().to_string(),
+                    msg["winner"].as_str().map(str::to_string),
+                ));
+            }
+        }
+        resolutions
+    }
+
+    #[test]
+    fn every_way_an_answer_ends_resolves_it_once() {
+        block_on(async {
+            let (mut room, buzz_tx, mut rx) = mock_room(1000);
+            room.output.names_by_id.insert(BOB, "Bob".to_string());
+            let start = RoomControl::StartRound {
+                countdown_ms: 0,
+                question: None,
+            };
+            room.on_control(start).await;
+            let buzz = |room: &mut RoomLoop<MockTime>| {
+                buzz_tx.send(untagged(BOB, room.time.now_ms())).unwrap();
+                room.step();
+            };
+
+            buzz(&mut room);
+            room.on_control(RoomControl::MarkCorrect).await;
+            assert_eq!(
+                drain_resolutions(&mut rx),
+                [("correct".to_string(), Some("Bob".to_string()))]
+            );
+
+            room.on_control(RoomControl::StartRound {
+                countdown_ms: 0,
+                question: None,
+            })
+            .await;
+            buzz(&mut room);
+            room.on_control(RoomControl::ContinueRound).await;
+            assert_eq!(drain_resolutions(&mut rx), [("wrong".to_string(), None)]);
+
+            // Nobody holds the floor, so there is nothing to resolve.
+            room.on_control(RoomControl::ContinueRound).await;
+            assert!(drain_resolutions(&mut rx).is_empty());
+
+            room.on_control(RoomControl::StartRound {
+                countdown_ms: 0,
+                question: None,
+            })
+            .await;
+            buzz(&mut room);
+            room.time.advance(1000);
+            room.on_tick().await;
+            assert_eq!(
+                drain_resolutions(&mut rx),
+                [("timed_out".to_string(), None)]
+            );
+
+            room.on_control(RoomControl::StartRound {
+                countdown_ms: 0,
+                question: None,
+            })
+            .await;
+            buzz(&mut room);
+            room.on_control(RoomControl::StartRound {
+                countdown_ms: 0,
+                question: None,
+            })
+            .await;
+            assert_eq!(drain_resolutions(&mut rx), [("aborted".to_string(), None)]);
+
+            buzz(&mut room);
+            room.on_control(RoomControl::Grant {
+                player_id: BOB,
+                force: true,
+                requester_id: ADMIN,
+                ack_id: None,
+            })
+            .await;
+            assert_eq!(drain_resolutions(&mut rx), [("aborted".to_string(), None)]);
+
+            room.on_control(RoomControl::NewGame).await;
+            assert_eq!(drain_resolutions(&mut rx), [("aborted".to_string(), None)]);
+        });
+    }
+
+    #[test]
+    fn queued_buzz_counts_from_when_it_was_received() {
+        block_on(async {
+            let (mut room, buzz_tx, mut rx) = mock_room(1000);
+            room.on_control(RoomControl::StartRound {
+                countdown_ms: 0,
+                question: None,
+            })
+            .await;
+            buzz_tx.send(untagged(BOB, 100)).unwrap();
+            // The loop only gets to it later; the deadline still runs from 100.
+            room.time.advance(300);
+            room.step();
+            rx.try_recv().unwrap();
+            let accepted: serde_json::Value =
+                serde_json::from_str(&rx.try_recv().unwrap().payload).unwrap();
+            assert_eq!(accepted["type"], "accepted");
+            assert_eq!(accepted["remaining_ms"], 800);
+            assert_eq!(room.next_wakeup_ms(), Some(1100));
+        });
+    }
+
+    #[test]
+    fn wakeups_follow_deadlines_and_stop_when_idle() {
+        block_on(async {
+            let (mut room, buzz_tx, _rx) = mock_room(1000);
+            assert_eq!(room.next_wakeup_ms(), None);
+
+            room.on_control(RoomControl::StartRound {
+                countdown_ms: 300,
+                question: None,
+            })
+            .await;
+            assert_eq!(room.next_wakeup_ms(), Some(300));
+            room.time.advance(300);
+            room.on_tick().await;
+            assert_eq!(room.next_wakeup_ms(), None);
+
+            room.time.advance(50);
+            buzz_tx.send(untagged(BOB, room.time.now_ms())).unwrap();
+            room.step();
+            assert_eq!(room.next_wakeup_ms(), Some(1350));
+            room.on_control(RoomControl::Pause).await;
+            assert_eq!(room.next_wakeup_ms(), None);
+            room.on_control(RoomControl::Resume).await;
+            assert_eq!(room.next_wakeup_ms(), Some(1350));
+
+            room.on_control(RoomControl::MarkCorrect).await;
+            assert_eq!(room.next_wakeup_ms(), None);
+        });
+    }
+
+    #[test]
+    fn running_loop_accepts_promptly_and_times_out_on_the_deadline() {
+        block_on(async {
+            // Paused, tokio's clock only jumps ahead when every task is waiting,
+            // so the timings below are exact however busy the machine is.
+            time::pause();
+            let clock = InstantTime::new();
+            let (room, buzz_tx, mut broadcasts) = room_loop(clock.clone(), 200);
+            let (control_tx, control_rx) = mpsc::unbounded_channel();
+            let (view_tx, _view_rx) = watch::channel(GameView::default());
+            tokio::spawn(run_room_loop(room, control_rx, view_tx));
+
+            let mut next_type = async || {
+                let broadcast = time::timeout(Duration::from_secs(2), broadcasts.recv())
+                    .await
+                    .expect("loop stalled")
+                    .unwrap();
+                message_type(&broadcast)
+            };
+
+            control_tx
+                .send(RoomControl::StartRound {
+                    countdown_ms: 0,
+                    question: None,
+                })
+                .unwrap();
+            assert_eq!(next_type().await, "round_started");
+
+            let buzzed_at = Instant::now();
+            buzz_tx.send(untagged(BOB, clock.now_ms())).unwrap();
+            assert_eq!(next_type().await, "accepted");
+            assert_eq!(buzzed_at.elapsed(), Duration::ZERO);
+
+            assert_eq!(next_type().await, "timed_out");
+            // Tokio's timers round up to the next whole millisecond.
+            let waited = buzzed_at.elapsed().as_millis();
+            assert!((200..=201).contains(&waited), "timed out after {waited} ms");
+        });
+    }
+
+    #[test]
+    fn every_subscriber_gets_the_same_serialized_broadcast() {
+        let broadcaster = Broadcaster::default();
+        let mut subscribers: Vec<_> = (0..3).map(|_| broadcaster.subscribe()).collect();
+        broadcaster.send(&ServerMessage::Paused);
+
+        let received: Vec<_> = subscribers
+            .iter_mut()
+            .map(|rx| rx.try_recv().unwrap())
+            .collect();
+        for broadcast in &received {
+            assert_eq!(broadcast.index, 0);
+            assert_eq!(&*broadcast.payload, r#"{"type":"paused"}"#);
+            assert!(Arc::ptr_eq(&broadcast.payload, &received[0].payload));
+        }
+    }
+
+    #[test]
+    fn slow_subscriber_does_not_hold_up_others() {
+        block_on(async {
+            let broadcaster = Broadcaster::default();
+            let mut slow = broadcaster.subscribe();
+            let mut fast = broadcaster.subscribe();
+            let total = BROADCAST_CAPACITY + 10;
+
+            let reader = tokio::spawn(async move {
+                for _ in 0..total {
+                    fast.recv().await.unwrap();
+                }
+            });
+            for _ in 0..total {
+                broadcaster.send(&ServerMessage::Paused);
+                tokio::task::yield_now().await;
+            }
+            time::timeout(Duration::from_secs(2), reader)
+                .await
+                .expect("fast subscriber kept up")
+                .unwrap();
+            assert!(matches!(
+                slow.try_recv(),
+                Err(broadcast::error::TryRecvError::Lagged(10))
+            ));
+        });
+    }
+
+    #[test]
+    fn route_stamps_broadcasts_once_and_catches_up_while_buffered() {
+        let broadcaster = Broadcaster::default();
+        let (tx, mut rx) = mpsc::channel(DEFAULT_OUTBOUND_CAPACITY);
+        let route = Route::new(tx, &broadcaster);
+        let mut subscription = broadcaster.subscribe();
+
+        broadcaster.send(&ServerMessage::Paused);
+        let paused = subscription.try_recv().unwrap();
+        route.deliver(&paused);
+        route.deliver(&paused);
+        route.send(&ServerMessage::Kicked);
+        broadcaster.send(&ServerMessage::Resumed);
+        assert!(route.catch_up(&broadcaster));
+
+        let mut texts = Vec::new();
+        while let Ok(text) = rx.try_recv() {
+            texts.push(text);
+        }
+        assert_eq!(
+            texts,
+            [
+                r#"{"v":1,"seq":0,"type":"paused"}"#,
+                r#"{"v":1,"seq":1,"type":"kicked"}"#,
+                r#"{"v":1,"seq":2,"type":"resumed"}"#,
+            ]
+        );
+
+        for _ in 0..=REPLAY_BUFFER_LEN {
+            broadcaster.send(&ServerMessage::Paused);
+        }
+        assert!(!route.catch_up(&broadcaster));
+    }
+
+    #[test]
+    fn sent_and_buffered_copies_share_one_payload() {
+        let broadcaster = Broadcaster::default();
+        let (tx, mut rx) = mpsc::channel(DEFAULT_OUTBOUND_CAPACITY);
+        let route = Route::new(tx, &broadcaster);
+        route.send(&ServerMessage::Kicked);
+
+        let sent = rx.try_recv().unwrap();
+        let inner = route.inner.lock().unwrap();
+        let (_, buffered) = inner.recent.back().unwrap();
+        assert_eq!(sent.as_str(), r#"{"v":1,"seq":0,"type":"kicked"}"#);
+        assert_eq!(sent.as_ptr(), buffered.as_ptr());
+        // A replay reuses the MessagePack encoding the first send made.
+        assert_eq!(
+            sent.msgpack().unwrap().as_ptr(),
+            buffered.msgpack().unwrap().as_ptr()
+        );
+    }
+
+    #[test]
+    fn client_that_stops_reading_is_cut_off_and_can_resume() {
+        let broadcaster = Broadcaster::default();
+        let (tx, mut rx) = mpsc::channel(4);
+        let route = Route::new(tx, &broadcaster);
+
+        for _ in 0..5 {
+            route.send(&ServerMessage::Paused);
+        }
+        assert!(!route.is_connected());
+        // What was queued still drains, then the socket sees its channel close.
+        for _ in 0..4 {
+            assert!(rx.try_recv().is_ok());
+        }
+        assert_eq!(rx.try_recv(), Err(mpsc::error::TryRecvError::Disconnected));
+
+        // The overflowing message was buffered, so a reconnect gets it back,
+        // but only into a channel with room for the whole replay.
+        let (tight_tx, _tight_rx) = mpsc::channel(1);
+        route.send(&ServerMessage::Resumed);
+        assert!(!route.resume(&tight_tx, 3, &broadcaster));
+        let (tx, mut rx) = mpsc::channel(DEFAULT_OUTBOUND_CAPACITY);
+        assert!(route.resume(&tx, 3, &broadcaster));
+        assert_eq!(rx.try_recv().unwrap(), r#"{"v":1,"seq":4,"type":"paused"}"#);
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            r#"{"v":1,"seq":5,"type":"resumed"}"#
+        );
+        assert!(route.is_connected());
+    }
 }
@@ -8,8 +8,13 @@ use crate::dtos::Role;
 use crate::errors::AppError;
 use crate::utils::time::now_seconds;
 
+pub const DEFAULT_ISSUER: &str = "buzzer";
+
 #[derive(Serialize, Deserialize)]
 pub struct Claims {
+    pub iss: String,
+    /// Audience is the room id, binding the token to its room at the JWT layer.
+    pub aud: String,
     pub room_id: String,
     pub player_id: PlayerId,
     pub name: String,
@@ -22,19 +27,41 @@ pub struct JwtAuth {
     encoding: EncodingKey,
     decoding: DecodingKey,
     validation: Validation,
+    issuer: String,
     ttl_seconds: u64,
+    idle_timeout_seconds: u64,
 }
 
 impl JwtAuth {
-    pub fn new(secret: &[u8], ttl_seconds: u64) -> Self {
+    pub fn new(secret: &[u8], ttl_seconds: u64, issuer: &str) -> Self {
+        let mut validation = Validation::default();
+        validation.set_issuer(&[issuer]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
         Self {
             encoding: EncodingKey::from_secret(secret),
             decoding: DecodingKey::from_secret(secret),
-            validation: Validation::default(),
+            validation,
+            issuer: issuer.to_string(),
             ttl_seconds,
+            idle_timeout_seconds: ttl_seconds,
         }
     }
 
+    /// How long a player may go without a connection and still refresh;
+    /// defaults to the token lifetime.
+    pub fn with_idle_timeout(mut self, idle_timeout_seconds: u64) -> Self {
+        self.idle_timeout_seconds = idle_timeout_seconds;
+        self
+    }
+
+    pub fn ttl_seconds(&self) -> u64 {
+        self.ttl_seconds
+    }
+
+    pub fn idle_timeout_seconds(&self) -> u64 {
+        self.idle_timeout_seconds
+    }
+
     pub fn issue(
         &self,
         room_id: &str,
@@ -46,6 +73,8 @@ impl JwtAuth {
         let exp = now + self.ttl_seconds;
 
         let claims = Claims {
+            iss: self.issuer.clone(),
+            aud: room_id.to_string(),
             room_id: room_id.to_string(),
             player_id,
             name: name.to_string(),
@@ -59,12 +88,117 @@ impl JwtAuth {
         Ok((token, exp))
     }
 
-    pub fn verify(&self, token: &str) -> Result<Claims, AppError> {
-        let data = jsonwebtoken::decode::<Claims>(token, &self.decoding, &self.validation)
-            .map_err(|err| match err.kind() {
-                ErrorKind::ExpiredSignature => AppError::SessionExpired,
-                _ => AppError::InvalidToken,
+    /// Verifies the token's signature, expiry, issuer and that its audience is
+    /// `room_id`, so a token for another room is an `InvalidToken`. A `room_id`
+    /// claim that disagrees with the audience is a `RoomMismatch`.
+    pub fn verify(&self, token: &str, room_id: &str) -> Result<Claims, AppError> {
+        let mut validation = self.validation.clone();
+        validation.set_audience(&[room_id]);
+        let data =
+            jsonwebtoken::decode::<Claims>(token, &self.decoding, &validation).map_err(|err| {
+                match err.kind() {
+                    ErrorKind::ExpiredSignature => AppError::SessionExpired,
+                    _ => AppError::InvalidToken,
+                }
             })?;
+        if data.claims.room_id != room_id {
+            return Err(AppError::RoomMismatch);
+        }
         Ok(data.claims)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"test-secret-test-secret-test-secret";
+
+    fn auth() -> JwtAuth {
+        JwtAuth::new(SECRET, 60, DEFAULT_ISSUER)
+    }
+
+    #[test]
+    fn verifies_token_for_its_room() {
+        let auth = auth();
+        let (token, _) = auth.issue("abc123", 1, "bob", Role::Player).unwrap();
+        let claims = auth.verify(&token, "abc123").unwrap();
+        assert_eq!(claims.aud, "abc123");
+        assert_eq!(claims.iss, DEFAULT_ISSUER);
+        assert_eq!(claims.player_id, 1);
+    }
+
+    #[test]
+    fn token_for_other_room_is_invalid() {
+        let auth = auth();
+        let (token, _) = auth.issue("abc123", 1, "bob", Role::Player).unwrap();
+        assert!(matches!(
+            auth.verify(&token, "zzz999"),
+            Err(AppError::InvalidToken)
+        ));
+    }
+
+    #[test]
+    fn tampered_audience_is_invalid() {
+        // Re-signed with the right key but an audience that disagrees with the
+        // room; the `room_id` claim does not override it.
+        let auth = auth();
+        let now = now_seconds();
+        let claims = Claims {
+            iss: DEFAULT_ISSUER.to_string(),
+            aud: "zzz999".to_string(),
+            room_id: "abc123".to_string(),
+            player_id: 1,
+            name: "bob".to_string(),
+            role: Role::Player,
+            iat: now,
+            exp: now + 60,
+        };
+        let token = jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET),
+        )
+        .unwrap();
+        assert!(matches!(
+            auth.verify(&token, "abc123"),
+            Err(AppError::InvalidToken)
+        ));
+    }
+
+    #[test]
+    fn room_claim_disagreeing_with_audience_is_a_mismatch() {
+        let auth = auth();
+        let now = now_seconds();
+        let claims = Claims {
+            iss: DEFAULT_ISSUER.to_string(),
+            aud: "abc123".to_string(),
+            room_id: "zzz999".to_string(),
+            player_id: 1,
+            name: "bob".to_string(),
+            role: Role::Player,
+            iat: now,
+            exp: now + 60,
+        };
+        let token = jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET),
+        )
+        .unwrap();
+        assert!(matches!(
+            auth.verify(&token, "abc123"),
+            Err(AppError::RoomMismatch)
+        ));
+    }
+
+    #[test]
+    fn foreign_issuer_is_invalid() {
+        let other = JwtAuth::new(SECRET, 60, "someone-else");
+        let (token, _) = other.issue("abc123", 1, "bob", Role::Player).unwrap();
+        assert!(matches!(
+            auth().verify(&token, "abc123"),
+            Err(AppError::InvalidToken)
+        ));
+    }
+}
@@ -0,0 +1,188 @@
+//! A small typed client for the HTTP API and the room websocket, so bots and
+//! tests need not hand-roll JSON. Speaks JSON over `http://`/`ws://` and
+//! `https://`; the websocket side has no TLS of its own.
+
+use std::fmt;
+
+use futures::stream::{BoxStream, SplitSink};
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::dtos::{
+    ClientMessage, CreateRoomRequest, CreateRoomResponse, JoinRoomRequest, JoinRoomResponse,
+    ServerMessage,
+};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Decoded server messages, in order, until the server closes the socket.
+pub type ServerMessages = BoxStream<'static, Result<ServerMessage, ClientError>>;
+
+#[derive(Debug)]
+pub enum ClientError {
+    Http(reqwest::Error),
+    /// The server refused the request with its usual error body.
+    Api {
+        status: u16,
+        code: String,
+        message: String,
+    },
+    WebSocket(tungstenite::Error),
+    /// A body or frame that is not what the protocol promises.
+    Decode(serde_json::Error),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Http(err) => write!(f, "http: {err}"),
+            ClientError::Api {
+                status,
+                code,
+                message,
+            } => write!(f, "{status} {code}: {message}"),
+            ClientError::WebSocket(err) => write!(f, "websocket: {err}"),
+            ClientError::Decode(err) => write!(f, "decode: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(err: reqwest::Error) -> Self {
+        ClientError::Http(err)
+    }
+}
+
+impl From<tungstenite::Error> for ClientError {
+    fn from(err: tungstenite::Error) -> Self {
+        ClientError::WebSocket(err)
+    }
+}
+
+impl From<serde_json::Error> for ClientError {
+    fn from(err: serde_json::Error) -> Self {
+        ClientError::Decode(err)
+    }
+}
+
+/// The parts of [`ErrorResponse`](crate::dtos::ErrorResponse) worth reporting.
+#[derive(Deserialize)]
+struct ApiError {
+    error: String,
+    message: String,
+}
+
+/// One server, e.g. `Client::new("http://127.0.0.1:3000")`.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl Client {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// The response's token belongs to the room's admin.
+    pub async fn create_room(
+        &self,
+        request: &CreateRoomRequest,
+    ) -> Result<CreateRoomResponse, ClientError> {
+        self.post("/api/rooms", request, None).await
+    }
+
+    /// Pass the token of an earlier join to rejoin under the same name.
+    pub async fn join(
+        &self,
+        room_id: &str,
+        request: &JoinRoomRequest,
+        token: Option<&str>,
+    ) -> Result<JoinRoomResponse, ClientError> {
+        self.post(&format!("/api/rooms/{room_id}/join"), request, token)
+            .await
+    }
+
+    /// Opens the room's websocket with a token from [`create_room`](Self::create_room)
+    /// or [`join`](Self::join).
+    pub async fn connect(
+        &self,
+        room_id: &str,
+        token: &str,
+    ) -> Result<(ClientSender, ServerMessages), ClientError> {
+        let ws_base = match self.base_url.strip_prefix("http") {
+            Some(rest) => format!("ws{rest}"),
+            None => self.base_url.clone(),
+        };
+        let url = format!("{ws_base}/ws/{room_id}?token={token}&format=json");
+        let (socket, _) = tokio_tungstenite::connect_async(url).await?;
+        let (sink, stream) = socket.split();
+        let messages = stream
+            .filter_map(|frame| async move {
+                match frame {
+                    Ok(Message::Text(text)) => {
+                        Some(serde_json::from_str(&text).map_err(Into::into))
+                    }
+                    Ok(_) => None,
+                    Err(err) => Some(Err(err.into())),
+                }
+            })
+            .boxed();
+        Ok((ClientSender { sink }, messages))
+    }
+
+    async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &impl serde::Serialize,
+        token: Option<&str>,
+    ) -> Result<T, ClientError> {
+        let mut request = self
+            .http
+            .post(format!("{}{path}", self.base_url))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(body)?);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        let bytes = response.bytes().await?;
+        if !status.is_success() {
+            let error: ApiError = serde_json::from_slice(&bytes)?;
+            return Err(ClientError::Api {
+                status: status.as_u16(),
+                code: error.error,
+                message: error.message,
+            });
+        }
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+}
+
+/// The sending half of a room websocket.
+pub struct ClientSender {
+    sink: SplitSink<Socket, Message>,
+}
+
+impl ClientSender {
+    pub async fn send(&mut self, message: &ClientMessage) -> Result<(), ClientError> {
+        let text = serde_json::to_string(message)?;
+        self.sink.send(Message::Text(text.into())).await?;
+        Ok(())
+    }
+
+    pub async fn close(mut self) -> Result<(), ClientError> {
+        self.sink.close().await?;
+        Ok(())
+    }
+}
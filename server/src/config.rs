@@ -0,0 +1,620 @@
+//! Process-wide settings, read once at startup.
+//!
+//! Everything comes from `BUZZER_*` environment variables with defaults that match
+//! a plain local run. A value that is set but unusable stops the server at startup
+//! instead of being silently replaced by the default. The rate-limit variables
+//! keep their historical `TRUSTED_PROXY_HOPS` / `RL_*` names, as do
+//! `ROUND_HISTORY_LIMIT` and `JWT_ISSUER`, and the older `BIND_ADDR` is still
+//! read when `BUZZER_BIND` / `BUZZER_PORT` leave it room.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::ops::RangeInclusive;
+
+use crate::adapter::{DEFAULT_OUTBOUND_CAPACITY, MIN_OUTBOUND_CAPACITY};
+use crate::auth::DEFAULT_ISSUER;
+use crate::ratelimit::RateLimitSettings;
+use crate::socket::{InboundQuota, InboundQuotas};
+use crate::state::room_state::{
+    DEFAULT_ADMIN_GRACE_IN_MS, DEFAULT_BUZZ_DEBOUNCE_IN_MS, DEFAULT_IDLE_TIMEOUT_IN_MS,
+};
+use crate::webhook::WebhookSettings;
+
+const DEFAULT_BIND: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+const DEFAULT_PORT: u16 = 3000;
+const DEFAULT_TOKEN_TTL_SECS: u64 = 30 * 60;
+const DEFAULT_TOKEN_IDLE_TIMEOUT_SECS: u64 = 10 * 60;
+const DEFAULT_ROOM_TTL_SECS: u64 = 30 * 60;
+const DEFAULT_ROOM_IDLE_TIMEOUT_SECS: u64 = DEFAULT_IDLE_TIMEOUT_IN_MS / 1000;
+const DEFAULT_ADMIN_GRACE_SECS: u64 = DEFAULT_ADMIN_GRACE_IN_MS / 1000;
+const DEFAULT_MAX_ROOMS: u64 = 1000;
+const DEFAULT_WS_PING_INTERVAL_MS: u64 = 30_000;
+/// Unless set, the idle timeout is this many ping intervals.
+const WS_IDLE_TIMEOUT_PINGS: u64 = 3;
+const DEFAULT_WS_MAX_MESSAGE_BYTES: u64 = 8 * 1024;
+const DEFAULT_ROUND_HISTORY_LIMIT: u64 = 200;
+
+const TOKEN_TTL_RANGE: RangeInclusive<u64> = 60..=7 * 24 * 60 * 60;
+const TOKEN_IDLE_TIMEOUT_RANGE: RangeInclusive<u64> = 60..=7 * 24 * 60 * 60;
+const ROOM_TTL_RANGE: RangeInclusive<u64> = 60..=7 * 24 * 60 * 60;
+const ROOM_IDLE_TIMEOUT_RANGE: RangeInclusive<u64> = 60..=7 * 24 * 60 * 60;
+const ADMIN_GRACE_RANGE: RangeInclusive<u64> = 10..=24 * 60 * 60;
+const BUZZ_DEBOUNCE_MS_RANGE: RangeInclusive<u64> = 0..=1000;
+const MAX_ROOMS_RANGE: RangeInclusive<u64> = 1..=1_000_000;
+const OUTBOUND_CAPACITY_RANGE: RangeInclusive<u64> = MIN_OUTBOUND_CAPACITY as u64..=65_536;
+const WS_PING_INTERVAL_MS_RANGE: RangeInclusive<u64> = 100..=10 * 60 * 1000;
+const MAX_WS_IDLE_TIMEOUT_MS: u64 = 60 * 60 * 1000;
+const WS_MAX_MESSAGE_BYTES_RANGE: RangeInclusive<u64> = 512..=1024 * 1024;
+const MIN_WEBHOOK_SECRET_LEN: usize = 16;
+const TRUSTED_HOPS_RANGE: RangeInclusive<u64> = 0..=8;
+const BURST_RANGE: RangeInclusive<u64> = 1..=10_000;
+const PERIOD_MS_RANGE: RangeInclusive<u64> = 1..=60 * 60 * 1000;
+const INBOUND_QUOTA_RANGE: RangeInclusive<u64> = 1..=1000;
+const ROUND_HISTORY_LIMIT_RANGE: RangeInclusive<u64> = 0..=10_000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServerConfig {
+    pub bind_addr: SocketAddr,
+    /// Lifetime of issued session tokens. Refreshing issues a new one, so this
+    /// only bounds how long a stolen or forgotten token stays usable.
+    pub token_ttl_secs: u64,
+    /// A player with no socket for this long can no longer refresh and lapses
+    /// once their current token expires.
+    pub token_idle_timeout_secs: u64,
+    /// How often rooms without a live admin are swept up, i.e. how long an
+    /// abandoned room may linger at most.
+    pub room_ttl_secs: u64,
+    /// Rooms with no buzz, round or command for this long are closed by the
+    /// next sweep.
+    pub room_idle_timeout_secs: u64,
+    /// An admin whose socket has been gone this long hands the room to the
+    /// longest-connected player.
+    pub admin_grace_secs: u64,
+    /// A player's repeat buzz within this many milliseconds is dropped rather
+    /// than rejected; 0 turns this off.
+    pub buzz_debounce_ms: u64,
+    /// Room creation fails with `server_full` once this many rooms exist.
+    pub max_rooms: usize,
+    /// Messages queued per websocket; a client that lets this many pile up is
+    /// disconnected, see [`Route`](crate::adapter::Route).
+    pub outbound_capacity: usize,
+    /// How often each websocket is pinged, so a client that vanished without
+    /// closing is noticed.
+    pub ws_ping_interval_ms: u64,
+    /// A websocket that sends nothing, pongs included, for this long is closed.
+    pub ws_idle_timeout_ms: u64,
+    /// Larger client messages are denied without being parsed.
+    pub ws_max_message_bytes: usize,
+    /// Browser origins (`https://quiz.example.com`) allowed to call the API
+    /// from another site; `*` allows any. Empty means same-origin only.
+    pub allowed_origins: Vec<String>,
+    /// Per-client-IP limits on the HTTP API and websocket upgrades.
+    pub rate_limits: RateLimitSettings,
+    /// Per-connection websocket message quotas by kind of message.
+    pub inbound_quotas: InboundQuotas,
+    /// Where room events are posted, if anywhere; see [`webhook`](crate::webhook).
+    pub webhook: Option<WebhookSettings>,
+    /// Rounds of answers each room keeps for its history export; 0 keeps none.
+    pub round_history_limit: usize,
+    /// The `iss` claim of issued session tokens, and the only one accepted.
+    pub jwt_issuer: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_addr: SocketAddr::new(DEFAULT_BIND, DEFAULT_PORT),
+            token_ttl_secs: DEFAULT_TOKEN_TTL_SECS,
+            token_idle_timeout_secs: DEFAULT_TOKEN_IDLE_TIMEOUT_SECS,
+            room_ttl_secs: DEFAULT_ROOM_TTL_SECS,
+            room_idle_timeout_secs: DEFAULT_ROOM_IDLE_TIMEOUT_SECS,
+            admin_grace_secs: DEFAULT_ADMIN_GRACE_SECS,
+            buzz_debounce_ms: DEFAULT_BUZZ_DEBOUNCE_IN_MS,
+            max_rooms: DEFAULT_MAX_ROOMS as usize,
+            outbound_capacity: DEFAULT_OUTBOUND_CAPACITY,
+            ws_ping_interval_ms: DEFAULT_WS_PING_INTERVAL_MS,
+            ws_idle_timeout_ms: WS_IDLE_TIMEOUT_PINGS * DEFAULT_WS_PING_INTERVAL_MS,
+            ws_max_message_bytes: DEFAULT_WS_MAX_MESSAGE_BYTES as usize,
+            allowed_origins: Vec::new(),
+            rate_limits: RateLimitSettings::default(),
+            inbound_quotas: InboundQuotas::default(),
+            webhook: None,
+            round_history_limit: DEFAULT_ROUND_HISTORY_LIMIT as usize,
+            jwt_issuer: DEFAULT_ISSUER.to_string(),
+        }
+    }
+}
+
+/// A variable that is set but cannot be used.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConfigError {
+    pub key: &'static str,
+    pub value: String,
+    pub expected: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}={:?} is invalid: expected {}",
+            self.key, self.value, self.expected
+        )
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ServerConfig {
+    /// Reads the process environment, with `webhook_url` (from
+    /// `--webhook-url`) taking the place of `BUZZER_WEBHOOK_URL` when given.
+    pub fn from_env(webhook_url: Option<String>) -> Result<Self, ConfigError> {
+        Self::from_lookup(|key| match key {
+            "BUZZER_WEBHOOK_URL" if webhook_url.is_some() => webhook_url.clone(),
+            _ => std::env::var(key).ok(),
+        })
+    }
+
+    /// Builds the config from any key lookup, so tests need not touch the
+    /// process environment.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let d = Self::default();
+        let ws_ping_interval_ms = parse_in(
+            &lookup,
+            "BUZZER_WS_PING_INTERVAL_MS",
+            d.ws_ping_interval_ms,
+            WS_PING_INTERVAL_MS_RANGE,
+        )?;
+        // `BIND_ADDR` is the older single-variable form; the split variables
+        // override whichever half they name.
+        let legacy: SocketAddr = parse(
+            &lookup,
+            "BIND_ADDR",
+            d.bind_addr,
+            "a socket address such as 0.0.0.0:3000",
+        )?;
+        if legacy.port() == 0 {
+            return Err(invalid(
+                "BIND_ADDR",
+                &legacy.to_string(),
+                "a socket address with a port from 1 to 65535",
+            ));
+        }
+        let ip = parse(
+            &lookup,
+            "BUZZER_BIND",
+            legacy.ip(),
+            "an IP address such as 0.0.0.0",
+        )?;
+        let port = parse(
+            &lookup,
+            "BUZZER_PORT",
+            legacy.port(),
+            "a port from 1 to 65535",
+        )?;
+        if port == 0 {
+            return Err(invalid("BUZZER_PORT", "0", "a port from 1 to 65535"));
+        }
+        Ok(Self {
+            bind_addr: SocketAddr::new(ip, port),
+            token_ttl_secs: parse_in(
+                &lookup,
+                "BUZZER_TOKEN_TTL_SECS",
+                d.token_ttl_secs,
+                TOKEN_TTL_RANGE,
+            )?,
+            token_idle_timeout_secs: parse_in(
+                &lookup,
+                "BUZZER_TOKEN_IDLE_TIMEOUT_SECS",
+                d.token_idle_timeout_secs,
+                TOKEN_IDLE_TIMEOUT_RANGE,
+            )?,
+            room_ttl_secs: parse_in(
+                &lookup,
+                "BUZZER_ROOM_TTL_SECS",
+                d.room_ttl_secs,
+                ROOM_TTL_RANGE,
+            )?,
+            room_idle_timeout_secs: parse_in(
+                &lookup,
+                "BUZZER_ROOM_IDLE_TIMEOUT_SECS",
+                d.room_idle_timeout_secs,
+                ROOM_IDLE_TIMEOUT_RANGE,
+            )?,
+            admin_grace_secs: parse_in(
+                &lookup,
+                "BUZZER_ADMIN_GRACE_SECS",
+                d.admin_grace_secs,
+                ADMIN_GRACE_RANGE,
+            )?,
+            buzz_debounce_ms: parse_in(
+                &lookup,
+                "BUZZER_BUZZ_DEBOUNCE_MS",
+                d.buzz_debounce_ms,
+                BUZZ_DEBOUNCE_MS_RANGE,
+            )?,
+            max_rooms: parse_in(
+                &lookup,
+                "BUZZER_MAX_ROOMS",
+                d.max_rooms as u64,
+                MAX_ROOMS_RANGE,
+            )? as usize,
+            outbound_capacity: parse_in(
+                &lookup,
+                "BUZZER_OUTBOUND_CAPACITY",
+                d.outbound_capacity as u64,
+                OUTBOUND_CAPACITY_RANGE,
+            )? as usize,
+            ws_ping_interval_ms,
+            // At least two pings, so a single lost pong does not cost the connection.
+            ws_idle_timeout_ms: parse_in(
+                &lookup,
+                "BUZZER_WS_IDLE_TIMEOUT_MS",
+                WS_IDLE_TIMEOUT_PINGS * ws_ping_interval_ms,
+                2 * ws_ping_interval_ms..=MAX_WS_IDLE_TIMEOUT_MS,
+            )?,
+            ws_max_message_bytes: parse_in(
+                &lookup,
+                "BUZZER_WS_MAX_MESSAGE_BYTES",
+                d.ws_max_message_bytes as u64,
+                WS_MAX_MESSAGE_BYTES_RANGE,
+            )? as usize,
+            allowed_origins: parse_origins(&lookup)?,
+            rate_limits: parse_rate_limits(&lookup, d.rate_limits)?,
+            inbound_quotas: InboundQuotas {
+                buzz: parse_inbound_quota(
+                    &lookup,
+                    ["BUZZER_WS_BUZZ_RATE", "BUZZER_WS_BUZZ_BURST"],
+                    d.inbound_quotas.buzz,
+                )?,
+                control: parse_inbound_quota(
+                    &lookup,
+                    ["BUZZER_WS_CONTROL_RATE", "BUZZER_WS_CONTROL_BURST"],
+                    d.inbound_quotas.control,
+                )?,
+                chat: parse_inbound_quota(
+                    &lookup,
+                    ["BUZZER_WS_CHAT_RATE", "BUZZER_WS_CHAT_BURST"],
+                    d.inbound_quotas.chat,
+                )?,
+                sync: parse_inbound_quota(
+                    &lookup,
+                    ["BUZZER_WS_SYNC_RATE", "BUZZER_WS_SYNC_BURST"],
+                    d.inbound_quotas.sync,
+                )?,
+            },
+            webhook: parse_webhook(&lookup)?,
+            round_history_limit: parse_in(
+                &lookup,
+                "ROUND_HISTORY_LIMIT",
+                d.round_history_limit as u64,
+                ROUND_HISTORY_LIMIT_RANGE,
+            )? as usize,
+            jwt_issuer: parse_issuer(&lookup, d.jwt_issuer)?,
+        })
+    }
+
+    /// Whether a browser page served from `origin` may talk to us cross-site.
+    pub fn origin_allowed(&self, origin: &str) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|allowed| allowed == "*" || allowed == origin)
+    }
+}
+
+fn invalid(key: &'static str, value: &str, expected: &str) -> ConfigError {
+    ConfigError {
+        key,
+        value: value.to_string(),
+        expected: expected.to_string(),
+    }
+}
+
+fn parse<T: std::str::FromStr>(
+    lookup: &impl Fn(&str) -> Option<String>,
+    key: &'static str,
+    default: T,
+    expected: &str,
+) -> Result<T, ConfigError> {
+    match lookup(key) {
+        None => Ok(default),
+        Some(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| invalid(key, &value, expected)),
+    }
+}
+
+/// Comma-separated `scheme://host[:port]` entries, or `*`.
+fn parse_origins(lookup: &impl Fn(&str) -> Option<String>) -> Result<Vec<String>, ConfigError> {
+    const KEY: &str = "BUZZER_ALLOWED_ORIGINS";
+    const EXPECTED: &str = "comma-separated origins such as https://quiz.example.com, or *";
+    let Some(value) = lookup(KEY) else {
+        return Ok(Vec::new());
+    };
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| {
+            let origin = origin.trim_end_matches('/');
+            let valid = origin == "*"
+                || ["http://", "https://"].iter().any(|scheme| {
+                    origin
+                        .strip_prefix(scheme)
+                        .is_some_and(|host| !host.is_empty() && !host.contains('/'))
+                });
+            if valid {
+                Ok(origin.to_string())
+            } else {
+                Err(invalid(KEY, &value, EXPECTED))
+            }
+        })
+        .collect()
+}
+
+/// Any non-blank `JWT_ISSUER`, trimmed.
+fn parse_issuer(
+    lookup: &impl Fn(&str) -> Option<String>,
+    default: String,
+) -> Result<String, ConfigError> {
+    const KEY: &str = "JWT_ISSUER";
+    match lookup(KEY) {
+        None => Ok(default),
+        Some(value) if value.trim().is_empty() => Err(invalid(KEY, &value, "a non-empty issuer")),
+        Some(value) => Ok(value.trim().to_string()),
+    }
+}
+
+/// `BUZZER_WEBHOOK_URL` plus the `BUZZER_WEBHOOK_SECRET` its events are signed
+/// with; setting one without the other is an error.
+fn parse_webhook(
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> Result<Option<WebhookSettings>, ConfigError> {
+    const URL_KEY: &str = "BUZZER_WEBHOOK_URL";
+    const SECRET_KEY: &str = "BUZZER_WEBHOOK_SECRET";
+    let url = lookup(URL_KEY).map(|url| url.trim().to_string());
+    let secret = lookup(SECRET_KEY);
+    match (url, secret) {
+        (None, None) => Ok(None),
+        (Some(url), _)
+            if !["http://", "https://"].iter().any(|scheme| {
+                url.strip_prefix(scheme)
+                    .is_some_and(|rest| !rest.is_empty())
+            }) =>
+        {
+            Err(invalid(URL_KEY, &url, "an http:// or https:// URL"))
+        }
+        (Some(url), Some(secret)) if secret.len() >= MIN_WEBHOOK_SECRET_LEN => {
+            Ok(Some(WebhookSettings { url, secret }))
+        }
+        // Never echo the secret into the startup error.
+        (Some(_), _) => Err(invalid(
+            SECRET_KEY,
+            "<hidden>",
+            "a shared secret of at least 16 bytes to sign webhook events with",
+        )),
+        (None, Some(_)) => Err(invalid(
+            SECRET_KEY,
+            "<hidden>",
+            "to be set only together with BUZZER_WEBHOOK_URL",
+        )),
+    }
+}
+
+fn parse_rate_limits(
+    lookup: &impl Fn(&str) -> Option<String>,
+    d: RateLimitSettings,
+) -> Result<RateLimitSettings, ConfigError> {
+    let burst = |key, default: u32| -> Result<u32, ConfigError> {
+        // BURST_RANGE fits in a u32.
+        Ok(parse_in(lookup, key, default.into(), BURST_RANGE)? as u32)
+    };
+    Ok(RateLimitSettings {
+        trusted_hops: parse_in(
+            lookup,
+            "TRUSTED_PROXY_HOPS",
+            d.trusted_hops as u64,
+            TRUSTED_HOPS_RANGE,
+        )? as usize,
+        api_burst: burst("RL_API_BURST", d.api_burst)?,
+        api_period_ms: parse_in(lookup, "RL_API_PERIOD_MS", d.api_period_ms, PERIOD_MS_RANGE)?,
+        create_burst: burst("RL_CREATE_BURST", d.create_burst)?,
+        create_period_ms: parse_in(
+            lookup,
+            "RL_CREATE_PERIOD_MS",
+            d.create_period_ms,
+            PERIOD_MS_RANGE,
+        )?,
+        join_burst: burst("RL_JOIN_BURST", d.join_burst)?,
+        join_period_ms: parse_in(
+            lookup,
+            "RL_JOIN_PERIOD_MS",
+            d.join_period_ms,
+            PERIOD_MS_RANGE,
+        )?,
+    })
+}
+
+/// Messages per second and burst, from the `[rate, burst]` keys.
+fn parse_inbound_quota(
+    lookup: &impl Fn(&str) -> Option<String>,
+    [rate_key, burst_key]: [&'static str; 2],
+    d: InboundQuota,
+) -> Result<InboundQuota, ConfigError> {
+    // INBOUND_QUOTA_RANGE fits in a u32.
+    Ok(InboundQuota {
+        per_sec: parse_in(lookup, rate_key, d.per_sec.into(), INBOUND_QUOTA_RANGE)? as u32,
+        burst: parse_in(lookup, burst_key, d.burst.into(), INBOUND_QUOTA_RANGE)? as u32,
+    })
+}
+
+fn parse_in(
+    lookup: &impl Fn(&str) -> Option<String>,
+    key: &'static str,
+    default: u64,
+    range: RangeInclusive<u64>,
+) -> Result<u64, ConfigError> {
+    let expected = format!("a whole number from {} to {}", range.start(), range.end());
+    let value = parse(lookup, key, default, &expected)?;
+    if range.contains(&value) {
+        Ok(value)
+    } else {
+        Err(invalid(key, &value.to_string(), &expected))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_from(vars: &[(&str, &str)]) -> Result<ServerConfig, ConfigError> {
+        ServerConfig::from_lookup(|key| {
+            vars.iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn defaults_match_a_plain_local_run() {
+        let config = config_from(&[]).unwrap();
+        assert_eq!(config, ServerConfig::default());
+        assert_eq!(config.bind_addr, "127.0.0.1:3000".parse().unwrap());
+        assert_eq!(config.token_ttl_secs, 30 * 60);
+        assert_eq!(config.token_idle_timeout_secs, 10 * 60);
+        assert_eq!(config.ws_ping_interval_ms, 30_000);
+        assert_eq!(config.ws_idle_timeout_ms, 90_000);
+        assert_eq!(config.ws_max_message_bytes, 8 * 1024);
+    }
+
+    #[test]
+    fn ws_idle_timeout_follows_the_ping_interval() {
+        let config = config_from(&[("BUZZER_WS_PING_INTERVAL_MS", "60000")]).unwrap();
+        assert_eq!(config.ws_idle_timeout_ms, 180_000);
+
+        let err = config_from(&[
+            ("BUZZER_WS_PING_INTERVAL_MS", "60000"),
+            ("BUZZER_WS_IDLE_TIMEOUT_MS", "90000"),
+        ])
+        .unwrap_err();
+        assert_eq!(err.key, "BUZZER_WS_IDLE_TIMEOUT_MS");
+        assert_eq!(err.expected, "a whole number from 120000 to 3600000");
+    }
+
+    #[test]
+    fn every_variable_is_honored() {
+        let config = config_from(&[
+            ("BUZZER_BIND", "0.0.0.0"),
+            ("BUZZER_PORT", "8080"),
+            ("BUZZER_TOKEN_TTL_SECS", "600"),
+            ("BUZZER_TOKEN_IDLE_TIMEOUT_SECS", "300"),
+            ("BUZZER_ROOM_TTL_SECS", " 120 "),
+            ("BUZZER_ROOM_IDLE_TIMEOUT_SECS", "900"),
+            ("BUZZER_ADMIN_GRACE_SECS", "45"),
+            ("BUZZER_BUZZ_DEBOUNCE_MS", "0"),
+            ("BUZZER_MAX_ROOMS", "50"),
+            ("BUZZER_OUTBOUND_CAPACITY", "1024"),
+            ("BUZZER_WS_PING_INTERVAL_MS", "10000"),
+            ("BUZZER_WS_IDLE_TIMEOUT_MS", "25000"),
+            ("BUZZER_WS_MAX_MESSAGE_BYTES", "2048"),
+            (
+                "BUZZER_ALLOWED_ORIGINS",
+                "https://quiz.example.com/, http://localhost:5173",
+            ),
+            ("TRUSTED_PROXY_HOPS", "0"),
+            ("RL_CREATE_BURST", "2"),
+            ("RL_JOIN_PERIOD_MS", "500"),
+            ("BUZZER_WS_BUZZ_RATE", "20"),
+            ("BUZZER_WS_SYNC_BURST", "8"),
+            ("BUZZER_WEBHOOK_URL", "https://hooks.example.com/buzzer"),
+            ("BUZZER_WEBHOOK_SECRET", "sixteen-byte-key"),
+            ("ROUND_HISTORY_LIMIT", "0"),
+            ("JWT_ISSUER", " quiz-night "),
+        ])
+        .unwrap();
+        assert_eq!(config.bind_addr, "0.0.0.0:8080".parse().unwrap());
+        assert_eq!(config.token_ttl_secs, 600);
+        assert_eq!(config.token_idle_timeout_secs, 300);
+        assert_eq!(config.room_ttl_secs, 120);
+        assert_eq!(config.room_idle_timeout_secs, 900);
+        assert_eq!(config.admin_grace_secs, 45);
+        assert_eq!(config.buzz_debounce_ms, 0);
+        assert_eq!(config.max_rooms, 50);
+        assert_eq!(config.outbound_capacity, 1024);
+        assert_eq!(config.ws_ping_interval_ms, 10_000);
+        assert_eq!(config.ws_idle_timeout_ms, 25_000);
+        assert_eq!(config.ws_max_message_bytes, 2048);
+        assert_eq!(
+            config.allowed_origins,
+            ["https://quiz.example.com", "http://localhost:5173"]
+        );
+        assert!(config.origin_allowed("http://localhost:5173"));
+        assert!(!config.origin_allowed("https://evil.example.com"));
+        assert_eq!(config.rate_limits.trusted_hops, 0);
+        assert_eq!(config.rate_limits.create_burst, 2);
+        assert_eq!(config.rate_limits.join_period_ms, 500);
+        assert_eq!(config.inbound_quotas.buzz.per_sec, 20);
+        assert_eq!(config.inbound_quotas.sync.burst, 8);
+        assert_eq!(
+            config.inbound_quotas.chat,
+            ServerConfig::default().inbound_quotas.chat
+        );
+        let webhook = config.webhook.unwrap();
+        assert_eq!(webhook.url, "https://hooks.example.com/buzzer");
+        assert_eq!(webhook.secret, "sixteen-byte-key");
+        assert_eq!(config.round_history_limit, 0);
+        assert_eq!(config.jwt_issuer, "quiz-night");
+    }
+
+    #[test]
+    fn bind_addr_is_still_honored() {
+        let config = config_from(&[("BIND_ADDR", "0.0.0.0:8080")]).unwrap();
+        assert_eq!(config.bind_addr, "0.0.0.0:8080".parse().unwrap());
+
+        let config =
+            config_from(&[("BIND_ADDR", "0.0.0.0:8080"), ("BUZZER_PORT", "9000")]).unwrap();
+        assert_eq!(config.bind_addr, "0.0.0.0:9000".parse().unwrap());
+
+        let config =
+            config_from(&[("BIND_ADDR", "0.0.0.0:8080"), ("BUZZER_BIND", "10.0.0.1")]).unwrap();
+        assert_eq!(config.bind_addr, "10.0.0.1:8080".parse().unwrap());
+    }
+
+    #[test]
+    fn invalid_values_name_the_variable() {
+        for (key, value) in [
+            ("BIND_ADDR", "localhost:3000"),
+            ("BIND_ADDR", "0.0.0.0:0"),
+            ("BUZZER_BIND", "localhost"),
+            ("BUZZER_PORT", "0"),
+            ("BUZZER_PORT", "70000"),
+            ("BUZZER_TOKEN_TTL_SECS", "10"),
+            ("BUZZER_TOKEN_IDLE_TIMEOUT_SECS", "0"),
+            ("BUZZER_ROOM_TTL_SECS", "-1"),
+            ("BUZZER_ROOM_IDLE_TIMEOUT_SECS", "30"),
+            ("BUZZER_ADMIN_GRACE_SECS", "5"),
+            ("BUZZER_BUZZ_DEBOUNCE_MS", "5000"),
+            ("BUZZER_MAX_ROOMS", "0"),
+            ("BUZZER_OUTBOUND_CAPACITY", "8"),
+            ("BUZZER_WS_PING_INTERVAL_MS", "50"),
+            ("BUZZER_WS_IDLE_TIMEOUT_MS", "59999"),
+            ("BUZZER_WS_MAX_MESSAGE_BYTES", "100"),
+            ("BUZZER_ALLOWED_ORIGINS", "quiz.example.com"),
+            ("BUZZER_ALLOWED_ORIGINS", "https://quiz.example.com/play"),
+            ("RL_JOIN_BURST", "0"),
+            ("RL_CREATE_PERIOD_MS", "soon"),
+            ("BUZZER_WS_CONTROL_RATE", "0"),
+            ("BUZZER_WS_CHAT_BURST", "5000"),
+            ("BUZZER_WEBHOOK_URL", "hooks.example.com"),
+            ("BUZZER_WEBHOOK_SECRET", "sixteen-byte-key"),
+            ("ROUND_HISTORY_LIMIT", "lots"),
+            ("ROUND_HISTORY_LIMIT", "100000"),
+            ("JWT_ISSUER", "  "),
+        ] {
+            let err = config_from(&[(key, value)]).unwrap_err();
+            assert_eq!(err.key, key);
+            assert!(err.to_string().starts_with(key), "{err}");
+        }
+    }
+}
@@ -1,69 +1,194 @@
+use std::collections::BTreeMap;
+
+use core::game::PlayerId;
+pub use core::protocol::{
+    ClientMessage, GamePhase, GameStateInfo, MAX_ACK_ID_LEN, PROTOCOL_VERSION, ParticipantInfo,
+    RateCategory, Role, RoomSettingsInfo, RoundOutcome, SUPPORTED_PROTOCOL_VERSIONS, ScoreEntry,
+    ServerMessage,
+};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
-#[serde(rename_all = "snake_case")]
-pub enum Role {
-    Admin,
-    Player,
+#[derive(Serialize, Deserialize, Default)]
+pub struct CreateRoomRequest {
+    pub name: String,
+    pub answer_window_in_ms: Option<u64>,
+    /// Custom code such as "QUIZ-NIGHT"; generated when omitted.
+    pub room_code: Option<String>,
+    /// Required from new players joining the room.
+    pub password: Option<String>,
+    /// Websocket messages per second per connection; 20 by default.
+    pub inbound_rate_per_sec: Option<u32>,
 }
 
+/// Creates `count` rooms with the same settings, e.g. for a tournament bracket.
 #[derive(Deserialize)]
-pub struct CreateRoomRequest {
+pub struct CreateRoomsRequest {
+    pub count: usize,
+    /// Admin name in every room.
     pub name: String,
     pub answer_window_in_ms: Option<u64>,
+    /// Rooms are coded `PREFIX-1`, `PREFIX-2`, ...; generated when omitted.
+    pub prefix: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct CreateRoomResponse {
     pub room_id: String,
     pub token: String,
     pub answer_window_in_ms: u64,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct JoinRoomRequest {
     pub name: String,
+    /// Ignored when rejoining with a valid token.
+    pub password: Option<String>,
+    /// `player` (default) or `spectator`; ignored when rejoining with a valid token.
+    pub role: Option<Role>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct JoinRoomResponse {
     pub room_id: String,
     pub token: String,
     pub answer_window_in_ms: u64,
+    pub player_name: String,
+    pub role: Role,
+}
+
+/// Partial update; omitted fields keep their current value.
+#[derive(Deserialize)]
+pub struct UpdateRoomRequest {
+    pub answer_window_in_ms: Option<u64>,
+    pub max_players: Option<usize>,
+    /// Sets the join password; an empty string removes it.
+    pub password: Option<String>,
+}
+
+/// Body of `POST /api/rooms/{room_id}/start_round`; `{}` starts right away.
+#[derive(Deserialize)]
+pub struct StartRoundRequest {
+    pub countdown_ms: Option<u64>,
+    pub question: Option<String>,
+}
+
+/// Names the player an admin action applies to.
+#[derive(Deserialize)]
+pub struct PlayerNameRequest {
+    pub name: String,
+}
+
+/// Room state after an admin action over HTTP.
+#[derive(Serialize)]
+pub struct RoomStatusResponse {
+    pub room_id: String,
+    pub round: u64,
+    pub paused: bool,
+    pub participants: Vec<ParticipantInfo>,
+}
+
+/// Everything needed to rebuild a room after a restart, minus live sockets,
+/// the round in play and the answer history. Tokens issued before stay valid
+/// as long as `JWT_SECRET` does.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RoomSnapshot {
+    pub room_id: String,
+    pub answer_window_in_ms: u64,
+    pub max_players: usize,
+    pub inbound_rate_per_sec: u32,
+    /// Argon2 PHC string, as stored; never the password itself.
+    pub password_hash: Option<String>,
+    pub admin_id: PlayerId,
+    pub next_id: PlayerId,
+    pub next_spectator_id: PlayerId,
+    /// Rounds started in the current game.
+    pub round: u64,
+    pub players: Vec<PlayerSnapshot>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PlayerSnapshot {
+    pub id: PlayerId,
+    pub name: String,
+    /// Follows from `id` and the room's `admin_id`; informational on restore.
     pub role: Role,
+    pub score: u32,
+    pub muted: bool,
+    pub color: u8,
+    /// Unix seconds at which the player's latest token expires.
+    pub token_exp: u64,
+}
+
+/// Public room details, shown before joining.
+#[derive(Serialize)]
+pub struct RoomInfoResponse {
+    pub room_id: String,
+    pub requires_password: bool,
+    pub answer_window_in_ms: u64,
+}
+
+#[derive(Serialize)]
+pub struct RoomSettingsResponse {
+    pub room_id: String,
+    pub answer_window_in_ms: u64,
+    pub max_players: usize,
+    pub requires_password: bool,
 }
 
+/// Carries enough to restore a session without waiting for the first broadcast.
 #[derive(Serialize)]
 pub struct RefreshTokenResponse {
     pub room_id: String,
     pub new_token: String,
+    pub answer_window_in_ms: u64,
+    pub player_name: String,
+    pub role: Role,
 }
 
-#[derive(Deserialize)]
-#[serde(tag = "type", rename_all = "snake_case")]
-pub enum ClientMessage {
-    Buzz,
-    StartRound,
-    ContinueRound,
-    Kick { name: String },
+/// Clock-sync sample. With the client's send and receive times `t0`/`t3`, the
+/// offset is `((received_ms - t0) + (server_ms - t3)) / 2`.
+#[derive(Serialize)]
+pub struct TimeResponse {
+    /// Echo of `?client_ms=`, so a client can match replies to requests.
+    pub client_ms: Option<u64>,
+    pub received_ms: u64,
+    pub server_ms: u64,
 }
 
 #[derive(Serialize)]
-#[serde(tag = "type", rename_all = "snake_case")]
-pub enum ServerMessage {
-    Accepted { name: String },
-    Participants { participants: Vec<ParticipantInfo> },
-    RoundStarted,
-    RoundContinued,
-    Rejected,
-    TimedOut { name: String },
-    ActionDenied { reason: String },
-    Kicked,
+pub struct HealthResponse {
+    pub status: &'static str,
+    pub uptime_secs: u64,
+    pub rooms: usize,
+    pub max_rooms: usize,
+    pub connections: usize,
+    /// Sockets closed since startup, by close reason such as `kicked` or
+    /// `slow_consumer`.
+    pub socket_closes: BTreeMap<&'static str, u64>,
 }
 
+/// Body of every HTTP error.
 #[derive(Serialize)]
-pub struct ParticipantInfo {
-    pub name: String,
-    pub role: Role,
-    pub locked_out: bool,
+pub struct ErrorResponse {
+    /// Stable code such as `name_taken`; see `AppError::code`.
+    pub error: &'static str,
+    pub message: &'static str,
+    pub retryable: bool,
+    /// Also sent as the `x-request-id` header.
+    pub request_id: Option<String>,
+}
+
+/// Body of a `429`; `retry_after_secs` is also in the `Retry-After` header.
+#[derive(Serialize)]
+pub struct RateLimitedResponse {
+    #[serde(flatten)]
+    pub error: ErrorResponse,
+    pub retry_after_secs: u64,
+}
+
+#[derive(Serialize)]
+pub struct ScoreboardResponse {
+    pub room_id: String,
+    pub entries: Vec<ScoreEntry>,
+    pub ts_ms: u64,
 }
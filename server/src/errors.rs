@@ -1,41 +1,294 @@
 use axum::{
+    Json,
+    extract::rejection::{JsonRejection, QueryRejection},
     http::StatusCode,
     response::{IntoResponse, Response},
 };
 use tracing::warn;
 
+use crate::dtos::ErrorResponse;
+use crate::request_id;
+
 #[derive(Debug)]
 pub enum AppError {
     RoomNotFound,
+    /// The room shut down while the player was connected.
+    RoomClosed,
+    InvalidRoomCode,
+    RoomCodeTaken,
     InvalidEmptyName,
+    InvalidName,
+    InvalidPassword,
+    InvalidRole,
+    /// The body or query string could not be parsed.
+    InvalidRequest,
+    WrongPassword,
     NameTaken,
     FullRoom,
     AuthRequired,
+    /// Includes a token for another room: tokens name their room as the JWT
+    /// audience, so since audiences are checked such a token fails here rather
+    /// than as `room_mismatch`.
     InvalidToken,
+    /// The token's `room_id` claim disagrees with the room in its audience.
     RoomMismatch,
     UserNotInRoom,
     SessionExpired,
+    /// The player already has a live socket and asked not to take it over.
+    AlreadyConnected,
     Kicked,
+    /// A kicked player's name, until the admin unbans it.
+    Banned,
+    Forbidden,
+    /// An admin action named someone who is not in the room.
+    UserNotFound,
+    CannotKickSelf,
+    QuestionEmpty,
+    QuestionTooLong,
+    /// No such endpoint.
+    NotFound,
+    /// A socket kept sending past its message quotas.
+    RateLimited,
+    ServerFull,
     Internal,
 }
 
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::RoomNotFound | AppError::UserNotFound | AppError::NotFound => {
+                StatusCode::NOT_FOUND
+            }
+            AppError::RoomClosed => StatusCode::GONE,
+            AppError::InvalidRoomCode
+            | AppError::InvalidEmptyName
+            | AppError::InvalidName
+            | AppError::InvalidPassword
+            | AppError::InvalidRole
+            | AppError::InvalidRequest
+            | AppError::CannotKickSelf
+            | AppError::QuestionEmpty
+            | AppError::QuestionTooLong => StatusCode::BAD_REQUEST,
+            AppError::RoomCodeTaken
+            | AppError::NameTaken
+            | AppError::FullRoom
+            | AppError::AlreadyConnected => StatusCode::CONFLICT,
+            AppError::AuthRequired | AppError::InvalidToken | AppError::WrongPassword => {
+                StatusCode::UNAUTHORIZED
+            }
+            AppError::RoomMismatch
+            | AppError::UserNotInRoom
+            | AppError::SessionExpired
+            | AppError::Kicked
+            | AppError::Banned
+            | AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            AppError::ServerFull => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Machine-readable code, shared by HTTP bodies and websocket denials.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::RoomNotFound => "room_not_found",
+            AppError::RoomClosed => "room_closed",
+            AppError::InvalidRoomCode => "invalid_room_code",
+            AppError::RoomCodeTaken => "room_code_taken",
+            AppError::InvalidEmptyName => "invalid_empty_name",
+            AppError::InvalidName => "invalid_name",
+            AppError::InvalidPassword => "invalid_password",
+            AppError::InvalidRole => "invalid_role",
+            AppError::InvalidRequest => "invalid_request",
+            AppError::WrongPassword => "wrong_password",
+            AppError::NameTaken => "name_taken",
+            AppError::FullRoom => "full_room",
+            AppError::AuthRequired => "auth_required",
+            AppError::InvalidToken => "invalid_token",
+            AppError::RoomMismatch => "room_mismatch",
+            AppError::UserNotInRoom => "user_not_in_room",
+            AppError::SessionExpired => "session_expired",
+            AppError::AlreadyConnected => "already_connected",
+            AppError::Kicked => "kicked",
+            AppError::Banned => "banned",
+            AppError::Forbidden => "forbidden",
+            AppError::UserNotFound => "user_not_found",
+            AppError::CannotKickSelf => "cannot_kick_self",
+            AppError::QuestionEmpty => "question_empty",
+            AppError::QuestionTooLong => "question_too_long",
+            AppError::NotFound => "not_found",
+            AppError::RateLimited => "rate_limited",
+            AppError::ServerFull => "server_full",
+            AppError::Internal => "internal",
+        }
+    }
+
+    /// Human-readable explanation; clients should branch on [`code`](Self::code).
+    pub fn message(&self) -> &'static str {
+        match self {
+            AppError::RoomNotFound => "No room with that code exists",
+            AppError::RoomClosed => "The room has been closed",
+            AppError::InvalidRoomCode => "Room codes are 4 to 16 letters, digits or dashes",
+            AppError::RoomCodeTaken => "That room code is already in use",
+            AppError::InvalidEmptyName => "Please enter a name",
+            AppError::InvalidName => "That name is not allowed",
+            AppError::InvalidPassword => "That password cannot be used",
+            AppError::InvalidRole => "You cannot join with that role",
+            AppError::InvalidRequest => "The request could not be understood",
+            AppError::WrongPassword => "Wrong room password",
+            AppError::NameTaken => "That name is already in use",
+            AppError::FullRoom => "The room is full",
+            AppError::AuthRequired => "Sign in to the room first",
+            AppError::InvalidToken => "Your session is not valid",
+            AppError::RoomMismatch => "Your session belongs to another room",
+            AppError::UserNotInRoom => "You are no longer in this room",
+            AppError::SessionExpired => "Your session has expired",
+            AppError::AlreadyConnected => "You are already connected from another tab",
+            AppError::Kicked => "You were removed from the room",
+            AppError::Banned => "You were removed from the room and cannot rejoin",
+            AppError::Forbidden => "You are not allowed to do that",
+            AppError::UserNotFound => "Nobody by that name is in the room",
+            AppError::CannotKickSelf => "You cannot kick yourself",
+            AppError::QuestionEmpty => "The question is empty",
+            AppError::QuestionTooLong => "The question is too long",
+            AppError::NotFound => "No such endpoint",
+            AppError::RateLimited => "Too many messages, please slow down",
+            AppError::ServerFull => "The server is full, please try again later",
+            AppError::Internal => "Something went wrong on our side",
+        }
+    }
+
+    /// Whether the same request may succeed later without changes.
+    pub fn retryable(&self) -> bool {
+        matches!(
+            self,
+            AppError::RateLimited | AppError::ServerFull | AppError::Internal
+        )
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        warn!("Rejected request body: {}", rejection.body_text());
+        AppError::InvalidRequest
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        warn!("Rejected query string: {}", rejection.body_text());
+        AppError::InvalidRequest
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         warn!("Responding with error: {:?}", self);
-        match self {
-            AppError::RoomNotFound => (StatusCode::NOT_FOUND, "room_not_found").into_response(),
-            AppError::InvalidEmptyName => {
-                (StatusCode::BAD_REQUEST, "invalid_empty_name").into_response()
-            }
-            AppError::NameTaken => (StatusCode::CONFLICT, "name_taken").into_response(),
-            AppError::FullRoom => (StatusCode::CONFLICT, "full_room").into_response(),
-            AppError::AuthRequired => (StatusCode::UNAUTHORIZED, "auth_required").into_response(),
-            AppError::InvalidToken => (StatusCode::UNAUTHORIZED, "invalid_token").into_response(),
-            AppError::RoomMismatch => (StatusCode::FORBIDDEN, "room_mismatch").into_response(),
-            AppError::UserNotInRoom => (StatusCode::FORBIDDEN, "user_not_in_room").into_response(),
-            AppError::SessionExpired => (StatusCode::FORBIDDEN, "session_expired").into_response(),
-            AppError::Kicked => (StatusCode::FORBIDDEN, "kicked").into_response(),
-            AppError::Internal => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        let body = ErrorResponse {
+            error: self.code(),
+            message: self.message(),
+            retryable: self.retryable(),
+            request_id: request_id::current(),
+        };
+        (self.status(), Json(body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The documented contract. The match has no wildcard, so adding a variant
+    /// without documenting it here does not compile.
+    fn documented(err: &AppError) -> (u16, &'static str, bool) {
+        match err {
+            AppError::RoomNotFound => (404, "room_not_found", false),
+            AppError::RoomClosed => (410, "room_closed", false),
+            AppError::InvalidRoomCode => (400, "invalid_room_code", false),
+            AppError::RoomCodeTaken => (409, "room_code_taken", false),
+            AppError::InvalidEmptyName => (400, "invalid_empty_name", false),
+            AppError::InvalidName => (400, "invalid_name", false),
+            AppError::InvalidPassword => (400, "invalid_password", false),
+            AppError::InvalidRole => (400, "invalid_role", false),
+            AppError::InvalidRequest => (400, "invalid_request", false),
+            AppError::WrongPassword => (401, "wrong_password", false),
+            AppError::NameTaken => (409, "name_taken", false),
+            AppError::FullRoom => (409, "full_room", false),
+            AppError::AuthRequired => (401, "auth_required", false),
+            AppError::InvalidToken => (401, "invalid_token", false),
+            AppError::RoomMismatch => (403, "room_mismatch", false),
+            AppError::UserNotInRoom => (403, "user_not_in_room", false),
+            AppError::SessionExpired => (403, "session_expired", false),
+            AppError::AlreadyConnected => (409, "already_connected", false),
+            AppError::Kicked => (403, "kicked", false),
+            AppError::Banned => (403, "banned", false),
+            AppError::Forbidden => (403, "forbidden", false),
+            AppError::UserNotFound => (404, "user_not_found", false),
+            AppError::CannotKickSelf => (400, "cannot_kick_self", false),
+            AppError::QuestionEmpty => (400, "question_empty", false),
+            AppError::QuestionTooLong => (400, "question_too_long", false),
+            AppError::NotFound => (404, "not_found", false),
+            AppError::RateLimited => (429, "rate_limited", true),
+            AppError::ServerFull => (503, "server_full", true),
+            AppError::Internal => (500, "internal", true),
         }
     }
+
+    const ALL: [AppError; 29] = [
+        AppError::RoomNotFound,
+        AppError::RoomClosed,
+        AppError::InvalidRoomCode,
+        AppError::RoomCodeTaken,
+        AppError::InvalidEmptyName,
+        AppError::InvalidName,
+        AppError::InvalidPassword,
+        AppError::InvalidRole,
+        AppError::InvalidRequest,
+        AppError::WrongPassword,
+        AppError::NameTaken,
+        AppError::FullRoom,
+        AppError::AuthRequired,
+        AppError::InvalidToken,
+        AppError::RoomMismatch,
+        AppError::UserNotInRoom,
+        AppError::SessionExpired,
+        AppError::AlreadyConnected,
+        AppError::Kicked,
+        AppError::Banned,
+        AppError::Forbidden,
+        AppError::UserNotFound,
+        AppError::CannotKickSelf,
+        AppError::QuestionEmpty,
+        AppError::QuestionTooLong,
+        AppError::NotFound,
+        AppError::RateLimited,
+        AppError::ServerFull,
+        AppError::Internal,
+    ];
+
+    #[test]
+    fn every_error_serializes_with_its_documented_code() {
+        crate::utils::testing::block_on(async {
+            for err in ALL {
+                let (status, code, retryable) = documented(&err);
+                let message = err.message();
+                let res = err.into_response();
+                assert_eq!(res.status().as_u16(), status, "{code}");
+                assert_eq!(res.headers()["content-type"], "application/json", "{code}");
+                let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+                assert_eq!(
+                    body,
+                    serde_json::json!({
+                        "error": code,
+                        "message": message,
+                        "retryable": retryable,
+                        "request_id": null,
+                    })
+                );
+            }
+        });
+    }
 }
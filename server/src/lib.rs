@@ -0,0 +1,5 @@
+//! The wire protocol of the buzzer server, for bots, tests and other Rust
+//! clients; the server binary uses the same types.
+
+pub mod client;
+pub mod dtos;
@@ -1,40 +1,51 @@
 mod adapter;
 mod auth;
-mod dtos;
+mod config;
 mod errors;
+mod extract;
 mod ratelimit;
+mod request_id;
 mod socket;
 mod state;
 mod utils;
+mod webhook;
 
 use std::net::SocketAddr;
 use std::sync::Arc;
 
 use axum::{
     Json, Router,
-    extract::{Path, Query, State, ws::WebSocketUpgrade},
-    http::{HeaderMap, StatusCode, header},
+    extract::{Path, State, ws::WebSocketUpgrade},
+    http::{HeaderMap, HeaderName, Method, StatusCode, header},
     response::IntoResponse,
     routing::{get, post},
 };
+use axum_server::tls_rustls::RustlsConfig;
+use server::dtos;
 use tokio::net::TcpListener;
-use tower_governor::{GovernorLayer, governor::GovernorConfigBuilder};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 
+use config::ServerConfig;
 use dtos::{
-    CreateRoomRequest, CreateRoomResponse, JoinRoomRequest, JoinRoomResponse, RefreshTokenResponse,
+    CreateRoomRequest, CreateRoomResponse, CreateRoomsRequest, HealthResponse, JoinRoomRequest,
+    JoinRoomResponse, PlayerNameRequest, RefreshTokenResponse, Role, RoomInfoResponse,
+    RoomSettingsResponse, RoomSnapshot, RoomStatusResponse, ScoreboardResponse, StartRoundRequest,
+    TimeResponse, UpdateRoomRequest,
 };
 use errors::AppError;
-use ratelimit::RateLimitSettings;
-use socket::{PlayerSession, handle_socket};
+use extract::{AppJson, AppQuery};
+use socket::{HARD_MESSAGE_LIMIT_FACTOR, PlayerSession, WireFormat, handle_socket};
 use state::app_state::AppState;
 
-use crate::state::room_state::RoomConfig;
-use tracing::info;
+use crate::state::room_state::{DEFAULT_INBOUND_RATE_PER_SEC, MAX_PLAYERS, RoomConfig, RoomState};
+use crate::utils::password::hash_password;
+use crate::utils::time::now_millis;
+use tracing::{error, info};
 
-const TICK_IN_MS: u64 = 10;
 const DEFAULT_ANSWER_WINDOW_IN_MS: u64 = 5000;
 const MIN_ANSWER_WINDOW_IN_MS: u64 = 500;
 const MAX_ANSWER_WINDOW_IN_MS: u64 = 60000;
+const MAX_BATCH_ROOMS: usize = 32;
 
 #[tokio::main]
 async fn main() {
@@ -44,95 +55,355 @@ async fn main() {
         )
         .init();
 
-    let state = AppState::new();
+    let args = CliArgs::parse(std::env::args().skip(1)).unwrap_or_else(|err| {
+        error!("{err}");
+        std::process::exit(2);
+    });
+    let config = ServerConfig::from_env(args.webhook_url).unwrap_or_else(|err| {
+        error!("Invalid configuration: {err}");
+        std::process::exit(2);
+    });
+    let state = AppState::new(&config);
+    restore_rooms(&state, &args.restore);
 
     // Rate limiting is keyed per real client IP (resolved through trusted proxy
-    // hops, see `ratelimit`). NOTE: tower_governor's `per_*` methods set the
-    // REPLENISH INTERVAL for one token, not a rate -- `per_millisecond(100)`
-    // replenishes a token every 100ms => 10 req/s sustained.
-    let rl = RateLimitSettings::from_env();
+    // hops, see `ratelimit`).
+    let rl = &config.rate_limits;
     info!(
-        "Rate limits per client IP: api {}/burst over {}ms, create {}/burst over {}ms, trusted proxy hops: {}",
-        rl.api_burst, rl.api_period_ms, rl.create_burst, rl.create_period_ms, rl.trusted_hops
+        "Rate limits per client IP: api {}/burst over {}ms, create {}/burst over {}ms, join {}/burst over {}ms, trusted proxy hops: {}",
+        rl.api_burst,
+        rl.api_period_ms,
+        rl.create_burst,
+        rl.create_period_ms,
+        rl.join_burst,
+        rl.join_period_ms,
+        rl.trusted_hops
     );
 
-    // General interactive traffic: join, token refresh, ws upgrade. Generous so a
+    let app = router(state.clone());
+
+    let addr = config.bind_addr;
+    let listener = TcpListener::bind(addr).await.expect("bind");
+    let Some(tls) = args.tls else {
+        // Background tasks were spawned by `AppState::new`, so we can take traffic now.
+        state.mark_ready();
+        info!("Web server running on http://{}", addr);
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .expect("serve");
+        return;
+    };
+
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let tls_config = RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+        .await
+        .unwrap_or_else(|err| {
+            error!(
+                "Cannot load TLS certificate {} and key {}: {err}",
+                tls.cert, tls.key
+            );
+            std::process::exit(2);
+        });
+    state.mark_ready();
+    info!("Web server running on https://{}", addr);
+    axum_server::from_tcp_rustls(listener.into_std().expect("listener"), tls_config)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .expect("serve");
+}
+
+/// Command line flags; everything else is configured through `BUZZER_*`.
+#[derive(Default)]
+struct CliArgs {
+    /// Every `--restore <file>`, in order.
+    restore: Vec<String>,
+    /// `--webhook-url <url>`.
+    webhook_url: Option<String>,
+    /// Serve HTTPS (and `wss`) directly instead of plain HTTP.
+    tls: Option<TlsFiles>,
+}
+
+/// PEM files from `--tls-cert <file>` and `--tls-key <file>`.
+#[derive(Debug, PartialEq)]
+struct TlsFiles {
+    cert: String,
+    key: String,
+}
+
+impl CliArgs {
+    /// Fails on an unknown flag, a flag missing its value, or only one of
+    /// `--tls-cert` and `--tls-key`.
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let (mut tls_cert, mut tls_key) = (None, None);
+        while let Some(arg) = args.next() {
+            let slot = match arg.as_str() {
+                "--restore" => None,
+                "--webhook-url" => Some(&mut parsed.webhook_url),
+                "--tls-cert" => Some(&mut tls_cert),
+                "--tls-key" => Some(&mut tls_key),
+                _ => {
+                    return Err(format!(
+                        "Unknown argument {arg:?}; expected --restore <file>, --webhook-url <url>, --tls-cert <file> or --tls-key <file>"
+                    ));
+                }
+            };
+            let Some(value) = args.next() else {
+                return Err(format!("{arg} needs a value"));
+            };
+            match slot {
+                Some(slot) => *slot = Some(value),
+                None => parsed.restore.push(value),
+            }
+        }
+        parsed.tls = match (tls_cert, tls_key) {
+            (Some(cert), Some(key)) => Some(TlsFiles { cert, key }),
+            (None, None) => None,
+            _ => return Err("--tls-cert and --tls-key must be given together".to_string()),
+        };
+        Ok(parsed)
+    }
+}
+
+/// Rebuilds the rooms in every `--restore <file>` argument, each file holding
+/// one [`RoomSnapshot`] as served by `GET /api/rooms/{room_id}/snapshot`.
+/// Any unreadable snapshot stops the server rather than starting without it.
+fn restore_rooms(state: &AppState, paths: &[String]) {
+    for path in paths {
+        let restored = std::fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|json| {
+                serde_json::from_str::<RoomSnapshot>(&json).map_err(|err| err.to_string())
+            })
+            .and_then(|snapshot| {
+                state
+                    .restore(&snapshot)
+                    .map_err(|err| err.message().to_string())
+            });
+        match restored {
+            Ok(room) => info!(
+                "Restored room {} with {} participants from {path}",
+                room.room_id(),
+                room.participants().len()
+            ),
+            Err(err) => {
+                error!("Cannot restore {path}: {err}");
+                std::process::exit(2);
+            }
+        }
+    }
+}
+
+fn router(state: AppState) -> Router {
+    let rl = state.config().rate_limits;
+    // General interactive traffic: token refresh, ws upgrade, reads. Generous so a
     // reconnect flurry or several tabs from one user never trips it.
-    let api_conf = Arc::new(
-        GovernorConfigBuilder::default()
-            .key_extractor(rl.extractor())
-            .per_millisecond(rl.api_period_ms)
-            .burst_size(rl.api_burst)
-            .finish()
-            .expect("valid api rate limit config"),
-    );
+    let api_conf = rl.bucket(rl.api_period_ms, rl.api_burst);
     // Room creation allocates a room + background tasks, so it gets a much tighter
     // bucket than the interactive endpoints.
-    let create_conf = Arc::new(
-        GovernorConfigBuilder::default()
-            .key_extractor(rl.extractor())
-            .per_millisecond(rl.create_period_ms)
-            .burst_size(rl.create_burst)
-            .finish()
-            .expect("valid create rate limit config"),
+    let create_conf = rl.bucket(rl.create_period_ms, rl.create_burst);
+    // Joining takes a player slot and mints a token; tight enough to stop scripted
+    // slot grabbing, loose enough for a classroom behind one NAT.
+    let join_conf = rl.bucket(rl.join_period_ms, rl.join_burst);
+    ratelimit::spawn_eviction(
+        vec![
+            Arc::clone(&api_conf),
+            Arc::clone(&create_conf),
+            Arc::clone(&join_conf),
+        ],
+        ratelimit::EVICTION_INTERVAL,
     );
 
-    let app = Router::new()
+    let api = Router::new()
+        .route(
+            "/api/time",
+            get(server_time).layer(ratelimit::layer(&api_conf)),
+        )
         .route(
             "/api/rooms",
-            post(create_room).layer(GovernorLayer::new(Arc::clone(&create_conf))),
+            post(create_room).layer(ratelimit::layer(&create_conf)),
+        )
+        .route(
+            "/api/rooms/batch",
+            post(create_rooms).layer(ratelimit::layer(&create_conf)),
+        )
+        .route(
+            "/api/rooms/{room_id}",
+            get(room_info)
+                .patch(update_room)
+                .delete(delete_room)
+                .layer(ratelimit::layer(&api_conf)),
         )
         .route(
             "/api/rooms/{room_id}/join",
-            post(join_room).layer(GovernorLayer::new(Arc::clone(&api_conf))),
+            post(join_room).layer(ratelimit::layer(&join_conf)),
         )
         .route(
             "/api/rooms/{room_id}/refresh_token",
-            post(token_refresh).layer(GovernorLayer::new(Arc::clone(&api_conf))),
+            post(token_refresh).layer(ratelimit::layer(&api_conf)),
+        )
+        .route(
+            "/api/rooms/{room_id}/leave",
+            post(leave_room).layer(ratelimit::layer(&api_conf)),
+        )
+        // Admin controls for clients that don't hold a websocket open.
+        .route(
+            "/api/rooms/{room_id}/start_round",
+            post(start_round).layer(ratelimit::layer(&api_conf)),
+        )
+        .route(
+            "/api/rooms/{room_id}/continue",
+            post(continue_round).layer(ratelimit::layer(&api_conf)),
+        )
+        .route(
+            "/api/rooms/{room_id}/kick",
+            post(kick_player).layer(ratelimit::layer(&api_conf)),
+        )
+        .route(
+            "/api/rooms/{room_id}/admin",
+            post(transfer_admin).layer(ratelimit::layer(&api_conf)),
         )
+        .route(
+            "/api/rooms/{room_id}/scoreboard",
+            get(scoreboard).layer(ratelimit::layer(&api_conf)),
+        )
+        .route(
+            "/api/rooms/{room_id}/export.csv",
+            get(export_csv).layer(ratelimit::layer(&api_conf)),
+        )
+        .route(
+            "/api/rooms/{room_id}/snapshot",
+            get(room_snapshot).layer(ratelimit::layer(&api_conf)),
+        )
+        .layer(cors_layer(&state.config().allowed_origins));
+
+    Router::new()
+        // Probes come from the load balancer and are never rate limited.
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .merge(api)
+        // Websockets are not subject to CORS; `ws_handler` checks `Origin` itself.
         .route(
             "/ws/{room_id}",
-            get(ws_handler).layer(GovernorLayer::new(Arc::clone(&api_conf))),
+            get(ws_handler).layer(ratelimit::layer(&api_conf)),
         )
-        .with_state(state);
+        .fallback(not_found)
+        // Outermost, so rate-limit and CORS responses carry an id too.
+        .layer(axum::middleware::from_fn(request_id::assign))
+        .with_state(state)
+}
 
-    let bind_addr = std::env::var("BIND_ADDR").unwrap_or_else(|_| "127.0.0.1:3000".to_string());
-    let addr: SocketAddr = bind_addr
-        .parse()
-        .expect("BIND_ADDR must be a valid socket address, e.g. 0.0.0.0:3000");
-    let listener = TcpListener::bind(addr).await.expect("bind");
-    info!("Web server running on http://{}", addr);
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .await
-    .expect("serve");
+async fn not_found() -> AppError {
+    AppError::NotFound
+}
+
+/// Lets a frontend on another origin call the JSON API. Preflight requests are
+/// answered here, before they reach the rate limiters.
+fn cors_layer(allowed_origins: &[String]) -> CorsLayer {
+    let layer = CorsLayer::new()
+        .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE])
+        .expose_headers([
+            header::AUTHORIZATION,
+            HeaderName::from_static(request_id::HEADER),
+        ]);
+    if allowed_origins.iter().any(|origin| origin == "*") {
+        layer.allow_origin(Any)
+    } else {
+        layer.allow_origin(AllowOrigin::list(
+            allowed_origins
+                .iter()
+                .filter_map(|origin| origin.parse().ok()),
+        ))
+    }
+}
+
+/// Browsers always send `Origin` on a websocket upgrade, and unlike fetches the
+/// upgrade is not covered by CORS. Pages from our own host need no allow-list
+/// entry; clients that send no `Origin` at all are not browsers.
+fn ws_origin_allowed(state: &AppState, headers: &HeaderMap) -> bool {
+    let Some(origin) = headers
+        .get(header::ORIGIN)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return true;
+    };
+    let same_origin = headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .zip(origin.split_once("://"))
+        .is_some_and(|(host, (_, origin_host))| host == origin_host);
+    same_origin || state.config().origin_allowed(origin)
+}
+
+async fn healthz(State(state): State<AppState>) -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok",
+        uptime_secs: state.uptime_secs(),
+        rooms: state.room_count(),
+        max_rooms: state.config().max_rooms,
+        connections: state.connection_count(),
+        socket_closes: state.socket_closes(),
+    })
+}
+
+async fn readyz(State(state): State<AppState>) -> StatusCode {
+    if state.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct TimeQuery {
+    client_ms: Option<u64>,
+}
+
+async fn server_time(AppQuery(query): AppQuery<TimeQuery>) -> Json<TimeResponse> {
+    let received_ms = now_millis();
+    Json(TimeResponse {
+        client_ms: query.client_ms,
+        received_ms,
+        server_ms: now_millis(),
+    })
 }
 
 async fn create_room(
     State(state): State<AppState>,
-    Json(req): Json<CreateRoomRequest>,
+    AppJson(req): AppJson<CreateRoomRequest>,
 ) -> Result<(StatusCode, Json<CreateRoomResponse>), AppError> {
-    if req.name.trim().is_empty() {
-        return Err(AppError::InvalidEmptyName);
-    }
+    let name = state.name_filter().validate(&req.name)?;
+    let password_hash = req
+        .password
+        .as_deref()
+        .filter(|password| !password.is_empty())
+        .map(hash_password)
+        .transpose()?;
 
-    let answer_window_in_ms = match req.answer_window_in_ms {
-        Some(value) if value < MIN_ANSWER_WINDOW_IN_MS => MIN_ANSWER_WINDOW_IN_MS,
-        Some(value) if value > MAX_ANSWER_WINDOW_IN_MS => MAX_ANSWER_WINDOW_IN_MS,
-        Some(value) => value,
-        None => DEFAULT_ANSWER_WINDOW_IN_MS,
-    };
+    let answer_window_in_ms = req
+        .answer_window_in_ms
+        .map_or(DEFAULT_ANSWER_WINDOW_IN_MS, clamp_answer_window);
 
     let (room_id, room) = state.create_room(
         RoomConfig {
             answer_window_in_ms,
+            history_limit: state.round_history_limit(),
+            inbound_rate_per_sec: req
+                .inbound_rate_per_sec
+                .unwrap_or(DEFAULT_INBOUND_RATE_PER_SEC),
+            idle_timeout_in_ms: state.room_idle_timeout_in_ms(),
+            admin_grace_in_ms: state.admin_grace_in_ms(),
+            buzz_debounce_in_ms: state.buzz_debounce_in_ms(),
         },
-        TICK_IN_MS,
-    );
+        req.room_code.as_deref(),
+    )?;
+    room.set_password_hash(password_hash);
 
-    let token = room.create_admin(&req.name).await?;
+    let token = room.create_admin(&name).await?;
 
     let response = CreateRoomResponse {
         room_id,
@@ -142,28 +413,136 @@ async fn create_room(
     Ok((StatusCode::CREATED, Json(response)))
 }
 
+/// Creates up to [`MAX_BATCH_ROOMS`] rooms at once, each with its own admin
+/// token. Nothing is left behind if any of them fails.
+async fn create_rooms(
+    State(state): State<AppState>,
+    AppJson(req): AppJson<CreateRoomsRequest>,
+) -> Result<(StatusCode, Json<Vec<CreateRoomResponse>>), AppError> {
+    if !(1..=MAX_BATCH_ROOMS).contains(&req.count) {
+        return Err(AppError::InvalidRequest);
+    }
+    let name = state.name_filter().validate(&req.name)?;
+    let answer_window_in_ms = req
+        .answer_window_in_ms
+        .map_or(DEFAULT_ANSWER_WINDOW_IN_MS, clamp_answer_window);
+
+    let rooms = state.create_rooms(
+        RoomConfig {
+            answer_window_in_ms,
+            history_limit: state.round_history_limit(),
+            inbound_rate_per_sec: DEFAULT_INBOUND_RATE_PER_SEC,
+            idle_timeout_in_ms: state.room_idle_timeout_in_ms(),
+            admin_grace_in_ms: state.admin_grace_in_ms(),
+            buzz_debounce_in_ms: state.buzz_debounce_in_ms(),
+        },
+        req.prefix.as_deref(),
+        req.count,
+    )?;
+
+    let mut responses = Vec::with_capacity(rooms.len());
+    for (room_id, room) in &rooms {
+        match room.create_admin(&name).await {
+            Ok(token) => responses.push(CreateRoomResponse {
+                room_id: room_id.clone(),
+                token,
+                answer_window_in_ms,
+            }),
+            Err(err) => {
+                state.close_rooms(&rooms, "batch_failed");
+                return Err(err);
+            }
+        }
+    }
+    Ok((StatusCode::CREATED, Json(responses)))
+}
+
+fn clamp_answer_window(value: u64) -> u64 {
+    value.clamp(MIN_ANSWER_WINDOW_IN_MS, MAX_ANSWER_WINDOW_IN_MS)
+}
+
+/// Changes live room settings; admin only. A new answer window applies from the
+/// next round.
+async fn update_room(
+    Path(room_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    AppJson(req): AppJson<UpdateRoomRequest>,
+) -> Result<Json<RoomSettingsResponse>, AppError> {
+    let room = state.get_room(&room_id)?;
+    let room_id = room.room_id().to_string();
+    let token = bearer_token(&headers).ok_or(AppError::AuthRequired)?;
+    room.authorize_admin(token)?;
+
+    let password_hash = match req.password.as_deref() {
+        None => None,
+        Some("") => Some(None),
+        Some(password) => Some(Some(hash_password(password)?)),
+    };
+    let settings = room
+        .update_settings(
+            req.answer_window_in_ms.map(clamp_answer_window),
+            req.max_players.map(|value| value.clamp(1, MAX_PLAYERS)),
+            password_hash,
+        )
+        .await?;
+    Ok(Json(RoomSettingsResponse {
+        room_id,
+        answer_window_in_ms: settings.answer_window_in_ms,
+        max_players: settings.max_players,
+        requires_password: room.requires_password(),
+    }))
+}
+
+async fn room_info(
+    Path(room_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<RoomInfoResponse>, AppError> {
+    let room = state.get_room(&room_id)?;
+    Ok(Json(RoomInfoResponse {
+        room_id: room.room_id().to_string(),
+        requires_password: room.requires_password(),
+        answer_window_in_ms: room.answer_window_in_ms(),
+    }))
+}
+
+/// Closes the room for everyone; admin only.
+async fn delete_room(
+    Path(room_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<StatusCode, AppError> {
+    let room = state.get_room(&room_id)?;
+    let room_id = room.room_id().to_string();
+    let token = bearer_token(&headers).ok_or(AppError::AuthRequired)?;
+    room.authorize_admin(token)?;
+
+    state.close_room(&room_id, "closed_by_admin")?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 async fn join_room(
     Path(room_id): Path<String>,
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(req): Json<JoinRoomRequest>,
+    AppJson(req): AppJson<JoinRoomRequest>,
 ) -> Result<(StatusCode, Json<JoinRoomResponse>), AppError> {
-    let requested_name = req.name.trim().to_string();
-    if requested_name.is_empty() {
-        return Err(AppError::InvalidEmptyName);
-    }
+    let requested_name = state.name_filter().validate(&req.name)?;
 
     let room = state.get_room(&room_id)?;
-    let token = headers
-        .get(header::AUTHORIZATION)
-        .and_then(|value| value.to_str().ok())
-        .and_then(|value| value.strip_prefix("Bearer "));
+    let room_id = room.room_id().to_string();
+    let token = bearer_token(&headers);
+    if token.is_none() {
+        room.check_password(req.password.as_deref())?;
+    }
 
-    let (token, role) = room.join(&requested_name, token).await?;
+    let role = req.role.unwrap_or(Role::Player);
+    let (token, role) = room.join(&requested_name, token, role).await?;
     let response = JoinRoomResponse {
         room_id: room_id.to_string(),
         token,
         answer_window_in_ms: room.answer_window_in_ms(),
+        player_name: requested_name.to_string(),
         role,
     };
     Ok((StatusCode::OK, Json(response)))
@@ -175,53 +554,222 @@ async fn token_refresh(
     headers: HeaderMap,
 ) -> Result<(StatusCode, Json<RefreshTokenResponse>), AppError> {
     let room = state.get_room(&room_id)?;
+    let room_id = room.room_id().to_string();
 
-    let Some(token) = headers
-        .get(header::AUTHORIZATION)
-        .and_then(|value| value.to_str().ok())
-        .and_then(|value| value.strip_prefix("Bearer "))
-    else {
+    let Some(token) = bearer_token(&headers) else {
         return Err(AppError::AuthRequired);
     };
 
-    let new_token = room.refresh_token(token).await?;
+    let (new_token, claims) = room.refresh_token(token).await?;
 
     Ok((
         StatusCode::OK,
         Json(RefreshTokenResponse {
             room_id: room_id.to_string(),
             new_token,
+            answer_window_in_ms: room.answer_window_in_ms(),
+            player_name: claims.name,
+            role: claims.role,
         }),
     ))
 }
 
+async fn leave_room(
+    Path(room_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<StatusCode, AppError> {
+    let room = state.get_room(&room_id)?;
+    let room_id = room.room_id().to_string();
+    let token = bearer_token(&headers).ok_or(AppError::AuthRequired)?;
+    let claims = state.auth().verify(token, &room_id)?;
+    if !room.player_matches(claims.player_id, &claims.name) {
+        return Err(AppError::UserNotInRoom);
+    }
+
+    room.leave(claims.player_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn scoreboard(
+    Path(room_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<ScoreboardResponse>, AppError> {
+    let room = state.get_room(&room_id)?;
+    let room_id = room.room_id().to_string();
+    Ok(Json(ScoreboardResponse {
+        room_id,
+        entries: room.scoreboard(),
+        ts_ms: now_millis(),
+    }))
+}
+
+async fn start_round(
+    Path(room_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    AppJson(req): AppJson<StartRoundRequest>,
+) -> Result<Json<RoomStatusResponse>, AppError> {
+    let room = state.get_room(&room_id)?;
+    let token = bearer_token(&headers).ok_or(AppError::AuthRequired)?;
+    let admin_id = room.authorize_admin(token)?;
+    room.start_round(admin_id, req.countdown_ms, req.question, None)
+        .await?;
+    room_status(&room).await
+}
+
+async fn continue_round(
+    Path(room_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<RoomStatusResponse>, AppError> {
+    let room = state.get_room(&room_id)?;
+    let token = bearer_token(&headers).ok_or(AppError::AuthRequired)?;
+    let admin_id = room.authorize_admin(token)?;
+    room.continue_round(admin_id, None).await?;
+    room_status(&room).await
+}
+
+async fn kick_player(
+    Path(room_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    AppJson(req): AppJson<PlayerNameRequest>,
+) -> Result<Json<RoomStatusResponse>, AppError> {
+    let room = state.get_room(&room_id)?;
+    let token = bearer_token(&headers).ok_or(AppError::AuthRequired)?;
+    let admin_id = room.authorize_admin(token)?;
+    room.kick_by_name(admin_id, &req.name, None).await?;
+    room_status(&room).await
+}
+
+/// Hands the room to another player. Their current token keeps working and
+/// grants admin rights straight away; refreshing it updates its `role` claim.
+async fn transfer_admin(
+    Path(room_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    AppJson(req): AppJson<PlayerNameRequest>,
+) -> Result<Json<RoomStatusResponse>, AppError> {
+    let room = state.get_room(&room_id)?;
+    let token = bearer_token(&headers).ok_or(AppError::AuthRequired)?;
+    let admin_id = room.authorize_admin(token)?;
+    room.transfer_admin(admin_id, &req.name).await?;
+    room_status(&room).await
+}
+
+/// Round controls go through the room loop's queue, so the view it reports
+/// next already reflects them.
+async fn room_status(room: &RoomState) -> Result<Json<RoomStatusResponse>, AppError> {
+    let view = room.query_game_view().await?;
+    Ok(Json(RoomStatusResponse {
+        room_id: room.room_id().to_string(),
+        round: view.round,
+        paused: view.paused,
+        participants: room.participants(),
+    }))
+}
+
+/// The room as JSON for the admin, to be fed back with `--restore` after a restart.
+async fn room_snapshot(
+    Path(room_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<RoomSnapshot>, AppError> {
+    let room = state.get_room(&room_id)?;
+    let token = bearer_token(&headers).ok_or(AppError::AuthRequired)?;
+    room.authorize_admin(token)?;
+    Ok(Json(room.snapshot()))
+}
+
+/// Round history as CSV for the room's admin.
+async fn export_csv(
+    Path(room_id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let room = state.get_room(&room_id)?;
+    let room_id = room.room_id().to_string();
+    let token = bearer_token(&headers).ok_or(AppError::AuthRequired)?;
+    room.authorize_admin(token)?;
+
+    let disposition = format!("attachment; filename=\"buzzer-{room_id}.csv\"");
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        room.history_csv(),
+    ))
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
 #[derive(serde::Deserialize)]
 struct WsAuthQuery {
     token: String,
+    /// Last `seq` seen before reconnecting; missed messages are replayed.
+    since_seq: Option<u64>,
+    /// Wins over any subprotocol the client offers.
+    format: Option<WireFormat>,
+    /// `false` refuses the socket while another one is connected, instead of
+    /// superseding it.
+    takeover: Option<bool>,
 }
 
 async fn ws_handler(
     Path(room_id): Path<String>,
     State(state): State<AppState>,
-    Query(query): Query<WsAuthQuery>,
+    AppQuery(query): AppQuery<WsAuthQuery>,
+    headers: HeaderMap,
     ws: WebSocketUpgrade,
 ) -> Result<axum::response::Response, AppError> {
     info!("[WS] Handshake initiated for room: {}", room_id);
+    if !ws_origin_allowed(&state, &headers) {
+        return Err(AppError::Forbidden);
+    }
     let room = state.get_room(&room_id)?;
+    let room_id = room.room_id().to_string();
+    // A room whose loop has stopped is on its way out; don't hand it new sockets.
+    room.query_game_view().await?;
 
-    let claims = state.auth().verify(&query.token)?;
-
-    if claims.room_id != room_id {
-        return Err(AppError::RoomMismatch);
-    }
+    let claims = state.auth().verify(&query.token, &room_id)?;
 
     if !room.player_matches(claims.player_id, &claims.name) {
         return Err(AppError::UserNotInRoom);
     }
 
+    let formats = match query.format {
+        Some(format) => vec![format],
+        None => WireFormat::PREFERENCE.to_vec(),
+    };
+    let hard_limit = state.config().ws_max_message_bytes * HARD_MESSAGE_LIMIT_FACTOR;
+    let ws = ws
+        .protocols(formats.into_iter().map(WireFormat::subprotocol))
+        .max_message_size(hard_limit)
+        .max_frame_size(hard_limit);
+    let format = ws
+        .selected_protocol()
+        .and_then(|protocol| protocol.to_str().ok())
+        .and_then(WireFormat::from_subprotocol)
+        .or(query.format)
+        .unwrap_or_default();
+
     let session = PlayerSession {
+        room_id: room_id.clone(),
         player_id: claims.player_id,
         name: claims.name,
+        role: claims.role,
+        issued_at: claims.iat,
+        expires_at: claims.exp,
+        format,
+        since_seq: query.since_seq,
+        takeover: query.takeover.unwrap_or(true),
     };
 
     info!(
@@ -230,6 +778,9 @@ async fn ws_handler(
     );
 
     Ok(ws
-        .on_upgrade(move |socket| handle_socket(socket, room, session))
+        .on_upgrade(move |socket| handle_socket(socket, state, room, session))
         .into_response())
 }
+
+#[cfg(test)]
+mod main_tests;
@@ -0,0 +1,2327 @@
+use super::*;
+use crate::dtos::ScoreEntry;
+use crate::state::room_state::{
+    DEFAULT_ADMIN_GRACE_IN_MS, DEFAULT_BUZZ_DEBOUNCE_IN_MS, DEFAULT_IDLE_TIMEOUT_IN_MS,
+};
+use crate::utils::testing::{block_on, forward_broadcasts, next_of_type, outbound_channel};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+/// The settings a room gets unless a test says otherwise.
+fn room_config(answer_window_in_ms: u64) -> RoomConfig {
+    RoomConfig {
+        answer_window_in_ms,
+        history_limit: 10,
+        inbound_rate_per_sec: DEFAULT_INBOUND_RATE_PER_SEC,
+        idle_timeout_in_ms: DEFAULT_IDLE_TIMEOUT_IN_MS,
+        admin_grace_in_ms: DEFAULT_ADMIN_GRACE_IN_MS,
+        buzz_debounce_in_ms: DEFAULT_BUZZ_DEBOUNCE_IN_MS,
+    }
+}
+
+/// A default server with one empty room in it.
+fn new_room(answer_window_in_ms: u64) -> (AppState, String, Arc<RoomState>) {
+    let state = AppState::new(&ServerConfig::default());
+    let (room_id, room) = state
+        .create_room(room_config(answer_window_in_ms), None)
+        .unwrap();
+    (state, room_id, room)
+}
+
+/// Serves `app` on a free local port and returns where.
+async fn serve(app: Router) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+    });
+    addr
+}
+
+fn auth_headers(token: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        format!("Bearer {token}").parse().unwrap(),
+    );
+    headers
+}
+
+fn parse_args(args: &[&str]) -> Result<CliArgs, String> {
+    CliArgs::parse(args.iter().map(|arg| arg.to_string()))
+}
+
+#[test]
+fn tls_needs_both_cert_and_key() {
+    let err = parse_args(&["--tls-cert", "cert.pem"]).err().unwrap();
+    assert!(err.contains("together"), "{err}");
+    assert!(parse_args(&["--tls-key", "key.pem", "--restore", "room.json"]).is_err());
+
+    let args = parse_args(&["--tls-cert", "cert.pem", "--tls-key", "key.pem"]).unwrap();
+    assert_eq!(
+        args.tls,
+        Some(TlsFiles {
+            cert: "cert.pem".to_string(),
+            key: "key.pem".to_string(),
+        })
+    );
+    assert_eq!(parse_args(&[]).unwrap().tls, None);
+    assert!(parse_args(&["--tls-cert"]).is_err());
+    assert!(parse_args(&["--tls"]).is_err());
+}
+
+#[test]
+fn scoreboard_endpoint_orders_by_score_then_name() {
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        room.create_admin("Aaron").await.unwrap();
+        let (tx, mut rx) = outbound_channel();
+        room.attach_connection(0, "Aaron", tx, None, true)
+            .await
+            .unwrap();
+        forward_broadcasts(&room, 0);
+
+        for name in ["Carol", "Bob"] {
+            let (token, _) = room.join(name, None, Role::Player).await.unwrap();
+            let player_id = state.auth().verify(&token, &room_id).unwrap().player_id;
+            room.start_round(0, None, None, None).await.unwrap();
+            next_of_type(&mut rx, "round_started").await;
+            room.send_buzz(player_id, None);
+            next_of_type(&mut rx, "accepted").await;
+            room.mark_correct(0, None);
+            next_of_type(&mut rx, "scoreboard").await;
+        }
+
+        let Json(board) = scoreboard(Path(room_id.clone()), State(state))
+            .await
+            .unwrap();
+        let entry = |name: &str, score| ScoreEntry {
+            name: name.to_string(),
+            score,
+        };
+        assert_eq!(board.room_id, room_id);
+        assert_eq!(
+            board.entries,
+            [entry("Bob", 1), entry("Carol", 1), entry("Aaron", 0)]
+        );
+    });
+}
+
+#[test]
+fn snapshot_endpoint_is_admin_only() {
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        let admin_token = room.create_admin("Aaron").await.unwrap();
+        let (player_token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+
+        let denied = room_snapshot(
+            Path(room_id.clone()),
+            State(state.clone()),
+            auth_headers(&player_token),
+        )
+        .await;
+        assert!(matches!(denied, Err(AppError::Forbidden)));
+        let Json(snapshot) = room_snapshot(
+            Path(room_id.to_lowercase()),
+            State(state),
+            auth_headers(&admin_token),
+        )
+        .await
+        .unwrap();
+        assert_eq!(snapshot.room_id, room_id);
+        let names: Vec<_> = snapshot
+            .players
+            .iter()
+            .map(|p| (p.id, p.name.as_str(), p.role))
+            .collect();
+        assert_eq!(names, [(0, "Aaron", Role::Admin), (1, "Bob", Role::Player)]);
+    });
+}
+
+#[test]
+fn export_csv_requires_admin_and_returns_csv() {
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        let admin_token = room.create_admin("Aaron").await.unwrap();
+        let (player_token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let (tx, mut rx) = outbound_channel();
+        room.attach_connection(0, "Aaron", tx, None, true)
+            .await
+            .unwrap();
+        forward_broadcasts(&room, 0);
+
+        let bob = state
+            .auth()
+            .verify(&player_token, &room_id)
+            .unwrap()
+            .player_id;
+        room.start_round(0, None, None, None).await.unwrap();
+        next_of_type(&mut rx, "round_started").await;
+        room.send_buzz(bob, None);
+        next_of_type(&mut rx, "accepted").await;
+        room.mark_correct(0, None);
+        next_of_type(&mut rx, "correct").await;
+
+        let denied = export_csv(
+            Path(room_id.clone()),
+            State(state.clone()),
+            auth_headers(&player_token),
+        )
+        .await;
+        assert!(matches!(denied, Err(AppError::Forbidden)));
+
+        let response = export_csv(Path(room_id), State(state), auth_headers(&admin_token))
+            .await
+            .unwrap()
+            .into_response();
+        let headers = response.headers();
+        assert_eq!(headers[header::CONTENT_TYPE], "text/csv; charset=utf-8");
+        assert!(
+            headers[header::CONTENT_DISPOSITION]
+                .to_str()
+                .unwrap()
+                .starts_with("attachment;")
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let mut lines = body.lines();
+        assert_eq!(lines.next(), Some("round,player,result,reaction_ms"));
+        assert!(lines.next().unwrap().starts_with("1,Bob,correct,"));
+    });
+}
+
+#[test]
+fn room_cleanup_closes_every_socket() {
+    use futures::StreamExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        room.create_admin("Aaron").await.unwrap();
+
+        let addr = serve(router(state.clone())).await;
+
+        let mut clients = Vec::new();
+        for name in ["Bob", "Carol"] {
+            let (token, _) = room.join(name, None, Role::Player).await.unwrap();
+            let url = format!("ws://{addr}/ws/{room_id}?token={token}");
+            let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+            // The welcome means the connection is attached.
+            let Some(Ok(Message::Text(_))) = ws.next().await else {
+                panic!("{name} got no welcome");
+            };
+            clients.push(ws);
+        }
+
+        // Without its admin the room counts as abandoned.
+        room.leave(0).await.unwrap();
+        state.remove_abandoned_rooms();
+
+        for mut ws in clients {
+            let closing = async {
+                let mut saw_room_closed = false;
+                while let Some(msg) = ws.next().await {
+                    match msg.unwrap() {
+                        Message::Text(text) => {
+                            let msg: serde_json::Value = serde_json::from_str(&text).unwrap();
+                            if msg["type"] == "room_closed" {
+                                assert_eq!(msg["reason"], "admin_expired");
+                                saw_room_closed = true;
+                            }
+                        }
+                        Message::Close(frame) => {
+                            assert!(saw_room_closed, "closed before room_closed");
+                            return frame.map(|frame| (frame.code, frame.reason.to_string()));
+                        }
+                        _ => {}
+                    }
+                }
+                panic!("socket ended without a close frame");
+            };
+            let code = tokio::time::timeout(std::time::Duration::from_secs(2), closing)
+                .await
+                .expect("socket was not closed");
+            assert_eq!(
+                code,
+                Some((CloseCode::from(4410), "room_closed".to_string()))
+            );
+        }
+        assert!(matches!(
+            state.get_room(&room_id),
+            Err(AppError::RoomNotFound)
+        ));
+    });
+}
+
+#[test]
+fn one_socket_per_player_receives_broadcasts() {
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        room.create_admin("Aaron").await.unwrap();
+        let (token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+
+        let addr = serve(router(state.clone())).await;
+        let url = format!("ws://{addr}/ws/{room_id}?token={token}");
+
+        let (mut first, _) = tokio_tungstenite::connect_async(url.as_str())
+            .await
+            .unwrap();
+        next_ws_message(&mut first, "welcome").await;
+
+        // Asking not to take over leaves the first socket alone.
+        let (mut refused, _) = tokio_tungstenite::connect_async(format!("{url}&takeover=false"))
+            .await
+            .unwrap();
+        assert_eq!(
+            next_close_frame(&mut refused).await,
+            Some((CloseCode::from(4409), "already_connected".to_string()))
+        );
+
+        // By default the newest socket wins and the old one is told so.
+        let (mut second, _) = tokio_tungstenite::connect_async(url.as_str())
+            .await
+            .unwrap();
+        next_ws_message(&mut second, "welcome").await;
+        next_ws_message(&mut first, "replaced").await;
+        assert_eq!(
+            next_close_frame(&mut first).await,
+            Some((CloseCode::from(4409), "replaced".to_string()))
+        );
+
+        room.start_round(0, None, None, None).await.unwrap();
+        next_ws_message(&mut second, "round_started").await;
+        assert_eq!(room.connection_count(), 1);
+    });
+}
+
+#[test]
+fn silent_sockets_are_reaped_while_live_ones_stay() {
+    use futures::{SinkExt, StreamExt};
+    use std::time::{Duration, Instant};
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        let state = AppState::new(&ServerConfig {
+            ws_ping_interval_ms: 100,
+            ws_idle_timeout_ms: 300,
+            ..ServerConfig::default()
+        });
+        let (room_id, room) = state.create_room(room_config(1000), None).unwrap();
+        room.create_admin("Aaron").await.unwrap();
+
+        let addr = serve(router(state.clone())).await;
+
+        let mut clients = Vec::new();
+        for name in ["Bob", "Carol"] {
+            let (token, _) = room.join(name, None, Role::Player).await.unwrap();
+            let url = format!("ws://{addr}/ws/{room_id}?token={token}");
+            let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+            next_ws_message(&mut ws, "welcome").await;
+            clients.push(ws);
+        }
+        let [mut bob, _carol] = <[_; 2]>::try_from(clients).unwrap();
+        let connected = Instant::now();
+
+        // Carol never reads again, so she answers no pings; Bob keeps
+        // reading, which pongs, and pings the server himself.
+        bob.send(Message::Ping("hi".into())).await.unwrap();
+        let mut ponged = false;
+        while connected.elapsed() < Duration::from_millis(1000) {
+            match tokio::time::timeout(Duration::from_millis(50), bob.next()).await {
+                Ok(Some(Ok(Message::Pong(payload)))) => {
+                    assert_eq!(&payload[..], b"hi");
+                    ponged = true;
+                }
+                Ok(Some(Ok(Message::Ping(_) | Message::Text(_)))) | Err(_) => {}
+                Ok(other) => panic!("Bob's socket ended: {other:?}"),
+            }
+            if room.connection_count() == 2 {
+                assert!(
+                    connected.elapsed() < Duration::from_millis(700),
+                    "Carol outlived the idle timeout"
+                );
+            } else {
+                assert!(connected.elapsed() >= Duration::from_millis(300));
+            }
+        }
+        assert!(ponged);
+        assert_eq!(room.connection_count(), 1);
+        assert!(
+            room.participants()
+                .iter()
+                .any(|p| p.name == "Bob" && p.connected)
+        );
+    });
+}
+
+#[test]
+fn leave_endpoint_invalidates_token() {
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        room.create_admin("Aaron").await.unwrap();
+        let (token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Bearer {token}").parse().unwrap(),
+        );
+
+        let status = leave_room(Path(room_id.clone()), State(state.clone()), headers.clone())
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let again = leave_room(Path(room_id), State(state), headers).await;
+        assert!(matches!(again, Err(AppError::UserNotInRoom)));
+        assert!(matches!(
+            room.refresh_token(&token).await,
+            Err(AppError::UserNotInRoom)
+        ));
+    });
+}
+
+#[test]
+fn delete_room_requires_admin_and_closes_room() {
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        let admin_token = room.create_admin("Aaron").await.unwrap();
+        let (player_token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let (tx, mut rx) = outbound_channel();
+        room.attach_connection(0, "Aaron", tx, None, true)
+            .await
+            .unwrap();
+        forward_broadcasts(&room, 0);
+
+        let denied = delete_room(
+            Path(room_id.clone()),
+            State(state.clone()),
+            auth_headers(&player_token),
+        )
+        .await;
+        assert!(matches!(denied, Err(AppError::Forbidden)));
+
+        let status = delete_room(
+            Path(room_id.clone()),
+            State(state.clone()),
+            auth_headers(&admin_token),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let closed = next_of_type(&mut rx, "room_closed").await;
+        assert_eq!(closed["reason"], "closed_by_admin");
+
+        let join = join_room(
+            Path(room_id.clone()),
+            State(state.clone()),
+            HeaderMap::new(),
+            AppJson(JoinRoomRequest {
+                name: "Carol".to_string(),
+                password: None,
+                role: None,
+            }),
+        )
+        .await;
+        assert!(matches!(join, Err(AppError::RoomNotFound)));
+        let again = delete_room(Path(room_id), State(state), auth_headers(&admin_token)).await;
+        assert!(matches!(again, Err(AppError::RoomNotFound)));
+    });
+}
+
+#[test]
+fn update_room_clamps_and_broadcasts_settings() {
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        let admin_token = room.create_admin("Aaron").await.unwrap();
+        let (player_token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let (tx, mut rx) = outbound_channel();
+        room.attach_connection(0, "Aaron", tx, None, true)
+            .await
+            .unwrap();
+        forward_broadcasts(&room, 0);
+        let update = || UpdateRoomRequest {
+            answer_window_in_ms: Some(1),
+            max_players: Some(1),
+            password: None,
+        };
+
+        let denied = update_room(
+            Path(room_id.clone()),
+            State(state.clone()),
+            auth_headers(&player_token),
+            AppJson(update()),
+        )
+        .await;
+        assert!(matches!(denied, Err(AppError::Forbidden)));
+
+        let Json(settings) = update_room(
+            Path(room_id.clone()),
+            State(state.clone()),
+            auth_headers(&admin_token),
+            AppJson(update()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            (settings.answer_window_in_ms, settings.max_players),
+            (MIN_ANSWER_WINDOW_IN_MS, 1)
+        );
+        let changed = next_of_type(&mut rx, "settings_changed").await;
+        assert_eq!(changed["answer_window_in_ms"], MIN_ANSWER_WINDOW_IN_MS);
+        assert_eq!(changed["requires_password"], false);
+        assert_eq!(room.answer_window_in_ms(), MIN_ANSWER_WINDOW_IN_MS);
+
+        // The room hears about a new password in the same update.
+        let Json(settings) = update_room(
+            Path(room_id.clone()),
+            State(state.clone()),
+            auth_headers(&admin_token),
+            AppJson(UpdateRoomRequest {
+                answer_window_in_ms: None,
+                max_players: None,
+                password: Some("s3cret".to_string()),
+            }),
+        )
+        .await
+        .unwrap();
+        assert!(settings.requires_password);
+        let changed = next_of_type(&mut rx, "settings_changed").await;
+        assert_eq!(changed["requires_password"], true);
+
+        // Joining afterwards reports the window in force, not the original.
+        let (_, Json(joined)) = join_room(
+            Path(room_id.clone()),
+            State(state.clone()),
+            auth_headers(&player_token),
+            AppJson(JoinRoomRequest {
+                name: "Bob".to_string(),
+                password: None,
+                role: None,
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(joined.room_id, room_id);
+        assert_eq!(joined.answer_window_in_ms, MIN_ANSWER_WINDOW_IN_MS);
+
+        // Bob already fills the single player seat.
+        assert!(matches!(
+            room.join("Carol", None, Role::Player).await,
+            Err(AppError::FullRoom)
+        ));
+    });
+}
+
+#[test]
+fn password_gates_new_joins_but_not_token_rejoins() {
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        let admin_token = room.create_admin("Aaron").await.unwrap();
+        let (bob_token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let bearer = |token: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::AUTHORIZATION,
+                format!("Bearer {token}").parse().unwrap(),
+            );
+            headers
+        };
+        let join = |name: &str, password: Option<&str>, headers: HeaderMap| {
+            join_room(
+                Path(room_id.clone()),
+                State(state.clone()),
+                headers,
+                AppJson(JoinRoomRequest {
+                    name: name.to_string(),
+                    password: password.map(str::to_string),
+                    role: None,
+                }),
+            )
+        };
+
+        let Json(settings) = update_room(
+            Path(room_id.clone()),
+            State(state.clone()),
+            bearer(&admin_token),
+            AppJson(UpdateRoomRequest {
+                answer_window_in_ms: None,
+                max_players: None,
+                password: Some("s3cret".to_string()),
+            }),
+        )
+        .await
+        .unwrap();
+        assert!(settings.requires_password);
+        let Json(info) = room_info(Path(room_id.clone()), State(state.clone()))
+            .await
+            .unwrap();
+        assert!(info.requires_password);
+
+        for password in [None, Some("wrong")] {
+            let denied = join("Carol", password, HeaderMap::new()).await;
+            assert!(matches!(denied, Err(AppError::WrongPassword)));
+        }
+        assert!(
+            join("Carol", Some("s3cret"), HeaderMap::new())
+                .await
+                .is_ok()
+        );
+        // Bob's token proves he was already in.
+        assert!(join("Bob", None, bearer(&bob_token)).await.is_ok());
+
+        let Json(settings) = update_room(
+            Path(room_id.clone()),
+            State(state.clone()),
+            bearer(&admin_token),
+            AppJson(UpdateRoomRequest {
+                answer_window_in_ms: None,
+                max_players: None,
+                password: Some(String::new()),
+            }),
+        )
+        .await
+        .unwrap();
+        assert!(!settings.requires_password);
+        assert!(join("Dave", None, HeaderMap::new()).await.is_ok());
+    });
+}
+
+#[test]
+fn failed_settings_update_leaves_the_password_alone() {
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        let admin_token = room.create_admin("Aaron").await.unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Bearer {admin_token}").parse().unwrap(),
+        );
+        room.shutdown("closed by test");
+
+        let denied = update_room(
+            Path(room_id.clone()),
+            State(state.clone()),
+            headers,
+            AppJson(UpdateRoomRequest {
+                answer_window_in_ms: Some(2000),
+                max_players: None,
+                password: Some("s3cret".to_string()),
+            }),
+        )
+        .await;
+
+        assert!(matches!(denied, Err(AppError::RoomClosed)));
+        assert!(!room.requires_password());
+    });
+}
+
+/// The DTOs are the contract with clients; pin the exact keys they serialize to.
+#[test]
+fn join_and_refresh_responses_match_their_dtos() {
+    fn keys(value: serde_json::Value) -> Vec<String> {
+        let mut keys: Vec<_> = value.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    block_on(async {
+        let (state, room_id, room) = new_room(1500);
+        room.create_admin("Aaron").await.unwrap();
+
+        let (status, Json(joined)) = join_room(
+            Path(room_id.clone()),
+            State(state.clone()),
+            HeaderMap::new(),
+            AppJson(JoinRoomRequest {
+                name: "Bob".to_string(),
+                password: None,
+                role: Some(Role::Spectator),
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let joined = serde_json::to_value(&joined).unwrap();
+        assert_eq!(
+            keys(joined.clone()),
+            [
+                "answer_window_in_ms",
+                "player_name",
+                "role",
+                "room_id",
+                "token"
+            ]
+        );
+        assert_eq!(joined["room_id"], room_id);
+        assert_eq!(joined["answer_window_in_ms"], 1500);
+        assert_eq!(joined["player_name"], "Bob");
+        assert_eq!(joined["role"], "spectator");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Bearer {}", joined["token"].as_str().unwrap())
+                .parse()
+                .unwrap(),
+        );
+        let (status, Json(refreshed)) =
+            token_refresh(Path(room_id.clone()), State(state.clone()), headers)
+                .await
+                .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let refreshed = serde_json::to_value(&refreshed).unwrap();
+        assert_eq!(
+            keys(refreshed.clone()),
+            [
+                "answer_window_in_ms",
+                "new_token",
+                "player_name",
+                "role",
+                "room_id"
+            ]
+        );
+        assert_eq!(refreshed["room_id"], room_id);
+        assert_eq!(refreshed["answer_window_in_ms"], 1500);
+        assert_eq!(refreshed["player_name"], "Bob");
+        assert_eq!(refreshed["role"], "spectator");
+    });
+}
+
+#[test]
+fn health_endpoints_report_rooms_connections_and_readiness() {
+    block_on(async {
+        let state = AppState::new(&ServerConfig::default());
+        assert_eq!(
+            readyz(State(state.clone())).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        state.mark_ready();
+        assert_eq!(readyz(State(state.clone())).await, StatusCode::OK);
+
+        let Json(health) = healthz(State(state.clone())).await;
+        assert_eq!(health.status, "ok");
+        assert_eq!((health.rooms, health.connections), (0, 0));
+        assert_eq!(health.max_rooms, 1000);
+
+        let mut receivers = Vec::new();
+        for _ in 0..3 {
+            let (_, room) = state.create_room(room_config(1000), None).unwrap();
+            room.create_admin("Aaron").await.unwrap();
+            let (tx, rx) = outbound_channel();
+            room.attach_connection(0, "Aaron", tx, None, true)
+                .await
+                .unwrap();
+            forward_broadcasts(&room, 0);
+            receivers.push(rx);
+        }
+        let Json(health) = healthz(State(state.clone())).await;
+        assert_eq!((health.rooms, health.connections), (3, 3));
+
+        // A closed socket stops counting even while its route waits for a resume.
+        receivers.pop();
+        let Json(health) = healthz(State(state.clone())).await;
+        assert_eq!((health.rooms, health.connections), (3, 2));
+    });
+}
+
+#[test]
+fn room_inbound_rate_limits_each_socket() {
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        let state = AppState::new(&ServerConfig::default());
+        let (room_id, room) = state
+            .create_room(
+                RoomConfig {
+                    inbound_rate_per_sec: 0,
+                    ..room_config(1000)
+                },
+                None,
+            )
+            .unwrap();
+        // Clamped up to the minimum of one message a second.
+        assert_eq!(room.inbound_rate_per_sec().get(), 1);
+        room.create_admin("Aaron").await.unwrap();
+
+        let addr = serve(router(state.clone())).await;
+
+        let (token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let url = format!("ws://{addr}/ws/{room_id}?token={token}");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let Some(Ok(Message::Text(_))) = ws.next().await else {
+            panic!("Bob got no welcome");
+        };
+
+        for _ in 0..2 {
+            let ready = r#"{"type":"set_ready","ready":true}"#;
+            ws.send(Message::Text(ready.into())).await.unwrap();
+        }
+        let denied = async {
+            while let Some(Ok(msg)) = ws.next().await {
+                if let Message::Text(text) = msg {
+                    let msg: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    if msg["type"] == "action_denied" {
+                        return msg;
+                    }
+                }
+            }
+            panic!("socket ended without a denial");
+        };
+        let denied = tokio::time::timeout(std::time::Duration::from_secs(2), denied)
+            .await
+            .expect("second message was not rate limited");
+        assert_eq!(denied["reason"], "rate_limited");
+        assert_eq!(denied["category"], "general");
+    });
+}
+
+#[test]
+fn msgpack_and_json_sockets_share_a_room() {
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        room.create_admin("Aaron").await.unwrap();
+
+        let addr = serve(router(state.clone())).await;
+
+        // Bob asks for MessagePack with a subprotocol rather than `?format=`.
+        let (bob, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let mut request = format!("ws://{addr}/ws/{room_id}?token={bob}")
+            .into_client_request()
+            .unwrap();
+        request.headers_mut().insert(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            "buzzer.json, buzzer.msgpack".parse().unwrap(),
+        );
+        let (mut bob_ws, response) = tokio_tungstenite::connect_async(request).await.unwrap();
+        assert_eq!(
+            response.headers()[header::SEC_WEBSOCKET_PROTOCOL],
+            "buzzer.msgpack"
+        );
+        let mut next_msgpack = async |kind: &str| loop {
+            let frame = tokio::time::timeout(std::time::Duration::from_secs(2), bob_ws.next())
+                .await
+                .expect("no message in time");
+            let Some(Ok(Message::Binary(bytes))) = frame else {
+                panic!("expected a binary frame, got {frame:?}");
+            };
+            let msg: serde_json::Value = rmp_serde::from_slice(&bytes).unwrap();
+            if msg["type"] == kind {
+                break msg;
+            }
+        };
+        next_msgpack("participants").await;
+
+        let (carol, _) = room.join("Carol", None, Role::Player).await.unwrap();
+        let url = format!("ws://{addr}/ws/{room_id}?token={carol}");
+        let (mut carol_ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        next_ws_message(&mut carol_ws, "participants").await;
+
+        let chat = serde_json::json!({ "type": "chat", "text": "hola" });
+        carol_ws
+            .send(Message::Text(chat.to_string().into()))
+            .await
+            .unwrap();
+        assert_eq!(next_msgpack("chat").await["from"], "Carol");
+
+        let chat = serde_json::json!({ "type": "chat", "text": "hi" });
+        bob_ws
+            .send(Message::Binary(
+                rmp_serde::to_vec_named(&chat).unwrap().into(),
+            ))
+            .await
+            .unwrap();
+        let heard = loop {
+            let msg = next_ws_message(&mut carol_ws, "chat").await;
+            if msg["from"] == "Bob" {
+                break msg;
+            }
+        };
+        assert_eq!(heard["text"], "hi");
+    });
+}
+
+#[test]
+fn unreadable_frames_get_a_protocol_error() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        room.create_admin("Aaron").await.unwrap();
+
+        let addr = serve(router(state.clone())).await;
+
+        let (token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let url = format!("ws://{addr}/ws/{room_id}?token={token}");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let participants = next_ws_message(&mut ws, "participants").await;
+        assert_eq!(participants["v"], 1);
+
+        ws.send(Message::Text(r#"{"v":7,"type":"buzz"}"#.into()))
+            .await
+            .unwrap();
+        let error = next_ws_message(&mut ws, "protocol_error").await;
+        assert_eq!(error["detail"], "unsupported protocol version 7");
+        assert_eq!(error["supported_versions"], serde_json::json!([1]));
+    });
+}
+
+#[test]
+fn time_sync_is_answered_straight_from_the_socket() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        room.create_admin("Aaron").await.unwrap();
+
+        let addr = serve(router(state.clone())).await;
+
+        let (token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let url = format!("ws://{addr}/ws/{room_id}?token={token}");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        next_ws_message(&mut ws, "participants").await;
+
+        let before = now_millis();
+        let sync = r#"{"type":"time_sync","client_ts_ms":1234}"#;
+        ws.send(Message::Text(sync.into())).await.unwrap();
+        let reply = next_ws_message(&mut ws, "time_sync").await;
+        assert_eq!(
+            (&reply["v"], &reply["client_ts_ms"]),
+            (&1.into(), &1234.into())
+        );
+        let server_ts = reply["server_ts_ms"].as_u64().unwrap();
+        assert!((before..=now_millis()).contains(&server_ts));
+        // Never replayed on a resume, so it takes no place in the sequence.
+        assert!(reply.get("seq").is_none());
+    });
+}
+
+#[test]
+fn reconnecting_mid_answer_is_welcomed_with_the_answer_in_flight() {
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        let (state, room_id, room) = new_room(30_000);
+        room.create_admin("Aaron").await.unwrap();
+
+        let addr = serve(router(state.clone())).await;
+
+        let (token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let url = format!("ws://{addr}/ws/{room_id}?token={token}");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str())
+            .await
+            .unwrap();
+        next_ws_message(&mut ws, "participants").await;
+        room.start_round(0, None, None, None).await.unwrap();
+        next_ws_message(&mut ws, "round_started").await;
+        let buzz = r#"{"type":"buzz"}"#;
+        ws.send(Message::Text(buzz.into())).await.unwrap();
+        next_ws_message(&mut ws, "accepted").await;
+        ws.close(None).await.unwrap();
+        drop(ws);
+
+        // A cold reload: no `since_seq`, so nothing is replayed.
+        let before = now_millis();
+        let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str())
+            .await
+            .unwrap();
+        let welcome = loop {
+            if let Some(Ok(Message::Text(text))) = ws.next().await {
+                break serde_json::from_str::<serde_json::Value>(&text).unwrap();
+            }
+        };
+        assert_eq!(
+            (&welcome["type"], &welcome["seq"]),
+            (&"welcome".into(), &0.into())
+        );
+        assert_eq!(welcome["you"]["name"], "Bob");
+        assert_eq!(welcome["participants"].as_array().unwrap().len(), 2);
+        assert_eq!(welcome["settings"]["answer_window_in_ms"], 30_000);
+
+        let game = &welcome["game"];
+        assert_eq!(
+            (&game["phase"], &game["round"]),
+            (&"answering".into(), &1.into())
+        );
+        assert_eq!(game["answering"], "Bob");
+        assert_eq!(game["paused"], false);
+        let remaining = game["remaining_ms"].as_u64().unwrap();
+        assert!(remaining > 25_000 && remaining <= 30_000, "{remaining}");
+        let deadline = game["deadline_ms"].as_u64().unwrap();
+        assert!((before + remaining..=now_millis() + remaining).contains(&deadline));
+        assert_eq!(game["locked_out"], serde_json::json!([]));
+        let scores = game["scores"].as_array().unwrap();
+        assert!(scores.iter().any(|entry| entry["name"] == "Bob"));
+    });
+}
+
+#[test]
+fn a_full_round_runs_through_the_client_library() {
+    use dtos::{ClientMessage, ServerMessage};
+    use futures::StreamExt;
+    use server::client::{Client, ClientError, ServerMessages};
+
+    async fn next_matching<T>(
+        messages: &mut ServerMessages,
+        mut pick: impl FnMut(ServerMessage) -> Option<T>,
+    ) -> T {
+        let wait = async {
+            while let Some(message) = messages.next().await {
+                if let Some(found) = pick(message.unwrap()) {
+                    return found;
+                }
+            }
+            panic!("socket closed");
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(5), wait)
+            .await
+            .expect("message in time")
+    }
+
+    block_on(async {
+        let state = AppState::new(&ServerConfig::default());
+        let addr = serve(router(state.clone())).await;
+        let client = Client::new(format!("http://{addr}"));
+
+        let room = client
+            .create_room(&CreateRoomRequest {
+                name: "Aaron".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let bob = client
+            .join(
+                &room.room_id,
+                &JoinRoomRequest {
+                    name: "Bob".to_string(),
+                    ..Default::default()
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        let taken = client
+            .join(
+                &room.room_id,
+                &JoinRoomRequest {
+                    name: "Bob".to_string(),
+                    ..Default::default()
+                },
+                None,
+            )
+            .await;
+        assert!(
+            matches!(taken, Err(ClientError::Api { status: 409, ref code, .. }) if code == "name_taken"),
+            "{taken:?}"
+        );
+
+        let (mut admin, mut admin_messages) =
+            client.connect(&room.room_id, &room.token).await.unwrap();
+        let (mut player, mut messages) = client.connect(&room.room_id, &bob.token).await.unwrap();
+        let you = next_matching(&mut messages, |message| match message {
+            ServerMessage::Welcome { you, .. } => Some(you.name),
+            _ => None,
+        })
+        .await;
+        assert_eq!(you, "Bob");
+
+        admin
+            .send(&ClientMessage::StartRound {
+                countdown_ms: None,
+                question: Some("Capital of France?".to_string()),
+            })
+            .await
+            .unwrap();
+        next_matching(&mut messages, |message| {
+            matches!(message, ServerMessage::RoundStarted { round: 1 }).then_some(())
+        })
+        .await;
+        player.send(&ClientMessage::Buzz).await.unwrap();
+        let answering = next_matching(&mut admin_messages, |message| match message {
+            ServerMessage::Accepted { name, .. } => Some(name),
+            _ => None,
+        })
+        .await;
+        assert_eq!(answering, "Bob");
+
+        admin.send(&ClientMessage::MarkCorrect).await.unwrap();
+        let entries = next_matching(&mut messages, |message| match message {
+            ServerMessage::Scoreboard { entries, .. } => Some(entries),
+            _ => None,
+        })
+        .await;
+        assert!(entries.contains(&ScoreEntry {
+            name: "Bob".to_string(),
+            score: 1,
+        }));
+        admin.close().await.unwrap();
+        player.close().await.unwrap();
+    });
+}
+
+#[test]
+fn kicked_socket_closes_within_a_second() {
+    use futures::{SinkExt, StreamExt};
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        let admin = room.create_admin("Aaron").await.unwrap();
+
+        let addr = serve(router(state.clone())).await;
+
+        let (bob, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let bob_url = format!("ws://{addr}/ws/{room_id}?token={bob}");
+        let (mut bob_ws, _) = tokio_tungstenite::connect_async(bob_url).await.unwrap();
+        next_ws_message(&mut bob_ws, "participants").await;
+        let admin_url = format!("ws://{addr}/ws/{room_id}?token={admin}");
+        let (mut admin_ws, _) = tokio_tungstenite::connect_async(admin_url).await.unwrap();
+        next_ws_message(&mut admin_ws, "participants").await;
+
+        let kick = r#"{"type":"kick","name":"Bob"}"#;
+        admin_ws.send(Message::Text(kick.into())).await.unwrap();
+        // The server ends the connection itself: a close frame, then the
+        // stream runs dry without the client having closed anything.
+        let closed = async {
+            let mut close = None;
+            while let Some(Ok(msg)) = bob_ws.next().await {
+                if let Message::Close(frame) = msg {
+                    close = frame.map(|frame| frame.code);
+                }
+            }
+            close
+        };
+        let close = tokio::time::timeout(Duration::from_secs(1), closed)
+            .await
+            .expect("kicked socket still open after a second");
+        assert_eq!(close, Some(CloseCode::from(4403)));
+
+        // Detaching the closed socket leaves no trace of Bob behind.
+        assert!(room.participants().iter().all(|p| p.name != "Bob"));
+        assert_eq!(state.socket_closes().get("banned"), Some(&1));
+    });
+}
+
+/// A served room with an admin, whose tokens last `token_ttl_secs`.
+async fn room_with_token_ttl(
+    token_ttl_secs: u64,
+) -> (AppState, String, Arc<RoomState>, SocketAddr) {
+    let state = AppState::new(&ServerConfig {
+        token_ttl_secs,
+        ..ServerConfig::default()
+    });
+    let (room_id, room) = state.create_room(room_config(1000), None).unwrap();
+    room.create_admin("Aaron").await.unwrap();
+
+    let addr = serve(router(state.clone())).await;
+    (state, room_id, room, addr)
+}
+
+#[test]
+fn socket_closes_when_its_token_expires() {
+    use futures::StreamExt;
+
+    block_on(async {
+        let (state, room_id, room, addr) = room_with_token_ttl(1).await;
+        let (token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let url = format!("ws://{addr}/ws/{room_id}?token={token}");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        next_ws_message(&mut ws, "participants").await;
+
+        let expired = next_ws_message(&mut ws, "session_expired").await;
+        assert!(expired.get("seq").is_none());
+        assert_eq!(
+            next_close_frame(&mut ws).await,
+            Some((CloseCode::from(4401), "session_expired".to_string()))
+        );
+        // The close is not the end of the session: Bob keeps his seat and
+        // may reconnect with a refreshed token.
+        assert!(room.participants().iter().any(|p| p.name == "Bob"));
+        while ws.next().await.is_some() {}
+        assert_eq!(state.socket_closes().get("session_expired"), Some(&1));
+    });
+}
+
+#[test]
+fn update_token_keeps_the_socket_open_past_expiry() {
+    use futures::SinkExt;
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        let (state, room_id, room, addr) = room_with_token_ttl(2).await;
+        let (token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let claims = state.auth().verify(&token, &room_id).unwrap();
+        let url = format!("ws://{addr}/ws/{room_id}?token={token}");
+        let connected = tokio::time::Instant::now();
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        next_ws_message(&mut ws, "participants").await;
+
+        // A second later, so the new token outlives the first. Issued
+        // straight from the signer, so only the hand-over tells the room.
+        tokio::time::sleep_until(connected + Duration::from_secs(1)).await;
+        let (fresh, fresh_exp) = state
+            .auth()
+            .issue(&room_id, claims.player_id, "Bob", Role::Player)
+            .unwrap();
+        assert!(fresh_exp > claims.exp);
+        let update = serde_json::json!({ "type": "update_token", "token": fresh, "id": "t1" });
+        ws.send(Message::Text(update.to_string().into()))
+            .await
+            .unwrap();
+        assert_eq!(next_ws_message(&mut ws, "action_ok").await["id"], "t1");
+        assert_eq!(room.token_expiry(claims.player_id), Some(fresh_exp));
+
+        // Past the first token's expiry.
+        tokio::time::sleep_until(connected + Duration::from_millis(2300)).await;
+        room.start_round(0, None, None, None).await.unwrap();
+        next_ws_message(&mut ws, "round_started").await;
+        assert!(room.is_connected(claims.player_id));
+    });
+}
+
+#[test]
+fn update_token_refuses_another_players_token() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        let (_state, room_id, room, addr) = room_with_token_ttl(60).await;
+        let (bob, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let (carol, _) = room.join("Carol", None, Role::Player).await.unwrap();
+        let url = format!("ws://{addr}/ws/{room_id}?token={bob}");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        next_ws_message(&mut ws, "participants").await;
+
+        let update = serde_json::json!({ "type": "update_token", "token": carol, "id": "t1" });
+        ws.send(Message::Text(update.to_string().into()))
+            .await
+            .unwrap();
+        let denied = next_ws_message(&mut ws, "action_denied").await;
+        assert_eq!(
+            (&denied["reason"], &denied["id"]),
+            (&"invalid_token".into(), &"t1".into())
+        );
+    });
+}
+
+#[test]
+fn malformed_messages_are_denied_until_the_socket_is_closed() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        room.create_admin("Aaron").await.unwrap();
+
+        let addr = serve(router(state.clone())).await;
+
+        let (token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let url = format!("ws://{addr}/ws/{room_id}?token={token}");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        next_ws_message(&mut ws, "participants").await;
+
+        ws.send(Message::Text("{not json".into())).await.unwrap();
+        let denied = next_ws_message(&mut ws, "action_denied").await;
+        assert_eq!(denied["reason"], "bad_message");
+        let error = next_ws_message(&mut ws, "protocol_error").await;
+        assert!(
+            error["detail"]
+                .as_str()
+                .unwrap()
+                .starts_with("malformed JSON")
+        );
+
+        for _ in 1..socket::MAX_MALFORMED_MESSAGES {
+            let garbage = r#"{"type":"teleport"}"#;
+            if ws.send(Message::Text(garbage.into())).await.is_err() {
+                break;
+            }
+        }
+        assert_eq!(
+            next_close_frame(&mut ws).await,
+            Some((CloseCode::from(4400), "invalid_request".to_string()))
+        );
+    });
+}
+
+#[test]
+fn oversized_frames_are_denied_unread() {
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        room.create_admin("Aaron").await.unwrap();
+
+        let addr = serve(router(state.clone())).await;
+
+        let (token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let url = format!("ws://{addr}/ws/{room_id}?token={token}");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        next_ws_message(&mut ws, "participants").await;
+        let limit = ServerConfig::default().ws_max_message_bytes;
+
+        // Not even JSON: parsed, it would draw a protocol_error instead.
+        let garbage = "x".repeat(limit + 1);
+        ws.send(Message::Text(garbage.into())).await.unwrap();
+        let denied = next_ws_message(&mut ws, "action_denied").await;
+        assert_eq!(denied["reason"], "message_too_large");
+
+        // The socket stays usable.
+        let chat = serde_json::json!({ "type": "chat", "text": "sorry" });
+        ws.send(Message::Text(chat.to_string().into()))
+            .await
+            .unwrap();
+        assert_eq!(next_ws_message(&mut ws, "chat").await["text"], "sorry");
+
+        // Far past the limit, the websocket layer gives up on the connection.
+        let huge = "x".repeat(limit * HARD_MESSAGE_LIMIT_FACTOR + 1);
+        let _ = ws.send(Message::Text(huge.into())).await;
+        let ended = async {
+            while let Some(Ok(msg)) = ws.next().await {
+                if let Message::Close(_) = msg {
+                    break;
+                }
+            }
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(2), ended)
+            .await
+            .expect("socket stayed open");
+    });
+}
+
+#[test]
+fn flooding_socket_is_closed_as_rate_limited() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        let (state, room_id, room) = new_room(1000);
+        room.create_admin("Aaron").await.unwrap();
+
+        let addr = serve(router(state.clone())).await;
+
+        let (token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let url = format!("ws://{addr}/ws/{room_id}?token={token}");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        next_ws_message(&mut ws, "participants").await;
+
+        for _ in 0..100 {
+            let buzz = r#"{"type":"buzz"}"#;
+            if ws.send(Message::Text(buzz.into())).await.is_err() {
+                break;
+            }
+        }
+        assert_eq!(
+            next_close_frame(&mut ws).await,
+            Some((CloseCode::from(4429), "rate_limited".to_string()))
+        );
+    });
+}
+
+fn cors_state() -> AppState {
+    AppState::new(&ServerConfig {
+        allowed_origins: vec!["https://quiz.example.com".to_string()],
+        ..ServerConfig::default()
+    })
+}
+
+#[test]
+fn preflight_allows_only_configured_origins() {
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    block_on(async {
+        let app = router(cors_state());
+        let preflight = |origin: &str| {
+            Request::builder()
+                .method(Method::OPTIONS)
+                .uri("/api/rooms")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                .header(
+                    header::ACCESS_CONTROL_REQUEST_HEADERS,
+                    "authorization,content-type",
+                )
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let res = app
+            .clone()
+            .oneshot(preflight("https://quiz.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let headers = res.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://quiz.example.com"
+        );
+        let methods = headers[header::ACCESS_CONTROL_ALLOW_METHODS]
+            .to_str()
+            .unwrap();
+        assert!(methods.contains("POST"), "{methods}");
+        let allowed = headers[header::ACCESS_CONTROL_ALLOW_HEADERS]
+            .to_str()
+            .unwrap();
+        assert!(allowed.contains("authorization"), "{allowed}");
+
+        let res = app
+            .oneshot(preflight("https://evil.example.com"))
+            .await
+            .unwrap();
+        assert!(
+            !res.headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        );
+    });
+}
+
+#[test]
+fn websocket_upgrade_checks_origin() {
+    use tokio_tungstenite::tungstenite::{Error, client::IntoClientRequest};
+
+    block_on(async {
+        let state = cors_state();
+        let (room_id, room) = state.create_room(room_config(1000), None).unwrap();
+        room.create_admin("Aaron").await.unwrap();
+
+        let addr = serve(router(state.clone())).await;
+
+        let (token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let connect = |origin: String| {
+            let mut req = format!("ws://{addr}/ws/{room_id}?token={token}")
+                .into_client_request()
+                .unwrap();
+            req.headers_mut()
+                .insert(header::ORIGIN, origin.parse().unwrap());
+            tokio_tungstenite::connect_async(req)
+        };
+
+        match connect("https://evil.example.com".to_string()).await {
+            Err(Error::Http(res)) => assert_eq!(res.status(), StatusCode::FORBIDDEN),
+            other => panic!("disallowed origin upgraded: {:?}", other.map(|_| ())),
+        }
+        assert!(
+            connect("https://quiz.example.com".to_string())
+                .await
+                .is_ok()
+        );
+        // Our own host never needs an allow-list entry.
+        assert!(connect(format!("http://{addr}")).await.is_ok());
+    });
+}
+
+#[test]
+fn server_time_is_monotonic_and_echoes_client_time() {
+    block_on(async {
+        let Json(first) = server_time(AppQuery(TimeQuery {
+            client_ms: Some(1234),
+        }))
+        .await;
+        let Json(second) = server_time(AppQuery(TimeQuery { client_ms: None })).await;
+        assert_eq!(first.client_ms, Some(1234));
+        assert!(first.received_ms <= first.server_ms);
+        assert!(first.server_ms <= second.received_ms);
+        assert!(second.received_ms <= second.server_ms);
+    });
+}
+
+fn limited_state(rate_limits: ratelimit::RateLimitSettings) -> AppState {
+    AppState::new(&ServerConfig {
+        rate_limits,
+        ..ServerConfig::default()
+    })
+}
+
+/// POST a JSON body as `client` (via `X-Forwarded-For`); returns the status
+/// and the parsed body, if any.
+async fn post_as(
+    app: &Router,
+    client: &str,
+    uri: &str,
+    body: serde_json::Value,
+) -> (StatusCode, Option<serde_json::Value>) {
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("x-forwarded-for", client)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&bytes).ok())
+}
+
+#[test]
+fn room_creation_is_limited_per_client_ip_and_recovers() {
+    block_on(async {
+        let app = router(limited_state(ratelimit::RateLimitSettings {
+            create_burst: 2,
+            create_period_ms: 1000,
+            ..Default::default()
+        }));
+        let create = serde_json::json!({ "name": "Host" });
+
+        for i in 0..2 {
+            let (status, _) = post_as(&app, "203.0.113.30", "/api/rooms", create.clone()).await;
+            assert_eq!(status, StatusCode::CREATED, "create {i}");
+        }
+        for _ in 0..5 {
+            let (status, body) = post_as(&app, "203.0.113.30", "/api/rooms", create.clone()).await;
+            assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+            let body = body.expect("429 carries a JSON body");
+            assert_eq!(body["error"], "rate_limited");
+            assert_eq!(body["retry_after_secs"], 1);
+        }
+
+        // Another client is unaffected.
+        let (status, _) = post_as(&app, "203.0.113.31", "/api/rooms", create.clone()).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let (status, _) = post_as(&app, "203.0.113.30", "/api/rooms", create).await;
+        assert_eq!(status, StatusCode::CREATED, "a token was replenished");
+    });
+}
+
+#[test]
+fn joins_have_their_own_budget() {
+    block_on(async {
+        let app = router(limited_state(ratelimit::RateLimitSettings {
+            join_burst: 3,
+            join_period_ms: 1000,
+            ..Default::default()
+        }));
+        let (_, created) = post_as(
+            &app,
+            "203.0.113.40",
+            "/api/rooms",
+            serde_json::json!({ "name": "Host" }),
+        )
+        .await;
+        let room_id = created.unwrap()["room_id"].as_str().unwrap().to_string();
+        let join_uri = format!("/api/rooms/{room_id}/join");
+        let join = |n: usize| serde_json::json!({ "name": format!("Player{n}") });
+
+        for n in 0..3 {
+            let (status, _) = post_as(&app, "203.0.113.41", &join_uri, join(n)).await;
+            assert_eq!(status, StatusCode::OK, "join {n}");
+        }
+        let (status, body) = post_as(&app, "203.0.113.41", &join_uri, join(3)).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body.unwrap()["retry_after_secs"], 1);
+
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let (status, _) = post_as(&app, "203.0.113.41", &join_uri, join(4)).await;
+        assert_eq!(status, StatusCode::OK);
+    });
+}
+
+#[test]
+fn malformed_requests_get_the_json_error_envelope() {
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    block_on(async {
+        let app = router(AppState::new(&ServerConfig::default()));
+        let send = |req: Request<Body>| async {
+            let res = app.clone().oneshot(req).await.unwrap();
+            let status = res.status();
+            let request_id = res.headers()[request_id::HEADER]
+                .to_str()
+                .unwrap()
+                .to_string();
+            let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            (status, request_id, body)
+        };
+
+        let (status, request_id, body) = send(
+            Request::post("/api/rooms")
+                .header("x-forwarded-for", "203.0.113.50")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from("{\"nam"))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid_request");
+        assert_eq!(body["retryable"], false);
+        assert_eq!(body["request_id"], request_id.as_str());
+        assert_eq!(request_id.len(), 36);
+
+        let (status, other_id, body) =
+            send(Request::get("/api/nope").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "not_found");
+        assert_ne!(other_id, request_id);
+
+        let (status, _, body) = send(
+            Request::get("/api/rooms/NOPE")
+                .header("x-forwarded-for", "203.0.113.50")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "room_not_found");
+        assert_eq!(body["message"], "No room with that code exists");
+
+        let (status, _, body) = send(
+            Request::post("/api/rooms")
+                .header("x-forwarded-for", "203.0.113.50")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"name":"Aaron"}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let room_id = body["room_id"].as_str().unwrap();
+        let (status, request_id, body) = send(
+            Request::post(format!("/api/rooms/{room_id}/join"))
+                .header("x-forwarded-for", "203.0.113.50")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"name":"aaron"}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "error": "name_taken",
+                "message": "That name is already in use",
+                "retryable": false,
+                "request_id": request_id,
+            })
+        );
+    });
+}
+
+/// POST a JSON body with `token` as bearer; returns the status and body.
+async fn post_with_token(
+    app: &Router,
+    token: &str,
+    uri: &str,
+    body: serde_json::Value,
+) -> (StatusCode, serde_json::Value) {
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    let req = Request::post(uri)
+        .header("x-forwarded-for", "203.0.113.60")
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+/// Skips websocket messages until one with the given `type` arrives.
+async fn next_ws_message<S>(ws: &mut S, kind: &str) -> serde_json::Value
+where
+    S: futures::Stream<
+            Item = Result<
+                tokio_tungstenite::tungstenite::Message,
+                tokio_tungstenite::tungstenite::Error,
+            >,
+        > + Unpin,
+{
+    use futures::StreamExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let wait = async {
+        while let Some(Ok(msg)) = ws.next().await {
+            if let Message::Text(text) = msg {
+                let msg: serde_json::Value = serde_json::from_str(&text).unwrap();
+                if msg["type"] == kind {
+                    return msg;
+                }
+            }
+        }
+        panic!("socket ended before {kind}");
+    };
+    tokio::time::timeout(std::time::Duration::from_secs(2), wait)
+        .await
+        .unwrap_or_else(|_| panic!("timed out waiting for {kind}"))
+}
+
+/// Skips websocket messages until the server closes; returns its close frame.
+async fn next_close_frame<S>(ws: &mut S) -> Option<(CloseCode, String)>
+where
+    S: futures::Stream<
+            Item = Result<
+                tokio_tungstenite::tungstenite::Message,
+                tokio_tungstenite::tungstenite::Error,
+            >,
+        > + Unpin,
+{
+    use futures::StreamExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let wait = async {
+        while let Some(Ok(msg)) = ws.next().await {
+            if let Message::Close(frame) = msg {
+                return frame.map(|frame| (frame.code, frame.reason.to_string()));
+            }
+        }
+        panic!("socket ended without a close frame");
+    };
+    tokio::time::timeout(std::time::Duration::from_secs(2), wait)
+        .await
+        .expect("socket was not closed")
+}
+
+#[test]
+fn message_ids_come_back_on_the_answer_only() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        // Every buzz reaches the game, however quickly Bob repeats it.
+        let state = AppState::new(&ServerConfig {
+            buzz_debounce_ms: 0,
+            ..ServerConfig::default()
+        });
+        let app = router(state.clone());
+        let addr = serve(app.clone()).await;
+
+        let (_, created) = post_as(
+            &app,
+            "203.0.113.62",
+            "/api/rooms",
+            serde_json::json!({ "name": "Aaron" }),
+        )
+        .await;
+        let created = created.unwrap();
+        let room_id = created["room_id"].as_str().unwrap().to_string();
+        let admin = created["token"].as_str().unwrap().to_string();
+        let (_, joined) = post_as(
+            &app,
+            "203.0.113.62",
+            &format!("/api/rooms/{room_id}/join"),
+            serde_json::json!({ "name": "Bob" }),
+        )
+        .await;
+        let bob = joined.unwrap()["token"].as_str().unwrap().to_string();
+
+        let connect = |token: String| {
+            let url = format!("ws://{addr}/ws/{room_id}?token={token}");
+            async move {
+                let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+                next_ws_message(&mut ws, "participants").await;
+                ws
+            }
+        };
+        let mut admin_ws = connect(admin).await;
+        let mut bob_ws = connect(bob).await;
+        let send = async |ws: &mut _, text: &str| {
+            SinkExt::send(ws, Message::Text(text.into())).await.unwrap();
+        };
+
+        send(
+            &mut admin_ws,
+            r#"{"type":"kick","name":"Nobody","id":"k1"}"#,
+        )
+        .await;
+        let denied = next_ws_message(&mut admin_ws, "action_denied").await;
+        assert_eq!(
+            (&denied["reason"], &denied["id"]),
+            (&"user_not_found".into(), &"k1".into())
+        );
+
+        send(&mut admin_ws, r#"{"type":"start_round","id":"s1"}"#).await;
+        assert_eq!(
+            next_ws_message(&mut admin_ws, "action_ok").await["id"],
+            "s1"
+        );
+        next_ws_message(&mut bob_ws, "round_started").await;
+
+        send(&mut bob_ws, r#"{"type":"buzz","id":"b1"}"#).await;
+        assert_eq!(next_ws_message(&mut bob_ws, "action_ok").await["id"], "b1");
+        let accepted = next_ws_message(&mut admin_ws, "accepted").await;
+        assert_eq!(accepted.get("id"), None);
+
+        send(&mut bob_ws, r#"{"type":"buzz","id":"b2"}"#).await;
+        assert_eq!(next_ws_message(&mut bob_ws, "rejected").await["id"], "b2");
+
+        // Untagged messages are answered as before: denials only.
+        send(&mut bob_ws, r#"{"type":"buzz"}"#).await;
+        assert_eq!(
+            next_ws_message(&mut bob_ws, "rejected").await.get("id"),
+            None
+        );
+        send(&mut bob_ws, r#"{"type":"pause"}"#).await;
+        let denied = next_ws_message(&mut bob_ws, "action_denied").await;
+        assert_eq!(
+            (&denied["reason"], denied.get("id")),
+            (&"forbidden".into(), None)
+        );
+
+        send(&mut bob_ws, r#"{"type":"buzz","id":7}"#).await;
+        let error = next_ws_message(&mut bob_ws, "protocol_error").await;
+        assert!(
+            error["detail"].as_str().unwrap().contains("`id`"),
+            "{error}"
+        );
+    });
+}
+
+#[test]
+fn admin_sets_the_answer_window_for_the_next_round_over_the_socket() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        let state = AppState::new(&ServerConfig::default());
+        let app = router(state.clone());
+        let addr = serve(app.clone()).await;
+
+        let (_, created) = post_as(
+            &app,
+            "203.0.113.61",
+            "/api/rooms",
+            serde_json::json!({ "name": "Aaron", "answer_window_in_ms": MAX_ANSWER_WINDOW_IN_MS }),
+        )
+        .await;
+        let created = created.unwrap();
+        let room_id = created["room_id"].as_str().unwrap().to_string();
+        let admin = created["token"].as_str().unwrap().to_string();
+        let (_, joined) = post_as(
+            &app,
+            "203.0.113.61",
+            &format!("/api/rooms/{room_id}/join"),
+            serde_json::json!({ "name": "Bob" }),
+        )
+        .await;
+        let bob = joined.unwrap()["token"].as_str().unwrap().to_string();
+
+        let connect = |token: String| {
+            let url = format!("ws://{addr}/ws/{room_id}?token={token}");
+            async move {
+                let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+                next_ws_message(&mut ws, "participants").await;
+                ws
+            }
+        };
+        let mut admin_ws = connect(admin).await;
+        let mut bob_ws = connect(bob).await;
+        let set_window = r#"{"type":"set_answer_window","answer_window_in_ms":1}"#;
+
+        bob_ws.send(Message::Text(set_window.into())).await.unwrap();
+        let denied = next_ws_message(&mut bob_ws, "action_denied").await;
+        assert_eq!(denied["reason"], "forbidden");
+
+        // Clamped up to the minimum, then in force from the next round on.
+        admin_ws
+            .send(Message::Text(set_window.into()))
+            .await
+            .unwrap();
+        let changed = next_ws_message(&mut bob_ws, "settings_changed").await;
+        assert_eq!(changed["answer_window_in_ms"], MIN_ANSWER_WINDOW_IN_MS);
+
+        admin_ws
+            .send(Message::Text(r#"{"type":"start_round"}"#.into()))
+            .await
+            .unwrap();
+        next_ws_message(&mut bob_ws, "round_started").await;
+        bob_ws
+            .send(Message::Text(r#"{"type":"buzz"}"#.into()))
+            .await
+            .unwrap();
+        assert_eq!(
+            next_ws_message(&mut bob_ws, "accepted").await["name"],
+            "Bob"
+        );
+        // Well within the wait for a message, unlike the window the room began with.
+        assert_eq!(
+            next_ws_message(&mut bob_ws, "timed_out").await["name"],
+            "Bob"
+        );
+    });
+}
+
+#[test]
+fn admin_can_run_a_round_over_http_while_players_watch() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        let state = AppState::new(&ServerConfig::default());
+        let app = router(state.clone());
+        let addr = serve(app.clone()).await;
+
+        let (_, created) = post_as(
+            &app,
+            "203.0.113.60",
+            "/api/rooms",
+            serde_json::json!({ "name": "Aaron", "answer_window_in_ms": 500 }),
+        )
+        .await;
+        let created = created.unwrap();
+        let room_id = created["room_id"].as_str().unwrap().to_string();
+        let admin = created["token"].as_str().unwrap().to_string();
+        let (_, joined) = post_as(
+            &app,
+            "203.0.113.60",
+            &format!("/api/rooms/{room_id}/join"),
+            serde_json::json!({ "name": "Bob" }),
+        )
+        .await;
+        let bob = joined.unwrap()["token"].as_str().unwrap().to_string();
+
+        let url = format!("ws://{addr}/ws/{room_id}?token={bob}");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        next_ws_message(&mut ws, "participants").await;
+
+        let uri = |action: &str| format!("/api/rooms/{room_id}/{action}");
+        let (status, body) = post_with_token(
+            &app,
+            &admin,
+            &uri("start_round"),
+            serde_json::json!({ "question": "Capital of Peru?" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["round"], 1);
+        assert_eq!(next_ws_message(&mut ws, "round_started").await["round"], 1);
+        assert_eq!(
+            next_ws_message(&mut ws, "question").await["text"],
+            "Capital of Peru?"
+        );
+
+        ws.send(Message::Text(r#"{"type":"buzz"}"#.into()))
+            .await
+            .unwrap();
+        assert_eq!(next_ws_message(&mut ws, "accepted").await["name"], "Bob");
+        assert_eq!(next_ws_message(&mut ws, "timed_out").await["name"], "Bob");
+        let (status, _) =
+            post_with_token(&app, &admin, &uri("continue"), serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+        next_ws_message(&mut ws, "round_continued").await;
+
+        // Players cannot use the admin endpoints.
+        let (status, body) =
+            post_with_token(&app, &bob, &uri("start_round"), serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"], "forbidden");
+        let (status, body) = post_with_token(
+            &app,
+            &admin,
+            &uri("kick"),
+            serde_json::json!({ "name": "Nobody" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "user_not_found");
+
+        let (status, body) = post_with_token(
+            &app,
+            &admin,
+            &uri("admin"),
+            serde_json::json!({ "name": "Bob" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let roles: Vec<_> = body["participants"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| (p["name"].as_str().unwrap(), p["role"].as_str().unwrap()))
+            .collect();
+        assert_eq!(roles, [("Aaron", "player"), ("Bob", "admin")]);
+        next_ws_message(&mut ws, "participants").await;
+
+        let (status, _) = post_with_token(
+            &app,
+            &bob,
+            &uri("kick"),
+            serde_json::json!({ "name": "Aaron" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let participants = next_ws_message(&mut ws, "participants").await;
+        assert_eq!(participants["participants"].as_array().unwrap().len(), 1);
+        let (status, body) =
+            post_with_token(&app, &admin, &uri("continue"), serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"], "user_not_in_room");
+    });
+}
+
+#[test]
+fn webhook_receives_signed_room_events_and_retries_failures() {
+    use axum::body::Bytes;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tokio::sync::mpsc;
+
+    block_on(async {
+        // The receiver fails its first request to force a retry.
+        let (seen_tx, mut seen) = mpsc::unbounded_channel::<(HeaderMap, Bytes)>();
+        let failed_once = Arc::new(AtomicBool::new(false));
+        let receiver = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: Bytes| {
+                let seen_tx = seen_tx.clone();
+                let failed_once = Arc::clone(&failed_once);
+                async move {
+                    seen_tx.send((headers, body)).unwrap();
+                    if failed_once.swap(true, Ordering::SeqCst) {
+                        StatusCode::NO_CONTENT
+                    } else {
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    }
+                }
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let hook_addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+
+        let secret = "0123456789abcdef";
+        let state = AppState::new(&ServerConfig {
+            webhook: Some(webhook::WebhookSettings {
+                url: format!("http://{hook_addr}/hook"),
+                secret: secret.to_string(),
+            }),
+            ..ServerConfig::default()
+        });
+        let mut next_delivery = async || {
+            let (headers, body) =
+                tokio::time::timeout(std::time::Duration::from_secs(5), seen.recv())
+                    .await
+                    .expect("webhook delivery")
+                    .unwrap();
+            assert_eq!(
+                headers[webhook::SIGNATURE_HEADER],
+                webhook::sign(secret.as_bytes(), &body).as_str()
+            );
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let (room_id, room) = state.create_room(room_config(1000), None).unwrap();
+        let created = next_delivery().await;
+        assert_eq!(created["event"], "room_created");
+        assert_eq!(created["room_id"], room_id.as_str());
+        let retried = next_delivery().await;
+        assert_eq!(retried, created, "a retry resends the same delivery");
+
+        room.create_admin("Aaron").await.unwrap();
+        let (token, _) = room.join("Bob", None, Role::Player).await.unwrap();
+        let bob = state.auth().verify(&token, &room_id).unwrap().player_id;
+        let (tx, mut rx) = outbound_channel();
+        room.attach_connection(0, "Aaron", tx, None, true)
+            .await
+            .unwrap();
+        forward_broadcasts(&room, 0);
+        room.start_round(0, None, None, None).await.unwrap();
+        next_of_type(&mut rx, "round_started").await;
+        room.send_buzz(bob, None);
+        next_of_type(&mut rx, "accepted").await;
+        room.mark_correct(0, None);
+
+        let resolved = next_delivery().await;
+        assert_eq!(resolved["event"], "round_resolved");
+        assert_eq!(resolved["room_id"], room_id.as_str());
+        assert_eq!(resolved["round"], 1);
+        assert_eq!(resolved["winner"], "Bob");
+        assert_eq!(resolved["score"], 1);
+        assert_ne!(resolved["id"], created["id"]);
+
+        state.close_room(&room_id, "closed_by_admin").unwrap();
+        let closed = next_delivery().await;
+        assert_eq!(closed["event"], "room_closed");
+        assert_eq!(closed["room_id"], room_id.as_str());
+        assert_eq!(closed["reason"], "closed_by_admin");
+        assert!(closed["ts_ms"].as_u64().unwrap() >= created["ts_ms"].as_u64().unwrap());
+    });
+}
+
+#[test]
+fn kicked_player_stays_out_until_unbanned() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    block_on(async {
+        let state = AppState::new(&ServerConfig::default());
+        let app = router(state.clone());
+        let addr = serve(app.clone()).await;
+
+        let (_, created) = post_as(
+            &app,
+            "203.0.113.62",
+            "/api/rooms",
+            serde_json::json!({ "name": "Aaron" }),
+        )
+        .await;
+        let created = created.unwrap();
+        let room_id = created["room_id"].as_str().unwrap().to_string();
+        let admin = created["token"].as_str().unwrap().to_string();
+        let join_uri = format!("/api/rooms/{room_id}/join");
+        let (_, joined) = post_as(
+            &app,
+            "203.0.113.62",
+            &join_uri,
+            serde_json::json!({ "name": "Bob" }),
+        )
+        .await;
+        let bob = joined.unwrap()["token"].as_str().unwrap().to_string();
+        let bob_url = format!("ws://{addr}/ws/{room_id}?token={bob}");
+        let (mut bob_ws, _) = tokio_tungstenite::connect_async(bob_url.as_str())
+            .await
+            .unwrap();
+        next_ws_message(&mut bob_ws, "participants").await;
+
+        let (status, _) = post_with_token(
+            &app,
+            &admin,
+            &format!("/api/rooms/{room_id}/kick"),
+            serde_json::json!({ "name": "Bob" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        // Told why, then closed with a code that says not to reconnect.
+        next_ws_message(&mut bob_ws, "kicked").await;
+        assert_eq!(
+            next_close_frame(&mut bob_ws).await,
+            Some((CloseCode::from(4403), "banned".to_string()))
+        );
+
+        let (status, body) =
+            post_with_token(&app, &bob, &join_uri, serde_json::json!({ "name": "Bob" })).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"], "banned");
+        let (status, body) = post_with_token(
+            &app,
+            &bob,
+            &format!("/api/rooms/{room_id}/refresh_token"),
+            serde_json::json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"], "banned");
+        let (status, body) = post_as(
+            &app,
+            "203.0.113.62",
+            &join_uri,
+            serde_json::json!({ "name": " bob " }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body.unwrap()["error"], "banned");
+
+        let url = format!("ws://{addr}/ws/{room_id}?token={admin}");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        next_ws_message(&mut ws, "participants").await;
+        for name in ["Bob", "Nobody"] {
+            let unban = serde_json::json!({ "type": "unban", "name": name });
+            ws.send(Message::Text(unban.to_string().into()))
+                .await
+                .unwrap();
+        }
+        // Commands run in order, so Bob is unbanned once "Nobody" is refused.
+        let denied = next_ws_message(&mut ws, "action_denied").await;
+        assert_eq!(denied["reason"], "user_not_found");
+
+        let (status, _) = post_as(
+            &app,
+            "203.0.113.62",
+            &join_uri,
+            serde_json::json!({ "name": "Bob" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        // The old token stays revoked; Bob is back only under his new one.
+        let (status, body) =
+            post_with_token(&app, &bob, &join_uri, serde_json::json!({ "name": "Bob" })).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"], "kicked");
+    });
+}
+
+#[test]
+fn concurrent_joins_under_one_name_admit_exactly_one() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let state = AppState::new(&ServerConfig::default());
+        let app = router(state.clone());
+        let (_, created) = post_as(
+            &app,
+            "203.0.113.80",
+            "/api/rooms",
+            serde_json::json!({ "name": "Aaron" }),
+        )
+        .await;
+        let room_id = created.unwrap()["room_id"].as_str().unwrap().to_string();
+        let join_uri = format!("/api/rooms/{room_id}/join");
+
+        let joins: Vec<_> = ["Bob", "bob", " BOB ", "Bob"]
+            .into_iter()
+            .cycle()
+            .take(16)
+            .enumerate()
+            .map(|(i, name)| {
+                let (app, join_uri) = (app.clone(), join_uri.clone());
+                tokio::spawn(async move {
+                    let client = format!("198.51.100.{i}");
+                    let join = serde_json::json!({ "name": name });
+                    post_as(&app, &client, &join_uri, join).await
+                })
+            })
+            .collect();
+        let mut admitted = 0;
+        for join in joins {
+            let (status, body) = join.await.unwrap();
+            if status == StatusCode::OK {
+                admitted += 1;
+            } else {
+                assert_eq!(status, StatusCode::CONFLICT);
+                assert_eq!(body.unwrap()["error"], "name_taken");
+            }
+        }
+        assert_eq!(admitted, 1);
+        assert_eq!(state.get_room(&room_id).unwrap().participants().len(), 2);
+    });
+}
+
+#[test]
+fn room_codes_match_in_any_case_for_join_refresh_and_websocket() {
+    block_on(async {
+        let state = AppState::new(&ServerConfig::default());
+        let app = router(state.clone());
+        let addr = serve(app.clone()).await;
+
+        let (status, created) = post_as(
+            &app,
+            "203.0.113.61",
+            "/api/rooms",
+            serde_json::json!({ "name": "Aaron", "room_code": "quiz-7" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(created.unwrap()["room_id"], "QUIZ-7");
+
+        // As typed off a projector: lowercase with stray spaces.
+        let (status, joined) = post_as(
+            &app,
+            "203.0.113.61",
+            "/api/rooms/%20quiz-7%20/join",
+            serde_json::json!({ "name": "Bob" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let joined = joined.unwrap();
+        assert_eq!(joined["room_id"], "QUIZ-7");
+        let bob = joined["token"].as_str().unwrap().to_string();
+
+        let (status, refreshed) = post_with_token(
+            &app,
+            &bob,
+            "/api/rooms/Quiz-7/refresh_token",
+            serde_json::json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(refreshed["room_id"], "QUIZ-7");
+        let bob = refreshed["new_token"].as_str().unwrap();
+
+        let url = format!("ws://{addr}/ws/quiz-7?token={bob}");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let participants = next_ws_message(&mut ws, "participants").await;
+        assert_eq!(participants["participants"].as_array().unwrap().len(), 2);
+    });
+}
+
+#[test]
+fn batch_creates_a_bracket_of_rooms_with_admin_tokens() {
+    block_on(async {
+        let state = AppState::new(&ServerConfig::default());
+        let app = router(state.clone());
+
+        let (status, body) = post_as(
+            &app,
+            "203.0.113.70",
+            "/api/rooms/batch",
+            serde_json::json!({ "count": 16, "name": "Quizmaster", "prefix": "QF" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let rooms = body.unwrap();
+        let rooms = rooms.as_array().unwrap();
+        assert_eq!(rooms.len(), 16);
+        for (i, room) in rooms.iter().enumerate() {
+            let room_id = room["room_id"].as_str().unwrap();
+            assert_eq!(room_id, format!("QF-{}", i + 1));
+            assert_eq!(room["answer_window_in_ms"], DEFAULT_ANSWER_WINDOW_IN_MS);
+            let token = room["token"].as_str().unwrap();
+            state
+                .get_room(room_id)
+                .unwrap()
+                .authorize_admin(token)
+                .unwrap();
+        }
+
+        for count in [0, MAX_BATCH_ROOMS + 1] {
+            let (status, body) = post_as(
+                &app,
+                "203.0.113.70",
+                "/api/rooms/batch",
+                serde_json::json!({ "count": count, "name": "Quizmaster" }),
+            )
+            .await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(body.unwrap()["error"], "invalid_request");
+        }
+
+        // QF-1 is taken, so nothing from this batch survives.
+        let (status, body) = post_as(
+            &app,
+            "203.0.113.70",
+            "/api/rooms/batch",
+            serde_json::json!({ "count": 2, "name": "Quizmaster", "prefix": "qf" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body.unwrap()["error"], "room_code_taken");
+        assert_eq!(state.room_count(), 16);
+    });
+}
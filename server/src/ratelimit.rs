@@ -5,72 +5,148 @@
 //! [`ClientIpKeyExtractor`] instead reads `X-Forwarded-For` and trusts only the
 //! right-most entries appended by our own proxy chain (`TRUSTED_PROXY_HOPS`),
 //! which is the only way to extract the client IP without letting callers spoof it.
+//!
+//! Every bucket keeps one entry per client IP it has seen, so [`spawn_eviction`]
+//! periodically drops the entries of clients that have been idle long enough to
+//! be back at a full burst.
 
 use std::net::{IpAddr, SocketAddr};
-use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
+use axum::Json;
+use axum::body::Body;
 use axum::extract::ConnectInfo;
-use axum::http::Request;
-use tower_governor::GovernorError;
+use axum::http::{Request, Response, StatusCode};
+use axum::response::IntoResponse;
+use governor::clock::QuantaInstant;
+use governor::middleware::NoOpMiddleware;
+use tower_governor::governor::{GovernorConfig, GovernorConfigBuilder};
 use tower_governor::key_extractor::KeyExtractor;
+use tower_governor::{GovernorError, GovernorLayer};
+use tracing::debug;
+
+use crate::dtos::{ErrorResponse, RateLimitedResponse};
+use crate::request_id;
 
 const X_FORWARDED_FOR: &str = "x-forwarded-for";
 
-/// Tunable rate-limit parameters, sourced from the environment with production
-/// defaults. All limits are **per real client IP**.
-#[derive(Clone, Copy, Debug)]
+/// How often idle client entries are dropped from every bucket.
+pub const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+
+pub type ClientIpConfig = GovernorConfig<ClientIpKeyExtractor, NoOpMiddleware<QuantaInstant>>;
+pub type ClientIpLayer = GovernorLayer<ClientIpKeyExtractor, NoOpMiddleware<QuantaInstant>, Body>;
+
+/// Tunable rate-limit parameters with production defaults, part of
+/// [`ServerConfig`](crate::config::ServerConfig). All limits are **per real client IP**.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct RateLimitSettings {
     /// Number of trusted proxies in front of the app that append to
     /// `X-Forwarded-For`. `1` for a single reverse proxy (the default), `2` for
     /// e.g. CDN -> nginx -> app, `0` for a directly-exposed server (peer IP only).
     pub trusted_hops: usize,
-    /// Burst capacity for general interactive endpoints (join / refresh / ws).
+    /// Burst capacity for general interactive endpoints (refresh / ws / reads).
     pub api_burst: u32,
     /// Replenish interval (ms) for one general-endpoint token. 100ms => 10 req/s.
     pub api_period_ms: u64,
     /// Burst capacity for room creation.
     pub create_burst: u32,
-    /// Replenish interval (ms) for one room-creation token. 2000ms => 0.5 req/s.
+    /// Replenish interval (ms) for one room-creation token. 12000ms => 5 req/min.
     pub create_period_ms: u64,
+    /// Burst capacity for joining a room.
+    pub join_burst: u32,
+    /// Replenish interval (ms) for one join token. 2000ms => 30 req/min.
+    pub join_period_ms: u64,
 }
 
 impl Default for RateLimitSettings {
     /// Production defaults (per real client IP):
-    /// - general endpoints: burst 60, +1 token / 100ms => 10 req/s sustained
-    /// - room creation:     burst 10, +1 token / 2000ms => 0.5 req/s sustained
+    /// - general endpoints: burst 60, +1 token / 100ms   => 10 req/s sustained
+    /// - room creation:     burst 5,  +1 token / 12000ms => 5 req/min sustained
+    /// - joining a room:    burst 30, +1 token / 2000ms  => 30 req/min sustained
     fn default() -> Self {
         Self {
             trusted_hops: 1,
             api_burst: 60,
             api_period_ms: 100,
-            create_burst: 10,
-            create_period_ms: 2000,
+            create_burst: 5,
+            create_period_ms: 12_000,
+            join_burst: 30,
+            join_period_ms: 2000,
         }
     }
 }
 
 impl RateLimitSettings {
-    pub fn from_env() -> Self {
-        let d = Self::default();
-        Self {
-            trusted_hops: env_or("TRUSTED_PROXY_HOPS", d.trusted_hops),
-            api_burst: env_or("RL_API_BURST", d.api_burst),
-            api_period_ms: env_or("RL_API_PERIOD_MS", d.api_period_ms),
-            create_burst: env_or("RL_CREATE_BURST", d.create_burst),
-            create_period_ms: env_or("RL_CREATE_PERIOD_MS", d.create_period_ms),
-        }
-    }
-
     pub fn extractor(&self) -> ClientIpKeyExtractor {
         ClientIpKeyExtractor::new(self.trusted_hops)
     }
+
+    /// One per-client-IP bucket. NOTE: tower_governor's `per_*` methods set the
+    /// REPLENISH INTERVAL for one token, not a rate -- `per_millisecond(100)`
+    /// replenishes a token every 100ms => 10 req/s sustained.
+    pub fn bucket(&self, period_ms: u64, burst: u32) -> Arc<ClientIpConfig> {
+        Arc::new(
+            GovernorConfigBuilder::default()
+                .key_extractor(self.extractor())
+                .per_millisecond(period_ms)
+                .burst_size(burst)
+                .finish()
+                .expect("rate limit period and burst are non-zero"),
+        )
+    }
 }
 
-fn env_or<T: FromStr>(key: &str, default: T) -> T {
-    std::env::var(key)
-        .ok()
-        .and_then(|value| value.parse().ok())
-        .unwrap_or(default)
+/// Guard a route with `conf`, answering throttled requests with [`too_many_requests`].
+pub fn layer(conf: &Arc<ClientIpConfig>) -> ClientIpLayer {
+    GovernorLayer::new(Arc::clone(conf)).error_handler(too_many_requests)
+}
+
+/// `429` with a JSON body telling the client how long to back off. The
+/// `Retry-After` header governor computed is kept as well.
+pub fn too_many_requests(err: GovernorError) -> Response<Body> {
+    match err {
+        GovernorError::TooManyRequests { wait_time, headers } => {
+            let body = RateLimitedResponse {
+                error: ErrorResponse {
+                    error: "rate_limited",
+                    message: "Too many requests, please slow down",
+                    retryable: true,
+                    request_id: request_id::current(),
+                },
+                // Governor truncates to whole seconds; never tell a client to retry
+                // immediately when it would be throttled again.
+                retry_after_secs: wait_time.max(1),
+            };
+            let mut res = (StatusCode::TOO_MANY_REQUESTS, Json(body)).into_response();
+            if let Some(headers) = headers {
+                res.headers_mut().extend(headers);
+            }
+            res
+        }
+        other => Response::from(other),
+    }
+}
+
+/// Periodically forget clients whose buckets have refilled completely. Without
+/// this every distinct IP that ever called us would stay in memory.
+pub fn spawn_eviction(confs: Vec<Arc<ClientIpConfig>>, every: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            for conf in &confs {
+                let limiter = conf.limiter();
+                limiter.retain_recent();
+                limiter.shrink_to_fit();
+                debug!(
+                    "Rate limiter holds {} client(s) after eviction",
+                    limiter.len()
+                );
+            }
+        }
+    });
 }
 
 /// A [`KeyExtractor`] that resolves the real client IP behind trusted proxies.
@@ -150,7 +226,10 @@ mod tests {
     #[test]
     fn single_proxy_takes_only_entry() {
         let ext = ClientIpKeyExtractor::new(1);
-        assert_eq!(ext.extract(&req_with_xff("203.0.113.7")).unwrap(), ip("203.0.113.7"));
+        assert_eq!(
+            ext.extract(&req_with_xff("203.0.113.7")).unwrap(),
+            ip("203.0.113.7")
+        );
     }
 
     #[test]
@@ -173,7 +252,8 @@ mod tests {
         );
         // even with a spoofed left entry the real client survives
         assert_eq!(
-            ext.extract(&req_with_xff("9.9.9.9, 203.0.113.7, 70.0.0.1")).unwrap(),
+            ext.extract(&req_with_xff("9.9.9.9, 203.0.113.7, 70.0.0.1"))
+                .unwrap(),
             ip("203.0.113.7")
         );
     }
@@ -197,13 +277,13 @@ mod throughput {
     use std::sync::Arc;
     use std::time::Duration;
 
+    use axum::Router;
     use axum::body::Body;
     use axum::http::{Request, StatusCode};
     use axum::routing::get;
-    use axum::Router;
     use tower::ServiceExt; // for `oneshot`
-    use tower_governor::governor::GovernorConfigBuilder;
     use tower_governor::GovernorLayer;
+    use tower_governor::governor::GovernorConfigBuilder;
 
     /// Build a one-route app guarded by a per-client-IP limiter with the given
     /// replenish period and burst -- the exact builder used in `main`.
@@ -274,7 +354,7 @@ mod throughput {
     }
 
     #[test]
-    fn create_default_budget_is_10_requests() {
+    fn create_default_budget_is_5_requests() {
         block_on(async {
             let s = RateLimitSettings::default();
             let app = limited_app(s.extractor(), s.create_period_ms, s.create_burst);
@@ -298,7 +378,11 @@ mod throughput {
             // 1 token / minute => effectively no replenish during the test.
             let app = limited_app(ClientIpKeyExtractor::new(1), 60_000, 3);
             for i in 0..3 {
-                assert_eq!(send(&app, "203.0.113.20").await, StatusCode::OK, "request {i}");
+                assert_eq!(
+                    send(&app, "203.0.113.20").await,
+                    StatusCode::OK,
+                    "request {i}"
+                );
             }
             assert_eq!(
                 send(&app, "203.0.113.20").await,
@@ -315,7 +399,10 @@ mod throughput {
 
             // Client A burns its whole budget...
             assert_eq!(count_allowed(&app, "203.0.113.1", 3).await, 3);
-            assert_eq!(send(&app, "203.0.113.1").await, StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(
+                send(&app, "203.0.113.1").await,
+                StatusCode::TOO_MANY_REQUESTS
+            );
 
             // ...client B is unaffected and still gets the full burst.
             assert_eq!(count_allowed(&app, "203.0.113.2", 5).await, 3);
@@ -341,7 +428,10 @@ mod throughput {
                     allowed += 1;
                 }
             }
-            assert_eq!(allowed, 3, "spoofing the left XFF entries must not buy extra budget");
+            assert_eq!(
+                allowed, 3,
+                "spoofing the left XFF entries must not buy extra budget"
+            );
         });
     }
 
@@ -352,7 +442,10 @@ mod throughput {
             let app = limited_app(ClientIpKeyExtractor::new(1), 50, 2);
             assert_eq!(send(&app, "203.0.113.9").await, StatusCode::OK);
             assert_eq!(send(&app, "203.0.113.9").await, StatusCode::OK);
-            assert_eq!(send(&app, "203.0.113.9").await, StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(
+                send(&app, "203.0.113.9").await,
+                StatusCode::TOO_MANY_REQUESTS
+            );
 
             // Wait for a couple of tokens to replenish, then we can send again.
             tokio::time::sleep(Duration::from_millis(120)).await;
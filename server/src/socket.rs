@@ -1,36 +1,171 @@
 use std::num::NonZeroU32;
 use std::sync::Arc;
+use std::time::Duration;
 
-use axum::extract::ws::{Message, WebSocket};
+use axum::extract::ws::{CloseFrame, Message, WebSocket, close_code};
+use futures::stream::SplitSink;
 use futures::{SinkExt, StreamExt};
-use governor::{Quota, RateLimiter};
-use tokio::sync::mpsc;
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+use tokio::time::{Instant, MissedTickBehavior};
 use tracing::{info, warn};
 
 use core::game::PlayerId;
 
-use crate::dtos::ClientMessage;
+use crate::adapter::Outbound;
+use crate::dtos::{
+    ClientMessage, MAX_ACK_ID_LEN, PROTOCOL_VERSION, RateCategory, Role,
+    SUPPORTED_PROTOCOL_VERSIONS, ServerMessage,
+};
+use crate::errors::AppError;
+use crate::state::app_state::AppState;
 use crate::state::room_state::RoomState;
+use crate::utils::time::{now_millis, now_seconds};
+
+/// The websocket layer refuses frames past this many times
+/// [`ws_max_message_bytes`](crate::config::ServerConfig::ws_max_message_bytes)
+/// before reading them in full; smaller oversized ones are only denied.
+pub const HARD_MESSAGE_LIMIT_FACTOR: usize = 4;
+/// A socket denied more than this many messages for quotas or size within
+/// [`VIOLATION_WINDOW`] is closed as abusive.
+pub const MAX_VIOLATIONS: u32 = 5;
+const VIOLATION_WINDOW: Duration = Duration::from_secs(10);
+/// Unreadable messages one connection may send, however slowly, before it is
+/// closed as `invalid_request`: a client that keeps getting the protocol wrong
+/// will not start getting it right.
+pub const MAX_MALFORMED_MESSAGES: u32 = 10;
+/// A write that takes longer means the client's TCP window is shut; we give up
+/// on it like on a full outbound queue.
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+/// The close frame is a courtesy; a client that stopped reading won't get it.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Messages per second and burst for one [`RateCategory`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InboundQuota {
+    pub per_sec: u32,
+    pub burst: u32,
+}
+
+impl InboundQuota {
+    fn limiter(self) -> DefaultDirectRateLimiter {
+        let per_sec = NonZeroU32::new(self.per_sec).unwrap_or(NonZeroU32::MIN);
+        let burst = NonZeroU32::new(self.burst).unwrap_or(NonZeroU32::MIN);
+        RateLimiter::direct(Quota::per_second(per_sec).allow_burst(burst))
+    }
+}
+
+/// Per-connection quotas by kind of message, part of
+/// [`ServerConfig`](crate::config::ServerConfig); the general quota is the
+/// room's own `inbound_rate_per_sec`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InboundQuotas {
+    pub buzz: InboundQuota,
+    pub control: InboundQuota,
+    pub chat: InboundQuota,
+    /// A client syncs its clock with a few round trips, then now and again.
+    pub sync: InboundQuota,
+}
+
+impl Default for InboundQuotas {
+    fn default() -> Self {
+        Self {
+            buzz: InboundQuota {
+                per_sec: 10,
+                burst: 3,
+            },
+            control: InboundQuota {
+                per_sec: 5,
+                burst: 5,
+            },
+            chat: InboundQuota {
+                per_sec: 2,
+                burst: 2,
+            },
+            sync: InboundQuota {
+                per_sec: 1,
+                burst: 4,
+            },
+        }
+    }
+}
 
 pub struct PlayerSession {
+    pub room_id: String,
     pub player_id: PlayerId,
     pub name: String,
+    pub role: Role,
+    /// `iat` of the token the socket was opened with.
+    pub issued_at: u64,
+    /// `exp` of that token; the socket is closed when it passes, unless the
+    /// player has refreshed it since.
+    pub expires_at: u64,
+    pub format: WireFormat,
+    /// Resume from this `seq` instead of starting a fresh stream.
+    pub since_seq: Option<u64>,
+    /// Displace the player's current socket, if any, instead of being refused.
+    pub takeover: bool,
 }
 
-pub async fn handle_socket(socket: WebSocket, room: Arc<RoomState>, session: PlayerSession) {
+pub async fn handle_socket(
+    socket: WebSocket,
+    state: AppState,
+    room: Arc<RoomState>,
+    session: PlayerSession,
+) {
+    let started = Instant::now();
     let (mut sender, mut receiver) = socket.split();
-    let (local_tx, mut local_rx) = mpsc::unbounded_channel::<String>();
+    let (local_tx, mut local_rx) = mpsc::channel::<Outbound>(state.config().outbound_capacity);
+    // Identifies this connection on detach without keeping the channel open.
+    let connection = local_tx.downgrade();
+    // Subscribed before attaching, so nothing sent in between is missed; the
+    // route skips whatever it already has.
+    let mut broadcasts = room.subscribe();
 
     let attached = room
-        .attach_connection(session.player_id, &session.name, local_tx.clone())
-        .await
-        .unwrap_or(false);
-    if !attached {
-        warn!(
-            "[WS] Failed to attach connection for player {} (id: {})",
-            session.name, session.player_id
-        );
-        return;
+        .attach_connection(
+            session.player_id,
+            &session.name,
+            local_tx,
+            session.since_seq,
+            session.takeover,
+        )
+        .await;
+    match attached {
+        Ok(true) => {}
+        Err(AppError::AlreadyConnected) => {
+            info!(
+                "[WS] Refused second connection for player {} (id: {})",
+                session.name, session.player_id
+            );
+            let reason = CloseReason::Refused(AppError::AlreadyConnected);
+            close_socket(&mut sender, &state, &session, &reason, started).await;
+            return;
+        }
+        attached => {
+            warn!(
+                "[WS] Failed to attach connection for player {} (id: {})",
+                session.name, session.player_id
+            );
+            let refusal = match attached {
+                Err(err) => err,
+                _ => room
+                    .connection_refusal(session.player_id, &session.name, session.issued_at)
+                    .unwrap_or(AppError::UserNotInRoom),
+            };
+            let denied = ServerMessage::ActionDenied {
+                reason: "attach_failed".to_string(),
+                category: None,
+                id: None,
+            };
+            let denied = serde_json::to_string(&denied).expect("serialize server message");
+            if let Some(frame) = session.format.encode(denied.into()) {
+                let _ = sender.send(frame).await;
+            }
+            let reason = CloseReason::Refused(refusal);
+            close_socket(&mut sender, &state, &session, &reason, started).await;
+            return;
+        }
     }
 
     info!(
@@ -38,62 +173,1111 @@ pub async fn handle_socket(socket: WebSocket, room: Arc<RoomState>, session: Pla
         session.name, session.player_id
     );
 
-    let inbound_limiter = RateLimiter::direct(Quota::per_second(
-        NonZeroU32::new(20).expect("non-zero ws inbound quota"),
-    ));
+    let inbound_limits =
+        InboundLimits::new(room.inbound_rate_per_sec(), &state.config().inbound_quotas);
+    let mut malformed = 0;
+    let max_message_bytes = state.config().ws_max_message_bytes;
+    // Pings keep proxies from dropping a quiet socket and draw a pong from a
+    // live client; a client that answers nothing at all has gone away without
+    // closing, e.g. a locked phone or a dropped network.
+    let ping_every = Duration::from_millis(state.config().ws_ping_interval_ms);
+    let idle_timeout = Duration::from_millis(state.config().ws_idle_timeout_ms);
+    let mut ping = tokio::time::interval_at(Instant::now() + ping_every, ping_every);
+    ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let silent = tokio::time::sleep(idle_timeout);
+    tokio::pin!(silent);
+    // A socket lives no longer than the player's latest token.
+    let expired = tokio::time::sleep_until(expiry_instant(session.expires_at));
+    tokio::pin!(expired);
 
-    loop {
+    let reason = loop {
         tokio::select! {
+            _ = ping.tick() => {
+                let ping = Message::Ping(Default::default());
+                if !matches!(tokio::time::timeout(SEND_TIMEOUT, sender.send(ping)).await, Ok(Ok(()))) {
+                    warn!("[WS] Failed to ping player {}", session.player_id);
+                    break CloseReason::ConnectionLost;
+                }
+            }
+            _ = &mut silent => {
+                warn!(
+                    "[WS] Nothing heard from player {} in {:?}, closing",
+                    session.player_id, idle_timeout
+                );
+                break CloseReason::IdleTimeout;
+            }
+            _ = &mut expired => {
+                // Refreshed over HTTP or with `update_token` in the meantime.
+                let renewed = room
+                    .token_expiry(session.player_id)
+                    .filter(|exp| *exp > now_seconds());
+                if let Some(exp) = renewed {
+                    expired.as_mut().reset(expiry_instant(exp));
+                    continue;
+                }
+                info!("[WS] Token of player {} expired, closing", session.player_id);
+                if let Some(frame) = session.format.encode(unsequenced(&ServerMessage::SessionExpired)) {
+                    let _ = tokio::time::timeout(CLOSE_TIMEOUT, sender.send(frame)).await;
+                }
+                break CloseReason::SessionExpired;
+            }
             outbound = local_rx.recv() => {
                 match outbound {
                     Some(text) => {
-                        if sender.send(Message::Text(text.into())).await.is_err() {
-                            warn!("[WS] Failed to send message to player {}", session.player_id);
-                            break;
+                        let Some(frame) = session.format.encode(text) else {
+                            continue;
+                        };
+                        match tokio::time::timeout(SEND_TIMEOUT, sender.send(frame)).await {
+                            Ok(Ok(())) => {}
+                            Ok(Err(_)) => {
+                                warn!("[WS] Failed to send message to player {}", session.player_id);
+                                break CloseReason::ConnectionLost;
+                            }
+                            Err(_) => {
+                                warn!("[WS] Player {} stopped reading, closing", session.player_id);
+                                break CloseReason::SlowConsumer;
+                            }
+                        }
+                    }
+                    None => {
+                        // The room dropped our route: we were kicked or left, the
+                        // room closed, another socket took over, or we were cut
+                        // off for not keeping up. Only the first two end the session.
+                        let refusal = room.connection_refusal(
+                            session.player_id,
+                            &session.name,
+                            session.issued_at,
+                        );
+                        break match refusal {
+                            Some(err) => CloseReason::Refused(err),
+                            // A socket that reconnected after being cut off also
+                            // counts as a takeover; either way the client is fine.
+                            None if room.is_connected(session.player_id) => CloseReason::Replaced,
+                            None => CloseReason::SlowConsumer,
+                        };
+                    }
+                }
+            }
+            broadcast = broadcasts.recv() => {
+                match broadcast {
+                    Ok(broadcast) => room.deliver_broadcast(session.player_id, &broadcast),
+                    Err(RecvError::Lagged(skipped)) => {
+                        if !room.catch_up_broadcasts(session.player_id) {
+                            // Closing makes the client reconnect and get a fresh welcome.
+                            warn!(
+                                "[WS] Player {} fell {} broadcasts behind, closing",
+                                session.player_id, skipped
+                            );
+                            break CloseReason::SlowConsumer;
                         }
                     }
-                    None => break,
+                    Err(RecvError::Closed) => break CloseReason::Refused(AppError::RoomClosed),
                 }
             }
             inbound = receiver.next() => {
+                if let Some(Ok(_)) = inbound {
+                    silent.as_mut().reset(Instant::now() + idle_timeout);
+                }
                 match inbound {
-                    Some(Ok(Message::Text(text))) => {
-                        if inbound_limiter.check().is_err() {
-                            warn!("[WS] Rate limit exceeded for player {}", session.player_id);
-                            room.send_denied_to(session.player_id, "rate_limited");
-                            continue;
-                        }
-                        if let Some(msg) = parse_client_message(&text) {
-                            match msg {
-                                ClientMessage::Buzz => {
-                                    room.send_buzz(session.player_id);
+                    Some(Ok(frame @ (Message::Text(_) | Message::Binary(_)))) => {
+                        let (request, ack_id) = if frame_len(&frame) > max_message_bytes {
+                            // Denied unread, so nothing is spent parsing it.
+                            (Err(None), None)
+                        } else {
+                            let request = session.format.decode(&frame);
+                            let ok = request.as_ref().ok();
+                            let ack_id = ok.and_then(|request| request.ack_id.clone());
+                            // Unreadable frames count against the general quota too,
+                            // so garbage draws no more replies than anything else.
+                            let checked = inbound_limits.check(ok.map(|request| &request.msg));
+                            (checked.map(|()| request).map_err(Some), ack_id)
+                        };
+                        let request = match request {
+                            Ok(request) => request,
+                            Err(category) => {
+                                match category {
+                                    Some(category) => warn!(
+                                        "[WS] {:?} quota ran out for player {}",
+                                        category, session.player_id
+                                    ),
+                                    None => warn!("[WS] Message too large from player {}", session.player_id),
+                                }
+                                if inbound_limits.abusive() {
+                                    warn!(
+                                        "[WS] Player {} kept sending past the limits, closing",
+                                        session.player_id
+                                    );
+                                    break CloseReason::Refused(AppError::RateLimited);
                                 }
-                                ClientMessage::StartRound => {
-                                    room.start_round(session.player_id);
+                                match category {
+                                    Some(category) => {
+                                        room.send_rate_limited_to(session.player_id, category, ack_id)
+                                    }
+                                    None => room.send_denied_to(session.player_id, "message_too_large", ack_id),
+                                }
+                                continue;
+                            }
+                        };
+                        match request {
+                            Err(detail) => {
+                                warn!(
+                                    "[WS] Unreadable message from player {}: {}",
+                                    session.player_id, detail
+                                );
+                                malformed += 1;
+                                if malformed >= MAX_MALFORMED_MESSAGES {
+                                    warn!(
+                                        "[WS] Player {} sent {} unreadable messages, closing",
+                                        session.player_id, malformed
+                                    );
+                                    break CloseReason::Refused(AppError::InvalidRequest);
                                 }
-                                ClientMessage::Kick { name } => {
-                                    let _ = room.kick_by_name(session.player_id, &name).await;
+                                // The denial is what a client waiting on an answer sees;
+                                // the protocol error says what was wrong.
+                                room.send_denied_to(session.player_id, "bad_message", None);
+                                room.send_protocol_error_to(session.player_id, detail);
+                            }
+                            Ok(ClientRequest { msg, ack_id }) => {
+                                if session.role == Role::Spectator && !spectator_may_send(&msg) {
+                                    room.send_denied_to(session.player_id, "spectator", ack_id);
+                                    continue;
                                 }
-                                ClientMessage::ContinueRound => {
-                                    room.continue_round(session.player_id);
+                                match msg {
+                                    ClientMessage::TimeSync { client_ts_ms } => {
+                                        // Answered here instead of by the room, so nothing
+                                        // queued ahead of it adds to the measured round trip.
+                                        let reply = ServerMessage::TimeSync {
+                                            client_ts_ms,
+                                            server_ts_ms: now_millis(),
+                                        };
+                                        let Some(frame) = session.format.encode(unsequenced(&reply))
+                                        else {
+                                            continue;
+                                        };
+                                        let sent = tokio::time::timeout(SEND_TIMEOUT, sender.send(frame)).await;
+                                        if !matches!(sent, Ok(Ok(()))) {
+                                            warn!(
+                                                "[WS] Failed to answer time sync for player {}",
+                                                session.player_id
+                                            );
+                                            break CloseReason::ConnectionLost;
+                                        }
+                                    }
+                                    ClientMessage::Buzz => {
+                                        room.send_buzz(session.player_id, ack_id);
+                                    }
+                                    ClientMessage::StartRound { countdown_ms, question } => {
+                                        let _ = room
+                                            .start_round(session.player_id, countdown_ms, question, ack_id)
+                                            .await;
+                                    }
+                                    ClientMessage::Kick { name } => {
+                                        let _ = room.kick_by_name(session.player_id, &name, ack_id).await;
+                                    }
+                                    ClientMessage::Mute { name } => {
+                                        room.set_muted(session.player_id, &name, true, ack_id);
+                                    }
+                                    ClientMessage::Unmute { name } => {
+                                        room.set_muted(session.player_id, &name, false, ack_id);
+                                    }
+                                    ClientMessage::Unban { name } => {
+                                        room.unban(session.player_id, &name, ack_id);
+                                    }
+                                    ClientMessage::ContinueRound => {
+                                        let _ = room.continue_round(session.player_id, ack_id).await;
+                                    }
+                                    ClientMessage::MarkCorrect => {
+                                        room.mark_correct(session.player_id, ack_id);
+                                    }
+                                    ClientMessage::Grant { name, force } => {
+                                        room.grant(session.player_id, &name, force, ack_id);
+                                    }
+                                    ClientMessage::Undo => {
+                                        room.undo(session.player_id, ack_id);
+                                    }
+                                    ClientMessage::NewGame => {
+                                        room.new_game(session.player_id, ack_id);
+                                    }
+                                    ClientMessage::Leave => {
+                                        // Our route is dropped, so the outbound branch closes the
+                                        // socket; the close is all the acknowledgement there is.
+                                        let _ = room.leave(session.player_id).await;
+                                    }
+                                    ClientMessage::SetReady { ready } => {
+                                        room.set_ready(session.player_id, ready, ack_id);
+                                    }
+                                    ClientMessage::RequestReady => {
+                                        room.request_ready(session.player_id, ack_id);
+                                    }
+                                    ClientMessage::CloseRoom => {
+                                        // Like leaving, a closed room answers by closing the socket.
+                                        if room.is_admin(session.player_id) {
+                                            let _ = state.close_room(&session.room_id, "closed_by_admin");
+                                        } else {
+                                            room.send_denied_to(session.player_id, "forbidden", ack_id);
+                                        }
+                                    }
+                                    ClientMessage::SetAnswerWindow { answer_window_in_ms } => {
+                                        let result = if room.is_admin(session.player_id) {
+                                            let window = crate::clamp_answer_window(answer_window_in_ms);
+                                            room.update_settings(Some(window), None, None)
+                                                .await
+                                                .map(|_| ())
+                                                .map_err(|err| err.code())
+                                        } else {
+                                            Err("forbidden")
+                                        };
+                                        room.acknowledge(session.player_id, ack_id, result);
+                                    }
+                                    ClientMessage::React { emoji } => {
+                                        room.react(session.player_id, &emoji, ack_id);
+                                    }
+                                    ClientMessage::Chat { text, system } => {
+                                        room.chat(session.player_id, &text, system, ack_id);
+                                    }
+                                    ClientMessage::Pause => {
+                                        room.pause(session.player_id, ack_id);
+                                    }
+                                    ClientMessage::LockRoom => {
+                                        room.set_locked(session.player_id, true, ack_id);
+                                    }
+                                    ClientMessage::UnlockRoom => {
+                                        room.set_locked(session.player_id, false, ack_id);
+                                    }
+                                    ClientMessage::Resume => {
+                                        room.resume(session.player_id, ack_id);
+                                    }
+                                    ClientMessage::Rename { new_name } => {
+                                        room.rename(session.player_id, &new_name, ack_id);
+                                    }
+                                    ClientMessage::UpdateToken { token } => {
+                                        let renewed = room.renew_session(
+                                            session.player_id,
+                                            &session.name,
+                                            &token,
+                                        );
+                                        if let Ok(exp) = renewed {
+                                            expired.as_mut().reset(expiry_instant(exp));
+                                        }
+                                        let result = renewed.map(|_| ()).map_err(|err| err.code());
+                                        room.acknowledge(session.player_id, ack_id, result);
+                                    }
+                                    ClientMessage::GetRoundState => {
+                                        room.send_round_state_to(session.player_id);
+                                        room.acknowledge(session.player_id, ack_id, Ok(()));
+                                    }
+                                    ClientMessage::Resync { last_seq } => {
+                                        info!(
+                                            "[WS] Player {} resyncing after seq {:?}",
+                                            session.player_id, last_seq
+                                        );
+                                        room.resync(session.player_id, ack_id);
+                                    }
                                 }
                             }
                         }
                     }
-                    Some(Ok(Message::Close(_))) | None => {
+                    Some(Ok(Message::Close(_))) => {
                         info!("[WS] Client closed connection for player {}", session.player_id);
-                        break;
+                        break CloseReason::ClientClosed;
+                    }
+                    // The websocket layer already answers pings with a pong;
+                    // like pongs, they only count as a sign of life.
+                    Some(Ok(Message::Ping(_) | Message::Pong(_))) => {}
+                    Some(Err(err)) => {
+                        warn!("[WS] Connection error for player {}: {}", session.player_id, err);
+                        break CloseReason::ConnectionLost;
                     }
-                    _ => {}
+                    None => break CloseReason::ConnectionLost,
                 }
             }
         }
+    };
+
+    close_socket(&mut sender, &state, &session, &reason, started).await;
+    room.detach_connection(session.player_id, connection);
+}
+
+/// Why a socket ended, as sent in its close frame and counted in `/healthz`.
+/// Codes are 4000 plus the nearest HTTP status, so a client can tell a session
+/// that ended (4403, 4410) from a connection worth retrying.
+#[derive(Debug)]
+pub enum CloseReason {
+    /// With 4000 plus the error's HTTP status and its code as the reason, e.g.
+    /// 4403 `kicked`, 4410 `room_closed` or 4429 `rate_limited`.
+    Refused(AppError),
+    /// The player's token ran out and was not refreshed: 4401.
+    SessionExpired,
+    /// Another socket took over the session: 4409.
+    Replaced,
+    /// Nothing heard from the client, not even a pong: 4408.
+    IdleTimeout,
+    /// The client fell too far behind on what we sent it: 4503.
+    SlowConsumer,
+    /// The client closed first; its close frame is echoed as is.
+    ClientClosed,
+    /// The connection broke without a close; nothing is sent.
+    ConnectionLost,
+}
+
+impl CloseReason {
+    pub fn code(&self) -> u16 {
+        match self {
+            CloseReason::Refused(err) => 4000 + err.status().as_u16(),
+            CloseReason::SessionExpired => 4401,
+            CloseReason::Replaced => 4409,
+            CloseReason::IdleTimeout => 4408,
+            CloseReason::SlowConsumer => 4503,
+            CloseReason::ClientClosed => close_code::NORMAL,
+            CloseReason::ConnectionLost => close_code::ABNORMAL,
+        }
     }
 
-    info!("[WS] Detaching connection for player {}", session.player_id);
-    room.detach_connection(session.player_id);
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CloseReason::Refused(err) => err.code(),
+            CloseReason::SessionExpired => "session_expired",
+            CloseReason::Replaced => "replaced",
+            CloseReason::IdleTimeout => "idle_timeout",
+            CloseReason::SlowConsumer => "slow_consumer",
+            CloseReason::ClientClosed => "client_closed",
+            CloseReason::ConnectionLost => "connection_lost",
+        }
+    }
 }
 
-fn parse_client_message(text: &str) -> Option<ClientMessage> {
-    serde_json::from_str(text).ok()
+/// Sends the close frame `reason` calls for, then logs and counts it.
+async fn close_socket(
+    sender: &mut SplitSink<WebSocket, Message>,
+    state: &AppState,
+    session: &PlayerSession,
+    reason: &CloseReason,
+    started: Instant,
+) {
+    if !matches!(
+        reason,
+        CloseReason::ClientClosed | CloseReason::ConnectionLost
+    ) {
+        let frame = Message::Close(Some(CloseFrame {
+            code: reason.code(),
+            reason: reason.as_str().into(),
+        }));
+        let _ = tokio::time::timeout(CLOSE_TIMEOUT, sender.send(frame)).await;
+    }
+    state.record_socket_close(reason.as_str());
+    info!(
+        room_id = %session.room_id,
+        player_id = session.player_id,
+        code = reason.code(),
+        reason = reason.as_str(),
+        session_ms = started.elapsed().as_millis() as u64,
+        "[WS] Connection closed"
+    );
+}
+
+/// Separate buckets so a burst of buzzes cannot starve admin controls and
+/// vice versa, and neither holds up a clock sync. Everything else, unparseable
+/// frames included, shares the room's general quota.
+struct InboundLimits {
+    buzz: DefaultDirectRateLimiter,
+    control: DefaultDirectRateLimiter,
+    chat: DefaultDirectRateLimiter,
+    sync: DefaultDirectRateLimiter,
+    general: DefaultDirectRateLimiter,
+    violations: DefaultDirectRateLimiter,
+}
+
+impl InboundLimits {
+    fn new(general_rate_per_sec: NonZeroU32, quotas: &InboundQuotas) -> Self {
+        let max_violations = NonZeroU32::new(MAX_VIOLATIONS).expect("non-zero violation quota");
+        Self {
+            buzz: quotas.buzz.limiter(),
+            control: quotas.control.limiter(),
+            chat: quotas.chat.limiter(),
+            sync: quotas.sync.limiter(),
+            general: RateLimiter::direct(Quota::per_second(general_rate_per_sec)),
+            violations: RateLimiter::direct(
+                Quota::with_period(VIOLATION_WINDOW / MAX_VIOLATIONS)
+                    .expect("non-zero violation period")
+                    .allow_burst(max_violations),
+            ),
+        }
+    }
+
+    /// Counts a denied message; `true` once the client keeps sending anyway.
+    fn abusive(&self) -> bool {
+        self.violations.check().is_err()
+    }
+
+    /// On failure, returns the category whose bucket ran out.
+    fn check(&self, msg: Option<&ClientMessage>) -> Result<(), RateCategory> {
+        let category = msg.map_or(RateCategory::General, rate_category);
+        let limiter = match category {
+            RateCategory::Buzz => &self.buzz,
+            RateCategory::Control => &self.control,
+            RateCategory::Chat => &self.chat,
+            RateCategory::Sync => &self.sync,
+            RateCategory::General => &self.general,
+        };
+        limiter.check().map_err(|_| category)
+    }
+}
+
+fn rate_category(msg: &ClientMessage) -> RateCategory {
+    match msg {
+        ClientMessage::Buzz => RateCategory::Buzz,
+        ClientMessage::Chat { .. } | ClientMessage::React { .. } => RateCategory::Chat,
+        ClientMessage::TimeSync { .. }
+        | ClientMessage::Resync { .. }
+        | ClientMessage::GetRoundState => RateCategory::Sync,
+        msg if is_control(msg) => RateCategory::Control,
+        _ => RateCategory::General,
+    }
+}
+
+fn expiry_instant(exp_secs: u64) -> Instant {
+    Instant::now() + Duration::from_secs(exp_secs.saturating_sub(now_seconds()))
+}
+
+/// A message stamped with the protocol version but no `seq`, for replies that
+/// bypass the player's route.
+fn unsequenced(msg: &ServerMessage) -> Outbound {
+    let payload = serde_json::to_string(msg).expect("serialize server message");
+    format!("{{\"v\":{PROTOCOL_VERSION},{}", &payload[1..]).into()
+}
+
+fn frame_len(frame: &Message) -> usize {
+    match frame {
+        Message::Text(text) => text.len(),
+        Message::Binary(bytes) => bytes.len(),
+        _ => 0,
+    }
+}
+
+/// Messages that run the game rather than play it.
+fn is_control(msg: &ClientMessage) -> bool {
+    matches!(
+        msg,
+        ClientMessage::StartRound { .. }
+            | ClientMessage::ContinueRound
+            | ClientMessage::MarkCorrect
+            | ClientMessage::Pause
+            | ClientMessage::Resume
+            | ClientMessage::Kick { .. }
+            | ClientMessage::Mute { .. }
+            | ClientMessage::Unmute { .. }
+            | ClientMessage::Unban { .. }
+            | ClientMessage::Grant { .. }
+            | ClientMessage::Undo
+            | ClientMessage::NewGame
+            | ClientMessage::RequestReady
+            | ClientMessage::CloseRoom
+            | ClientMessage::LockRoom
+            | ClientMessage::UnlockRoom
+            | ClientMessage::SetAnswerWindow { .. }
+    )
+}
+
+/// Spectators watch and talk; anything that plays or runs the game is denied.
+fn spectator_may_send(msg: &ClientMessage) -> bool {
+    matches!(
+        msg,
+        ClientMessage::Leave
+            | ClientMessage::TimeSync { .. }
+            | ClientMessage::Rename { .. }
+            | ClientMessage::Resync { .. }
+            | ClientMessage::GetRoundState
+            | ClientMessage::UpdateToken { .. }
+            | ClientMessage::Chat { system: false, .. }
+            | ClientMessage::React { .. }
+    )
+}
+
+/// Encoding negotiated on the upgrade with `?format=` or a subprotocol; JSON
+/// unless asked otherwise.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WireFormat {
+    #[default]
+    Json,
+    /// MessagePack in binary frames, with the same field names as JSON.
+    Msgpack,
+}
+
+impl WireFormat {
+    /// Server preference when a client offers several subprotocols.
+    pub const PREFERENCE: [WireFormat; 2] = [WireFormat::Msgpack, WireFormat::Json];
+
+    /// Name for `Sec-WebSocket-Protocol`, for clients that cannot easily add
+    /// query parameters to the upgrade.
+    pub fn subprotocol(self) -> &'static str {
+        match self {
+            WireFormat::Json => "buzzer.json",
+            WireFormat::Msgpack => "buzzer.msgpack",
+        }
+    }
+
+    pub fn from_subprotocol(name: &str) -> Option<Self> {
+        Self::PREFERENCE
+            .into_iter()
+            .find(|format| format.subprotocol() == name)
+    }
+
+    /// Routes carry JSON so the replay buffer stays format-agnostic; MessagePack
+    /// sessions use the encoding cached with it.
+    fn encode(self, outbound: Outbound) -> Option<Message> {
+        match self {
+            WireFormat::Json => Some(Message::Text(outbound.json())),
+            WireFormat::Msgpack => outbound.msgpack().map(Message::Binary),
+        }
+    }
+
+    /// On failure, says what was wrong for a `protocol_error` reply.
+    fn decode(self, frame: &Message) -> Result<ClientRequest, String> {
+        let value = match (self, frame) {
+            (WireFormat::Json, Message::Text(text)) => {
+                serde_json::from_str(text).map_err(|err| format!("malformed JSON: {err}"))?
+            }
+            (WireFormat::Msgpack, Message::Binary(bytes)) => rmp_serde::from_slice(bytes)
+                .map_err(|err| format!("malformed MessagePack: {err}"))?,
+            (WireFormat::Json, _) => return Err("expected a text frame".to_string()),
+            (WireFormat::Msgpack, _) => return Err("expected a binary frame".to_string()),
+        };
+        parse_client_message(value)
+    }
+}
+
+/// A client message and the `id` it was tagged with, if any.
+struct ClientRequest {
+    msg: ClientMessage,
+    ack_id: Option<String>,
+}
+
+/// Checks the version before the message itself, so a client speaking a newer
+/// protocol hears about that rather than about a field it did not expect to need.
+fn parse_client_message(value: serde_json::Value) -> Result<ClientRequest, String> {
+    if !value.is_object() {
+        return Err("expected an object with a `type`".to_string());
+    }
+    if let Some(version) = value.get("v") {
+        let supported = version
+            .as_u64()
+            .is_some_and(|v| SUPPORTED_PROTOCOL_VERSIONS.contains(&v));
+        if !supported {
+            return Err(format!("unsupported protocol version {version}"));
+        }
+    }
+    let ack_id = match value.get("id") {
+        None | Some(serde_json::Value::Null) => None,
+        Some(serde_json::Value::String(id)) if id.len() <= MAX_ACK_ID_LEN => Some(id.clone()),
+        Some(_) => {
+            return Err(format!(
+                "`id` must be a string of at most {MAX_ACK_ID_LEN} bytes"
+            ));
+        }
+    };
+    // Serde names the problem: an unknown `type` lists the known ones, a
+    // missing or mistyped field names the field.
+    let msg = serde_json::from_value(value).map_err(|err| err.to_string())?;
+    Ok(ClientRequest { msg, ack_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dtos::{
+        GamePhase, GameStateInfo, ParticipantInfo, RoomSettingsInfo, RoundOutcome, ScoreEntry,
+    };
+
+    fn round_trip(format: WireFormat, client: serde_json::Value) -> Result<ClientMessage, String> {
+        let frame = match format {
+            WireFormat::Json => Message::Text(client.to_string().into()),
+            WireFormat::Msgpack => {
+                Message::Binary(rmp_serde::to_vec_named(&client).unwrap().into())
+            }
+        };
+        format.decode(&frame).map(|request| request.msg)
+    }
+
+    fn default_limits() -> InboundLimits {
+        InboundLimits::new(NonZeroU32::new(1).unwrap(), &InboundQuotas::default())
+    }
+
+    /// Spends `quota`'s whole burst on `msg`, then checks the next one is refused.
+    fn exhaust(limits: &InboundLimits, msg: &ClientMessage, quota: InboundQuota) -> RateCategory {
+        for _ in 0..quota.burst {
+            assert_eq!(limits.check(Some(msg)), Ok(()));
+        }
+        limits.check(Some(msg)).unwrap_err()
+    }
+
+    #[test]
+    fn buzz_quota_is_its_own() {
+        let limits = default_limits();
+        let quotas = InboundQuotas::default();
+        assert_eq!(
+            exhaust(&limits, &ClientMessage::Buzz, quotas.buzz),
+            RateCategory::Buzz
+        );
+        // Spent buzzes leave every other kind untouched.
+        assert_eq!(limits.check(Some(&ClientMessage::NewGame)), Ok(()));
+        assert_eq!(limits.check(Some(&ClientMessage::Leave)), Ok(()));
+        let chat = ClientMessage::Chat {
+            text: "hi".into(),
+            system: false,
+        };
+        assert_eq!(limits.check(Some(&chat)), Ok(()));
+        let sync = ClientMessage::TimeSync { client_ts_ms: 0 };
+        assert_eq!(limits.check(Some(&sync)), Ok(()));
+    }
+
+    #[test]
+    fn control_quota_does_not_block_buzzing() {
+        let limits = default_limits();
+        let start = ClientMessage::StartRound {
+            countdown_ms: None,
+            question: None,
+        };
+        assert_eq!(
+            exhaust(&limits, &start, InboundQuotas::default().control),
+            RateCategory::Control
+        );
+        let kick = ClientMessage::Kick { name: "Bob".into() };
+        assert_eq!(limits.check(Some(&kick)), Err(RateCategory::Control));
+        assert_eq!(limits.check(Some(&ClientMessage::Buzz)), Ok(()));
+    }
+
+    #[test]
+    fn chat_quota_covers_reactions_too() {
+        let limits = default_limits();
+        let chat = ClientMessage::Chat {
+            text: "hi".into(),
+            system: false,
+        };
+        assert_eq!(
+            exhaust(&limits, &chat, InboundQuotas::default().chat),
+            RateCategory::Chat
+        );
+        let react = ClientMessage::React {
+            emoji: "👏".into()
+        };
+        assert_eq!(limits.check(Some(&react)), Err(RateCategory::Chat));
+        assert_eq!(limits.check(Some(&ClientMessage::Buzz)), Ok(()));
+    }
+
+    #[test]
+    fn sync_quota_covers_time_sync_and_resync() {
+        let limits = default_limits();
+        let sync = ClientMessage::TimeSync { client_ts_ms: 0 };
+        assert_eq!(
+            exhaust(&limits, &sync, InboundQuotas::default().sync),
+            RateCategory::Sync
+        );
+        let resync = ClientMessage::Resync { last_seq: Some(3) };
+        assert_eq!(limits.check(Some(&resync)), Err(RateCategory::Sync));
+        assert_eq!(limits.check(Some(&ClientMessage::Buzz)), Ok(()));
+    }
+
+    #[test]
+    fn general_quota_takes_the_rest() {
+        let limits = default_limits();
+        assert_eq!(limits.check(None), Ok(()));
+        assert_eq!(
+            limits.check(Some(&ClientMessage::SetReady { ready: true })),
+            Err(RateCategory::General)
+        );
+        assert_eq!(limits.check(Some(&ClientMessage::Buzz)), Ok(()));
+    }
+
+    #[test]
+    fn quotas_follow_the_config() {
+        let quotas = InboundQuotas {
+            buzz: InboundQuota {
+                per_sec: 1,
+                burst: 1,
+            },
+            ..InboundQuotas::default()
+        };
+        let limits = InboundLimits::new(NonZeroU32::new(1).unwrap(), &quotas);
+        assert_eq!(
+            exhaust(&limits, &ClientMessage::Buzz, quotas.buzz),
+            RateCategory::Buzz
+        );
+    }
+
+    #[test]
+    fn repeated_violations_turn_abusive() {
+        let limits = default_limits();
+        for _ in 0..MAX_VIOLATIONS {
+            assert!(!limits.abusive());
+        }
+        assert!(limits.abusive());
+    }
+
+    #[test]
+    fn client_messages_decode_in_both_formats() {
+        for format in [WireFormat::Json, WireFormat::Msgpack] {
+            let msg = round_trip(format, serde_json::json!({ "type": "buzz" }));
+            assert!(matches!(msg, Ok(ClientMessage::Buzz)));
+            let msg = round_trip(
+                format,
+                serde_json::json!({ "type": "chat", "text": "hi", "system": true }),
+            );
+            assert!(matches!(msg, Ok(ClientMessage::Chat { text, system: true }) if text == "hi"));
+        }
+    }
+
+    #[test]
+    fn frames_in_the_other_format_are_refused() {
+        let msgpack = rmp_serde::to_vec_named(&serde_json::json!({ "type": "buzz" })).unwrap();
+        assert_eq!(
+            WireFormat::Json
+                .decode(&Message::Binary(msgpack.into()))
+                .err(),
+            Some("expected a text frame".to_string())
+        );
+        let json = Message::Text(r#"{"type":"buzz"}"#.into());
+        assert_eq!(
+            WireFormat::Msgpack.decode(&json).err(),
+            Some("expected a binary frame".to_string())
+        );
+    }
+
+    #[test]
+    fn unreadable_messages_say_what_is_wrong() {
+        let detail = |text: &str| {
+            WireFormat::Json
+                .decode(&Message::Text(text.into()))
+                .err()
+                .unwrap()
+        };
+        assert!(detail(r#"{"type":"buzz""#).starts_with("malformed JSON: "));
+        assert_eq!(detail("[1]"), "expected an object with a `type`");
+        assert!(
+            detail(r#"{"type":"buzzz"}"#).starts_with("unknown variant `buzzz`, expected one of")
+        );
+        assert_eq!(detail(r#"{"type":"kick"}"#), "missing field `name`");
+        assert_eq!(detail(r#"{"name":"Bob"}"#), "missing field `type`");
+        assert_eq!(
+            detail(r#"{"v":2,"type":"buzz"}"#),
+            "unsupported protocol version 2"
+        );
+        assert_eq!(
+            detail(r#"{"v":"1","type":"buzz"}"#),
+            r#"unsupported protocol version "1""#
+        );
+    }
+
+    #[test]
+    fn ids_are_read_off_the_envelope() {
+        for format in [WireFormat::Json, WireFormat::Msgpack] {
+            let decode = |client: serde_json::Value| {
+                let frame = match format {
+                    WireFormat::Json => Message::Text(client.to_string().into()),
+                    WireFormat::Msgpack => {
+                        Message::Binary(rmp_serde::to_vec_named(&client).unwrap().into())
+                    }
+                };
+                format.decode(&frame).map(|request| request.ack_id)
+            };
+            let id = decode(serde_json::json!({ "type": "kick", "name": "Bob", "id": "k1" }));
+            assert_eq!(id, Ok(Some("k1".to_string())));
+            assert_eq!(decode(serde_json::json!({ "type": "buzz" })), Ok(None));
+            assert_eq!(
+                decode(serde_json::json!({ "type": "buzz", "id": null })),
+                Ok(None)
+            );
+
+            let longest = "x".repeat(MAX_ACK_ID_LEN);
+            let id = decode(serde_json::json!({ "type": "buzz", "id": longest }));
+            assert_eq!(id, Ok(Some(longest)));
+            let refused = Err(format!(
+                "`id` must be a string of at most {MAX_ACK_ID_LEN} bytes"
+            ));
+            let too_long = "x".repeat(MAX_ACK_ID_LEN + 1);
+            assert_eq!(
+                decode(serde_json::json!({ "type": "buzz", "id": too_long })),
+                refused
+            );
+            assert_eq!(
+                decode(serde_json::json!({ "type": "buzz", "id": 7 })),
+                refused
+            );
+        }
+    }
+
+    #[test]
+    fn versioned_messages_with_extra_fields_are_accepted() {
+        for format in [WireFormat::Json, WireFormat::Msgpack] {
+            let msg = round_trip(format, serde_json::json!({ "v": 1, "type": "buzz" }));
+            assert!(matches!(msg, Ok(ClientMessage::Buzz)));
+            let msg = round_trip(
+                format,
+                serde_json::json!({ "type": "kick", "name": "Bob", "reason": "spam" }),
+            );
+            assert!(matches!(msg, Ok(ClientMessage::Kick { name }) if name == "Bob"));
+        }
+    }
+
+    /// No wildcard, so a new client message has to be added to the round trip below.
+    fn client_message_type(msg: &ClientMessage) -> &'static str {
+        match msg {
+            ClientMessage::Buzz => "buzz",
+            ClientMessage::StartRound { .. } => "start_round",
+            ClientMessage::ContinueRound => "continue_round",
+            ClientMessage::MarkCorrect => "mark_correct",
+            ClientMessage::Pause => "pause",
+            ClientMessage::Resume => "resume",
+            ClientMessage::Kick { .. } => "kick",
+            ClientMessage::Mute { .. } => "mute",
+            ClientMessage::Unmute { .. } => "unmute",
+            ClientMessage::Unban { .. } => "unban",
+            ClientMessage::Rename { .. } => "rename",
+            ClientMessage::Grant { .. } => "grant",
+            ClientMessage::Undo => "undo",
+            ClientMessage::NewGame => "new_game",
+            ClientMessage::Leave => "leave",
+            ClientMessage::SetReady { .. } => "set_ready",
+            ClientMessage::RequestReady => "request_ready",
+            ClientMessage::CloseRoom => "close_room",
+            ClientMessage::LockRoom => "lock_room",
+            ClientMessage::UnlockRoom => "unlock_room",
+            ClientMessage::SetAnswerWindow { .. } => "set_answer_window",
+            ClientMessage::React { .. } => "react",
+            ClientMessage::Chat { .. } => "chat",
+            ClientMessage::Resync { .. } => "resync",
+            ClientMessage::TimeSync { .. } => "time_sync",
+            ClientMessage::UpdateToken { .. } => "update_token",
+            ClientMessage::GetRoundState => "get_round_state",
+        }
+    }
+
+    #[test]
+    fn every_client_message_decodes_from_msgpack() {
+        let messages = [
+            serde_json::json!({ "type": "buzz" }),
+            serde_json::json!({ "type": "start_round", "countdown_ms": 3000, "question": "Why?" }),
+            serde_json::json!({ "type": "continue_round" }),
+            serde_json::json!({ "type": "mark_correct" }),
+            serde_json::json!({ "type": "pause" }),
+            serde_json::json!({ "type": "resume" }),
+            serde_json::json!({ "type": "kick", "name": "Bob" }),
+            serde_json::json!({ "type": "mute", "name": "Bob" }),
+            serde_json::json!({ "type": "unmute", "name": "Bob" }),
+            serde_json::json!({ "type": "unban", "name": "Bob" }),
+            serde_json::json!({ "type": "rename", "new_name": "Rob" }),
+            serde_json::json!({ "type": "grant", "name": "Bob", "force": true }),
+            serde_json::json!({ "type": "undo" }),
+            serde_json::json!({ "type": "new_game" }),
+            serde_json::json!({ "type": "leave" }),
+            serde_json::json!({ "type": "set_ready", "ready": true }),
+            serde_json::json!({ "type": "request_ready" }),
+            serde_json::json!({ "type": "close_room" }),
+            serde_json::json!({ "type": "lock_room" }),
+            serde_json::json!({ "type": "unlock_room" }),
+            serde_json::json!({ "type": "set_answer_window", "answer_window_in_ms": 5000 }),
+            serde_json::json!({ "type": "react", "emoji": "👏" }),
+            serde_json::json!({ "type": "chat", "text": "hi" }),
+            serde_json::json!({ "type": "resync", "last_seq": 7 }),
+            serde_json::json!({ "type": "time_sync", "client_ts_ms": 1_700_000_000_000u64 }),
+            serde_json::json!({ "type": "update_token", "token": "abc" }),
+            serde_json::json!({ "type": "get_round_state" }),
+        ];
+        for message in messages {
+            let kind = message["type"].as_str().unwrap().to_string();
+            let decoded = round_trip(WireFormat::Msgpack, message).unwrap();
+            assert_eq!(client_message_type(&decoded), kind);
+        }
+    }
+
+    #[test]
+    fn every_server_message_survives_msgpack() {
+        let entries = || {
+            vec![ScoreEntry {
+                name: "Bob".into(),
+                score: 2,
+            }]
+        };
+        let bob = || ParticipantInfo {
+            name: "Bob".into(),
+            role: Role::Player,
+            locked_out: true,
+            ready: false,
+            muted: false,
+            connected: true,
+            color: 3,
+        };
+        let messages = [
+            ServerMessage::Accepted {
+                name: "Bob".into(),
+                round: 1,
+                deadline_ms: 1_700_000_005_000,
+                remaining_ms: 5000,
+            },
+            ServerMessage::Participants {
+                participants: vec![bob()],
+            },
+            ServerMessage::Countdown {
+                starts_in_ms: 3000,
+                starts_at_ms: 1_700_000_003_000,
+                ts_ms: 1_700_000_000_000,
+            },
+            ServerMessage::TimeSync {
+                client_ts_ms: 1_699_999_999_000,
+                server_ts_ms: 1_700_000_000_000,
+            },
+            ServerMessage::RoundStarted { round: 1 },
+            ServerMessage::Question {
+                text: "Capital of Peru?".into(),
+                round: 1,
+                ts_ms: 1_700_000_000_000,
+            },
+            ServerMessage::Welcome {
+                you: bob(),
+                participants: vec![bob()],
+                game: GameStateInfo {
+                    phase: GamePhase::Answering,
+                    round: 1,
+                    paused: false,
+                    locked: false,
+                    answering: Some("Bob".into()),
+                    deadline_ms: Some(1_700_000_005_000),
+                    remaining_ms: Some(5000),
+                    locked_out: vec![],
+                    scores: entries(),
+                },
+                settings: RoomSettingsInfo {
+                    answer_window_in_ms: 5000,
+                    max_players: 8,
+                    requires_password: false,
+                },
+            },
+            ServerMessage::RoomLock {
+                locked: true,
+                ts_ms: 1,
+            },
+            ServerMessage::RoundState {
+                round: 1,
+                answering: Some("Bob".into()),
+                deadline_ms: Some(2),
+                remaining_ms: Some(1),
+                attempted: vec!["Bob".into()],
+                locked_out: Vec::new(),
+            },
+            ServerMessage::RoundContinued,
+            ServerMessage::Paused,
+            ServerMessage::Resumed,
+            ServerMessage::Rejected {
+                id: Some("b1".into()),
+            },
+            ServerMessage::BuzzDetail {
+                name: "Bob".into(),
+                accepted: true,
+                reaction_ms: Some(250),
+                ts_ms: 1_700_000_000_000,
+            },
+            ServerMessage::TimedOut { name: "Bob".into() },
+            ServerMessage::Correct { name: "Bob".into() },
+            ServerMessage::RoundResolved {
+                round: 2,
+                outcome: RoundOutcome::Correct,
+                winner: Some("Bob".into()),
+                ts_ms: 1,
+            },
+            ServerMessage::Undone {
+                name: "Bob".into(),
+                round: 2,
+            },
+            ServerMessage::Scoreboard {
+                entries: entries(),
+                ts_ms: 1_700_000_000_000,
+                round: 1,
+            },
+            ServerMessage::GameReset,
+            ServerMessage::ReadyCheck,
+            ServerMessage::SettingsChanged {
+                answer_window_in_ms: 5000,
+                max_players: 8,
+                requires_password: true,
+            },
+            ServerMessage::Chat {
+                from: "Bob".into(),
+                text: "hi".into(),
+                ts_ms: 1_700_000_000_000,
+                system: false,
+            },
+            ServerMessage::Reaction {
+                from: "Bob".into(),
+                emoji: "👏".into(),
+                ts_ms: 1_700_000_000_000,
+            },
+            ServerMessage::ActionDenied {
+                reason: "forbidden".into(),
+                category: None,
+                id: None,
+            },
+            ServerMessage::ActionDenied {
+                reason: "rate_limited".into(),
+                category: Some(RateCategory::Buzz),
+                id: Some("b1".into()),
+            },
+            ServerMessage::ActionOk { id: "k1".into() },
+            ServerMessage::ProtocolError {
+                detail: "missing field `name`".into(),
+                supported_versions: SUPPORTED_PROTOCOL_VERSIONS.to_vec(),
+            },
+            ServerMessage::Kicked,
+            ServerMessage::RoomClosed {
+                reason: "closed_by_admin".into(),
+            },
+            ServerMessage::Replaced,
+            ServerMessage::SessionExpired,
+            ServerMessage::AdminChanged {
+                name: "Bob".into(),
+                reason: "admin_disconnected".into(),
+            },
+            ServerMessage::Renamed {
+                old_name: "Bob".into(),
+                new_name: "Rob".into(),
+                token: "token".into(),
+            },
+        ];
+        for message in messages {
+            let json = serde_json::to_string(&message).unwrap();
+            let expected: serde_json::Value = serde_json::from_str(&json).unwrap();
+            let Some(Message::Binary(bytes)) = WireFormat::Msgpack.encode(json.into()) else {
+                panic!("msgpack should be a binary frame");
+            };
+            let decoded: serde_json::Value = rmp_serde::from_slice(&bytes).unwrap();
+            assert_eq!(decoded, expected);
+        }
+    }
+
+    #[test]
+    fn subprotocols_name_each_format() {
+        for format in WireFormat::PREFERENCE {
+            assert_eq!(
+                WireFormat::from_subprotocol(format.subprotocol()),
+                Some(format)
+            );
+        }
+        assert_eq!(WireFormat::from_subprotocol("msgpack"), None);
+    }
+
+    #[test]
+    fn server_messages_encode_in_both_formats() {
+        let json = r#"{"v":1,"seq":3,"type":"accepted","name":"Bob","round":2}"#;
+        let expected: serde_json::Value = serde_json::from_str(json).unwrap();
+
+        let Some(Message::Text(text)) = WireFormat::Json.encode(json.into()) else {
+            panic!("json should be a text frame");
+        };
+        assert_eq!(text.as_str(), json);
+
+        let Some(Message::Binary(bytes)) = WireFormat::Msgpack.encode(json.into()) else {
+            panic!("msgpack should be a binary frame");
+        };
+        let decoded: serde_json::Value = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(decoded, expected);
+    }
 }
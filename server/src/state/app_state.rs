@@ -1,21 +1,41 @@
 use core::game::PlayerId;
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Instant;
 
-use dashmap::DashMap;
-use rand::RngCore;
-use rand::distr::{Alphanumeric, SampleString};
+use dashmap::{DashMap, Entry};
+use rand::{Rng, RngCore};
 use tracing::warn;
 
 use crate::auth::JwtAuth;
+use crate::config::ServerConfig;
+use crate::dtos::RoomSnapshot;
 use crate::errors::AppError;
+use crate::utils::name::NameFilter;
+use crate::webhook::{Webhook, WebhookEvent};
 
 use super::room_state::{RoomConfig, RoomId, RoomState};
 
-pub const TOKEN_TTL_IN_SECS: u64 = 2 * 60 * 60;
-pub const APP_CLEANUP_INTERVAL_IN_SECS: u64 = 30 * 60;
-
 pub const ADMIN_PLAYER_ID: PlayerId = 0;
 
+/// Generated codes avoid characters that are easy to confuse when read aloud
+/// (0/O, 1/I/L).
+const ROOM_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+const GENERATED_ROOM_CODE_LEN: usize = 6;
+const MIN_ROOM_CODE_LEN: usize = 4;
+const MAX_ROOM_CODE_LEN: usize = 16;
+/// Codes drawn for one room before giving up: failing this often means the
+/// code space is close to full.
+const GENERATED_ROOM_CODE_ATTEMPTS: usize = 32;
+/// Codes that would shadow a fixed route under `/api/rooms/`.
+const RESERVED_ROOM_CODES: &[&str] = &["BATCH"];
+
+/// Room codes are case-insensitive; they are stored and compared uppercased.
+pub fn normalize_room_code(code: &str) -> String {
+    code.trim().to_ascii_uppercase()
+}
+
 #[derive(Clone)]
 pub struct AppState {
     inner: Arc<AppStateInner>,
@@ -23,19 +43,53 @@ pub struct AppState {
 
 struct AppStateInner {
     rooms: DashMap<RoomId, Arc<RoomState>>,
+    /// Rooms that exist or are being created. Reserved before the insert so
+    /// concurrent creates cannot overshoot `max_rooms`.
+    room_count: AtomicUsize,
     auth: Arc<JwtAuth>,
+    name_filter: Arc<NameFilter>,
+    config: ServerConfig,
+    started_at: Instant,
+    /// Set once the listener is bound; until then `/readyz` reports 503.
+    ready: AtomicBool,
+    webhook: Option<Webhook>,
+    /// Draws a candidate code for a room created without one.
+    room_codes: Box<dyn Fn() -> RoomId + Send + Sync>,
+    /// Closed websockets by close reason, see [`CloseReason`](crate::socket::CloseReason).
+    socket_closes: DashMap<&'static str, u64>,
 }
 
 impl AppState {
-    pub fn new() -> Self {
+    pub fn new(config: &ServerConfig) -> Self {
+        Self::new_with(config, random_room_code)
+    }
+
+    /// Like [`new`](Self::new), but generated room codes are drawn from
+    /// `room_codes` instead of at random, e.g. to force a collision in a test.
+    pub fn new_with(
+        config: &ServerConfig,
+        room_codes: impl Fn() -> RoomId + Send + Sync + 'static,
+    ) -> Self {
         let secret = Self::load_jwt_secret();
-        let auth = Arc::new(JwtAuth::new(&secret, TOKEN_TTL_IN_SECS));
+        let auth = Arc::new(
+            JwtAuth::new(&secret, config.token_ttl_secs, &config.jwt_issuer)
+                .with_idle_timeout(config.token_idle_timeout_secs),
+        );
         let inner = Arc::new(AppStateInner {
             rooms: DashMap::new(),
+            room_count: AtomicUsize::new(0),
             auth,
+            name_filter: Arc::new(NameFilter::from_env()),
+            config: config.clone(),
+            started_at: Instant::now(),
+            ready: AtomicBool::new(false),
+            webhook: config.webhook.clone().map(Webhook::spawn),
+            room_codes: Box::new(room_codes),
+            socket_closes: DashMap::new(),
         });
-        Self::spawn_room_cleanup(Arc::clone(&inner));
-        Self { inner }
+        let state = Self { inner };
+        Self::spawn_room_cleanup(state.clone());
+        state
     }
 
     /// Loads the JWT signing secret from the `JWT_SECRET` env var. Falls back to a
@@ -68,17 +122,139 @@ impl AppState {
         secret.to_vec()
     }
 
-    pub fn create_room(&self, config: RoomConfig, tick_in_ms: u64) -> (RoomId, Arc<RoomState>) {
-        let room_id = self.create_random_room_id();
-        let room = RoomState::new(room_id.clone(), config, tick_in_ms, self.auth());
-        self.inner.rooms.insert(room_id.clone(), Arc::clone(&room));
-        (room_id, room)
+    /// Creates a room under `room_code` (4-16 of `A-Z`, `0-9`, `-`, any case), or
+    /// under a generated code when none is given.
+    pub fn create_room(
+        &self,
+        config: RoomConfig,
+        room_code: Option<&str>,
+    ) -> Result<(RoomId, Arc<RoomState>), AppError> {
+        let room_code = room_code.map(normalize_room_code);
+        if let Some(code) = &room_code {
+            let valid_len = (MIN_ROOM_CODE_LEN..=MAX_ROOM_CODE_LEN).contains(&code.len());
+            let valid_chars = code
+                .bytes()
+                .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'-');
+            if !valid_len || !valid_chars || RESERVED_ROOM_CODES.contains(&code.as_str()) {
+                return Err(AppError::InvalidRoomCode);
+            }
+        }
+        self.reserve_room_slot()?;
+        let created = match room_code {
+            Some(code) => self
+                .try_insert_room(code, config)
+                .ok_or(AppError::RoomCodeTaken),
+            None => self.insert_generated_room(config),
+        };
+        if created.is_err() {
+            self.release_room_slot();
+        }
+        created
+    }
+
+    /// Creates `count` rooms with the same settings, coded `PREFIX-1`, `PREFIX-2`,
+    /// ... or generated when there is no prefix. All or nothing: if one fails,
+    /// the rooms already made are closed again.
+    pub fn create_rooms(
+        &self,
+        config: RoomConfig,
+        prefix: Option<&str>,
+        count: usize,
+    ) -> Result<Vec<(RoomId, Arc<RoomState>)>, AppError> {
+        let mut created = Vec::with_capacity(count);
+        for seq in 1..=count {
+            let code = prefix.map(|prefix| format!("{}-{seq}", prefix.trim()));
+            match self.create_room(config, code.as_deref()) {
+                Ok(room) => created.push(room),
+                Err(err) => {
+                    self.close_rooms(&created, "batch_failed");
+                    return Err(err);
+                }
+            }
+        }
+        Ok(created)
+    }
+
+    /// Rebuilds a room from [`RoomState::snapshot`] under its old code, without
+    /// live sockets; players reconnect with the tokens they hold.
+    pub fn restore(&self, snapshot: &RoomSnapshot) -> Result<Arc<RoomState>, AppError> {
+        let config = RoomConfig {
+            answer_window_in_ms: snapshot.answer_window_in_ms,
+            history_limit: self.round_history_limit(),
+            inbound_rate_per_sec: snapshot.inbound_rate_per_sec,
+            idle_timeout_in_ms: self.room_idle_timeout_in_ms(),
+            admin_grace_in_ms: self.admin_grace_in_ms(),
+            buzz_debounce_in_ms: self.buzz_debounce_in_ms(),
+        };
+        let (room_id, room) = self.create_room(config, Some(&snapshot.room_id))?;
+        if let Err(err) = room.restore(snapshot) {
+            let _ = self.close_room(&room_id, "restore_failed");
+            return Err(err);
+        }
+        Ok(room)
+    }
+
+    /// Undoes a [`create_rooms`](Self::create_rooms) whose rooms could not be set up.
+    pub fn close_rooms(&self, rooms: &[(RoomId, Arc<RoomState>)], reason: &str) {
+        for (room_id, _) in rooms {
+            let _ = self.close_room(room_id, reason);
+        }
+    }
+
+    fn reserve_room_slot(&self) -> Result<(), AppError> {
+        let max_rooms = self.inner.config.max_rooms;
+        self.inner
+            .room_count
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count < max_rooms).then_some(count + 1)
+            })
+            .map(|_| ())
+            .map_err(|_| AppError::ServerFull)
+    }
+
+    fn release_room_slot(&self) {
+        self.inner.room_count.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// Draws codes until one is free, up to [`GENERATED_ROOM_CODE_ATTEMPTS`].
+    fn insert_generated_room(
+        &self,
+        config: RoomConfig,
+    ) -> Result<(RoomId, Arc<RoomState>), AppError> {
+        (0..GENERATED_ROOM_CODE_ATTEMPTS)
+            .find_map(|_| self.try_insert_room((self.inner.room_codes)(), config))
+            .ok_or(AppError::ServerFull)
+    }
+
+    fn try_insert_room(
+        &self,
+        room_id: RoomId,
+        config: RoomConfig,
+    ) -> Option<(RoomId, Arc<RoomState>)> {
+        let Entry::Vacant(slot) = self.inner.rooms.entry(room_id.clone()) else {
+            return None;
+        };
+        let webhook = self.inner.webhook.as_ref();
+        let room = RoomState::new(
+            room_id.clone(),
+            config,
+            self.auth(),
+            Arc::clone(&self.inner.name_filter),
+            webhook.map(|webhook| webhook.for_room(&room_id)),
+        );
+        slot.insert(Arc::clone(&room));
+        if let Some(webhook) = webhook {
+            webhook.send(WebhookEvent::RoomCreated {
+                room_id: room_id.clone(),
+            });
+        }
+        Some((room_id, room))
     }
 
     pub fn get_room(&self, room_id: &str) -> Result<Arc<RoomState>, AppError> {
         self.inner
             .rooms
-            .get(room_id)
+            .get(&normalize_room_code(room_id))
             .map(|entry| Arc::clone(entry.value()))
             .ok_or(AppError::RoomNotFound)
     }
@@ -87,30 +263,435 @@ impl AppState {
         Arc::clone(&self.inner.auth)
     }
 
-    fn create_random_room_id(&self) -> RoomId {
-        let mut rng = rand::rng();
-        Alphanumeric.sample_string(&mut rng, 6)
+    pub fn name_filter(&self) -> &NameFilter {
+        &self.inner.name_filter
+    }
+
+    pub fn config(&self) -> &ServerConfig {
+        &self.inner.config
+    }
+
+    pub fn round_history_limit(&self) -> usize {
+        self.inner.config.round_history_limit
     }
 
-    fn spawn_room_cleanup(inner: Arc<AppStateInner>) {
+    pub fn room_idle_timeout_in_ms(&self) -> u64 {
+        self.inner.config.room_idle_timeout_secs * 1000
+    }
+
+    pub fn admin_grace_in_ms(&self) -> u64 {
+        self.inner.config.admin_grace_secs * 1000
+    }
+
+    pub fn buzz_debounce_in_ms(&self) -> u64 {
+        self.inner.config.buzz_debounce_ms
+    }
+
+    pub fn uptime_secs(&self) -> u64 {
+        self.inner.started_at.elapsed().as_secs()
+    }
+
+    pub fn room_count(&self) -> usize {
+        self.inner.room_count.load(Ordering::Acquire)
+    }
+
+    /// Open websockets across all rooms.
+    pub fn connection_count(&self) -> usize {
+        self.inner
+            .rooms
+            .iter()
+            .map(|entry| entry.value().connection_count())
+            .sum()
+    }
+
+    pub fn record_socket_close(&self, reason: &'static str) {
+        *self.inner.socket_closes.entry(reason).or_insert(0) += 1;
+    }
+
+    pub fn socket_closes(&self) -> BTreeMap<&'static str, u64> {
+        self.inner
+            .socket_closes
+            .iter()
+            .map(|entry| (*entry.key(), *entry.value()))
+            .collect()
+    }
+
+    pub fn mark_ready(&self) {
+        self.inner.ready.store(true, Ordering::Release);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.inner.ready.load(Ordering::Acquire)
+    }
+
+    fn spawn_room_cleanup(state: AppState) {
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
-                APP_CLEANUP_INTERVAL_IN_SECS,
-            ));
+            // The first sweep waits a full period: a room created just now has not
+            // had its admin registered yet.
+            let period = tokio::time::Duration::from_secs(state.inner.config.room_ttl_secs);
+            let mut interval =
+                tokio::time::interval_at(tokio::time::Instant::now() + period, period);
             loop {
                 interval.tick().await;
-                let mut to_remove = Vec::new();
-                for entry in inner.rooms.iter() {
-                    if !entry.value().admin_present() {
-                        to_remove.push(entry.key().clone());
-                    }
-                }
-                for room_id in to_remove {
-                    if let Some((_, room)) = inner.rooms.remove(&room_id) {
-                        room.shutdown();
+                state.remove_abandoned_rooms();
+            }
+        });
+    }
+
+    /// Closes and forgets every room whose admin is gone or that has sat idle
+    /// past its [`idle_timeout_in_ms`](RoomConfig::idle_timeout_in_ms).
+    pub fn remove_abandoned_rooms(&self) {
+        let mut to_remove = Vec::new();
+        for entry in self.inner.rooms.iter() {
+            let room = entry.value();
+            if !room.admin_present() {
+                to_remove.push((entry.key().clone(), "admin_expired"));
+            } else if room.is_idle() {
+                to_remove.push((entry.key().clone(), "idle"));
+            }
+        }
+        for (room_id, reason) in to_remove {
+            let _ = self.close_room(&room_id, reason);
+        }
+    }
+
+    /// Forgets the room and shuts its loop down, telling connected clients why.
+    pub fn close_room(&self, room_id: &str, reason: &str) -> Result<(), AppError> {
+        let (_, room) = self
+            .inner
+            .rooms
+            .remove(&normalize_room_code(room_id))
+            .ok_or(AppError::RoomNotFound)?;
+        self.release_room_slot();
+        room.shutdown(reason);
+        if let Some(webhook) = &self.inner.webhook {
+            webhook.send(WebhookEvent::RoomClosed {
+                room_id: room.room_id().to_string(),
+                reason: reason.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn random_room_code() -> RoomId {
+    let mut rng = rand::rng();
+    (0..GENERATED_ROOM_CODE_LEN)
+        .map(|_| char::from(ROOM_CODE_ALPHABET[rng.random_range(0..ROOM_CODE_ALPHABET.len())]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dtos::Role;
+    use crate::state::room_state::{
+        DEFAULT_ADMIN_GRACE_IN_MS, DEFAULT_BUZZ_DEBOUNCE_IN_MS, DEFAULT_IDLE_TIMEOUT_IN_MS,
+        DEFAULT_INBOUND_RATE_PER_SEC,
+    };
+    use crate::utils::testing::{block_on, next_of_type, outbound_channel};
+
+    const CONFIG: RoomConfig = RoomConfig {
+        answer_window_in_ms: 1000,
+        history_limit: 10,
+        inbound_rate_per_sec: DEFAULT_INBOUND_RATE_PER_SEC,
+        idle_timeout_in_ms: DEFAULT_IDLE_TIMEOUT_IN_MS,
+        admin_grace_in_ms: DEFAULT_ADMIN_GRACE_IN_MS,
+        buzz_debounce_in_ms: DEFAULT_BUZZ_DEBOUNCE_IN_MS,
+    };
+
+    #[test]
+    fn generated_code_retries_on_collision() {
+        block_on(async {
+            let codes = std::sync::Mutex::new(["ABCDEF", "ABCDEF", "GHJKMN"].into_iter());
+            let codes = Arc::new(codes);
+            let drawn = Arc::clone(&codes);
+            let state = AppState::new_with(&ServerConfig::default(), move || {
+                drawn.lock().unwrap().next().unwrap().to_string()
+            });
+            let (first_id, first) = state.create_room(CONFIG, None).unwrap();
+            assert_eq!(first_id, "ABCDEF");
+
+            let (room_id, _) = state.create_room(CONFIG, None).unwrap();
+            assert_eq!(room_id, "GHJKMN");
+            assert_eq!(codes.lock().unwrap().next(), None);
+            // The room already under the drawn code is left as it was.
+            assert!(Arc::ptr_eq(&state.get_room("ABCDEF").unwrap(), &first));
+        });
+    }
+
+    #[test]
+    fn generated_codes_give_up_when_every_draw_is_taken() {
+        block_on(async {
+            let state = AppState::new_with(&ServerConfig::default(), || "ABCDEF".to_string());
+            let (_, first) = state.create_room(CONFIG, None).unwrap();
+
+            assert!(matches!(
+                state.create_room(CONFIG, None),
+                Err(AppError::ServerFull)
+            ));
+            assert_eq!(state.room_count(), 1);
+            assert!(Arc::ptr_eq(&state.get_room("ABCDEF").unwrap(), &first));
+        });
+    }
+
+    #[test]
+    fn generated_codes_use_unambiguous_alphabet() {
+        for _ in 0..100 {
+            let code = random_room_code();
+            assert_eq!(code.len(), GENERATED_ROOM_CODE_LEN);
+            assert!(!code.contains(['0', 'O', '1', 'I', 'L']), "{code}");
+        }
+    }
+
+    #[test]
+    fn custom_code_is_case_insensitive_and_unique() {
+        block_on(async {
+            let state = AppState::new(&ServerConfig::default());
+            let (room_id, _) = state.create_room(CONFIG, Some("abc-12")).unwrap();
+            assert_eq!(room_id, "ABC-12");
+            assert!(state.get_room("abc-12").is_ok());
+            assert!(state.get_room("ABC-12").is_ok());
+            assert!(matches!(
+                state.create_room(CONFIG, Some("Abc-12")),
+                Err(AppError::RoomCodeTaken)
+            ));
+        });
+    }
+
+    #[test]
+    fn invalid_custom_codes_are_rejected() {
+        block_on(async {
+            let state = AppState::new(&ServerConfig::default());
+            for code in [
+                "abc",
+                "a".repeat(17).as_str(),
+                "quiz night",
+                "quiz_1",
+                "ÄBCD",
+            ] {
+                assert!(
+                    matches!(
+                        state.create_room(CONFIG, Some(code)),
+                        Err(AppError::InvalidRoomCode)
+                    ),
+                    "{code:?}"
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn tokens_use_the_configured_ttl() {
+        block_on(async {
+            let state = AppState::new(&ServerConfig {
+                token_ttl_secs: 90,
+                ..ServerConfig::default()
+            });
+            let (room_id, room) = state.create_room(CONFIG, None).unwrap();
+            let token = room.create_admin("Aaron").await.unwrap();
+            let claims = state.auth().verify(&token, &room_id).unwrap();
+            assert_eq!(claims.exp - claims.iat, 90);
+        });
+    }
+
+    #[test]
+    fn concurrent_creates_never_exceed_max_rooms() {
+        block_on(async {
+            let state = AppState::new(&ServerConfig {
+                max_rooms: 10,
+                ..ServerConfig::default()
+            });
+            let runtime = tokio::runtime::Handle::current();
+            let creators: Vec<_> = (0..32)
+                .map(|_| {
+                    let state = state.clone();
+                    let runtime = runtime.clone();
+                    std::thread::spawn(move || {
+                        let _guard = runtime.enter();
+                        (0..4)
+                            .map(|_| state.create_room(CONFIG, None))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            let mut created = 0;
+            for creator in creators {
+                for result in creator.join().unwrap() {
+                    match result {
+                        Ok(_) => created += 1,
+                        Err(err) => assert!(matches!(err, AppError::ServerFull), "{err:?}"),
                     }
                 }
             }
+            assert_eq!(created, 10);
+            assert_eq!(state.room_count(), 10);
+            assert_eq!(state.inner.rooms.len(), 10);
+
+            // Closing a room frees its slot, and a taken code does not leak one.
+            let room_id = state.inner.rooms.iter().next().unwrap().key().clone();
+            state.close_room(&room_id, "test").unwrap();
+            state.create_room(CONFIG, Some("QUIZ")).unwrap();
+            state.close_room("QUIZ", "test").unwrap();
+            let taken = state.inner.rooms.iter().next().unwrap().key().clone();
+            assert!(matches!(
+                state.create_room(CONFIG, Some(&taken)),
+                Err(AppError::RoomCodeTaken)
+            ));
+            state.create_room(CONFIG, None).unwrap();
+            assert!(matches!(
+                state.create_room(CONFIG, None),
+                Err(AppError::ServerFull)
+            ));
+            assert_eq!(state.room_count(), 10);
+        });
+    }
+
+    #[test]
+    fn idle_rooms_are_closed_and_active_ones_kept() {
+        block_on(async {
+            let state = AppState::new(&ServerConfig::default());
+            let config = RoomConfig {
+                idle_timeout_in_ms: 200,
+                ..CONFIG
+            };
+            let (_, idle) = state.create_room(config, Some("IDLE")).unwrap();
+            let (_, busy) = state.create_room(config, Some("BUSY")).unwrap();
+            idle.create_admin("Aaron").await.unwrap();
+            busy.create_admin("Aaron").await.unwrap();
+            let (tx, mut rx) = outbound_channel();
+            idle.attach_connection(0, "Aaron", tx, None, true)
+                .await
+                .unwrap();
+
+            tokio::time::sleep(tokio::time::Duration::from_millis(150)).await;
+            busy.start_round(0, None, None, None).await.unwrap();
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+            state.remove_abandoned_rooms();
+
+            assert!(matches!(
+                state.get_room("IDLE"),
+                Err(AppError::RoomNotFound)
+            ));
+            assert_eq!(next_of_type(&mut rx, "room_closed").await["reason"], "idle");
+            assert!(state.get_room("BUSY").is_ok());
+            assert_eq!(state.room_count(), 1);
+        });
+    }
+
+    #[test]
+    fn snapshot_restores_participants_and_admin() {
+        block_on(async {
+            let state = AppState::new(&ServerConfig::default());
+            let (_, room) = state.create_room(CONFIG, Some("SNAP")).unwrap();
+            let aaron = room.create_admin("Aaron").await.unwrap();
+            let (bob, _) = room.join("Bob", None, Role::Player).await.unwrap();
+            room.join("Cara", None, Role::Player).await.unwrap();
+            room.join("Projector", None, Role::Spectator).await.unwrap();
+            let bob_id = state.auth().verify(&bob, "SNAP").unwrap().player_id;
+            room.set_password_hash(Some("$argon2id$v=19$stub".to_string()));
+
+            room.start_round(0, None, None, None).await.unwrap();
+            room.query_game_view().await.unwrap();
+            room.send_buzz(bob_id, None);
+            while room.query_game_view().await.unwrap().answering != Some(bob_id) {
+                tokio::task::yield_now().await;
+            }
+            room.mark_correct(0, None);
+            room.set_muted(0, "Cara", true, None);
+            room.transfer_admin(0, "Bob").await.unwrap();
+            room.query_game_view().await.unwrap();
+
+            let snapshot = room.snapshot();
+            assert_eq!(snapshot.round, 1);
+            assert_eq!(snapshot.admin_id, bob_id);
+            let json = serde_json::to_string(&snapshot).unwrap();
+            state.close_room("SNAP", "restart").unwrap();
+
+            let restored = state
+                .restore(&serde_json::from_str(&json).unwrap())
+                .unwrap();
+            assert_eq!(restored.query_game_view().await.unwrap().round, 1);
+            assert_eq!(restored.snapshot(), snapshot);
+            assert_eq!(restored.scoreboard()[0].name, "Bob");
+            assert_eq!(restored.scoreboard()[0].score, 1);
+            assert!(restored.requires_password());
+            // Old tokens still work, and the handover survived.
+            assert_eq!(restored.authorize_admin(&bob).unwrap(), bob_id);
+            assert!(matches!(
+                restored.authorize_admin(&aaron),
+                Err(AppError::Forbidden)
+            ));
+            let (dave, _) = restored.join("Dave", None, Role::Player).await.unwrap();
+            let dave = state.auth().verify(&dave, "SNAP").unwrap();
+            assert_eq!(dave.player_id, snapshot.next_id);
+        });
+    }
+
+    #[test]
+    fn inconsistent_snapshot_is_rejected() {
+        block_on(async {
+            let state = AppState::new(&ServerConfig::default());
+            let (_, room) = state.create_room(CONFIG, Some("SNAP")).unwrap();
+            room.create_admin("Aaron").await.unwrap();
+            room.join("Bob", None, Role::Player).await.unwrap();
+            let mut snapshot = room.snapshot();
+            state.close_room("SNAP", "restart").unwrap();
+
+            snapshot.players[1].name = " aaron".to_string();
+            assert!(matches!(
+                state.restore(&snapshot),
+                Err(AppError::InvalidRequest)
+            ));
+            assert_eq!(state.room_count(), 0);
+            assert!(matches!(
+                state.get_room("SNAP"),
+                Err(AppError::RoomNotFound)
+            ));
+        });
+    }
+
+    #[test]
+    fn batch_creates_sequential_codes() {
+        block_on(async {
+            let state = AppState::new(&ServerConfig::default());
+            let rooms = state.create_rooms(CONFIG, Some("qf"), 3).unwrap();
+            let codes: Vec<_> = rooms.iter().map(|(code, _)| code.as_str()).collect();
+            assert_eq!(codes, ["QF-1", "QF-2", "QF-3"]);
+            assert_eq!(state.room_count(), 3);
+            assert!(matches!(
+                state.create_room(CONFIG, Some("batch")),
+                Err(AppError::InvalidRoomCode)
+            ));
+        });
+    }
+
+    #[test]
+    fn failed_batch_leaves_no_rooms_behind() {
+        block_on(async {
+            let state = AppState::new(&ServerConfig {
+                max_rooms: 5,
+                ..ServerConfig::default()
+            });
+            state.create_room(CONFIG, Some("QF-3")).unwrap();
+
+            assert!(matches!(
+                state.create_rooms(CONFIG, Some("QF"), 4),
+                Err(AppError::RoomCodeTaken)
+            ));
+            assert_eq!(state.room_count(), 1);
+            for code in ["QF-1", "QF-2"] {
+                assert!(matches!(state.get_room(code), Err(AppError::RoomNotFound)));
+            }
+
+            // Running out of room slots midway rolls back the same way.
+            assert!(matches!(
+                state.create_rooms(CONFIG, None, 5),
+                Err(AppError::ServerFull)
+            ));
+            assert_eq!(state.room_count(), 1);
+            assert!(state.get_room("QF-3").is_ok());
         });
     }
 }
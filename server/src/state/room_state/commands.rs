@@ -7,6 +7,19 @@ impl RoomState {
     ) {
         tokio::spawn(async move {
             while let Some(cmd) = command_rx.recv().await {
+                // Sockets closing, background refreshes and the sweep itself
+                // happen in a room nobody uses; everything else is activity.
+                if !matches!(
+                    cmd,
+                    RoomCommand::DetachConnection { .. }
+                        | RoomCommand::RefreshToken { .. }
+                        | RoomCommand::CleanupExpired
+                        | RoomCommand::CheckAdmin
+                        | RoomCommand::FlushParticipants
+                        | RoomCommand::Resync { .. }
+                ) {
+                    room.touch();
+                }
                 match cmd {
                     RoomCommand::CreateAdmin { name, resp } => {
                         let _ = resp.send(room.create_admin_direct(&name));
@@ -14,9 +27,11 @@ impl RoomState {
                     RoomCommand::Join {
                         requested_name,
                         token,
+                        role,
                         resp,
                     } => {
-                        let result = room.resolve_join_direct(&requested_name, token.as_deref());
+                        let result =
+                            room.resolve_join_direct(&requested_name, token.as_deref(), role);
                         if result.is_ok() {
                             room.broadcast_participants();
                         }
@@ -29,29 +44,207 @@ impl RoomState {
                         player_id,
                         name,
                         sender,
+                        since_seq,
+                        takeover,
+                        resp,
+                    } => {
+                        let attached =
+                            match since_seq {
+                                _ if !takeover && room.is_connected(player_id) => {
+                                    Err(AppError::AlreadyConnected)
+                                }
+                                Some(since_seq) => Ok(room
+                                    .resume_connection_direct(player_id, &name, sender, since_seq)),
+                                None => Ok(room.attach_connection_direct(player_id, &name, sender)),
+                            };
+                        let _ = resp.send(attached);
+                    }
+                    RoomCommand::DetachConnection { player_id, sender } => {
+                        room.detach_connection_direct(player_id, &sender);
+                    }
+                    RoomCommand::Leave { player_id, resp } => {
+                        let _ = resp.send(room.leave_direct(player_id));
+                    }
+                    RoomCommand::SetReady {
+                        player_id,
+                        ready,
+                        ack_id,
+                    } => {
+                        room.set_ready_direct(player_id, ready);
+                        room.acknowledge(player_id, ack_id, Ok(()));
+                    }
+                    RoomCommand::Resync { player_id, ack_id } => {
+                        room.send_room_state_to(player_id);
+                        room.acknowledge(player_id, ack_id, Ok(()));
+                    }
+                    RoomCommand::RequestReady {
+                        requester_id,
+                        ack_id,
+                    } => {
+                        let result = room.request_ready_direct(requester_id);
+                        room.acknowledge(requester_id, ack_id, result);
+                    }
+                    RoomCommand::Rename {
+                        player_id,
+                        new_name,
+                        ack_id,
+                    } => {
+                        let result =
+                            room.rename_player(player_id, &new_name)
+                                .map(|(old_name, token)| {
+                                    room.send_renamed_to(player_id, old_name, token);
+                                    room.broadcast_participants();
+                                });
+                        room.acknowledge(player_id, ack_id, result.map_err(|err| err.code()));
+                    }
+                    RoomCommand::UpdateSettings {
+                        answer_window_in_ms,
+                        max_players,
+                        password_hash,
                         resp,
                     } => {
-                        let _ = resp.send(room.attach_connection_direct(player_id, &name, sender));
+                        let _ = resp.send(room.update_settings_direct(
+                            answer_window_in_ms,
+                            max_players,
+                            password_hash,
+                        ));
                     }
-                    RoomCommand::DetachConnection { player_id } => {
-                        room.detach_connection_direct(player_id);
+                    RoomCommand::SetMuted {
+                        requester_id,
+                        name,
+                        muted,
+                        ack_id,
+                    } => {
+                        let result = room.set_muted_direct(requester_id, &name, muted);
+                        room.acknowledge(requester_id, ack_id, result);
+                    }
+                    RoomCommand::Unban {
+                        requester_id,
+                        name,
+                        ack_id,
+                    } => {
+                        let result = room.unban_direct(requester_id, &name);
+                        room.acknowledge(requester_id, ack_id, result);
                     }
                     RoomCommand::KickByName {
                         requester_id,
                         name,
+                        ack_id,
+                        resp,
+                    } => {
+                        let result = room.kick_by_name_direct(requester_id, &name);
+                        room.reply(requester_id, ack_id, result, resp);
+                    }
+                    RoomCommand::TransferAdmin {
+                        requester_id,
+                        name,
+                        resp,
+                    } => {
+                        let result = room.transfer_admin_direct(requester_id, &name);
+                        room.reply(requester_id, None, result, resp);
+                    }
+                    RoomCommand::StartRound {
+                        requester_id,
+                        countdown_ms,
+                        question,
+                        ack_id,
+                        resp,
+                    } => {
+                        let result = room.start_round_direct(requester_id, countdown_ms, question);
+                        room.reply(requester_id, ack_id, result, resp);
+                    }
+                    RoomCommand::ContinueRound {
+                        requester_id,
+                        ack_id,
                         resp,
                     } => {
-                        let _ = resp.send(room.kick_by_name_direct(requester_id, &name));
+                        let result = room.continue_round_direct(requester_id);
+                        room.reply(requester_id, ack_id, result, resp);
+                    }
+                    RoomCommand::MarkCorrect {
+                        requester_id,
+                        ack_id,
+                    } => {
+                        let result = room.mark_correct_direct(requester_id);
+                        room.acknowledge(requester_id, ack_id, result);
+                    }
+                    RoomCommand::Grant {
+                        requester_id,
+                        name,
+                        force,
+                        ack_id,
+                    } => {
+                        // Once checked, the room loop acknowledges it.
+                        let result = room.grant_direct(requester_id, &name, force, ack_id.clone());
+                        if result.is_err() {
+                            room.acknowledge(requester_id, ack_id, result);
+                        }
+                    }
+                    RoomCommand::Undo {
+                        requester_id,
+                        ack_id,
+                    } => {
+                        // Once checked, the room loop acknowledges it.
+                        let result = room.undo_direct(requester_id, ack_id.clone());
+                        if result.is_err() {
+                            room.acknowledge(requester_id, ack_id, result);
+                        }
+                    }
+                    RoomCommand::NewGame {
+                        requester_id,
+                        ack_id,
+                    } => {
+                        let result = room.new_game_direct(requester_id);
+                        room.acknowledge(requester_id, ack_id, result);
+                    }
+                    RoomCommand::Chat {
+                        player_id,
+                        text,
+                        system,
+                        ack_id,
+                    } => {
+                        let result = room.chat_direct(player_id, &text, system);
+                        room.acknowledge(player_id, ack_id, result);
+                    }
+                    RoomCommand::React {
+                        player_id,
+                        emoji,
+                        ack_id,
+                    } => {
+                        let result = room.react_direct(player_id, &emoji);
+                        room.acknowledge(player_id, ack_id, result);
                     }
-                    RoomCommand::StartRound { requester_id } => {
-                        room.start_round_direct(requester_id);
+                    RoomCommand::Pause {
+                        requester_id,
+                        ack_id,
+                    } => {
+                        let result = room.pause_direct(requester_id);
+                        room.acknowledge(requester_id, ack_id, result);
                     }
-                    RoomCommand::ContinueRound { requester_id } => {
-                        room.continue_round_direct(requester_id);
+                    RoomCommand::Resume {
+                        requester_id,
+                        ack_id,
+                    } => {
+                        let result = room.resume_direct(requester_id);
+                        room.acknowledge(requester_id, ack_id, result);
+                    }
+                    RoomCommand::SetLocked {
+                        requester_id,
+                        locked,
+                        ack_id,
+                    } => {
+                        let result = room.set_locked_direct(requester_id, locked);
+                        room.acknowledge(requester_id, ack_id, result);
                     }
                     RoomCommand::CleanupExpired => {
                         room.cleanup_expired();
                     }
+                    RoomCommand::CheckAdmin => {
+                        room.replace_departed_admin();
+                    }
+                    RoomCommand::FlushParticipants => {
+                        room.flush_participants();
+                    }
                 }
             }
         });
@@ -72,19 +265,21 @@ impl RoomState {
         &self,
         requested_name: &str,
         token: Option<&str>,
+        role: Role,
     ) -> Result<(String, Role), AppError> {
         let (tx, rx) = oneshot::channel();
         self.command_tx
             .send(RoomCommand::Join {
                 requested_name: requested_name.to_string(),
                 token: token.map(str::to_string),
+                role,
                 resp: tx,
             })
             .map_err(|_| AppError::Internal)?;
         rx.await.map_err(|_| AppError::Internal)?
     }
 
-    pub async fn refresh_token(&self, token: &str) -> Result<String, AppError> {
+    pub async fn refresh_token(&self, token: &str) -> Result<(String, Claims), AppError> {
         let (tx, rx) = oneshot::channel();
         self.command_tx
             .send(RoomCommand::RefreshToken {
@@ -95,11 +290,15 @@ impl RoomState {
         rx.await.map_err(|_| AppError::Internal)?
     }
 
+    /// A player has one socket at a time: with `takeover` the newest one
+    /// displaces the old, otherwise it fails with `AlreadyConnected`.
     pub async fn attach_connection(
         &self,
         player_id: PlayerId,
         name: &str,
-        sender: mpsc::UnboundedSender<String>,
+        sender: mpsc::Sender<Outbound>,
+        since_seq: Option<u64>,
+        takeover: bool,
     ) -> Result<bool, AppError> {
         let (tx, rx) = oneshot::channel();
         self.command_tx
@@ -107,43 +306,276 @@ impl RoomState {
                 player_id,
                 name: name.to_string(),
                 sender,
+                since_seq,
+                takeover,
+                resp: tx,
+            })
+            .map_err(|_| AppError::Internal)?;
+        rx.await.map_err(|_| AppError::Internal)?
+    }
+
+    /// `sender` identifies the connection, so one that was already replaced
+    /// leaves its successor attached.
+    pub fn detach_connection(&self, player_id: PlayerId, sender: mpsc::WeakSender<Outbound>) {
+        let _ = self
+            .command_tx
+            .send(RoomCommand::DetachConnection { player_id, sender });
+    }
+
+    pub async fn leave(&self, player_id: PlayerId) -> Result<(), AppError> {
+        let (tx, rx) = oneshot::channel();
+        self.command_tx
+            .send(RoomCommand::Leave {
+                player_id,
+                resp: tx,
+            })
+            .map_err(|_| AppError::Internal)?;
+        rx.await.map_err(|_| AppError::Internal)?
+    }
+
+    /// Applies a partial settings update; values must already be clamped, and a
+    /// password already hashed (`Some(None)` removes it). A room that is closing
+    /// takes no more changes.
+    pub async fn update_settings(
+        &self,
+        answer_window_in_ms: Option<u64>,
+        max_players: Option<usize>,
+        password_hash: Option<Option<String>>,
+    ) -> Result<RoomSettings, AppError> {
+        if self.closed.load(Ordering::Relaxed) {
+            return Err(AppError::RoomClosed);
+        }
+        let (tx, rx) = oneshot::channel();
+        self.command_tx
+            .send(RoomCommand::UpdateSettings {
+                answer_window_in_ms,
+                max_players,
+                password_hash,
                 resp: tx,
             })
             .map_err(|_| AppError::Internal)?;
         rx.await.map_err(|_| AppError::Internal)
     }
 
-    pub fn detach_connection(&self, player_id: PlayerId) {
+    pub fn set_ready(&self, player_id: PlayerId, ready: bool, ack_id: Option<String>) {
+        let _ = self.command_tx.send(RoomCommand::SetReady {
+            player_id,
+            ready,
+            ack_id,
+        });
+    }
+
+    /// Resends `player_id` the room as a fresh attach would see it.
+    pub fn resync(&self, player_id: PlayerId, ack_id: Option<String>) {
         let _ = self
             .command_tx
-            .send(RoomCommand::DetachConnection { player_id });
+            .send(RoomCommand::Resync { player_id, ack_id });
+    }
+
+    pub fn request_ready(&self, requester_id: PlayerId, ack_id: Option<String>) {
+        let _ = self.command_tx.send(RoomCommand::RequestReady {
+            requester_id,
+            ack_id,
+        });
     }
 
-    pub async fn kick_by_name(&self, requester_id: PlayerId, name: &str) -> Result<bool, AppError> {
+    pub async fn kick_by_name(
+        &self,
+        requester_id: PlayerId,
+        name: &str,
+        ack_id: Option<String>,
+    ) -> Result<(), AppError> {
         let (tx, rx) = oneshot::channel();
         self.command_tx
             .send(RoomCommand::KickByName {
                 requester_id,
                 name: name.to_string(),
+                ack_id,
                 resp: tx,
             })
             .map_err(|_| AppError::Internal)?;
-        rx.await.map_err(|_| AppError::Internal)
+        rx.await.map_err(|_| AppError::Internal)?
     }
 
-    pub fn start_round(&self, requester_id: PlayerId) {
-        let _ = self
-            .command_tx
-            .send(RoomCommand::StartRound { requester_id });
+    /// Makes the named player the admin; the requester stays on as a player.
+    pub async fn transfer_admin(&self, requester_id: PlayerId, name: &str) -> Result<(), AppError> {
+        let (tx, rx) = oneshot::channel();
+        self.command_tx
+            .send(RoomCommand::TransferAdmin {
+                requester_id,
+                name: name.to_string(),
+                resp: tx,
+            })
+            .map_err(|_| AppError::Internal)?;
+        rx.await.map_err(|_| AppError::Internal)?
     }
 
-    pub fn continue_round(&self, requester_id: PlayerId) {
-        let _ = self
-            .command_tx
-            .send(RoomCommand::ContinueRound { requester_id });
+    pub fn set_muted(
+        &self,
+        requester_id: PlayerId,
+        name: &str,
+        muted: bool,
+        ack_id: Option<String>,
+    ) {
+        let _ = self.command_tx.send(RoomCommand::SetMuted {
+            requester_id,
+            name: name.to_string(),
+            muted,
+            ack_id,
+        });
+    }
+
+    /// Lets a kicked player's name join again.
+    pub fn unban(&self, requester_id: PlayerId, name: &str, ack_id: Option<String>) {
+        let _ = self.command_tx.send(RoomCommand::Unban {
+            requester_id,
+            name: name.to_string(),
+            ack_id,
+        });
+    }
+
+    pub fn rename(&self, player_id: PlayerId, new_name: &str, ack_id: Option<String>) {
+        let _ = self.command_tx.send(RoomCommand::Rename {
+            player_id,
+            new_name: new_name.to_string(),
+            ack_id,
+        });
+    }
+
+    pub async fn start_round(
+        &self,
+        requester_id: PlayerId,
+        countdown_ms: Option<u64>,
+        question: Option<String>,
+        ack_id: Option<String>,
+    ) -> Result<(), AppError> {
+        let (tx, rx) = oneshot::channel();
+        self.command_tx
+            .send(RoomCommand::StartRound {
+                requester_id,
+                countdown_ms,
+                question,
+                ack_id,
+                resp: tx,
+            })
+            .map_err(|_| AppError::Internal)?;
+        rx.await.map_err(|_| AppError::Internal)?
+    }
+
+    pub async fn continue_round(
+        &self,
+        requester_id: PlayerId,
+        ack_id: Option<String>,
+    ) -> Result<(), AppError> {
+        let (tx, rx) = oneshot::channel();
+        self.command_tx
+            .send(RoomCommand::ContinueRound {
+                requester_id,
+                ack_id,
+                resp: tx,
+            })
+            .map_err(|_| AppError::Internal)?;
+        rx.await.map_err(|_| AppError::Internal)?
+    }
+
+    pub fn mark_correct(&self, requester_id: PlayerId, ack_id: Option<String>) {
+        let _ = self.command_tx.send(RoomCommand::MarkCorrect {
+            requester_id,
+            ack_id,
+        });
+    }
+
+    pub fn grant(&self, requester_id: PlayerId, name: &str, force: bool, ack_id: Option<String>) {
+        let _ = self.command_tx.send(RoomCommand::Grant {
+            requester_id,
+            name: name.to_string(),
+            force,
+            ack_id,
+        });
+    }
+
+    pub fn undo(&self, requester_id: PlayerId, ack_id: Option<String>) {
+        let _ = self.command_tx.send(RoomCommand::Undo {
+            requester_id,
+            ack_id,
+        });
+    }
+
+    pub fn new_game(&self, requester_id: PlayerId, ack_id: Option<String>) {
+        let _ = self.command_tx.send(RoomCommand::NewGame {
+            requester_id,
+            ack_id,
+        });
+    }
+
+    pub fn chat(&self, player_id: PlayerId, text: &str, system: bool, ack_id: Option<String>) {
+        let _ = self.command_tx.send(RoomCommand::Chat {
+            player_id,
+            text: text.to_string(),
+            system,
+            ack_id,
+        });
+    }
+
+    pub fn react(&self, player_id: PlayerId, emoji: &str, ack_id: Option<String>) {
+        let _ = self.command_tx.send(RoomCommand::React {
+            player_id,
+            emoji: emoji.to_string(),
+            ack_id,
+        });
+    }
+
+    pub fn pause(&self, requester_id: PlayerId, ack_id: Option<String>) {
+        let _ = self.command_tx.send(RoomCommand::Pause {
+            requester_id,
+            ack_id,
+        });
+    }
+
+    pub fn resume(&self, requester_id: PlayerId, ack_id: Option<String>) {
+        let _ = self.command_tx.send(RoomCommand::Resume {
+            requester_id,
+            ack_id,
+        });
+    }
+
+    /// Locks or unlocks buzzing for everyone; admin-only.
+    pub fn set_locked(&self, requester_id: PlayerId, locked: bool, ack_id: Option<String>) {
+        let _ = self.command_tx.send(RoomCommand::SetLocked {
+            requester_id,
+            locked,
+            ack_id,
+        });
+    }
+
+    /// Asks the room loop for its current state; fails once the loop has stopped.
+    pub async fn query_game_view(&self) -> Result<GameView, AppError> {
+        let (tx, rx) = oneshot::channel();
+        self.control_tx
+            .send(RoomControl::Query(tx))
+            .map_err(|_| AppError::RoomNotFound)?;
+        rx.await.map_err(|_| AppError::RoomNotFound)
     }
 
     pub fn request_cleanup(&self) {
         let _ = self.command_tx.send(RoomCommand::CleanupExpired);
     }
+
+    pub(super) fn request_admin_check(&self) {
+        let _ = self.command_tx.send(RoomCommand::CheckAdmin);
+    }
+
+    /// Hands an admin action's outcome back to its caller; it is also
+    /// acknowledged on the requester's socket, like every other command.
+    fn reply(
+        &self,
+        requester_id: PlayerId,
+        ack_id: Option<String>,
+        result: Result<(), AppError>,
+        resp: oneshot::Sender<Result<(), AppError>>,
+    ) {
+        let outcome = result.as_ref().map(|_| ()).map_err(AppError::code);
+        self.acknowledge(requester_id, ack_id, outcome);
+        let _ = resp.send(result);
+    }
 }
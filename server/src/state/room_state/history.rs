@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+/// How an answer in a round ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnswerResult {
+    Correct,
+    Wrong,
+    TimedOut,
+}
+
+impl AnswerResult {
+    fn as_str(self) -> &'static str {
+        match self {
+            AnswerResult::Correct => "correct",
+            AnswerResult::Wrong => "wrong",
+            AnswerResult::TimedOut => "timed_out",
+        }
+    }
+}
+
+struct AnswerRecord {
+    player: String,
+    result: AnswerResult,
+    reaction_ms: u64,
+}
+
+struct RoundRecord {
+    round: u64,
+    answers: Vec<AnswerRecord>,
+}
+
+/// In-memory log of answers per round, keeping only the most recent `limit`
+/// rounds. Fed by the room loop's output as game events happen.
+pub struct RoundHistory {
+    limit: usize,
+    rounds: VecDeque<RoundRecord>,
+    rounds_started: u64,
+    /// When buzzing last opened (round start or continue), epoch ms.
+    open_since_ms: u64,
+    /// Player holding the floor and their reaction time.
+    pending: Option<(String, u64)>,
+}
+
+impl RoundHistory {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            rounds: VecDeque::new(),
+            rounds_started: 0,
+            open_since_ms: 0,
+            pending: None,
+        }
+    }
+
+    pub fn start_round(&mut self, now_ms: u64) {
+        self.rounds_started += 1;
+        self.open_since_ms = now_ms;
+        self.pending = None;
+        if self.limit == 0 {
+            return;
+        }
+        while self.rounds.len() >= self.limit {
+            self.rounds.pop_front();
+        }
+        self.rounds.push_back(RoundRecord {
+            round: self.rounds_started,
+            answers: Vec::new(),
+        });
+    }
+
+    pub fn reopen(&mut self, now_ms: u64) {
+        self.open_since_ms = now_ms;
+    }
+
+    pub fn accepted(&mut self, player: String, now_ms: u64) {
+        let reaction_ms = now_ms.saturating_sub(self.open_since_ms);
+        self.pending = Some((player, reaction_ms));
+    }
+
+    /// Closes the pending answer, if any, with `result`.
+    pub fn resolve(&mut self, result: AnswerResult) {
+        let Some((player, reaction_ms)) = self.pending.take() else {
+            return;
+        };
+        if let Some(round) = self.rounds.back_mut() {
+            round.answers.push(AnswerRecord {
+                player,
+                result,
+                reaction_ms,
+            });
+        }
+    }
+
+    /// Forgets the pending answer, if any, without logging it: it was never judged.
+    pub fn discard(&mut self) {
+        self.pending = None;
+    }
+
+    /// Takes back `player`'s latest answer judged `result` this round; with
+    /// `reopen` they hold the floor again, so it is pending once more.
+    pub fn retract(&mut self, player: &str, result: AnswerResult, reopen: bool) {
+        let Some(round) = self.rounds.back_mut() else {
+            return;
+        };
+        let Some(index) = round
+            .answers
+            .iter()
+            .rposition(|answer| answer.player == player && answer.result == result)
+        else {
+            return;
+        };
+        let answer = round.answers.remove(index);
+        if reopen {
+            self.pending = Some((answer.player, answer.reaction_ms));
+        }
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("round,player,result,reaction_ms\n");
+        for round in &self.rounds {
+            for answer in &round.answers {
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{}",
+                    round.round,
+                    csv_field(&answer.player),
+                    answer.result.as_str(),
+                    answer.reaction_ms
+                );
+            }
+        }
+        csv
+    }
+}
+
+/// Quotes a field when it contains a delimiter, quote, or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_only_the_latest_rounds() {
+        let mut history = RoundHistory::new(2);
+        for (round, player) in ["ann", "bob", "cy"].into_iter().enumerate() {
+            let start = round as u64 * 1000;
+            history.start_round(start);
+            history.accepted(player.to_string(), start + 250);
+            history.resolve(AnswerResult::Correct);
+        }
+        assert_eq!(
+            history.to_csv(),
+            "round,player,result,reaction_ms\n2,bob,correct,250\n3,cy,correct,250\n"
+        );
+    }
+
+    #[test]
+    fn reaction_is_measured_from_reopening_and_names_are_escaped() {
+        let mut history = RoundHistory::new(10);
+        history.start_round(0);
+        history.accepted("Smith, \"J\"".to_string(), 100);
+        history.resolve(AnswerResult::Wrong);
+        history.reopen(500);
+        history.accepted("ann".to_string(), 700);
+        history.resolve(AnswerResult::TimedOut);
+        // Nothing pending: ignored.
+        history.resolve(AnswerResult::Wrong);
+        assert_eq!(
+            history.to_csv(),
+            "round,player,result,reaction_ms\n\
+             1,\"Smith, \"\"J\"\"\",wrong,100\n\
+             1,ann,timed_out,200\n"
+        );
+    }
+}
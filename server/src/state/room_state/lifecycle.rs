@@ -1,39 +1,104 @@
 use super::*;
-use crate::state::app_state::ADMIN_PLAYER_ID;
 use crate::utils::time::now_seconds;
 
 impl RoomState {
     pub(super) fn spawn_cleanup(room: Arc<Self>) {
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
+            let mut cleanup = tokio::time::interval(tokio::time::Duration::from_secs(
                 ROOM_CLEANUP_INTERVAL_IN_SECS,
             ));
+            // A few checks per grace period, so a handover is never much late.
+            let mut admin_check = tokio::time::interval(tokio::time::Duration::from_millis(
+                (room.admin_grace_in_ms / 4).max(MIN_ADMIN_CHECK_INTERVAL_IN_MS),
+            ));
             loop {
-                interval.tick().await;
-                if room.shutdown.load(Ordering::SeqCst) {
+                let cleanup_due = tokio::select! {
+                    _ = cleanup.tick() => true,
+                    _ = admin_check.tick() => false,
+                };
+                if room.control_tx.is_closed() {
                     break;
                 }
-                room.request_cleanup();
+                if cleanup_due {
+                    room.request_cleanup();
+                } else {
+                    room.request_admin_check();
+                }
             }
         });
     }
 
+    /// Records activity, pushing back the idle close.
+    pub(super) fn touch(&self) {
+        self.last_activity_ms.store(now_millis(), Ordering::Relaxed);
+    }
+
+    pub fn is_idle(&self) -> bool {
+        let last_activity_ms = self.last_activity_ms.load(Ordering::Relaxed);
+        now_millis().saturating_sub(last_activity_ms) >= self.idle_timeout_in_ms
+    }
+
+    /// Once the admin's socket has been gone for the grace period, the player
+    /// connected the longest becomes admin; the old admin stays on as a player.
+    /// An admin who never connected, or left the room, is not replaced here.
+    pub(super) fn replace_departed_admin(&self) {
+        let admin_id = self.admin_id();
+        if self.is_connected(admin_id) {
+            return;
+        }
+        let Some(away_since_ms) = self
+            .connection_changed_at_ms
+            .get(&admin_id)
+            .map(|entry| *entry.value())
+        else {
+            return;
+        };
+        if now_millis().saturating_sub(away_since_ms) < self.admin_grace_in_ms {
+            return;
+        }
+        let successor = self
+            .connection_changed_at_ms
+            .iter()
+            .map(|entry| (*entry.value(), *entry.key()))
+            .filter(|(_, player_id)| {
+                self.role_of(*player_id) == Role::Player && self.is_connected(*player_id)
+            })
+            .min()
+            .map(|(_, player_id)| player_id);
+        let Some(successor) = successor else {
+            return;
+        };
+        self.set_admin(successor);
+        let name = self
+            .names_by_id
+            .get(&successor)
+            .map(|entry| entry.value().clone())
+            .unwrap_or_default();
+        self.broadcast(ServerMessage::AdminChanged {
+            name,
+            reason: "admin_disconnected".to_string(),
+        });
+    }
+
+    /// Removes players whose token has lapsed while they were away. A live
+    /// socket keeps its seat regardless; the grace for reconnecting runs out
+    /// with the token once it is gone.
     pub(super) fn cleanup_expired(&self) {
         let now = now_seconds();
-        let mut expired = Vec::new();
-        for entry in self.token_exp_by_id.iter() {
-            let player_id = *entry.key();
-            let token_exp = *entry.value();
-            if now >= token_exp {
-                expired.push(player_id);
-            }
-        }
+        let expired: Vec<_> = self
+            .token_exp_by_id
+            .iter()
+            .filter(|entry| now >= *entry.value())
+            .map(|entry| *entry.key())
+            .filter(|player_id| !self.is_connected(*player_id))
+            .collect();
 
         for player_id in expired {
+            let was_admin = self.is_admin(player_id);
             self.send_kicked_to(player_id);
             let _ = self.remove_player(player_id);
-            if player_id == ADMIN_PLAYER_ID {
-                self.shutdown.store(true, Ordering::SeqCst);
+            if was_admin {
+                self.shutdown("admin_expired");
             }
         }
     }
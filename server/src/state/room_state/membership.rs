@@ -1,79 +1,376 @@
 use super::*;
-use crate::state::app_state::ADMIN_PLAYER_ID;
+use crate::utils::name::normalize_name;
+use crate::utils::password::verify_password;
+use crate::utils::time::now_seconds;
 
 impl RoomState {
     fn name_exists(&self, name: &str) -> bool {
-        self.ids_by_name.contains_key(name)
+        self.ids_by_name.contains_key(&normalize_name(name))
     }
 
-    pub fn insert_player(&self, name: String, role: Role) -> Result<PlayerId, AppError> {
-        let mut next_id = self.next_id.lock().expect("next_id lock");
-        let player_id = *next_id;
-        if player_id >= core::game::MAX_PLAYER_ID {
-            return Err(AppError::FullRoom);
-        }
-        *next_id = next_id.wrapping_add(1);
+    pub(super) fn insert_player(&self, name: String, role: Role) -> Result<PlayerId, AppError> {
+        let player_id = if role == Role::Spectator {
+            if self.spectator_count() >= MAX_SPECTATORS {
+                return Err(AppError::FullRoom);
+            }
+            let mut next_id = self
+                .next_spectator_id
+                .lock()
+                .expect("next_spectator_id lock");
+            let player_id = *next_id;
+            *next_id += 1;
+            player_id
+        } else if let Some(player_id) = self.take_free_id() {
+            player_id
+        } else {
+            let mut next_id = self.next_id.lock().expect("next_id lock");
+            let player_id = *next_id;
+            if player_id >= core::game::MAX_PLAYER_ID {
+                return Err(AppError::FullRoom);
+            }
+            *next_id = next_id.wrapping_add(1);
+            player_id
+        };
 
-        self.ids_by_name.insert(name.clone(), player_id);
+        self.ids_by_name.insert(normalize_name(&name), player_id);
         self.names_by_id.insert(player_id, name);
+        self.color_by_id.insert(player_id, self.next_color());
+        self.invalidate_participants();
 
         Ok(player_id)
     }
 
-    pub fn remove_player(&self, player_id: PlayerId) -> Result<(String, Role), AppError> {
+    /// The least used color, lowest index first: freed colors come back before
+    /// any repeats, and a full palette wraps around evenly.
+    fn next_color(&self) -> u8 {
+        let mut uses = [0usize; PLAYER_COLORS as usize];
+        for entry in self.color_by_id.iter() {
+            uses[*entry.value() as usize] += 1;
+        }
+        let fewest = uses.iter().min().copied().unwrap_or(0);
+        uses.iter().position(|&count| count == fewest).unwrap_or(0) as u8
+    }
+
+    /// The lowest released player id whose last token has expired by now.
+    fn take_free_id(&self) -> Option<PlayerId> {
+        let now = now_seconds();
+        let mut free_ids = self.free_ids.lock().expect("free_ids lock");
+        let player_id = free_ids
+            .iter()
+            .find(|(_, reusable_at)| **reusable_at <= now)
+            .map(|(player_id, _)| *player_id)?;
+        free_ids.remove(&player_id);
+        Some(player_id)
+    }
+
+    pub(super) fn remove_player(&self, player_id: PlayerId) -> Result<(String, Role), AppError> {
+        let token_exp = self.token_exp_by_id.remove(&player_id).map(|(_, exp)| exp);
+        self.last_active_by_id.remove(&player_id);
+        self.connection_changed_at_ms.remove(&player_id);
+        self.ready_by_id.remove(&player_id);
+        self.muted.remove(&player_id);
+        self.color_by_id.remove(&player_id);
+        self.scores.remove(&player_id);
+        let name = self.names_by_id.remove(&player_id).map(|(_, name)| name);
+        // Dropped only once the player is gone: the socket this closes asks
+        // the room why, see `connection_refusal`.
         self.routes.remove(&player_id);
-        self.token_exp_by_id.remove(&player_id);
-        let name = self
-            .names_by_id
-            .remove(&player_id)
-            .map(|(_, name)| name)
-            .map_or(Err(AppError::Kicked), Ok)?;
-        self.ids_by_name.remove(&name);
+        let name = name.ok_or(AppError::Kicked)?;
+        self.ids_by_name.remove(&normalize_name(&name));
+        // The admin's id is never handed out again: whoever got it would be the
+        // admin of a room that should be closing for want of one.
+        if player_id < core::game::MAX_PLAYER_ID && player_id != self.admin_id() {
+            // Held back until the departed player's token has expired, so it
+            // can never pass for whoever gets the id next.
+            self.free_ids
+                .lock()
+                .expect("free_ids lock")
+                .insert(player_id, token_exp.unwrap_or(0));
+        }
+        self.invalidate_participants();
+        Ok((name, self.role_of(player_id)))
+    }
 
-        let role = if player_id == ADMIN_PLAYER_ID {
-            Role::Admin
-        } else {
-            Role::Player
-        };
-        Ok((name, role))
+    /// Takes a fresh token from a connected player so their socket outlives the
+    /// one it was opened with. Returns the seat's expiry, which only moves forward.
+    pub fn renew_session(
+        &self,
+        player_id: PlayerId,
+        name: &str,
+        token: &str,
+    ) -> Result<u64, AppError> {
+        let claims = self.auth.verify(token, &self.room_id)?;
+        // A valid token of someone else's cannot keep this socket open.
+        if claims.player_id != player_id || claims.name != name {
+            return Err(AppError::InvalidToken);
+        }
+        self.check_not_revoked(&claims)?;
+        if !self.player_matches(player_id, name) {
+            return Err(AppError::UserNotInRoom);
+        }
+        let exp = self
+            .token_expiry(player_id)
+            .map_or(claims.exp, |exp| exp.max(claims.exp));
+        self.set_token_expiry(player_id, exp);
+        Ok(exp)
+    }
+
+    /// When the latest token issued to, or handed over by, `player_id` runs out.
+    pub fn token_expiry(&self, player_id: PlayerId) -> Option<u64> {
+        self.token_exp_by_id.get(&player_id).map(|exp| *exp.value())
     }
 
     fn set_token_expiry(&self, player_id: PlayerId, exp: u64) {
         self.token_exp_by_id.insert(player_id, exp);
     }
 
+    pub(super) fn mark_active(&self, player_id: PlayerId) {
+        self.last_active_by_id.insert(player_id, now_seconds());
+    }
+
+    pub fn is_connected(&self, player_id: PlayerId) -> bool {
+        self.routes
+            .get(&player_id)
+            .is_some_and(|route| route.is_connected())
+    }
+
+    /// Connected now, or disconnected for less than the idle timeout.
+    fn is_active(&self, player_id: PlayerId) -> bool {
+        if self.is_connected(player_id) {
+            return true;
+        }
+        let idle_timeout = self.auth.idle_timeout_seconds();
+        self.last_active_by_id
+            .get(&player_id)
+            .is_some_and(|entry| now_seconds().saturating_sub(*entry.value()) < idle_timeout)
+    }
+
     pub fn is_admin(&self, player_id: PlayerId) -> bool {
-        player_id == ADMIN_PLAYER_ID
+        player_id == self.admin_id()
     }
 
-    pub(super) fn kick_by_name_direct(&self, requester_id: PlayerId, name: &str) -> bool {
+    pub(super) fn admin_id(&self) -> PlayerId {
+        *self.admin_id.lock().expect("lock admin id")
+    }
+
+    pub fn is_spectator(&self, player_id: PlayerId) -> bool {
+        player_id >= FIRST_SPECTATOR_ID
+    }
+
+    /// Roles follow from the id range, so they never need storing separately.
+    pub fn role_of(&self, player_id: PlayerId) -> Role {
+        if self.is_admin(player_id) {
+            Role::Admin
+        } else if self.is_spectator(player_id) {
+            Role::Spectator
+        } else {
+            Role::Player
+        }
+    }
+
+    fn spectator_count(&self) -> usize {
+        self.names_by_id
+            .iter()
+            .filter(|entry| self.is_spectator(*entry.key()))
+            .count()
+    }
+
+    pub(super) fn id_of(&self, name: &str) -> Result<PlayerId, AppError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AppError::UserNotFound);
+        }
+        self.ids_by_name
+            .get(&normalize_name(name))
+            .map(|entry| *entry.value())
+            .ok_or(AppError::UserNotFound)
+    }
+
+    pub(super) fn kick_by_name_direct(
+        &self,
+        requester_id: PlayerId,
+        name: &str,
+    ) -> Result<(), AppError> {
         if !self.is_admin(requester_id) {
-            self.send_denied_to(requester_id, "forbidden");
-            return false;
+            return Err(AppError::Forbidden);
         }
-        let target = name.trim();
-        if target.is_empty() {
-            self.send_denied_to(requester_id, "user_not_found");
-            return false;
+        let target_id = self.id_of(name)?;
+        if target_id == requester_id {
+            return Err(AppError::CannotKickSelf);
         }
 
-        let target_id = match self.ids_by_name.get(target) {
-            Some(entry) => *entry.value(),
-            None => {
-                self.send_denied_to(requester_id, "user_not_found");
-                return false;
-            }
-        };
+        self.send_kicked_to(target_id);
+        self.kicked_at_by_id.insert(target_id, now_seconds());
+        if let Ok((name, _)) = self.remove_player(target_id) {
+            self.banned_names.insert(normalize_name(&name));
+        }
+        self.broadcast_participants();
+        Ok(())
+    }
+
+    pub(super) fn unban_direct(
+        &self,
+        requester_id: PlayerId,
+        name: &str,
+    ) -> Result<(), &'static str> {
+        if !self.is_admin(requester_id) {
+            return Err("forbidden");
+        }
+        if self.banned_names.remove(&normalize_name(name)).is_none() {
+            return Err("user_not_found");
+        }
+        Ok(())
+    }
+
+    fn is_banned(&self, name: &str) -> bool {
+        self.banned_names.contains(&normalize_name(name))
+    }
+
+    /// Refuses tokens issued to a player before they were kicked; the id may
+    /// since have gone to someone else, whose newer tokens still pass.
+    fn check_not_revoked(&self, claims: &Claims) -> Result<(), AppError> {
+        self.check_issued_after_kick(claims.player_id, &claims.name, claims.iat)
+    }
 
+    fn check_issued_after_kick(
+        &self,
+        player_id: PlayerId,
+        name: &str,
+        issued_at: u64,
+    ) -> Result<(), AppError> {
+        let revoked = self
+            .kicked_at_by_id
+            .get(&player_id)
+            .is_some_and(|kicked_at| issued_at <= *kicked_at);
+        if !revoked {
+            Ok(())
+        } else if self.is_banned(name) {
+            Err(AppError::Banned)
+        } else {
+            Err(AppError::Kicked)
+        }
+    }
+
+    /// Why a socket opened with the token `player_id` and `name` got at
+    /// `issued_at` has no place in the room (any more): the room closed, or they
+    /// were kicked, renamed since, or are gone. `None` while it may stay attached.
+    pub fn connection_refusal(
+        &self,
+        player_id: PlayerId,
+        name: &str,
+        issued_at: u64,
+    ) -> Option<AppError> {
+        if self.closed.load(Ordering::Relaxed) {
+            Some(AppError::RoomClosed)
+        } else if let Err(err) = self.check_issued_after_kick(player_id, name, issued_at) {
+            Some(err)
+        } else if self.player_matches(player_id, name) {
+            None
+        } else if self.names_by_id.contains_key(&player_id) {
+            // The token still carries the old name; a refresh fixes that.
+            Some(AppError::InvalidToken)
+        } else {
+            Some(AppError::UserNotInRoom)
+        }
+    }
+
+    /// Only players can take over; spectators would have to rejoin first.
+    pub(super) fn transfer_admin_direct(
+        &self,
+        requester_id: PlayerId,
+        name: &str,
+    ) -> Result<(), AppError> {
+        if !self.is_admin(requester_id) {
+            return Err(AppError::Forbidden);
+        }
+        let target_id = self.id_of(name)?;
         if target_id == requester_id {
-            self.send_denied_to(requester_id, "cannot_kick_self");
-            return false;
+            return Ok(());
+        }
+        if self.role_of(target_id) != Role::Player {
+            return Err(AppError::InvalidRole);
         }
 
-        self.send_kicked_to(target_id);
-        let _ = self.remove_player(target_id);
+        self.set_admin(target_id);
+        Ok(())
+    }
+
+    pub(super) fn set_admin(&self, player_id: PlayerId) {
+        *self.admin_id.lock().expect("lock admin id") = player_id;
+        self.invalidate_participants();
         self.broadcast_participants();
-        true
+    }
+
+    /// Removes a player at their own request. Dropping their route closes their
+    /// socket. An admin who leaves is not replaced: the room becomes admin-less
+    /// and is closed by the next app-level cleanup.
+    pub(super) fn leave_direct(&self, player_id: PlayerId) -> Result<(), AppError> {
+        self.remove_player(player_id)
+            .map_err(|_| AppError::UserNotInRoom)?;
+        self.broadcast_participants();
+        Ok(())
+    }
+
+    pub(super) fn set_muted_direct(
+        &self,
+        requester_id: PlayerId,
+        name: &str,
+        muted: bool,
+    ) -> Result<(), &'static str> {
+        if !self.is_admin(requester_id) {
+            return Err("forbidden");
+        }
+        let Some(target_id) = self
+            .ids_by_name
+            .get(&normalize_name(name))
+            .map(|entry| *entry.value())
+        else {
+            return Err("user_not_found");
+        };
+        if target_id == requester_id {
+            return Err("cannot_mute_self");
+        }
+
+        if muted {
+            self.muted.insert(target_id);
+        } else {
+            self.muted.remove(&target_id);
+        }
+        self.invalidate_participants();
+        self.broadcast_participants();
+        Ok(())
+    }
+
+    pub fn is_muted(&self, player_id: PlayerId) -> bool {
+        self.muted.contains(&player_id)
+    }
+
+    /// Replaces the join password hash (see [`hash_password`]); `None` opens the room.
+    ///
+    /// [`hash_password`]: crate::utils::password::hash_password
+    pub fn set_password_hash(&self, hash: Option<String>) {
+        *self.password_hash.lock().expect("lock password hash") = hash;
+    }
+
+    pub fn requires_password(&self) -> bool {
+        self.password_hash
+            .lock()
+            .expect("lock password hash")
+            .is_some()
+    }
+
+    /// Gate for new players; rejoining with a token skips it.
+    pub fn check_password(&self, password: Option<&str>) -> Result<(), AppError> {
+        let hash = self
+            .password_hash
+            .lock()
+            .expect("lock password hash")
+            .clone();
+        match (hash, password) {
+            (None, _) => Ok(()),
+            (Some(hash), Some(password)) if verify_password(&hash, password) => Ok(()),
+            (Some(_), _) => Err(AppError::WrongPassword),
+        }
     }
 
     pub fn player_matches(&self, player_id: PlayerId, name: &str) -> bool {
@@ -86,6 +383,7 @@ impl RoomState {
     fn issue_token(&self, player_id: PlayerId, name: &str, role: Role) -> Result<String, AppError> {
         let (token, exp) = self.auth.issue(&self.room_id, player_id, name, role)?;
         self.set_token_expiry(player_id, exp);
+        self.mark_active(player_id);
         Ok(token)
     }
 
@@ -93,60 +391,116 @@ impl RoomState {
         &self,
         requested_name: &str,
         token: Option<&str>,
+        role: Role,
     ) -> Result<(String, Role), AppError> {
         if let Some(token) = token {
-            let claims = self.auth.verify(token)?;
-            if claims.room_id != self.room_id {
-                return Err(AppError::RoomMismatch);
-            }
+            let claims = self.auth.verify(token, &self.room_id)?;
+            self.check_not_revoked(&claims)?;
 
             if !self.player_matches(claims.player_id, &claims.name) {
                 return Err(AppError::Kicked);
             }
 
-            // Rejoining with a valid token keeps the same player_id. Only touch the
-            // name maps when the player actually wants a different name, and never
-            // let them claim a name already owned by someone else.
-            if requested_name != claims.name {
-                let taken_by_other = self
-                    .ids_by_name
-                    .get(requested_name)
-                    .map(|entry| *entry.value() != claims.player_id)
-                    .unwrap_or(false);
-                if taken_by_other {
-                    return Err(AppError::NameTaken);
-                }
-
-                self.ids_by_name.remove(&claims.name);
-                self.ids_by_name
-                    .insert(requested_name.to_string(), claims.player_id);
-                self.names_by_id
-                    .insert(claims.player_id, requested_name.to_string());
-            }
-
-            let new_token = self.issue_token(claims.player_id, requested_name, claims.role)?;
-            return Ok((new_token, claims.role));
+            // Rejoining with a valid token is idempotent: same player_id, seat,
+            // score and lockout, so an open socket for it keeps working. A
+            // different name is a rename, never a leave and join.
+            let role = self.role_of(claims.player_id);
+            let new_token = if requested_name == claims.name {
+                self.issue_token(claims.player_id, requested_name, role)?
+            } else {
+                self.rename_player(claims.player_id, requested_name)?.1
+            };
+            return Ok((new_token, role));
         }
 
+        if role == Role::Admin {
+            return Err(AppError::InvalidRole);
+        }
+        if self.is_banned(requested_name) {
+            return Err(AppError::Banned);
+        }
         if self.name_exists(requested_name) {
             return Err(AppError::NameTaken);
         }
+        let players = self
+            .names_by_id
+            .iter()
+            .filter(|entry| self.role_of(*entry.key()) == Role::Player)
+            .count();
+        if role == Role::Player && players >= self.settings().max_players {
+            return Err(AppError::FullRoom);
+        }
 
-        let role = Role::Player;
         let player_id = self.insert_player(requested_name.to_string(), role)?;
         let token = self.issue_token(player_id, requested_name, role)?;
         Ok((token, role))
     }
 
-    pub(super) fn refresh_token_direct(&self, token: &str) -> Result<String, AppError> {
-        let claims = self.auth.verify(token)?;
-        if claims.room_id != self.room_id {
-            return Err(AppError::RoomMismatch);
+    /// Changes a player's display name in place, keeping their id, role, score
+    /// and lockout. Returns the old name and a token carrying the new one; the
+    /// old token stops matching right away.
+    pub(super) fn rename_player(
+        &self,
+        player_id: PlayerId,
+        requested_name: &str,
+    ) -> Result<(String, String), AppError> {
+        let name = &self.name_filter.validate(requested_name)?;
+        let old_name = self
+            .names_by_id
+            .get(&player_id)
+            .map(|entry| entry.value().clone())
+            .ok_or(AppError::UserNotInRoom)?;
+
+        let normalized = normalize_name(name);
+        if self.banned_names.contains(&normalized) {
+            return Err(AppError::Banned);
+        }
+        let taken_by_other = self
+            .ids_by_name
+            .get(&normalized)
+            .map(|entry| *entry.value() != player_id)
+            .unwrap_or(false);
+        if taken_by_other {
+            return Err(AppError::NameTaken);
+        }
+
+        self.ids_by_name.remove(&normalize_name(&old_name));
+        self.ids_by_name.insert(normalized, player_id);
+        self.names_by_id.insert(player_id, name.to_string());
+        self.invalidate_participants();
+        let token = self.issue_token(player_id, name, self.role_of(player_id))?;
+        Ok((old_name, token))
+    }
+
+    /// Checks that `token` belongs to this room's admin.
+    pub fn authorize_admin(&self, token: &str) -> Result<PlayerId, AppError> {
+        let claims = self.auth.verify(token, &self.room_id)?;
+        if !self.player_matches(claims.player_id, &claims.name) {
+            return Err(AppError::UserNotInRoom);
         }
+        // The room, not the token, says who is admin, so a handover applies at once.
+        if !self.is_admin(claims.player_id) {
+            return Err(AppError::Forbidden);
+        }
+        Ok(claims.player_id)
+    }
+
+    /// Returns the new token along with the claims of the old one; the name and
+    /// role they carry still hold, since a renamed player's old token is refused.
+    /// Each refresh slides the expiry forward, but only for players who are
+    /// connected or dropped off less than the idle timeout ago.
+    pub(super) fn refresh_token_direct(&self, token: &str) -> Result<(String, Claims), AppError> {
+        let claims = self.auth.verify(token, &self.room_id)?;
+        self.check_not_revoked(&claims)?;
         if !self.player_matches(claims.player_id, &claims.name) {
             return Err(AppError::UserNotInRoom);
         }
-        self.issue_token(claims.player_id, &claims.name, claims.role)
+        if !self.is_active(claims.player_id) {
+            return Err(AppError::SessionExpired);
+        }
+        let role = self.role_of(claims.player_id);
+        let new_token = self.issue_token(claims.player_id, &claims.name, role)?;
+        Ok((new_token, Claims { role, ..claims }))
     }
 
     pub(super) fn create_admin_direct(&self, name: &str) -> Result<String, AppError> {
@@ -1,60 +1,403 @@
 use super::*;
-use crate::state::app_state::ADMIN_PLAYER_ID;
-use crate::utils::time::now_seconds;
+use crate::utils::time::{now_millis, now_seconds};
+use core::adapter::TimeSource;
+use tracing::info;
 
 impl RoomState {
     pub(super) fn attach_connection_direct(
         &self,
         player_id: PlayerId,
         name: &str,
-        sender: mpsc::UnboundedSender<String>,
+        sender: mpsc::Sender<Outbound>,
     ) -> bool {
         if !self.player_matches(player_id, name) {
             return false;
         }
 
-        self.routes.insert(player_id, sender);
-        self.send_participants_to(player_id);
+        let was_connected = self.is_connected(player_id);
+        // A second tab or a reconnect racing the old socket's close: the newest
+        // connection wins and the old one is told to stop.
+        if let Some(old) = self.routes.get(&player_id) {
+            if was_connected {
+                info!("Player {player_id} reconnected, superseding their previous socket");
+            }
+            old.displace();
+        }
+        // A fresh route restarts `seq` at 0, followed by a full snapshot.
+        self.routes
+            .insert(player_id, Route::new(sender, &self.broadcaster));
+        self.mark_active(player_id);
+        if !was_connected {
+            self.connection_changed_at_ms
+                .insert(player_id, now_millis());
+            self.invalidate_participants();
+            self.broadcast_participants_soon();
+        }
+        self.send_room_state_to(player_id);
         true
     }
 
-    pub(super) fn detach_connection_direct(&self, player_id: PlayerId) {
-        self.routes.remove(&player_id);
+    /// Participants, scores and the round in play: what a client needs to
+    /// rebuild its view from scratch. `welcome` carries all of it but the open
+    /// question, which follows on its own.
+    pub(super) fn send_room_state_to(&self, player_id: PlayerId) {
+        self.send_welcome_to(player_id);
+        let question = self.game_view.borrow().question.clone();
+        if let Some(question) = question {
+            self.send_to_player(player_id, question.into());
+        }
+    }
+
+    /// Picks up where the previous connection left off, replaying what it
+    /// missed; falls back to a fresh attach when the gap is no longer buffered.
+    pub(super) fn resume_connection_direct(
+        &self,
+        player_id: PlayerId,
+        name: &str,
+        sender: mpsc::Sender<Outbound>,
+        since_seq: u64,
+    ) -> bool {
+        if !self.player_matches(player_id, name) {
+            return false;
+        }
+        let was_connected = self.is_connected(player_id);
+        let resumed = self
+            .routes
+            .get(&player_id)
+            .is_some_and(|route| route.resume(&sender, since_seq, &self.broadcaster));
+        if resumed {
+            self.mark_active(player_id);
+            if was_connected {
+                info!("Player {player_id} resumed, superseding their previous socket");
+            } else {
+                self.connection_changed_at_ms
+                    .insert(player_id, now_millis());
+                self.invalidate_participants();
+                self.broadcast_participants_soon();
+            }
+        }
+        resumed || self.attach_connection_direct(player_id, name, sender)
+    }
+
+    /// Room-wide messages from now on; pass each to [`deliver_broadcast`](Self::deliver_broadcast).
+    pub fn subscribe(&self) -> broadcast::Receiver<Broadcast> {
+        self.broadcaster.subscribe()
+    }
+
+    pub fn deliver_broadcast(&self, player_id: PlayerId, broadcast: &Broadcast) {
+        if let Some(route) = self.routes.get(&player_id) {
+            route.deliver(broadcast);
+        }
     }
 
-    pub fn send_buzz(&self, player_id: PlayerId) {
-        let _ = self.buzz_tx.send(player_id);
+    /// Fills in broadcasts a lagging subscriber skipped. `false` means they are
+    /// gone and the client needs a fresh snapshot.
+    pub fn catch_up_broadcasts(&self, player_id: PlayerId) -> bool {
+        self.routes
+            .get(&player_id)
+            .is_some_and(|route| route.catch_up(&self.broadcaster))
     }
 
-    pub(super) fn start_round_direct(&self, requester_id: PlayerId) {
-        if !self.is_admin(requester_id) {
-            self.send_denied_to(requester_id, "forbidden");
+    pub(super) fn detach_connection_direct(
+        &self,
+        player_id: PlayerId,
+        sender: &mpsc::WeakSender<Outbound>,
+    ) {
+        if let Some(route) = self.routes.get(&player_id) {
+            route.detach(sender);
+            // The idle timeout runs from when the socket went away.
+            self.mark_active(player_id);
+            if !route.is_connected() {
+                self.connection_changed_at_ms
+                    .insert(player_id, now_millis());
+                self.invalidate_participants();
+                self.broadcast_participants_soon();
+            }
+        }
+    }
+
+    /// A muted player's buzz is dropped, unanswered unless it carried an `ack_id`.
+    /// So is a repeat within the room's debounce of the same player's last buzz
+    /// since buzzing opened; with an `ack_id` it is acknowledged to them alone,
+    /// as the buzz it repeats already counted.
+    pub fn send_buzz(&self, player_id: PlayerId, ack_id: Option<String>) {
+        if self.is_muted(player_id) || self.is_spectator(player_id) {
+            if ack_id.is_some() {
+                self.send_denied_to(player_id, "muted", ack_id);
+            }
+            return;
+        }
+        if self.buzzing_locked.load(Ordering::Relaxed) {
+            self.send_denied_to(player_id, "room_locked", ack_id);
+            return;
+        }
+        let now_ms = now_millis();
+        let repeated = self
+            .last_buzz_ms_by_id
+            .get(&player_id)
+            .is_some_and(|last| now_ms.saturating_sub(*last) < self.buzz_debounce_in_ms);
+        if repeated {
+            if let Some(ack_id) = ack_id {
+                self.send_to_player(player_id, ServerMessage::ActionOk { id: ack_id });
+            }
             return;
         }
-        self.reset_flag.store(true, Ordering::SeqCst);
+        self.last_buzz_ms_by_id.insert(player_id, now_ms);
+        self.touch();
+        let _ = self.buzz_tx.send(Buzz {
+            player_id,
+            ack_id,
+            received_ms: self.clock.now_ms(),
+        });
     }
 
-    pub(super) fn continue_round_direct(&self, requester_id: PlayerId) {
+    pub(super) fn start_round_direct(
+        &self,
+        requester_id: PlayerId,
+        countdown_ms: Option<u64>,
+        question: Option<String>,
+    ) -> Result<(), AppError> {
         if !self.is_admin(requester_id) {
-            self.send_denied_to(requester_id, "forbidden");
+            return Err(AppError::Forbidden);
+        }
+        let question = match question.as_deref().map(str::trim) {
+            None => None,
+            Some("") => return Err(AppError::QuestionEmpty),
+            Some(text) if text.chars().count() > MAX_QUESTION_CHARS => {
+                return Err(AppError::QuestionTooLong);
+            }
+            Some(text) => Some(text.to_string()),
+        };
+        let countdown_ms = countdown_ms.unwrap_or(0).min(MAX_COUNTDOWN_IN_MS);
+        self.last_buzz_ms_by_id.clear();
+        self.set_buzzing_locked(false);
+        self.send_control(RoomControl::StartRound {
+            countdown_ms,
+            question,
+        });
+        if !self.ready_by_id.is_empty() {
+            self.ready_by_id.clear();
+            self.invalidate_participants();
+            self.broadcast_participants();
+        }
+        Ok(())
+    }
+
+    pub(super) fn set_ready_direct(&self, player_id: PlayerId, ready: bool) {
+        if !self.names_by_id.contains_key(&player_id) {
             return;
         }
-        self.continue_flag.store(true, Ordering::SeqCst);
+        self.ready_by_id.insert(player_id, ready);
+        self.invalidate_participants();
+        self.broadcast_participants();
+    }
+
+    pub(super) fn request_ready_direct(&self, requester_id: PlayerId) -> Result<(), &'static str> {
+        if !self.is_admin(requester_id) {
+            return Err("forbidden");
+        }
+        self.ready_by_id.clear();
+        self.invalidate_participants();
+        self.broadcast(ServerMessage::ReadyCheck);
+        self.broadcast_participants();
+        Ok(())
+    }
+
+    pub(super) fn continue_round_direct(&self, requester_id: PlayerId) -> Result<(), AppError> {
+        if !self.is_admin(requester_id) {
+            return Err(AppError::Forbidden);
+        }
+        self.last_buzz_ms_by_id.clear();
+        self.send_control(RoomControl::ContinueRound);
+        Ok(())
+    }
+
+    pub(super) fn mark_correct_direct(&self, requester_id: PlayerId) -> Result<(), &'static str> {
+        if !self.is_admin(requester_id) {
+            return Err("forbidden");
+        }
+        self.send_control(RoomControl::MarkCorrect);
+        Ok(())
+    }
+
+    /// Checks who may take the floor; the room loop decides whether they can.
+    pub(super) fn grant_direct(
+        &self,
+        requester_id: PlayerId,
+        name: &str,
+        force: bool,
+        ack_id: Option<String>,
+    ) -> Result<(), &'static str> {
+        if !self.is_admin(requester_id) {
+            return Err("forbidden");
+        }
+        let player_id = self.id_of(name).map_err(|err| err.code())?;
+        if self.role_of(player_id) != Role::Player {
+            return Err(AppError::InvalidRole.code());
+        }
+        self.send_control(RoomControl::Grant {
+            player_id,
+            force,
+            requester_id,
+            ack_id,
+        });
+        Ok(())
+    }
+
+    /// Checks who may undo; the room loop decides whether there is anything to.
+    pub(super) fn undo_direct(
+        &self,
+        requester_id: PlayerId,
+        ack_id: Option<String>,
+    ) -> Result<(), &'static str> {
+        if !self.is_admin(requester_id) {
+            return Err("forbidden");
+        }
+        self.send_control(RoomControl::Undo {
+            requester_id,
+            ack_id,
+        });
+        Ok(())
+    }
+
+    pub(super) fn new_game_direct(&self, requester_id: PlayerId) -> Result<(), &'static str> {
+        if !self.is_admin(requester_id) {
+            return Err("forbidden");
+        }
+        self.send_control(RoomControl::NewGame);
+        Ok(())
+    }
+
+    pub(super) fn chat_direct(
+        &self,
+        player_id: PlayerId,
+        text: &str,
+        system: bool,
+    ) -> Result<(), &'static str> {
+        if system && !self.is_admin(player_id) {
+            return Err("forbidden");
+        }
+        let Some(from) = self
+            .names_by_id
+            .get(&player_id)
+            .map(|entry| entry.value().clone())
+        else {
+            return Err(AppError::UserNotInRoom.code());
+        };
+        if self.is_muted(player_id) {
+            return Err("muted");
+        }
+        let text = sanitize_chat(text);
+        if text.is_empty() {
+            return Err("chat_empty");
+        }
+        if text.chars().count() > MAX_CHAT_CHARS {
+            return Err("chat_too_long");
+        }
+        if self.chat_limiter.check_key(&player_id).is_err() {
+            return Err("rate_limited");
+        }
+        self.broadcast(ServerMessage::Chat {
+            from,
+            text,
+            ts_ms: now_millis(),
+            system,
+        });
+        Ok(())
+    }
+
+    pub(super) fn react_direct(
+        &self,
+        player_id: PlayerId,
+        emoji: &str,
+    ) -> Result<(), &'static str> {
+        let Some(from) = self
+            .names_by_id
+            .get(&player_id)
+            .map(|entry| entry.value().clone())
+        else {
+            return Err(AppError::UserNotInRoom.code());
+        };
+        if self.is_muted(player_id) {
+            return Err("muted");
+        }
+        // Only a complete, known emoji (ZWJ sequences and skin tones included).
+        if emojis::get(emoji).is_none() {
+            return Err("invalid_emoji");
+        }
+        if self.reaction_limiter.check_key(&player_id).is_err() {
+            return Err("rate_limited");
+        }
+        self.broadcast(ServerMessage::Reaction {
+            from,
+            emoji: emoji.to_string(),
+            ts_ms: now_millis(),
+        });
+        Ok(())
+    }
+
+    pub(super) fn pause_direct(&self, requester_id: PlayerId) -> Result<(), &'static str> {
+        if !self.is_admin(requester_id) {
+            return Err("forbidden");
+        }
+        self.send_control(RoomControl::Pause);
+        Ok(())
+    }
+
+    pub(super) fn set_locked_direct(
+        &self,
+        requester_id: PlayerId,
+        locked: bool,
+    ) -> Result<(), &'static str> {
+        if !self.is_admin(requester_id) {
+            return Err("forbidden");
+        }
+        self.set_buzzing_locked(locked);
+        Ok(())
+    }
+
+    /// Tells everyone when the lock actually changes.
+    fn set_buzzing_locked(&self, locked: bool) {
+        if self.buzzing_locked.swap(locked, Ordering::Relaxed) != locked {
+            self.broadcast(ServerMessage::RoomLock {
+                locked,
+                ts_ms: now_millis(),
+            });
+        }
+    }
+
+    pub(super) fn resume_direct(&self, requester_id: PlayerId) -> Result<(), &'static str> {
+        if !self.is_admin(requester_id) {
+            return Err("forbidden");
+        }
+        self.send_control(RoomControl::Resume);
+        Ok(())
+    }
+
+    pub(super) fn send_control(&self, control: RoomControl) {
+        let _ = self.control_tx.send(control);
+    }
+
+    pub fn scoreboard(&self) -> Vec<ScoreEntry> {
+        build_scoreboard(&self.names_by_id, &self.scores)
+    }
+
+    pub fn history_csv(&self) -> String {
+        self.history.lock().expect("lock round history").to_csv()
     }
 
     pub fn participants(&self) -> Vec<ParticipantInfo> {
-        let mask = *self.locked_out_mask.lock().expect("lock shared mask");
+        self.participants_with_lockouts(self.game_view.borrow().locked_out)
+    }
+
+    fn participants_with_lockouts(&self, mask: u128) -> Vec<ParticipantInfo> {
         let mut list = self
             .names_by_id
             .iter()
             .map(|entry| {
                 let player_id = *entry.key();
                 let name = entry.value().clone();
-                let role = if player_id == ADMIN_PLAYER_ID {
-                    Role::Admin
-                } else {
-                    Role::Player
-                };
+                let role = self.role_of(player_id);
                 let locked_out = if player_id < 128 {
                     (mask & (1u128 << player_id)) != 0
                 } else {
@@ -64,6 +407,16 @@ impl RoomState {
                     name,
                     role,
                     locked_out,
+                    ready: self
+                        .ready_by_id
+                        .get(&player_id)
+                        .is_some_and(|entry| *entry.value()),
+                    muted: self.is_muted(player_id),
+                    connected: self.is_connected(player_id),
+                    color: self
+                        .color_by_id
+                        .get(&player_id)
+                        .map_or(0, |entry| *entry.value()),
                 }
             })
             .collect::<Vec<_>>();
@@ -71,35 +424,215 @@ impl RoomState {
         list
     }
 
+    /// Players with a live socket; routes kept only for replay don't count.
+    pub fn connection_count(&self) -> usize {
+        self.routes
+            .iter()
+            .filter(|entry| entry.value().is_connected())
+            .count()
+    }
+
+    pub fn room_id(&self) -> &str {
+        &self.room_id
+    }
+
+    pub fn settings(&self) -> RoomSettings {
+        *self.settings.lock().expect("lock room settings")
+    }
+
+    pub fn inbound_rate_per_sec(&self) -> NonZeroU32 {
+        self.inbound_rate_per_sec
+    }
+
     pub fn answer_window_in_ms(&self) -> u64 {
-        self.answer_window_in_ms
+        self.settings().answer_window_in_ms
     }
 
+    pub(super) fn update_settings_direct(
+        &self,
+        answer_window_in_ms: Option<u64>,
+        max_players: Option<usize>,
+        password_hash: Option<Option<String>>,
+    ) -> RoomSettings {
+        if let Some(hash) = password_hash {
+            self.set_password_hash(hash);
+        }
+        let settings = {
+            let mut settings = self.settings.lock().expect("lock room settings");
+            if let Some(answer_window_in_ms) = answer_window_in_ms {
+                settings.answer_window_in_ms = answer_window_in_ms;
+            }
+            if let Some(max_players) = max_players {
+                settings.max_players = max_players;
+            }
+            *settings
+        };
+        if answer_window_in_ms.is_some() {
+            self.send_control(RoomControl::SetAnswerWindow {
+                answer_window_in_ms: settings.answer_window_in_ms,
+            });
+        }
+        self.broadcast(ServerMessage::SettingsChanged {
+            answer_window_in_ms: settings.answer_window_in_ms,
+            max_players: settings.max_players,
+            requires_password: self.requires_password(),
+        });
+        settings
+    }
+
+    /// The admin is connected, or disconnected but still within their token's
+    /// lifetime and so able to come back.
     pub fn admin_present(&self) -> bool {
+        let admin_id = self.admin_id();
         let now = now_seconds();
-        self.token_exp_by_id
-            .get(&ADMIN_PLAYER_ID)
-            .map(|entry| now < *entry.value())
-            .unwrap_or(false)
+        self.is_connected(admin_id)
+            || self
+                .token_exp_by_id
+                .get(&admin_id)
+                .is_some_and(|entry| now < *entry.value())
     }
 
-    fn broadcast(&self, msg: ServerMessage) {
-        let payload = serde_json::to_string(&msg).expect("serialize server message");
-        for entry in self.routes.iter() {
-            let _ = entry.value().send(payload.clone());
-        }
+    pub(super) fn broadcast(&self, msg: ServerMessage) {
+        self.broadcaster.send(&msg);
     }
 
     pub fn broadcast_participants(&self) {
+        self.broadcaster
+            .send_serialized(self.participants_payload());
+    }
+
+    /// Broadcasts `participants` after [`PARTICIPANTS_DEBOUNCE`], once for any
+    /// number of calls in between, so a client flapping its connection costs
+    /// everyone else a few messages at most.
+    pub(super) fn broadcast_participants_soon(&self) {
+        if self
+            .participants_flush_pending
+            .swap(true, Ordering::Relaxed)
+        {
+            return;
+        }
+        let command_tx = self.command_tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(PARTICIPANTS_DEBOUNCE).await;
+            let _ = command_tx.send(RoomCommand::FlushParticipants);
+        });
+    }
+
+    pub(super) fn flush_participants(&self) {
+        self.participants_flush_pending
+            .store(false, Ordering::Relaxed);
+        self.broadcast_participants();
+    }
+
+    /// Must follow every change to who is in the room, their names, roles,
+    /// ready or mute flags. Lockouts are tracked by the cache itself.
+    pub(super) fn invalidate_participants(&self) {
+        *self
+            .participants_cache
+            .lock()
+            .expect("lock participants cache") = None;
+    }
+
+    /// The serialized `participants` message, rebuilt only when something in
+    /// it changed since the last call.
+    pub(super) fn participants_payload(&self) -> Arc<str> {
+        let mask = self.game_view.borrow().locked_out;
+        let mut cache = self
+            .participants_cache
+            .lock()
+            .expect("lock participants cache");
+        if let Some((cached_mask, payload)) = cache.as_ref()
+            && *cached_mask == mask
+        {
+            return Arc::clone(payload);
+        }
         let msg = ServerMessage::Participants {
-            participants: self.participants(),
+            participants: self.participants_with_lockouts(mask),
         };
-        self.broadcast(msg);
+        let payload: Arc<str> = serde_json::to_string(&msg)
+            .expect("serialize server message")
+            .into();
+        *cache = Some((mask, Arc::clone(&payload)));
+        payload
     }
 
-    pub fn send_participants_to(&self, player_id: PlayerId) {
-        let msg = ServerMessage::Participants {
-            participants: self.participants(),
+    fn send_welcome_to(&self, player_id: PlayerId) {
+        let view = self.game_view.borrow().clone();
+        let participants = self.participants_with_lockouts(view.locked_out);
+        let Some(you) = self
+            .names_by_id
+            .get(&player_id)
+            .and_then(|name| participants.iter().find(|p| p.name == *name).cloned())
+        else {
+            return;
+        };
+        let remaining_ms = view
+            .deadline_in_ms
+            .map(|deadline| deadline.saturating_sub(self.clock.now_ms()));
+        let game = GameStateInfo {
+            phase: view.phase,
+            round: view.round,
+            paused: view.paused,
+            locked: self.buzzing_locked.load(Ordering::Relaxed),
+            answering: view
+                .answering
+                .and_then(|id| self.names_by_id.get(&id).map(|entry| entry.value().clone())),
+            deadline_ms: remaining_ms.map(|remaining| now_millis() + remaining),
+            remaining_ms,
+            locked_out: participants
+                .iter()
+                .filter(|p| p.role == Role::Player && p.locked_out)
+                .map(|p| p.name.clone())
+                .collect(),
+            scores: self.scoreboard(),
+        };
+        let settings = self.settings();
+        let settings = RoomSettingsInfo {
+            answer_window_in_ms: settings.answer_window_in_ms,
+            max_players: settings.max_players,
+            requires_password: self.requires_password(),
+        };
+        let msg = ServerMessage::Welcome {
+            you,
+            participants,
+            game,
+            settings,
+        };
+        self.send_to_player(player_id, msg);
+    }
+
+    pub fn send_round_state_to(&self, player_id: PlayerId) {
+        let view = self.game_view.borrow().clone();
+        let name_of = |id: &PlayerId| self.names_by_id.get(id).map(|entry| entry.value().clone());
+        let remaining_ms = view
+            .deadline_in_ms
+            .map(|deadline| deadline.saturating_sub(self.clock.now_ms()));
+        let msg = ServerMessage::RoundState {
+            round: view.round,
+            answering: view.answering.as_ref().and_then(name_of),
+            deadline_ms: remaining_ms.map(|remaining| now_millis() + remaining),
+            remaining_ms,
+            attempted: view.attempted.iter().filter_map(name_of).collect(),
+            locked_out: self
+                .participants_with_lockouts(view.locked_out)
+                .into_iter()
+                .filter(|p| p.role == Role::Player && p.locked_out)
+                .map(|p| p.name)
+                .collect(),
+        };
+        self.send_to_player(player_id, msg);
+    }
+
+    pub fn send_renamed_to(&self, player_id: PlayerId, old_name: String, token: String) {
+        let new_name = self
+            .names_by_id
+            .get(&player_id)
+            .map(|entry| entry.value().clone())
+            .unwrap_or_default();
+        let msg = ServerMessage::Renamed {
+            old_name,
+            new_name,
+            token,
         };
         self.send_to_player(player_id, msg);
     }
@@ -108,25 +641,86 @@ impl RoomState {
         self.send_to_player(player_id, ServerMessage::Kicked);
     }
 
-    pub fn send_denied_to(&self, player_id: PlayerId, reason: &str) {
+    /// `ack_id` is the `id` the denied message carried, if any.
+    pub fn send_denied_to(&self, player_id: PlayerId, reason: &str, ack_id: Option<String>) {
         let msg = ServerMessage::ActionDenied {
             reason: reason.to_string(),
+            category: None,
+            id: ack_id,
+        };
+        self.send_to_player(player_id, msg);
+    }
+
+    pub fn send_rate_limited_to(
+        &self,
+        player_id: PlayerId,
+        category: RateCategory,
+        ack_id: Option<String>,
+    ) {
+        let msg = ServerMessage::ActionDenied {
+            reason: AppError::RateLimited.code().to_string(),
+            category: Some(category),
+            id: ack_id,
+        };
+        self.send_to_player(player_id, msg);
+    }
+
+    /// See [`ServerMessage::acknowledgement`].
+    pub fn acknowledge(
+        &self,
+        player_id: PlayerId,
+        ack_id: Option<String>,
+        result: Result<(), &str>,
+    ) {
+        if let Some(msg) = ServerMessage::acknowledgement(ack_id, result) {
+            self.send_to_player(player_id, msg);
+        }
+    }
+
+    pub fn send_protocol_error_to(&self, player_id: PlayerId, detail: String) {
+        let msg = ServerMessage::ProtocolError {
+            detail,
+            supported_versions: SUPPORTED_PROTOCOL_VERSIONS.to_vec(),
         };
         self.send_to_player(player_id, msg);
     }
 
     fn send_to_player(&self, player_id: PlayerId, msg: ServerMessage) {
-        if let Some(sender) = self
-            .routes
-            .get(&player_id)
-            .map(|entry| entry.value().clone())
-        {
-            let payload = serde_json::to_string(&msg).expect("serialize server message");
-            let _ = sender.send(payload);
+        if let Some(route) = self.routes.get(&player_id) {
+            route.send(&msg);
         }
     }
 
-    pub fn shutdown(&self) {
-        self.shutdown.store(true, Ordering::SeqCst);
+    pub fn shutdown(&self, reason: &str) {
+        self.closed.store(true, Ordering::Relaxed);
+        self.send_control(RoomControl::Shutdown {
+            reason: reason.to_string(),
+        });
     }
 }
+
+/// Every participant's score, highest first; ties are ordered by name.
+pub(crate) fn build_scoreboard(
+    names_by_id: &DashMap<PlayerId, String>,
+    scores: &DashMap<PlayerId, u32>,
+) -> Vec<ScoreEntry> {
+    let mut entries = names_by_id
+        .iter()
+        .filter(|entry| *entry.key() < FIRST_SPECTATOR_ID)
+        .map(|entry| ScoreEntry {
+            name: entry.value().clone(),
+            score: scores.get(entry.key()).map(|s| *s.value()).unwrap_or(0),
+        })
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+    entries
+}
+
+/// Drops control characters (newlines included) and surrounding whitespace.
+fn sanitize_chat(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
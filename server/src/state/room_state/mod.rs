@@ -1,47 +1,172 @@
-use crate::adapter::spawn_room_loop;
-use crate::auth::JwtAuth;
-use crate::dtos::{ParticipantInfo, Role, ServerMessage};
+use crate::adapter::{
+    Broadcast, Broadcaster, Buzz, GameView, InstantTime, Outbound, RoomControl, Route,
+    spawn_room_loop,
+};
+use crate::auth::{Claims, JwtAuth};
+use crate::dtos::{
+    GameStateInfo, ParticipantInfo, PlayerSnapshot, RateCategory, Role, RoomSettingsInfo,
+    RoomSnapshot, SUPPORTED_PROTOCOL_VERSIONS, ScoreEntry, ServerMessage,
+};
 use crate::errors::AppError;
+use crate::state::app_state::ADMIN_PLAYER_ID;
+use crate::utils::name::NameFilter;
+use crate::utils::time::now_millis;
+use crate::webhook::RoomWebhook;
 use core::game::PlayerId;
-use dashmap::DashMap;
-use std::sync::{
-    Arc, Mutex,
-    atomic::{AtomicBool, Ordering},
-};
-use tokio::sync::{mpsc, oneshot};
+use dashmap::{DashMap, DashSet};
+use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
+use std::collections::BTreeMap;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
 
 mod commands;
+mod history;
 mod lifecycle;
 mod membership;
 mod messaging;
+mod snapshot;
+
+pub(crate) use history::{AnswerResult, RoundHistory};
+pub(crate) use messaging::build_scoreboard;
+#[cfg(test)]
+mod tests;
 
 const ROOM_CLEANUP_INTERVAL_IN_SECS: u64 = 30 * 60;
+const MAX_COUNTDOWN_IN_MS: u64 = 10_000;
+/// Connects and disconnects within this window share one `participants` broadcast.
+const PARTICIPANTS_DEBOUNCE: Duration = Duration::from_millis(250);
+const MAX_QUESTION_CHARS: usize = 500;
+const MAX_CHAT_CHARS: usize = 280;
+/// Chat is much stricter than general websocket traffic: a burst of 3, then
+/// one message every 2 seconds per player.
+const CHAT_BURST: u32 = 3;
+const CHAT_PERIOD: Duration = Duration::from_secs(2);
+/// Reactions are cheap to render but easy to spam: a burst of 5, then one a second.
+const REACTION_BURST: u32 = 5;
+const REACTION_PERIOD: Duration = Duration::from_secs(1);
 
 pub type RoomId = String;
 
+pub const DEFAULT_INBOUND_RATE_PER_SEC: u32 = 20;
+pub const DEFAULT_IDLE_TIMEOUT_IN_MS: u64 = 60 * 60 * 1000;
+pub const DEFAULT_ADMIN_GRACE_IN_MS: u64 = 120 * 1000;
+pub const DEFAULT_BUZZ_DEBOUNCE_IN_MS: u64 = 50;
+/// Lower bound on how often the admin's connection is checked.
+const MIN_ADMIN_CHECK_INTERVAL_IN_MS: u64 = 50;
+const MIN_INBOUND_RATE_PER_SEC: u32 = 1;
+const MAX_INBOUND_RATE_PER_SEC: u32 = 100;
+
+/// Ids run from 1 to `MAX_PLAYER_ID - 1`; 0 is the admin.
+pub const MAX_PLAYERS: usize = core::game::MAX_PLAYER_ID - 1;
+/// Spectators get ids above anything the game accepts, so they can never buzz.
+pub const FIRST_SPECTATOR_ID: PlayerId = core::game::MAX_PLAYER_ID + 1;
+pub const MAX_SPECTATORS: usize = 32;
+/// Size of the clients' color palette; every [`ParticipantInfo::color`] is below it.
+pub const PLAYER_COLORS: u8 = 12;
+
+/// Settings an admin may change while the room is live.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RoomSettings {
+    pub answer_window_in_ms: u64,
+    /// Cap on players besides the admin; only checked when someone new joins.
+    pub max_players: usize,
+}
+
 #[derive(Clone, Copy)]
 pub struct RoomConfig {
     pub answer_window_in_ms: u64,
+    /// Number of most recent rounds kept for the CSV export.
+    pub history_limit: usize,
+    /// Websocket messages each connection may send per second, not counting
+    /// buzzes and admin controls, which have their own limits; clamped to `1..=100`.
+    pub inbound_rate_per_sec: u32,
+    /// The room is closed by the next sweep once nobody has buzzed, run a
+    /// round or sent a command for this long, even with its admin still around.
+    pub idle_timeout_in_ms: u64,
+    /// How long the admin's socket may stay gone before the longest-connected
+    /// player takes over.
+    pub admin_grace_in_ms: u64,
+    /// A player's buzz this soon after their last one is dropped, so a
+    /// double-fired button costs nothing; 0 keeps every buzz.
+    pub buzz_debounce_in_ms: u64,
 }
 
 pub struct RoomState {
     // id: RoomId,
     room_id: RoomId,
     auth: Arc<JwtAuth>,
-    answer_window_in_ms: u64,
-    buzz_tx: mpsc::UnboundedSender<PlayerId>,
-    routes: Arc<DashMap<PlayerId, mpsc::UnboundedSender<String>>>,
+    name_filter: Arc<NameFilter>,
+    settings: Mutex<RoomSettings>,
+    inbound_rate_per_sec: NonZeroU32,
+    idle_timeout_in_ms: u64,
+    /// Unix millis of the last buzz or command, see [`touch`](Self::touch).
+    last_activity_ms: AtomicU64,
+    admin_grace_in_ms: u64,
+    buzz_debounce_in_ms: u64,
+    /// Unix millis of each player's last buzz let through since buzzing last
+    /// opened; see [`send_buzz`](Self::send_buzz).
+    last_buzz_ms_by_id: DashMap<PlayerId, u64>,
+    /// Argon2 PHC string; never the password itself.
+    password_hash: Mutex<Option<String>>,
+    buzz_tx: mpsc::UnboundedSender<Buzz>,
+    /// The room loop's clock, shared so buzzes are stamped when they come in.
+    clock: InstantTime,
+    /// Per-player outbound streams; room-wide messages go through `broadcaster`.
+    routes: Arc<DashMap<PlayerId, Route>>,
+    broadcaster: Arc<Broadcaster>,
     names_by_id: Arc<DashMap<PlayerId, String>>,
+    /// Keyed by [`normalize_name`](crate::utils::name::normalize_name); display
+    /// names live in `names_by_id`.
     ids_by_name: Arc<DashMap<String, PlayerId>>,
     token_exp_by_id: Arc<DashMap<PlayerId, u64>>,
+    /// When each player last got a token or connected or dropped a socket;
+    /// refresh is refused once this is older than the idle timeout.
+    last_active_by_id: DashMap<PlayerId, u64>,
+    /// Unix millis of each player's last connect or disconnect; together with
+    /// their route it says how long they have been connected or away.
+    connection_changed_at_ms: DashMap<PlayerId, u64>,
+    /// Cleared on every round start and ready check.
+    ready_by_id: DashMap<PlayerId, bool>,
+    /// Players whose buzzes, chat and reactions are dropped.
+    muted: DashSet<PlayerId>,
+    /// When each kicked player was kicked; tokens they got before that are refused.
+    kicked_at_by_id: DashMap<PlayerId, u64>,
+    /// Normalized names of kicked players, refused on join and rename until
+    /// the admin unbans them.
+    banned_names: DashSet<String>,
+    /// Palette index per participant, handed out by [`next_color`](Self::next_color).
+    color_by_id: DashMap<PlayerId, u8>,
+    /// Serialized `participants` message and the lockout mask it was built
+    /// with; cleared by [`invalidate_participants`](Self::invalidate_participants).
+    participants_cache: Mutex<Option<(u128, Arc<str>)>>,
+    /// Set while a [`broadcast_participants_soon`](Self::broadcast_participants_soon) is due.
+    participants_flush_pending: AtomicBool,
+    /// Set by [`shutdown`](Self::shutdown), before the room loop drops the routes.
+    closed: AtomicBool,
+    /// Every buzz is denied while set; the next round start clears it.
+    buzzing_locked: AtomicBool,
+    scores: Arc<DashMap<PlayerId, u32>>,
+    history: Arc<Mutex<RoundHistory>>,
     command_tx: mpsc::UnboundedSender<RoomCommand>,
-    next_id: Arc<Mutex<PlayerId>>,
-    reset_flag: Arc<AtomicBool>,
-    continue_flag: Arc<AtomicBool>,
-    shutdown: Arc<AtomicBool>,
-    locked_out_mask: Arc<Mutex<u128>>,
+    next_id: Mutex<PlayerId>,
+    /// Player ids given back by [`remove_player`](Self::remove_player), each with
+    /// the time from which it may be handed out again.
+    free_ids: Mutex<BTreeMap<PlayerId, u64>>,
+    next_spectator_id: Mutex<PlayerId>,
+    /// The room creator until they hand over with [`transfer_admin`](Self::transfer_admin).
+    /// Shared with the room loop, which sends the admin buzz details.
+    admin_id: Arc<Mutex<PlayerId>>,
+    control_tx: mpsc::UnboundedSender<RoomControl>,
+    game_view: watch::Receiver<GameView>,
+    chat_limiter: DefaultKeyedRateLimiter<PlayerId>,
+    reaction_limiter: DefaultKeyedRateLimiter<PlayerId>,
 }
 
+/// Commands from a socket carry the `id` of the client message they came from
+/// as `ack_id`, to be echoed on the answer; the HTTP API passes `None`.
 enum RoomCommand {
     CreateAdmin {
         name: String,
@@ -50,83 +175,228 @@ enum RoomCommand {
     Join {
         requested_name: String,
         token: Option<String>,
+        role: Role,
         resp: oneshot::Sender<Result<(String, Role), AppError>>,
     },
     RefreshToken {
         token: String,
-        resp: oneshot::Sender<Result<String, AppError>>,
+        resp: oneshot::Sender<Result<(String, Claims), AppError>>,
     },
     AttachConnection {
         player_id: PlayerId,
         name: String,
-        sender: mpsc::UnboundedSender<String>,
-        resp: oneshot::Sender<bool>,
+        sender: mpsc::Sender<Outbound>,
+        /// Last `seq` the client saw on its previous connection.
+        since_seq: Option<u64>,
+        /// Whether to displace a socket that is still connected rather than
+        /// give up with `AlreadyConnected`.
+        takeover: bool,
+        resp: oneshot::Sender<Result<bool, AppError>>,
     },
     DetachConnection {
         player_id: PlayerId,
+        sender: mpsc::WeakSender<Outbound>,
+    },
+    Leave {
+        player_id: PlayerId,
+        resp: oneshot::Sender<Result<(), AppError>>,
+    },
+    SetReady {
+        player_id: PlayerId,
+        ready: bool,
+        ack_id: Option<String>,
+    },
+    Resync {
+        player_id: PlayerId,
+        ack_id: Option<String>,
+    },
+    RequestReady {
+        requester_id: PlayerId,
+        ack_id: Option<String>,
+    },
+    Rename {
+        player_id: PlayerId,
+        new_name: String,
+        ack_id: Option<String>,
+    },
+    UpdateSettings {
+        answer_window_in_ms: Option<u64>,
+        max_players: Option<usize>,
+        /// `Some(None)` removes the password.
+        password_hash: Option<Option<String>>,
+        resp: oneshot::Sender<RoomSettings>,
+    },
+    SetMuted {
+        requester_id: PlayerId,
+        name: String,
+        muted: bool,
+        ack_id: Option<String>,
+    },
+    Unban {
+        requester_id: PlayerId,
+        name: String,
+        ack_id: Option<String>,
     },
     KickByName {
         requester_id: PlayerId,
         name: String,
-        resp: oneshot::Sender<bool>,
+        ack_id: Option<String>,
+        resp: oneshot::Sender<Result<(), AppError>>,
+    },
+    TransferAdmin {
+        requester_id: PlayerId,
+        name: String,
+        resp: oneshot::Sender<Result<(), AppError>>,
     },
     StartRound {
         requester_id: PlayerId,
+        countdown_ms: Option<u64>,
+        question: Option<String>,
+        ack_id: Option<String>,
+        resp: oneshot::Sender<Result<(), AppError>>,
     },
     ContinueRound {
         requester_id: PlayerId,
+        ack_id: Option<String>,
+        resp: oneshot::Sender<Result<(), AppError>>,
+    },
+    MarkCorrect {
+        requester_id: PlayerId,
+        ack_id: Option<String>,
+    },
+    Grant {
+        requester_id: PlayerId,
+        name: String,
+        force: bool,
+        ack_id: Option<String>,
+    },
+    Undo {
+        requester_id: PlayerId,
+        ack_id: Option<String>,
+    },
+    NewGame {
+        requester_id: PlayerId,
+        ack_id: Option<String>,
+    },
+    Chat {
+        player_id: PlayerId,
+        text: String,
+        system: bool,
+        ack_id: Option<String>,
+    },
+    React {
+        player_id: PlayerId,
+        emoji: String,
+        ack_id: Option<String>,
+    },
+    Pause {
+        requester_id: PlayerId,
+        ack_id: Option<String>,
+    },
+    Resume {
+        requester_id: PlayerId,
+        ack_id: Option<String>,
+    },
+    SetLocked {
+        requester_id: PlayerId,
+        locked: bool,
+        ack_id: Option<String>,
     },
     CleanupExpired,
+    /// Hands the room over once the admin has been away past the grace period.
+    CheckAdmin,
+    /// The debounced broadcast of [`broadcast_participants_soon`](RoomState::broadcast_participants_soon).
+    FlushParticipants,
 }
 
 impl RoomState {
     pub(super) fn new(
         id: RoomId,
         config: RoomConfig,
-        tick_in_ms: u64,
         auth: Arc<JwtAuth>,
+        name_filter: Arc<NameFilter>,
+        webhook: Option<RoomWebhook>,
     ) -> Arc<Self> {
-        let (buzz_tx, buzz_rx) = mpsc::unbounded_channel::<PlayerId>();
+        let (buzz_tx, buzz_rx) = mpsc::unbounded_channel::<Buzz>();
         let routes = Arc::new(DashMap::new());
+        let broadcaster = Arc::new(Broadcaster::default());
         let names_by_id = Arc::new(DashMap::new());
         let ids_by_name = Arc::new(DashMap::new());
         let token_exp_by_id = Arc::new(DashMap::new());
-        let next_id = Arc::new(Mutex::new(0));
-        let reset_flag = Arc::new(AtomicBool::new(false));
-        let continue_flag = Arc::new(AtomicBool::new(false));
-        let shutdown = Arc::new(AtomicBool::new(false));
-        let locked_out_mask = Arc::new(Mutex::new(0));
+        let scores = Arc::new(DashMap::new());
+        let history = Arc::new(Mutex::new(RoundHistory::new(config.history_limit)));
+        let admin_id = Arc::new(Mutex::new(ADMIN_PLAYER_ID));
+        let next_id = Mutex::new(0);
+        let (control_tx, control_rx) = mpsc::unbounded_channel::<RoomControl>();
+        let (view_tx, game_view) = watch::channel(GameView::default());
         let (command_tx, command_rx) = mpsc::unbounded_channel::<RoomCommand>();
 
+        let clock = InstantTime::new();
         spawn_room_loop(
-            tick_in_ms,
+            clock.clone(),
             config.answer_window_in_ms,
             buzz_rx,
-            Arc::clone(&reset_flag),
-            Arc::clone(&continue_flag),
-            Arc::clone(&shutdown),
-            Arc::clone(&locked_out_mask),
+            control_rx,
+            view_tx,
             Arc::clone(&routes),
+            Arc::clone(&broadcaster),
             Arc::clone(&names_by_id),
-            Arc::clone(&next_id),
+            Arc::clone(&scores),
+            Arc::clone(&history),
+            Arc::clone(&admin_id),
+            webhook,
         );
 
         let room = Arc::new(Self {
             // id,
             room_id: id,
             auth,
-            answer_window_in_ms: config.answer_window_in_ms,
+            name_filter,
+            settings: Mutex::new(RoomSettings {
+                answer_window_in_ms: config.answer_window_in_ms,
+                max_players: MAX_PLAYERS,
+            }),
+            inbound_rate_per_sec: NonZeroU32::new(
+                config
+                    .inbound_rate_per_sec
+                    .clamp(MIN_INBOUND_RATE_PER_SEC, MAX_INBOUND_RATE_PER_SEC),
+            )
+            .expect("clamped inbound rate is non-zero"),
+            idle_timeout_in_ms: config.idle_timeout_in_ms,
+            last_activity_ms: AtomicU64::new(now_millis()),
+            admin_grace_in_ms: config.admin_grace_in_ms,
+            buzz_debounce_in_ms: config.buzz_debounce_in_ms,
+            last_buzz_ms_by_id: DashMap::new(),
+            password_hash: Mutex::new(None),
             buzz_tx,
+            clock,
             routes,
+            broadcaster,
             names_by_id,
             ids_by_name,
             token_exp_by_id,
+            last_active_by_id: DashMap::new(),
+            connection_changed_at_ms: DashMap::new(),
+            ready_by_id: DashMap::new(),
+            muted: DashSet::new(),
+            kicked_at_by_id: DashMap::new(),
+            banned_names: DashSet::new(),
+            color_by_id: DashMap::new(),
+            participants_cache: Mutex::new(None),
+            participants_flush_pending: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            buzzing_locked: AtomicBool::new(false),
+            scores,
+            history,
             command_tx,
             next_id,
-            reset_flag,
-            continue_flag,
-            shutdown,
-            locked_out_mask,
+            free_ids: Mutex::new(BTreeMap::new()),
+            next_spectator_id: Mutex::new(FIRST_SPECTATOR_ID),
+            admin_id,
+            control_tx,
+            game_view,
+            chat_limiter: per_player_limiter(CHAT_PERIOD, CHAT_BURST),
+            reaction_limiter: per_player_limiter(REACTION_PERIOD, REACTION_BURST),
         });
 
         RoomState::spawn_command_loop(Arc::clone(&room), command_rx);
@@ -134,3 +404,11 @@ impl RoomState {
         room
     }
 }
+
+fn per_player_limiter(period: Duration, burst: u32) -> DefaultKeyedRateLimiter<PlayerId> {
+    RateLimiter::keyed(
+        Quota::with_period(period)
+            .expect("non-zero rate limit period")
+            .allow_burst(NonZeroU32::new(burst).expect("non-zero rate limit burst")),
+    )
+}
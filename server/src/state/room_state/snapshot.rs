@@ -0,0 +1,109 @@
+use super::*;
+use crate::utils::name::normalize_name;
+use crate::utils::time::now_seconds;
+
+impl RoomState {
+    /// Captures the room for [`AppState::restore`](crate::state::app_state::AppState::restore).
+    /// Players are ordered by id.
+    pub fn snapshot(&self) -> RoomSnapshot {
+        let settings = self.settings();
+        let mut players = self
+            .names_by_id
+            .iter()
+            .map(|entry| {
+                let id = *entry.key();
+                PlayerSnapshot {
+                    id,
+                    name: entry.value().clone(),
+                    role: self.role_of(id),
+                    score: self.scores.get(&id).map_or(0, |score| *score.value()),
+                    muted: self.is_muted(id),
+                    color: self.color_by_id.get(&id).map_or(0, |color| *color.value()),
+                    token_exp: self.token_exp_by_id.get(&id).map_or(0, |exp| *exp.value()),
+                }
+            })
+            .collect::<Vec<_>>();
+        players.sort_by_key(|player| player.id);
+
+        RoomSnapshot {
+            room_id: self.room_id.clone(),
+            answer_window_in_ms: settings.answer_window_in_ms,
+            max_players: settings.max_players,
+            inbound_rate_per_sec: self.inbound_rate_per_sec.get(),
+            password_hash: self
+                .password_hash
+                .lock()
+                .expect("lock password hash")
+                .clone(),
+            admin_id: self.admin_id(),
+            next_id: *self.next_id.lock().expect("next_id lock"),
+            next_spectator_id: *self
+                .next_spectator_id
+                .lock()
+                .expect("next_spectator_id lock"),
+            round: self.game_view.borrow().round,
+            players,
+        }
+    }
+
+    /// Fills a room that was just created from `snapshot`; nobody may have
+    /// joined yet. Fails with `invalid_request` on a snapshot this room could
+    /// not have produced, leaving the caller to close the room again.
+    pub(crate) fn restore(&self, snapshot: &RoomSnapshot) -> Result<(), AppError> {
+        let valid_id = |id: PlayerId| {
+            (id < snapshot.next_id && id < core::game::MAX_PLAYER_ID)
+                || (FIRST_SPECTATOR_ID..snapshot.next_spectator_id).contains(&id)
+        };
+        let mut names = std::collections::HashSet::new();
+        let valid = snapshot.players.iter().all(|player| {
+            valid_id(player.id)
+                && player.color < PLAYER_COLORS
+                && names.insert(normalize_name(&player.name))
+        }) && snapshot
+            .players
+            .iter()
+            .any(|player| player.id == snapshot.admin_id && !self.is_spectator(player.id));
+        if !valid || !self.names_by_id.is_empty() {
+            return Err(AppError::InvalidRequest);
+        }
+
+        for player in &snapshot.players {
+            self.ids_by_name
+                .insert(normalize_name(&player.name), player.id);
+            self.names_by_id.insert(player.id, player.name.clone());
+            self.color_by_id.insert(player.id, player.color);
+            self.token_exp_by_id.insert(player.id, player.token_exp);
+            if player.score > 0 {
+                self.scores.insert(player.id, player.score);
+            }
+            if player.muted {
+                self.muted.insert(player.id);
+            }
+            // The idle timeout for refreshing starts over with the restart.
+            self.mark_active(player.id);
+        }
+        *self.admin_id.lock().expect("lock admin id") = snapshot.admin_id;
+        *self.next_id.lock().expect("next_id lock") = snapshot.next_id;
+        // Ids of players who left before the snapshot; their tokens may live
+        // for up to one more token lifetime.
+        let reusable_at = now_seconds() + self.auth.ttl_seconds();
+        *self.free_ids.lock().expect("free_ids lock") = (0..snapshot.next_id)
+            .filter(|id| !self.names_by_id.contains_key(id) && *id != snapshot.admin_id)
+            .map(|id| (id, reusable_at))
+            .collect();
+        *self
+            .next_spectator_id
+            .lock()
+            .expect("next_spectator_id lock") = snapshot.next_spectator_id;
+        self.settings
+            .lock()
+            .expect("lock room settings")
+            .max_players = snapshot.max_players;
+        self.set_password_hash(snapshot.password_hash.clone());
+        self.send_control(RoomControl::RestoreRound {
+            round: snapshot.round,
+        });
+        self.invalidate_participants();
+        Ok(())
+    }
+}
@@ -0,0 +1,1927 @@
+use super::*;
+use crate::auth::DEFAULT_ISSUER;
+use crate::state::app_state::ADMIN_PLAYER_ID;
+use crate::utils::testing::{block_on, forward_broadcasts, next_of_type, outbound_channel};
+use crate::utils::time::now_seconds;
+
+const SECRET: &[u8] = b"room-test-secret-room-test-secret";
+
+fn test_room() -> Arc<RoomState> {
+    room_with_auth(JwtAuth::new(SECRET, 60, DEFAULT_ISSUER))
+}
+
+const CONFIG: RoomConfig = RoomConfig {
+    answer_window_in_ms: 1000,
+    history_limit: 10,
+    inbound_rate_per_sec: DEFAULT_INBOUND_RATE_PER_SEC,
+    idle_timeout_in_ms: DEFAULT_IDLE_TIMEOUT_IN_MS,
+    admin_grace_in_ms: DEFAULT_ADMIN_GRACE_IN_MS,
+    buzz_debounce_in_ms: DEFAULT_BUZZ_DEBOUNCE_IN_MS,
+};
+
+fn room_with_auth(auth: JwtAuth) -> Arc<RoomState> {
+    room_with(auth, CONFIG)
+}
+
+fn room_with(auth: JwtAuth, config: RoomConfig) -> Arc<RoomState> {
+    RoomState::new(
+        "room01".to_string(),
+        config,
+        Arc::new(auth),
+        Arc::new(NameFilter::default()),
+        None,
+    )
+}
+
+#[test]
+fn names_collide_ignoring_case_and_whitespace() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Bob").unwrap();
+        for name in ["bob", " BOB ", "bOb"] {
+            assert!(
+                matches!(
+                    room.resolve_join_direct(name, None, Role::Player),
+                    Err(AppError::NameTaken)
+                ),
+                "{name:?} should collide with Bob"
+            );
+        }
+        assert!(
+            room.resolve_join_direct("Bobby", None, Role::Player)
+                .is_ok()
+        );
+    });
+}
+
+#[test]
+fn colors_are_unique_until_the_palette_wraps() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        for i in 1..=PLAYER_COLORS {
+            room.resolve_join_direct(&format!("P{i:02}"), None, Role::Player)
+                .unwrap();
+        }
+        let color_of = |name: &str| {
+            room.participants()
+                .into_iter()
+                .find(|p| p.name == name)
+                .map(|p| p.color)
+                .unwrap()
+        };
+
+        // Join order decides: Aaron 0, P01 1, ... P11 11, then P12 wraps to 0.
+        let mut colors: Vec<_> = room.participants().iter().map(|p| p.color).collect();
+        colors.sort();
+        colors.dedup();
+        assert_eq!(colors.len(), PLAYER_COLORS as usize);
+        assert_eq!(color_of("P05"), 5);
+        assert_eq!(color_of("P12"), 0);
+
+        // A freed color is handed out before any further repeats.
+        let p05 = room
+            .ids_by_name
+            .get("p05")
+            .map(|entry| *entry.value())
+            .unwrap();
+        room.remove_player(p05).unwrap();
+        room.resolve_join_direct("Zed", None, Role::Player).unwrap();
+        assert_eq!(color_of("Zed"), 5);
+        room.resolve_join_direct("Yan", None, Role::Player).unwrap();
+        assert_eq!(color_of("Yan"), 1);
+    });
+}
+
+#[test]
+fn joining_again_with_a_token_keeps_the_player() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        let (mut token, _) = room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = room
+            .ids_by_name
+            .get("bob")
+            .map(|entry| *entry.value())
+            .unwrap();
+        let next_id = *room.next_id.lock().unwrap();
+        let (bob_tx, _bob_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+
+        for _ in 0..5 {
+            token = room
+                .resolve_join_direct("Bob", Some(&token), Role::Player)
+                .unwrap()
+                .0;
+        }
+        let (token, role) = room
+            .resolve_join_direct("Robert", Some(&token), Role::Player)
+            .unwrap();
+
+        assert_eq!(role, Role::Player);
+        let claims = room.auth.verify(&token, "room01").unwrap();
+        assert_eq!((claims.player_id, claims.name.as_str()), (bob, "Robert"));
+        assert!(room.player_matches(bob, "Robert"));
+        assert_eq!(*room.next_id.lock().unwrap(), next_id);
+        assert_eq!(room.participants().len(), 2);
+        // The open socket was never dropped.
+        assert!(room.routes.get(&bob).unwrap().is_connected());
+    });
+}
+
+/// Pretends every released id's last token ran out.
+fn expire_free_ids(room: &RoomState) {
+    for reusable_at in room.free_ids.lock().unwrap().values_mut() {
+        *reusable_at = 0;
+    }
+}
+
+#[test]
+fn churn_reuses_player_ids_instead_of_filling_the_room() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        for i in 0..3 * core::game::MAX_PLAYER_ID {
+            let name = format!("Guest {i}");
+            room.resolve_join_direct(&name, None, Role::Player).unwrap();
+            room.remove_player(player_id_of(&room, &name)).unwrap();
+            expire_free_ids(&room);
+        }
+        assert_eq!(*room.next_id.lock().unwrap(), 2);
+        assert_eq!(room.participants().len(), 1);
+    });
+}
+
+#[test]
+fn kicked_players_id_goes_to_a_newcomer_only_after_their_token_expires() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        let (bob_token, _) = room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        room.kick_by_name_direct(ADMIN_PLAYER_ID, "Bob").unwrap();
+
+        // Bob's token is still live, so his id is not up for grabs yet.
+        room.resolve_join_direct("Cara", None, Role::Player)
+            .unwrap();
+        assert_ne!(player_id_of(&room, "Cara"), bob);
+
+        expire_free_ids(&room);
+        room.resolve_join_direct("Dan", None, Role::Player).unwrap();
+        assert_eq!(player_id_of(&room, "Dan"), bob);
+
+        // The old token names Bob, not Dan, and gets nothing.
+        assert!(matches!(
+            room.resolve_join_direct("Bob", Some(&bob_token), Role::Player),
+            Err(AppError::Banned)
+        ));
+        assert!(matches!(
+            room.refresh_token_direct(&bob_token),
+            Err(AppError::Banned)
+        ));
+        let (tx, _rx) = outbound_channel();
+        assert!(!room.attach_connection_direct(bob, "Bob", tx));
+        assert!(room.player_matches(bob, "Dan"));
+    });
+}
+
+#[test]
+fn display_name_is_preserved() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Alice").unwrap();
+        room.resolve_join_direct("Big  Bob", None, Role::Player)
+            .unwrap();
+        let names: Vec<_> = room.participants().into_iter().map(|p| p.name).collect();
+        assert_eq!(names, ["Alice", "Big  Bob"]);
+    });
+}
+
+#[test]
+fn kick_matches_normalized_name() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Alice").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        room.kick_by_name_direct(ADMIN_PLAYER_ID, " bOB ").unwrap();
+        assert!(room.participants().iter().all(|p| p.name != "Bob"));
+        assert!(matches!(
+            room.resolve_join_direct("bob", None, Role::Player),
+            Err(AppError::Banned)
+        ));
+    });
+}
+
+#[test]
+fn connection_refusal_says_why_a_socket_lost_its_seat() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Alice").unwrap();
+        let mut issued_at = Vec::new();
+        for name in ["Bob", "Carol", "Dave"] {
+            let (token, _) = room.resolve_join_direct(name, None, Role::Player).unwrap();
+            let claims = room.auth.verify(&token, "room01").unwrap();
+            issued_at.push((claims.player_id, claims.iat));
+        }
+        let [(bob, bob_iat), (carol, carol_iat), (dave, dave_iat)] = issued_at[..] else {
+            unreachable!()
+        };
+        assert!(room.connection_refusal(bob, "Bob", bob_iat).is_none());
+
+        room.kick_by_name_direct(ADMIN_PLAYER_ID, "Bob").unwrap();
+        room.rename_player(carol, "Caroline").unwrap();
+        room.leave_direct(dave).unwrap();
+        assert!(matches!(
+            room.connection_refusal(bob, "Bob", bob_iat),
+            Some(AppError::Banned)
+        ));
+        assert!(matches!(
+            room.connection_refusal(carol, "Carol", carol_iat),
+            Some(AppError::InvalidToken)
+        ));
+        assert!(
+            room.connection_refusal(carol, "Caroline", carol_iat)
+                .is_none()
+        );
+        assert!(matches!(
+            room.connection_refusal(dave, "Dave", dave_iat),
+            Some(AppError::UserNotInRoom)
+        ));
+    });
+}
+
+fn player_id_of(room: &RoomState, name: &str) -> PlayerId {
+    *room
+        .ids_by_name
+        .get(&crate::utils::name::normalize_name(name))
+        .expect("player in room")
+        .value()
+}
+
+#[test]
+fn rename_keeps_seat_and_reissues_token() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Alice").unwrap();
+        let (old_token, _) = room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+
+        let (old_name, token) = room.rename_player(bob, "  Robert ").unwrap();
+
+        assert_eq!(old_name, "Bob");
+        assert!(matches!(
+            room.refresh_token_direct(&old_token),
+            Err(AppError::UserNotInRoom)
+        ));
+        assert_eq!(player_id_of(&room, "robert"), bob);
+        assert!(room.player_matches(bob, "Robert"));
+        let claims = room.auth.verify(&token, "room01").unwrap();
+        assert_eq!((claims.player_id, claims.name.as_str()), (bob, "Robert"));
+        // The old name is free again.
+        assert!(room.resolve_join_direct("Bob", None, Role::Player).is_ok());
+    });
+}
+
+#[test]
+fn rename_to_taken_name_is_rejected() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Alice").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+
+        assert!(matches!(
+            room.rename_player(bob, "ALICE"),
+            Err(AppError::NameTaken)
+        ));
+        assert!(room.player_matches(bob, "Bob"));
+        // Changing only the case of your own name is fine.
+        assert!(room.rename_player(bob, "BOB").is_ok());
+    });
+}
+
+/// Start a round, have `player` buzz, and mark them correct; returns the
+/// resulting scoreboard broadcast.
+async fn play_correct_round(
+    room: &RoomState,
+    rx: &mut mpsc::Receiver<Outbound>,
+    player: PlayerId,
+) -> serde_json::Value {
+    room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+        .unwrap();
+    next_of_type(rx, "round_started").await;
+    room.send_buzz(player, None);
+    next_of_type(rx, "accepted").await;
+    room.mark_correct_direct(ADMIN_PLAYER_ID).unwrap();
+    next_of_type(rx, "correct").await;
+    next_of_type(rx, "scoreboard").await
+}
+
+#[test]
+fn scoreboard_broadcast_orders_by_score_then_name() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Carol", None, Role::Player)
+            .unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let (tx, mut rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+
+        play_correct_round(&room, &mut rx, player_id_of(&room, "Carol")).await;
+        let board = play_correct_round(&room, &mut rx, player_id_of(&room, "Bob")).await;
+
+        assert_eq!(
+            board["entries"],
+            serde_json::json!([
+                {"name": "Bob", "score": 1},
+                {"name": "Carol", "score": 1},
+                {"name": "Aaron", "score": 0},
+            ])
+        );
+        assert!(board["ts_ms"].as_u64().is_some());
+    });
+}
+
+#[test]
+fn buzz_during_countdown_is_a_false_start() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+
+        room.start_round_direct(ADMIN_PLAYER_ID, Some(200), None)
+            .unwrap();
+        let countdown = next_of_type(&mut admin_rx, "countdown").await;
+        assert_eq!(countdown["starts_in_ms"], 200);
+
+        room.send_buzz(bob, None);
+        next_of_type(&mut bob_rx, "rejected").await;
+
+        next_of_type(&mut admin_rx, "round_started").await;
+        room.send_buzz(bob, None);
+        let accepted = next_of_type(&mut admin_rx, "accepted").await;
+        assert_eq!(accepted["name"], "Bob");
+    });
+}
+
+#[test]
+fn admin_grants_the_floor_by_name() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        room.resolve_join_direct("Carol", None, Role::Player)
+            .unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let carol = player_id_of(&room, "Carol");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+
+        assert_eq!(
+            room.grant_direct(ADMIN_PLAYER_ID, "Nobody", false, None),
+            Err("user_not_found")
+        );
+        assert_eq!(
+            room.grant_direct(bob, "Carol", false, None),
+            Err("forbidden")
+        );
+
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        next_of_type(&mut admin_rx, "round_started").await;
+        room.send_buzz(bob, None);
+        next_of_type(&mut admin_rx, "accepted").await;
+
+        // A tie settled for Carol: she takes the floor from Bob.
+        room.grant(ADMIN_PLAYER_ID, "carol", false, Some("g1".into()));
+        assert_eq!(next_of_type(&mut admin_rx, "action_ok").await["id"], "g1");
+        assert_eq!(room.query_game_view().await.unwrap().answering, Some(carol));
+
+        room.continue_round_direct(ADMIN_PLAYER_ID).unwrap();
+        room.grant(ADMIN_PLAYER_ID, "Carol", false, Some("g2".into()));
+        let denied = next_of_type(&mut admin_rx, "action_denied").await;
+        assert_eq!(
+            (&denied["reason"], &denied["id"]),
+            (&"locked_out".into(), &"g2".into())
+        );
+
+        room.grant(ADMIN_PLAYER_ID, "Carol", true, Some("g3".into()));
+        assert_eq!(next_of_type(&mut admin_rx, "action_ok").await["id"], "g3");
+        assert_eq!(room.query_game_view().await.unwrap().answering, Some(carol));
+    });
+}
+
+#[test]
+fn undo_takes_back_a_correct_answer() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        next_of_type(&mut admin_rx, "round_started").await;
+        room.send_buzz(bob, None);
+        next_of_type(&mut admin_rx, "accepted").await;
+        room.mark_correct_direct(ADMIN_PLAYER_ID).unwrap();
+        let scoreboard = next_of_type(&mut admin_rx, "scoreboard").await;
+        assert_eq!(scoreboard["entries"][0]["score"], 1);
+
+        assert_eq!(room.undo_direct(bob, None), Err("forbidden"));
+        room.undo_direct(ADMIN_PLAYER_ID, None).unwrap();
+        let undone = next_of_type(&mut admin_rx, "undone").await;
+        assert_eq!(
+            (&undone["name"], &undone["round"]),
+            (&"Bob".into(), &1.into())
+        );
+        let scoreboard = next_of_type(&mut admin_rx, "scoreboard").await;
+        assert_eq!(scoreboard["entries"][0]["score"], 0);
+        // Bob is back on the floor, answering as if never judged.
+        assert_eq!(next_of_type(&mut admin_rx, "accepted").await["name"], "Bob");
+        assert_eq!(room.query_game_view().await.unwrap().answering, Some(bob));
+
+        // A wrong answer taken back lifts the lockout it cost too.
+        room.continue_round_direct(ADMIN_PLAYER_ID).unwrap();
+        room.undo_direct(ADMIN_PLAYER_ID, None).unwrap();
+        next_of_type(&mut admin_rx, "undone").await;
+        let view = room.query_game_view().await.unwrap();
+        assert_eq!(view.answering, Some(bob));
+        assert_eq!(view.locked_out & (1 << bob), 0);
+    });
+}
+
+#[test]
+fn undo_with_nothing_judged_is_denied() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+
+        room.undo(ADMIN_PLAYER_ID, Some("u1".into()));
+        let denied = next_of_type(&mut admin_rx, "action_denied").await;
+        assert_eq!(
+            (&denied["reason"], &denied["id"]),
+            (&"nothing_to_undo".into(), &"u1".into())
+        );
+
+        // One judgement undoes once; the stack is empty again after it.
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        next_of_type(&mut admin_rx, "round_started").await;
+        room.send_buzz(bob, None);
+        next_of_type(&mut admin_rx, "accepted").await;
+        room.mark_correct_direct(ADMIN_PLAYER_ID).unwrap();
+        room.undo(ADMIN_PLAYER_ID, Some("u2".into()));
+        assert_eq!(next_of_type(&mut admin_rx, "action_ok").await["id"], "u2");
+        room.undo(ADMIN_PLAYER_ID, Some("u3".into()));
+        let denied = next_of_type(&mut admin_rx, "action_denied").await;
+        assert_eq!(denied["id"], "u3");
+    });
+}
+
+#[test]
+fn countdown_and_answer_times_are_on_the_wall_clock() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+
+        room.start_round_direct(ADMIN_PLAYER_ID, Some(50), None)
+            .unwrap();
+        let countdown = next_of_type(&mut admin_rx, "countdown").await;
+        let (starts_in, starts_at, ts) = (
+            countdown["starts_in_ms"].as_u64().unwrap(),
+            countdown["starts_at_ms"].as_u64().unwrap(),
+            countdown["ts_ms"].as_u64().unwrap(),
+        );
+        assert_eq!((starts_in, starts_at), (50, ts + 50));
+
+        next_of_type(&mut admin_rx, "round_started").await;
+        let buzzed_at = now_millis();
+        room.send_buzz(bob, None);
+        let accepted = next_of_type(&mut admin_rx, "accepted").await;
+        let remaining = accepted["remaining_ms"].as_u64().unwrap();
+        let deadline = accepted["deadline_ms"].as_u64().unwrap();
+        assert_eq!(remaining, CONFIG.answer_window_in_ms);
+        let sent_at = deadline - remaining;
+        assert!(
+            (buzzed_at..=now_millis()).contains(&sent_at),
+            "sent at {sent_at}, buzzed at {buzzed_at}"
+        );
+    });
+}
+
+#[test]
+fn only_the_admin_gets_buzz_details() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        room.resolve_join_direct("Carol", None, Role::Player)
+            .unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let carol = player_id_of(&room, "Carol");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        let (carol_tx, mut carol_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+        assert!(room.attach_connection_direct(carol, "Carol", carol_tx));
+        forward_broadcasts(&room, carol);
+
+        room.start_round_direct(ADMIN_PLAYER_ID, Some(200), None)
+            .unwrap();
+        next_of_type(&mut admin_rx, "countdown").await;
+        room.send_buzz(carol, None);
+        let early = next_of_type(&mut admin_rx, "buzz_detail").await;
+        assert_eq!(early["name"], "Carol");
+        assert_eq!(early["accepted"], false);
+        assert!(early["reaction_ms"].is_null());
+
+        next_of_type(&mut admin_rx, "round_started").await;
+        room.send_buzz(bob, None);
+        let accepted = next_of_type(&mut admin_rx, "buzz_detail").await;
+        assert_eq!(accepted["name"], "Bob");
+        assert_eq!(accepted["accepted"], true);
+        assert!(accepted["reaction_ms"].is_u64());
+        assert!(accepted["ts_ms"].is_u64());
+
+        // Bob holds the floor, so this one is rejected too.
+        room.send_buzz(carol, None);
+        let rejected = next_of_type(&mut admin_rx, "buzz_detail").await;
+        assert_eq!(rejected["name"], "Carol");
+        assert_eq!(rejected["accepted"], false);
+
+        room.mark_correct(ADMIN_PLAYER_ID, None);
+        loop {
+            let text = carol_rx.recv().await.expect("route closed");
+            let msg: serde_json::Value = serde_json::from_str(&text).unwrap();
+            assert_ne!(msg["type"], "buzz_detail");
+            if msg["type"] == "correct" {
+                break;
+            }
+        }
+    });
+}
+
+#[test]
+fn locked_room_denies_every_buzz_until_unlocked() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+
+        room.set_locked(bob, true, Some("l0".into()));
+        let denied = next_of_type(&mut bob_rx, "action_denied").await;
+        assert_eq!(
+            (&denied["reason"], &denied["id"]),
+            (&"forbidden".into(), &"l0".into())
+        );
+
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        next_of_type(&mut admin_rx, "round_started").await;
+        room.set_locked(ADMIN_PLAYER_ID, true, Some("l1".into()));
+        assert_eq!(next_of_type(&mut admin_rx, "action_ok").await["id"], "l1");
+        assert_eq!(next_of_type(&mut bob_rx, "room_lock").await["locked"], true);
+        room.send_buzz(bob, None);
+        let denied = next_of_type(&mut bob_rx, "action_denied").await;
+        assert_eq!(denied["reason"], "room_locked");
+
+        room.set_locked(ADMIN_PLAYER_ID, false, None);
+        assert_eq!(
+            next_of_type(&mut bob_rx, "room_lock").await["locked"],
+            false
+        );
+        room.send_buzz(bob, None);
+        assert_eq!(next_of_type(&mut admin_rx, "accepted").await["name"], "Bob");
+
+        // The next round opens buzzing again without an unlock.
+        room.set_locked(ADMIN_PLAYER_ID, true, None);
+        next_of_type(&mut bob_rx, "room_lock").await;
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        assert_eq!(
+            next_of_type(&mut bob_rx, "room_lock").await["locked"],
+            false
+        );
+        next_of_type(&mut admin_rx, "round_started").await;
+        room.send_buzz(bob, None);
+        assert_eq!(next_of_type(&mut admin_rx, "accepted").await["name"], "Bob");
+    });
+}
+
+#[test]
+fn a_double_fired_buzz_counts_once() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        room.resolve_join_direct("Carol", None, Role::Player)
+            .unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let carol = player_id_of(&room, "Carol");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        next_of_type(&mut admin_rx, "round_started").await;
+        room.send_buzz(bob, None);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        room.send_buzz(bob, None);
+        // Someone else buzzing just as quickly still counts.
+        room.send_buzz(carol, None);
+        room.query_game_view().await.unwrap();
+
+        let mut details = Vec::new();
+        while let Ok(text) = admin_rx.try_recv() {
+            let msg: serde_json::Value = serde_json::from_str(&text).unwrap();
+            if msg["type"] == "buzz_detail" {
+                details.push((msg["name"].clone(), msg["accepted"].clone()));
+            }
+        }
+        assert_eq!(
+            details,
+            [("Bob".into(), true.into()), ("Carol".into(), false.into())]
+        );
+        while let Ok(text) = bob_rx.try_recv() {
+            let msg: serde_json::Value = serde_json::from_str(&text).unwrap();
+            assert_ne!(msg["type"], "rejected");
+        }
+
+        // A repeat that carries an id is acknowledged, not rejected: the buzz
+        // it repeats still stands.
+        room.send_buzz(bob, Some("b2".into()));
+        room.query_game_view().await.unwrap();
+        let mut replies = Vec::new();
+        while let Ok(text) = bob_rx.try_recv() {
+            let msg: serde_json::Value = serde_json::from_str(&text).unwrap();
+            replies.push((msg["type"].clone(), msg["id"].clone()));
+        }
+        assert_eq!(replies, [("action_ok".into(), "b2".into())]);
+    });
+}
+
+/// Collect the `type` of the next `count` round_started / round_continued messages.
+async fn next_round_events(rx: &mut mpsc::Receiver<Outbound>, count: usize) -> Vec<String> {
+    let mut kinds = Vec::new();
+    while kinds.len() < count {
+        let text = rx.recv().await.expect("route closed");
+        let msg: serde_json::Value = serde_json::from_str(&text).unwrap();
+        let kind = msg["type"].as_str().unwrap_or_default();
+        if kind == "round_started" || kind == "round_continued" {
+            kinds.push(kind.to_string());
+        }
+    }
+    kinds
+}
+
+#[test]
+fn controls_apply_in_arrival_order() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        let (tx, mut rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+
+        room.continue_round_direct(ADMIN_PLAYER_ID).unwrap();
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        assert_eq!(
+            next_round_events(&mut rx, 2).await,
+            ["round_continued", "round_started"]
+        );
+
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        room.continue_round_direct(ADMIN_PLAYER_ID).unwrap();
+        assert_eq!(
+            next_round_events(&mut rx, 2).await,
+            ["round_started", "round_continued"]
+        );
+    });
+}
+
+#[test]
+fn pause_rejects_buzzes_and_defers_controls() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        next_of_type(&mut admin_rx, "round_started").await;
+
+        room.pause_direct(ADMIN_PLAYER_ID).unwrap();
+        next_of_type(&mut admin_rx, "paused").await;
+        room.send_buzz(bob, None);
+        next_of_type(&mut bob_rx, "rejected").await;
+        room.continue_round_direct(ADMIN_PLAYER_ID).unwrap();
+        assert!(room.query_game_view().await.unwrap().paused);
+
+        room.resume_direct(ADMIN_PLAYER_ID).unwrap();
+        next_of_type(&mut admin_rx, "resumed").await;
+        next_of_type(&mut admin_rx, "round_continued").await;
+        room.send_buzz(bob, None);
+        let accepted = next_of_type(&mut admin_rx, "accepted").await;
+        assert_eq!(accepted["name"], "Bob");
+        assert_eq!(room.query_game_view().await.unwrap().answering, Some(bob));
+    });
+}
+
+#[test]
+fn reattaching_client_gets_active_question() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        let bob_conn = bob_tx.downgrade();
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+
+        room.start_round_direct(ADMIN_PLAYER_ID, None, Some("  Capital of Peru? ".into()))
+            .unwrap();
+        let question = next_of_type(&mut bob_rx, "question").await;
+        assert_eq!(question["text"], "Capital of Peru?");
+        assert_eq!(question["round"], 1);
+
+        room.detach_connection_direct(bob, &bob_conn);
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+        let resent = next_of_type(&mut bob_rx, "question").await;
+        assert_eq!(resent["text"], question["text"]);
+        assert_eq!(resent["ts_ms"], question["ts_ms"]);
+
+        // A round without a question clears it.
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        next_of_type(&mut bob_rx, "round_started").await;
+        assert_eq!(room.query_game_view().await.unwrap().question, None);
+    });
+}
+
+#[test]
+fn empty_or_oversized_question_is_denied() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        let (tx, mut rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+
+        assert!(matches!(
+            room.start_round(ADMIN_PLAYER_ID, None, Some("   ".into()), None)
+                .await,
+            Err(AppError::QuestionEmpty)
+        ));
+        let denied = next_of_type(&mut rx, "action_denied").await;
+        assert_eq!(denied["reason"], "question_empty");
+
+        let long = "?".repeat(MAX_QUESTION_CHARS + 1);
+        assert!(matches!(
+            room.start_round(ADMIN_PLAYER_ID, None, Some(long), None)
+                .await,
+            Err(AppError::QuestionTooLong)
+        ));
+        let denied = next_of_type(&mut rx, "action_denied").await;
+        assert_eq!(denied["reason"], "question_too_long");
+
+        let view = room.query_game_view().await.unwrap();
+        assert_eq!(view.question, None);
+    });
+}
+
+#[test]
+fn round_numbers_increment_and_reset_on_new_game() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (tx, mut rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+
+        let board = play_correct_round(&room, &mut rx, bob).await;
+        assert_eq!(board["round"], 1);
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        assert_eq!(next_of_type(&mut rx, "round_started").await["round"], 2);
+        room.send_buzz(bob, None);
+        assert_eq!(next_of_type(&mut rx, "accepted").await["round"], 2);
+
+        room.new_game_direct(ADMIN_PLAYER_ID).unwrap();
+        next_of_type(&mut rx, "game_reset").await;
+        let view = room.query_game_view().await.unwrap();
+        assert_eq!((view.round, view.answering), (0, None));
+        assert!(room.scoreboard().iter().all(|entry| entry.score == 0));
+
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        assert_eq!(next_of_type(&mut rx, "round_started").await["round"], 1);
+    });
+}
+
+#[test]
+fn chat_is_sanitized_and_length_checked() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        let (bob_tx, _bob_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+
+        room.chat_direct(bob, " hi\n\u{7}all ", false).unwrap();
+        let chat = next_of_type(&mut admin_rx, "chat").await;
+        assert_eq!(
+            (&chat["from"], &chat["text"], &chat["system"]),
+            (&"Bob".into(), &"hiall".into(), &false.into())
+        );
+
+        assert_eq!(
+            room.chat_direct(bob, &"x".repeat(MAX_CHAT_CHARS + 1), false),
+            Err("chat_too_long")
+        );
+
+        assert_eq!(room.chat_direct(bob, "psst", true), Err("forbidden"));
+
+        room.chat_direct(ADMIN_PLAYER_ID, "Break time", true)
+            .unwrap();
+        let chat = next_of_type(&mut admin_rx, "chat").await;
+        assert_eq!(chat["system"], true);
+    });
+}
+
+#[test]
+fn chat_queued_behind_a_kick_or_leave_is_dropped() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        room.resolve_join_direct("Carol", None, Role::Player)
+            .unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let carol = player_id_of(&room, "Carol");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+
+        // Bob's chat reaches the room right after the kick it raced.
+        let (kicked, ()) = tokio::join!(room.kick_by_name(ADMIN_PLAYER_ID, "Bob", None), async {
+            room.chat(bob, "that was within time!", false, None)
+        },);
+        kicked.unwrap();
+        room.leave(carol).await.unwrap();
+        room.chat(carol, "bye all", false, None);
+
+        room.chat(ADMIN_PLAYER_ID, "moving on", false, None);
+        let chat = next_of_type(&mut admin_rx, "chat").await;
+        assert_eq!(
+            (&chat["from"], &chat["text"]),
+            (&"Aaron".into(), &"moving on".into())
+        );
+    });
+}
+
+#[test]
+fn chat_is_rate_limited_per_player() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+
+        for n in 0..CHAT_BURST {
+            room.chat_direct(bob, &format!("msg {n}"), false).unwrap();
+            next_of_type(&mut bob_rx, "chat").await;
+        }
+        assert_eq!(
+            room.chat_direct(bob, "one too many", false),
+            Err("rate_limited")
+        );
+
+        // Other players have their own bucket.
+        room.chat_direct(ADMIN_PLAYER_ID, "still here", false)
+            .unwrap();
+        let chat = next_of_type(&mut bob_rx, "chat").await;
+        assert_eq!(chat["from"], "Aaron");
+    });
+}
+
+#[test]
+fn leaving_player_is_removed_and_token_rejected() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        let (token, _) = room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+        next_of_type(&mut admin_rx, "participants").await;
+
+        room.leave_direct(bob).unwrap();
+
+        let participants = next_of_type(&mut admin_rx, "participants").await;
+        assert_eq!(participants["participants"].as_array().unwrap().len(), 1);
+        // Bob's route is gone, which is what closes his socket.
+        while bob_rx.try_recv().is_ok() {}
+        assert!(bob_rx.is_closed());
+        assert!(matches!(
+            room.resolve_join_direct("Bob", Some(&token), Role::Player),
+            Err(AppError::Kicked)
+        ));
+        assert!(matches!(
+            room.leave_direct(bob),
+            Err(AppError::UserNotInRoom)
+        ));
+    });
+}
+
+#[test]
+fn admin_leaving_leaves_room_admin_less() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        assert!(room.admin_present());
+
+        room.leave_direct(ADMIN_PLAYER_ID).unwrap();
+
+        assert!(!room.admin_present());
+        let names: Vec<_> = room.participants().into_iter().map(|p| p.name).collect();
+        assert_eq!(names, ["Bob"]);
+    });
+}
+
+#[test]
+fn departed_admins_id_never_goes_to_a_newcomer() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        room.leave_direct(ADMIN_PLAYER_ID).unwrap();
+
+        expire_free_ids(&room);
+        let (_, role) = room
+            .resolve_join_direct("Cara", None, Role::Player)
+            .unwrap();
+
+        assert_eq!(role, Role::Player);
+        assert_ne!(player_id_of(&room, "Cara"), ADMIN_PLAYER_ID);
+        assert!(!room.admin_present());
+    });
+}
+
+#[test]
+fn lone_admin_leaving_empties_room() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+
+        room.leave_direct(ADMIN_PLAYER_ID).unwrap();
+
+        assert!(!room.admin_present());
+        assert!(room.participants().is_empty());
+    });
+}
+
+#[test]
+fn reactions_accept_a_single_emoji_only() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        let (bob_tx, _bob_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+
+        room.react_direct(bob, "👍🏽").unwrap();
+        let reaction = next_of_type(&mut admin_rx, "reaction").await;
+        assert_eq!(
+            (&reaction["from"], &reaction["emoji"]),
+            (&"Bob".into(), &"👍🏽".into())
+        );
+
+        for payload in ["👍👍", "a", "ok", ""] {
+            assert_eq!(
+                room.react_direct(bob, payload),
+                Err("invalid_emoji"),
+                "{payload:?}"
+            );
+        }
+    });
+}
+
+fn ready_names(participants: &serde_json::Value) -> Vec<String> {
+    participants["participants"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|p| p["ready"] == true)
+        .map(|p| p["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn ready_flags_are_broadcast_and_cleared_on_round_start() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (tx, mut rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+        next_of_type(&mut rx, "participants").await;
+
+        room.set_ready_direct(bob, true);
+        let participants = next_of_type(&mut rx, "participants").await;
+        assert_eq!(ready_names(&participants), ["Bob"]);
+
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        let participants = next_of_type(&mut rx, "participants").await;
+        assert!(ready_names(&participants).is_empty());
+    });
+}
+
+#[test]
+fn ready_check_resets_flags() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+        room.set_ready_direct(bob, true);
+        room.set_ready_direct(ADMIN_PLAYER_ID, true);
+
+        assert_eq!(room.request_ready_direct(bob), Err("forbidden"));
+
+        room.request_ready_direct(ADMIN_PLAYER_ID).unwrap();
+        next_of_type(&mut bob_rx, "ready_check").await;
+        let participants = next_of_type(&mut bob_rx, "participants").await;
+        assert!(ready_names(&participants).is_empty());
+    });
+}
+
+#[test]
+fn muted_player_cannot_buzz_until_unmuted() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        let (bob_tx, _bob_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+        next_of_type(&mut admin_rx, "participants").await;
+
+        room.set_muted_direct(ADMIN_PLAYER_ID, "bob", true).unwrap();
+        let participants = next_of_type(&mut admin_rx, "participants").await;
+        let bob_info = &participants["participants"][1];
+        assert_eq!(
+            (&bob_info["name"], &bob_info["muted"]),
+            (&"Bob".into(), &true.into())
+        );
+
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        next_of_type(&mut admin_rx, "round_started").await;
+        room.send_buzz(bob, None);
+        assert_eq!(room.chat_direct(bob, "let me in", false), Err("muted"));
+        // The buzz never reached the game.
+        assert_eq!(room.query_game_view().await.unwrap().answering, None);
+
+        room.set_muted_direct(ADMIN_PLAYER_ID, "Bob", false)
+            .unwrap();
+        room.send_buzz(bob, None);
+        let accepted = next_of_type(&mut admin_rx, "accepted").await;
+        assert_eq!(accepted["name"], "Bob");
+    });
+}
+
+#[test]
+fn seq_increments_per_connection_and_restarts_on_reattach() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        let bob_conn = bob_tx.downgrade();
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+
+        room.chat_direct(ADMIN_PLAYER_ID, "one", false).unwrap();
+        room.chat_direct(ADMIN_PLAYER_ID, "two", false).unwrap();
+        // Let the forwarder pass the broadcasts on.
+        tokio::task::yield_now().await;
+        let mut seqs = Vec::new();
+        while let Ok(text) = bob_rx.try_recv() {
+            let msg: serde_json::Value = serde_json::from_str(&text).unwrap();
+            seqs.push(msg["seq"].as_u64().unwrap());
+        }
+        // welcome, then the two chat broadcasts.
+        assert_eq!(seqs, [0, 1, 2]);
+
+        room.detach_connection_direct(bob, &bob_conn);
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+        let first: serde_json::Value = serde_json::from_str(&bob_rx.recv().await.unwrap()).unwrap();
+        assert_eq!(
+            (&first["type"], &first["seq"]),
+            (&"welcome".into(), &0.into())
+        );
+        assert_eq!(first["game"]["round"], 0);
+        assert_eq!(first["game"]["paused"], false);
+    });
+}
+
+fn seqs_and_texts(rx: &mut mpsc::Receiver<Outbound>) -> Vec<(u64, String)> {
+    let mut received = Vec::new();
+    while let Ok(text) = rx.try_recv() {
+        let msg: serde_json::Value = serde_json::from_str(&text).unwrap();
+        let text = msg["text"].as_str().unwrap_or_default().to_string();
+        received.push((msg["seq"].as_u64().unwrap(), text));
+    }
+    received
+}
+
+#[test]
+fn resync_resends_the_room_under_the_next_seqs() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        let (last_seq, _) = *seqs_and_texts(&mut bob_rx).last().unwrap();
+
+        room.start_round_direct(ADMIN_PLAYER_ID, None, Some("Capital of Peru?".into()))
+            .unwrap();
+        room.query_game_view().await.unwrap();
+        room.resync(bob, None);
+        let welcome = next_of_type(&mut bob_rx, "welcome").await;
+        assert_eq!(welcome["seq"], last_seq + 1);
+        assert_eq!(welcome["game"]["round"], 1);
+        let question = next_of_type(&mut bob_rx, "question").await;
+        assert_eq!(question["seq"], last_seq + 2);
+        assert_eq!(question["text"], "Capital of Peru?");
+    });
+}
+
+#[test]
+fn reconnect_with_since_seq_replays_missed_messages_in_order() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        let bob_conn = bob_tx.downgrade();
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+        room.chat_direct(ADMIN_PLAYER_ID, "seen", false).unwrap();
+        tokio::task::yield_now().await;
+        let last_seen = seqs_and_texts(&mut bob_rx).last().unwrap().0;
+
+        room.detach_connection_direct(bob, &bob_conn);
+        room.chat_direct(ADMIN_PLAYER_ID, "missed 1", false)
+            .unwrap();
+        room.chat_direct(ADMIN_PLAYER_ID, "missed 2", false)
+            .unwrap();
+        assert!(bob_rx.try_recv().is_err());
+
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        assert!(room.resume_connection_direct(bob, "Bob", bob_tx, last_seen));
+        assert_eq!(
+            seqs_and_texts(&mut bob_rx),
+            [
+                (last_seen + 1, "missed 1".to_string()),
+                (last_seen + 2, "missed 2".to_string())
+            ]
+        );
+
+        // Live traffic continues the same numbering.
+        room.send_denied_to(bob, "forbidden", None);
+        assert_eq!(seqs_and_texts(&mut bob_rx)[0].0, last_seen + 3);
+    });
+}
+
+#[test]
+fn reconnect_picks_up_broadcasts_nobody_forwarded() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        // No forwarder at all: like a websocket that has gone away.
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        let bob_conn = bob_tx.downgrade();
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        let last_seen = seqs_and_texts(&mut bob_rx).last().unwrap().0;
+        room.detach_connection_direct(bob, &bob_conn);
+        room.chat_direct(ADMIN_PLAYER_ID, "while away", false)
+            .unwrap();
+
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        assert!(room.resume_connection_direct(bob, "Bob", bob_tx, last_seen));
+        assert_eq!(
+            seqs_and_texts(&mut bob_rx),
+            [(last_seen + 1, "while away".to_string())]
+        );
+    });
+}
+
+#[test]
+fn reconnect_past_the_replay_buffer_starts_a_fresh_stream() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        let bob_conn = bob_tx.downgrade();
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        forward_broadcasts(&room, bob);
+        let last_seen = seqs_and_texts(&mut bob_rx).last().unwrap().0;
+
+        room.detach_connection_direct(bob, &bob_conn);
+        for _ in 0..=crate::adapter::REPLAY_BUFFER_LEN {
+            room.send_denied_to(bob, "forbidden", None);
+        }
+
+        let (bob_tx, mut bob_rx) = outbound_channel();
+        assert!(room.resume_connection_direct(bob, "Bob", bob_tx, last_seen));
+        let received = seqs_and_texts(&mut bob_rx);
+        assert_eq!(received[0].0, 0);
+        assert!(received.len() < crate::adapter::REPLAY_BUFFER_LEN);
+    });
+}
+
+#[test]
+fn spectator_receives_round_broadcasts_but_cannot_buzz() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.update_settings_direct(Some(50), None, None);
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let (_, role) = room
+            .resolve_join_direct("Projector", None, Role::Spectator)
+            .unwrap();
+        assert_eq!(role, Role::Spectator);
+        let bob = player_id_of(&room, "Bob");
+        let projector = player_id_of(&room, "Projector");
+        assert!(projector > core::game::MAX_PLAYER_ID);
+        let (tx, mut rx) = outbound_channel();
+        assert!(room.attach_connection_direct(projector, "Projector", tx));
+        forward_broadcasts(&room, projector);
+
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        next_of_type(&mut rx, "round_started").await;
+        room.send_buzz(projector, None);
+        room.send_buzz(bob, None);
+        let accepted = next_of_type(&mut rx, "accepted").await;
+        assert_eq!(accepted["name"], "Bob");
+        let timed_out = next_of_type(&mut rx, "timed_out").await;
+        assert_eq!(timed_out["name"], "Bob");
+
+        let names: Vec<_> = room
+            .scoreboard()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, ["Aaron", "Bob"]);
+        let participants = room.participants();
+        let spectator = participants.iter().find(|p| p.name == "Projector").unwrap();
+        assert_eq!(spectator.role, Role::Spectator);
+    });
+}
+
+async fn round_state(
+    room: &RoomState,
+    player_id: PlayerId,
+    rx: &mut mpsc::Receiver<Outbound>,
+) -> serde_json::Value {
+    // The view is published once the loop has finished with the last event.
+    room.query_game_view().await.unwrap();
+    room.send_round_state_to(player_id);
+    next_of_type(rx, "round_state").await
+}
+
+#[test]
+fn round_state_tells_anyone_who_answers_and_who_already_tried() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.update_settings_direct(Some(300), None, None);
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        room.resolve_join_direct("Carol", None, Role::Player)
+            .unwrap();
+        room.resolve_join_direct("Projector", None, Role::Spectator)
+            .unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let carol = player_id_of(&room, "Carol");
+        let projector = player_id_of(&room, "Projector");
+        let (tx, mut rx) = outbound_channel();
+        assert!(room.attach_connection_direct(projector, "Projector", tx));
+        forward_broadcasts(&room, projector);
+
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        next_of_type(&mut rx, "round_started").await;
+        room.send_buzz(bob, None);
+        next_of_type(&mut rx, "accepted").await;
+        let state = round_state(&room, projector, &mut rx).await;
+        assert_eq!(state["round"], 1);
+        assert_eq!(state["answering"], "Bob");
+        assert!(state["remaining_ms"].as_u64().unwrap() <= 300);
+        assert_eq!(state["attempted"], serde_json::json!(["Bob"]));
+        assert_eq!(state["locked_out"], serde_json::json!([]));
+
+        next_of_type(&mut rx, "timed_out").await;
+        room.send_buzz(carol, None);
+        next_of_type(&mut rx, "accepted").await;
+        room.continue_round_direct(ADMIN_PLAYER_ID).unwrap();
+        next_of_type(&mut rx, "round_continued").await;
+        let state = round_state(&room, projector, &mut rx).await;
+        assert!(state["answering"].is_null());
+        assert!(state["remaining_ms"].is_null());
+        assert_eq!(state["attempted"], serde_json::json!(["Bob", "Carol"]));
+        assert_eq!(state["locked_out"], serde_json::json!(["Bob", "Carol"]));
+
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        next_of_type(&mut rx, "round_started").await;
+        let state = round_state(&room, projector, &mut rx).await;
+        assert_eq!(state["round"], 2);
+        assert!(state["answering"].is_null());
+        assert_eq!(state["attempted"], serde_json::json!([]));
+        assert_eq!(state["locked_out"], serde_json::json!([]));
+    });
+}
+
+#[test]
+fn spectators_do_not_take_player_slots() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.update_settings_direct(None, Some(1), None);
+        room.resolve_join_direct("Projector", None, Role::Spectator)
+            .unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        assert!(matches!(
+            room.resolve_join_direct("Carol", None, Role::Player),
+            Err(AppError::FullRoom)
+        ));
+        assert!(
+            room.resolve_join_direct("Screen", None, Role::Spectator)
+                .is_ok()
+        );
+        assert!(matches!(
+            room.resolve_join_direct("Eve", None, Role::Admin),
+            Err(AppError::InvalidRole)
+        ));
+    });
+}
+
+#[test]
+fn participants_payload_is_reused_until_membership_changes() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Alice").unwrap();
+        let first = room.participants_payload();
+        assert!(Arc::ptr_eq(&first, &room.participants_payload()));
+
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let joined = room.participants_payload();
+        assert_ne!(first, joined);
+        assert!(joined.contains("\"Bob\""));
+        assert!(Arc::ptr_eq(&joined, &room.participants_payload()));
+
+        let bob = player_id_of(&room, "Bob");
+        room.set_muted_direct(ADMIN_PLAYER_ID, "Bob", true).unwrap();
+        let muted = room.participants_payload();
+        assert_ne!(joined, muted);
+        room.set_ready_direct(bob, true);
+        let ready = room.participants_payload();
+        assert_ne!(muted, ready);
+        room.rename_player(bob, "Robert").unwrap();
+        let renamed = room.participants_payload();
+        assert!(renamed.contains("\"Robert\""));
+        room.kick_by_name_direct(ADMIN_PLAYER_ID, "Robert").unwrap();
+        assert!(!room.participants_payload().contains("\"Robert\""));
+    });
+}
+
+fn connected_flag(participants: &serde_json::Value, name: &str) -> bool {
+    participants["participants"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|p| p["name"] == name)
+        .unwrap()["connected"]
+        .as_bool()
+        .unwrap()
+}
+
+#[test]
+fn connected_flag_follows_attach_and_detach() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+        let first = next_of_type(&mut admin_rx, "participants").await;
+        assert!(connected_flag(&first, "Aaron"));
+        assert!(!connected_flag(&first, "Bob"));
+
+        let (bob_tx, _bob_rx) = outbound_channel();
+        let bob_conn = bob_tx.downgrade();
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        let attached = next_of_type(&mut admin_rx, "participants").await;
+        assert!(connected_flag(&attached, "Bob"));
+
+        room.detach_connection_direct(bob, &bob_conn);
+        let detached = next_of_type(&mut admin_rx, "participants").await;
+        assert!(!connected_flag(&detached, "Bob"));
+        assert!(
+            !room
+                .participants()
+                .iter()
+                .any(|p| p.name == "Bob" && p.connected)
+        );
+    });
+}
+
+#[test]
+fn flapping_connection_broadcasts_participants_a_bounded_number_of_times() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+        next_of_type(&mut admin_rx, "participants").await;
+        // Let the admin's own connect go out before Bob starts flapping.
+        tokio::time::sleep(PARTICIPANTS_DEBOUNCE * 2).await;
+        while admin_rx.try_recv().is_ok() {}
+
+        let started = std::time::Instant::now();
+        for _ in 0..20 {
+            let (bob_tx, _bob_rx) = outbound_channel();
+            let bob_conn = bob_tx.downgrade();
+            assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+            room.detach_connection_direct(bob, &bob_conn);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let windows = started.elapsed().as_millis() / PARTICIPANTS_DEBOUNCE.as_millis() + 1;
+        tokio::time::sleep(PARTICIPANTS_DEBOUNCE * 2).await;
+
+        let mut broadcasts = Vec::new();
+        while let Ok(text) = admin_rx.try_recv() {
+            let msg: serde_json::Value = serde_json::from_str(&text).unwrap();
+            if msg["type"] == "participants" {
+                broadcasts.push(msg);
+            }
+        }
+        // At most one per debounce window the flapping spanned, not one per flap.
+        assert!(
+            (1..=windows as usize).contains(&broadcasts.len()),
+            "{} broadcasts in {windows} windows",
+            broadcasts.len()
+        );
+        assert!(!connected_flag(broadcasts.last().unwrap(), "Bob"));
+    });
+}
+
+#[test]
+fn concurrent_joins_and_kicks_keep_name_maps_consistent() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let room = test_room();
+        room.create_admin("Alice").await.unwrap();
+
+        let mut tasks = Vec::new();
+        for i in 0..40 {
+            let room = Arc::clone(&room);
+            tasks.push(tokio::spawn(async move {
+                let name = format!("Player{}", i % 10);
+                let _ = room.join(&name, None, Role::Player).await;
+                let _ = room.kick_by_name(ADMIN_PLAYER_ID, &name, None).await;
+                let _ = room.join(&name, None, Role::Player).await;
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(room.names_by_id.len(), room.ids_by_name.len());
+        for entry in room.names_by_id.iter() {
+            assert_eq!(player_id_of(&room, entry.value()), *entry.key());
+        }
+        let listed: Vec<_> = room.participants().into_iter().map(|p| p.name).collect();
+        assert_eq!(listed.len(), room.names_by_id.len());
+        assert!(listed.contains(&"Alice".to_string()));
+    });
+}
+
+#[test]
+fn admin_handover_moves_every_admin_right() {
+    block_on(async {
+        let room = test_room();
+        let aaron_token = room.create_admin_direct("Aaron").unwrap();
+        let (bob_token, _) = room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        room.resolve_join_direct("Sam", None, Role::Spectator)
+            .unwrap();
+        let bob = player_id_of(&room, "Bob");
+
+        assert!(matches!(
+            room.transfer_admin_direct(bob, "Aaron"),
+            Err(AppError::Forbidden)
+        ));
+        assert!(matches!(
+            room.transfer_admin_direct(ADMIN_PLAYER_ID, "Sam"),
+            Err(AppError::InvalidRole)
+        ));
+        assert!(matches!(
+            room.transfer_admin_direct(ADMIN_PLAYER_ID, "Nobody"),
+            Err(AppError::UserNotFound)
+        ));
+        room.transfer_admin_direct(ADMIN_PLAYER_ID, " bob ")
+            .unwrap();
+
+        assert!(room.is_admin(bob));
+        assert_eq!(room.role_of(ADMIN_PLAYER_ID), Role::Player);
+        assert_eq!(room.authorize_admin(&bob_token).unwrap(), bob);
+        assert!(matches!(
+            room.authorize_admin(&aaron_token),
+            Err(AppError::Forbidden)
+        ));
+        let (_, claims) = room.refresh_token_direct(&bob_token).unwrap();
+        assert_eq!(claims.role, Role::Admin);
+        assert!(matches!(
+            room.start_round_direct(ADMIN_PLAYER_ID, None, None),
+            Err(AppError::Forbidden)
+        ));
+        room.start_round_direct(bob, None, None).unwrap();
+        room.kick_by_name_direct(bob, "Aaron").unwrap();
+        assert!(room.admin_present());
+    });
+}
+
+#[test]
+fn refresh_slides_the_expiry_forward() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        let (bob_token, _) = room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = room
+            .ids_by_name
+            .get("bob")
+            .map(|entry| *entry.value())
+            .unwrap();
+
+        // About to lapse: the next cleanup would kick Bob.
+        let now = now_seconds();
+        room.token_exp_by_id.insert(bob, now);
+        let (new_token, _) = room.refresh_token_direct(&bob_token).unwrap();
+
+        let claims = room.auth.verify(&new_token, "room01").unwrap();
+        assert!(claims.exp >= now + 60);
+        assert_eq!(*room.token_exp_by_id.get(&bob).unwrap(), claims.exp);
+        room.cleanup_expired();
+        assert!(room.player_matches(bob, "Bob"));
+    });
+}
+
+#[test]
+fn cleanup_spares_connected_players_until_they_disconnect() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (bob_tx, _bob_rx) = outbound_channel();
+        let weak = bob_tx.downgrade();
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+
+        // A lapsed token alone does not cost a live socket its seat.
+        room.token_exp_by_id.insert(bob, now_seconds());
+        room.cleanup_expired();
+        assert!(room.player_matches(bob, "Bob"));
+
+        // Once away, the lapsed token ends the grace for coming back.
+        room.detach_connection_direct(bob, &weak);
+        room.cleanup_expired();
+        assert!(!room.player_matches(bob, "Bob"));
+        assert!(!room.token_exp_by_id.contains_key(&bob));
+    });
+}
+
+#[test]
+fn admin_present_follows_the_connection_then_the_token() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        assert!(room.admin_present());
+
+        let (tx, _rx) = outbound_channel();
+        let weak = tx.downgrade();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", tx));
+        room.token_exp_by_id.insert(ADMIN_PLAYER_ID, now_seconds());
+        assert!(room.admin_present());
+
+        room.detach_connection_direct(ADMIN_PLAYER_ID, &weak);
+        assert!(!room.admin_present());
+    });
+}
+
+#[test]
+fn refresh_is_refused_once_idle_past_the_timeout() {
+    block_on(async {
+        let room = room_with_auth(JwtAuth::new(SECRET, 60, DEFAULT_ISSUER).with_idle_timeout(0));
+        room.create_admin_direct("Aaron").unwrap();
+        let (bob_token, _) = room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = room
+            .ids_by_name
+            .get("bob")
+            .map(|entry| *entry.value())
+            .unwrap();
+
+        assert!(matches!(
+            room.refresh_token_direct(&bob_token),
+            Err(AppError::SessionExpired)
+        ));
+
+        // A live socket keeps the session going however long it lasts.
+        let (bob_tx, _bob_rx) = outbound_channel();
+        let bob_conn = bob_tx.downgrade();
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+        let (bob_token, _) = room.refresh_token_direct(&bob_token).unwrap();
+
+        room.detach_connection_direct(bob, &bob_conn);
+        assert!(matches!(
+            room.refresh_token_direct(&bob_token),
+            Err(AppError::SessionExpired)
+        ));
+    });
+}
+
+#[test]
+fn second_connection_replaces_the_first() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+
+        let (first_tx, mut first_rx) = outbound_channel();
+        let first = first_tx.downgrade();
+        assert!(room.attach_connection_direct(bob, "Bob", first_tx));
+        let (second_tx, mut second_rx) = outbound_channel();
+        let second = second_tx.downgrade();
+        assert!(room.attach_connection_direct(bob, "Bob", second_tx));
+
+        let replaced = next_of_type(&mut first_rx, "replaced").await;
+        assert_eq!(replaced.get("seq"), None);
+        assert_eq!(first_rx.recv().await, None);
+        next_of_type(&mut second_rx, "welcome").await;
+
+        // The displaced socket's late detach leaves its successor attached.
+        room.detach_connection_direct(bob, &first);
+        assert_eq!(room.connection_count(), 1);
+
+        // Resuming from a third tab displaces the second the same way, and the
+        // `replaced` notice is never replayed.
+        let (third_tx, mut third_rx) = outbound_channel();
+        assert!(room.resume_connection_direct(bob, "Bob", third_tx, 0));
+        next_of_type(&mut second_rx, "replaced").await;
+        assert_eq!(second_rx.recv().await, None);
+        let types: Vec<_> = seqs_and_texts(&mut third_rx)
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect();
+        assert!(
+            !types.iter().any(|text| text.contains("replaced")),
+            "{types:?}"
+        );
+        room.detach_connection_direct(bob, &second);
+        assert_eq!(room.connection_count(), 1);
+    });
+}
+
+#[test]
+fn answerer_can_rename_mid_answer_and_keeps_the_point() {
+    block_on(async {
+        let room = test_room();
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Jhon", None, Role::Player)
+            .unwrap();
+        let john = player_id_of(&room, "Jhon");
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        forward_broadcasts(&room, ADMIN_PLAYER_ID);
+        let (john_tx, mut john_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(john, "Jhon", john_tx));
+        forward_broadcasts(&room, john);
+
+        room.start_round_direct(ADMIN_PLAYER_ID, None, None)
+            .unwrap();
+        next_of_type(&mut john_rx, "round_started").await;
+        room.send_buzz(john, None);
+        next_of_type(&mut admin_rx, "accepted").await;
+
+        room.rename(john, "Aaron", None);
+        let denied = next_of_type(&mut john_rx, "action_denied").await;
+        assert_eq!(denied["reason"], "name_taken");
+
+        room.rename(john, "John", None);
+        let renamed = next_of_type(&mut john_rx, "renamed").await;
+        assert_eq!(renamed["old_name"], "Jhon");
+        assert_eq!(renamed["new_name"], "John");
+        let claims = room
+            .auth
+            .verify(renamed["token"].as_str().unwrap(), "room01")
+            .unwrap();
+        assert_eq!((claims.player_id, claims.name.as_str()), (john, "John"));
+        let participants = next_of_type(&mut admin_rx, "participants").await;
+        assert!(participants.to_string().contains("\"John\""));
+
+        // Still the one answering, and the point lands under the new name.
+        assert_eq!(room.query_game_view().await.unwrap().answering, Some(john));
+        room.mark_correct_direct(ADMIN_PLAYER_ID).unwrap();
+        assert_eq!(next_of_type(&mut admin_rx, "correct").await["name"], "John");
+        let scoreboard = next_of_type(&mut admin_rx, "scoreboard").await;
+        assert_eq!(scoreboard["entries"][0]["name"], "John");
+        assert_eq!(scoreboard["entries"][0]["score"], 1);
+        // Only the renamed player is told about their new token.
+        while let Ok(text) = admin_rx.try_recv() {
+            assert!(!text.contains("\"renamed\""), "{}", text.as_str());
+        }
+    });
+}
+
+fn room_with_admin_grace(admin_grace_in_ms: u64) -> Arc<RoomState> {
+    room_with(
+        JwtAuth::new(SECRET, 60, DEFAULT_ISSUER),
+        RoomConfig {
+            admin_grace_in_ms,
+            ..CONFIG
+        },
+    )
+}
+
+#[test]
+fn departed_admin_is_replaced_by_the_longest_connected_player() {
+    block_on(async {
+        let room = room_with_admin_grace(200);
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Sam", None, Role::Spectator)
+            .unwrap();
+        room.resolve_join_direct("Carol", None, Role::Player)
+            .unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        // Sam has been here longest but cannot run the room as a spectator.
+        let mut receivers = Vec::new();
+        for name in ["Sam", "Carol", "Bob"] {
+            let player_id = player_id_of(&room, name);
+            let (tx, rx) = outbound_channel();
+            assert!(room.attach_connection_direct(player_id, name, tx));
+            forward_broadcasts(&room, player_id);
+            receivers.push(rx);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let (admin_tx, _admin_rx) = outbound_channel();
+        let admin_conn = admin_tx.downgrade();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        room.detach_connection_direct(ADMIN_PLAYER_ID, &admin_conn);
+
+        let bob_rx = &mut receivers[2];
+        let changed = next_of_type(bob_rx, "admin_changed").await;
+        assert_eq!(changed["name"], "Carol");
+        assert_eq!(changed["reason"], "admin_disconnected");
+        assert!(room.is_admin(player_id_of(&room, "Carol")));
+
+        // Too late: Aaron is back, but as a player.
+        let (admin_tx, mut admin_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        let welcome = next_of_type(&mut admin_rx, "welcome").await;
+        let aaron = welcome["participants"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|p| p["name"] == "Aaron")
+            .unwrap()
+            .clone();
+        assert_eq!(aaron["role"], "player");
+        assert!(matches!(
+            room.start_round_direct(ADMIN_PLAYER_ID, None, None),
+            Err(AppError::Forbidden)
+        ));
+    });
+}
+
+#[test]
+fn admin_back_within_the_grace_period_keeps_the_room() {
+    block_on(async {
+        let room = room_with_admin_grace(300);
+        room.create_admin_direct("Aaron").unwrap();
+        room.resolve_join_direct("Bob", None, Role::Player).unwrap();
+        let bob = player_id_of(&room, "Bob");
+        let (bob_tx, _bob_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(bob, "Bob", bob_tx));
+
+        let (admin_tx, _admin_rx) = outbound_channel();
+        let admin_conn = admin_tx.downgrade();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+        room.detach_connection_direct(ADMIN_PLAYER_ID, &admin_conn);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let (admin_tx, _admin_rx) = outbound_channel();
+        assert!(room.attach_connection_direct(ADMIN_PLAYER_ID, "Aaron", admin_tx));
+
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        room.replace_departed_admin();
+        assert!(room.is_admin(ADMIN_PLAYER_ID));
+        assert!(!room.is_admin(bob));
+    });
+}
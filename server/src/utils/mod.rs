@@ -1 +1,5 @@
+pub mod name;
+pub mod password;
+#[cfg(test)]
+pub mod testing;
 pub mod time;
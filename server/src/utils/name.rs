@@ -0,0 +1,205 @@
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::errors::AppError;
+
+/// Counted in grapheme clusters, so an accented letter or a flag is one.
+pub const MAX_NAME_CHARS: usize = 32;
+
+/// Cap on the code points in one grapheme cluster, so a few letters stacked
+/// with hundreds of combining marks cannot pass the grapheme count. Leaves room
+/// for subdivision flags (7) and Indic conjuncts such as "क्षि" (4).
+pub const MAX_GRAPHEME_CHARS: usize = 10;
+
+/// Validates player display names: no control or invisible formatting
+/// characters, at most [`MAX_NAME_CHARS`] graphemes of at most
+/// [`MAX_GRAPHEME_CHARS`] code points each after trimming, and nothing from
+/// the blocklist (matched case-insensitively as a substring). Accepted names
+/// come back NFC-normalized, so the same name typed on two keyboards is one
+/// name.
+#[derive(Default)]
+pub struct NameFilter {
+    blocklist: Vec<String>,
+}
+
+impl NameFilter {
+    pub fn new<I, S>(blocklist: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let blocklist = blocklist
+            .into_iter()
+            .map(|word| word.as_ref().trim().to_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect();
+        Self { blocklist }
+    }
+
+    /// Reads a comma-separated blocklist from `NAME_BLOCKLIST` (empty if unset).
+    pub fn from_env() -> Self {
+        let list = std::env::var("NAME_BLOCKLIST").unwrap_or_default();
+        Self::new(list.split(','))
+    }
+
+    /// Returns the trimmed, NFC-normalized name if it is acceptable.
+    pub fn validate(&self, name: &str) -> Result<String, AppError> {
+        let name: String = name.trim().nfc().collect();
+        if name.is_empty() {
+            return Err(AppError::InvalidEmptyName);
+        }
+        let mut graphemes = 0;
+        for grapheme in name.graphemes(true) {
+            graphemes += 1;
+            if graphemes > MAX_NAME_CHARS || grapheme.chars().count() > MAX_GRAPHEME_CHARS {
+                return Err(AppError::InvalidName);
+            }
+        }
+        if name.chars().any(is_forbidden_char) {
+            return Err(AppError::InvalidName);
+        }
+        let lowered = name.to_lowercase();
+        if self.blocklist.iter().any(|word| lowered.contains(word)) {
+            return Err(AppError::InvalidName);
+        }
+        Ok(name)
+    }
+}
+
+/// Key used for name uniqueness: NFC-normalized, trimmed, internal whitespace
+/// collapsed to single spaces, and lowercased, so "Bob", "bob " and " BOB" all
+/// collide, and so do a composed and a decomposed "é".
+pub fn normalize_name(name: &str) -> String {
+    name.nfc()
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn is_forbidden_char(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            // zero-width and directional marks
+            '\u{200B}'..='\u{200F}'
+                // bidi embeddings / overrides
+                | '\u{202A}'..='\u{202E}'
+                // word joiner and invisible operators
+                | '\u{2060}'..='\u{2064}'
+                // bidi isolates
+                | '\u{2066}'..='\u{2069}'
+                // byte order mark / zero-width no-break space
+                | '\u{FEFF}'
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trims_surrounding_whitespace() {
+        let filter = NameFilter::default();
+        assert_eq!(filter.validate("  bob \t").unwrap(), "bob");
+    }
+
+    #[test]
+    fn rejects_blank_names() {
+        let filter = NameFilter::default();
+        assert!(matches!(
+            filter.validate("   "),
+            Err(AppError::InvalidEmptyName)
+        ));
+    }
+
+    #[test]
+    fn enforces_length_after_trim() {
+        let filter = NameFilter::default();
+        let max = "a".repeat(MAX_NAME_CHARS);
+        assert!(filter.validate(&format!("  {max}  ")).is_ok());
+        let too_long = "a".repeat(MAX_NAME_CHARS + 1);
+        assert!(matches!(
+            filter.validate(&too_long),
+            Err(AppError::InvalidName)
+        ));
+        // Length counts what is seen, not bytes or code points.
+        assert!(filter.validate(&"é".repeat(MAX_NAME_CHARS)).is_ok());
+        assert!(filter.validate(&"e\u{301}".repeat(MAX_NAME_CHARS)).is_ok());
+        assert!(filter.validate(&"🇫🇷".repeat(MAX_NAME_CHARS)).is_ok());
+        // England's flag: a black flag, five tag characters and a cancel tag.
+        let england = "\u{1F3F4}\u{E0067}\u{E0062}\u{E0065}\u{E006E}\u{E0067}\u{E007F}";
+        assert!(filter.validate(&england.repeat(MAX_NAME_CHARS)).is_ok());
+        assert!(matches!(
+            filter.validate(&"🇫🇷".repeat(MAX_NAME_CHARS + 1)),
+            Err(AppError::InvalidName)
+        ));
+    }
+
+    #[test]
+    fn rejects_stacked_combining_marks() {
+        let filter = NameFilter::default();
+        // Three graphemes, each buried under a hundred combining marks.
+        let zalgo: String = "bob"
+            .chars()
+            .flat_map(|c| std::iter::once(c).chain(std::iter::repeat_n('\u{336}', 100)))
+            .collect();
+        assert_eq!(zalgo.graphemes(true).count(), 3);
+        assert!(matches!(
+            filter.validate(&zalgo),
+            Err(AppError::InvalidName)
+        ));
+    }
+
+    #[test]
+    fn accepts_full_length_indic_names() {
+        let filter = NameFilter::default();
+        // A conjunct with a vowel sign, 12 bytes but one grapheme.
+        let name = "\u{915}\u{94D}\u{937}\u{93F}".repeat(MAX_NAME_CHARS);
+        assert_eq!(name.graphemes(true).count(), MAX_NAME_CHARS);
+        assert_eq!(filter.validate(&name).unwrap(), name);
+    }
+
+    #[test]
+    fn composes_decomposed_names() {
+        let filter = NameFilter::default();
+        assert_eq!(filter.validate(" Re\u{301}my ").unwrap(), "R\u{e9}my");
+        assert_eq!(normalize_name("RE\u{301}MY"), normalize_name("r\u{e9}my"));
+    }
+
+    #[test]
+    fn rejects_control_and_zero_width_chars() {
+        let filter = NameFilter::default();
+        for name in [
+            "bo\u{0007}b",
+            "bo\nb",
+            "bo\u{200B}b",
+            "\u{202E}bob",
+            "bob\u{FEFF}x",
+            // an emoji sequence held together by a zero-width joiner
+            "\u{1F469}\u{200D}\u{1F52C}",
+        ] {
+            assert!(
+                matches!(filter.validate(name), Err(AppError::InvalidName)),
+                "{name:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn normalizes_case_and_whitespace() {
+        assert_eq!(normalize_name("  Bob   The\tBuilder "), "bob the builder");
+        assert_eq!(normalize_name("BOB"), normalize_name(" bob"));
+    }
+
+    #[test]
+    fn rejects_blocklisted_words_case_insensitively() {
+        let filter = NameFilter::new(["darn", " "]);
+        assert!(matches!(
+            filter.validate("DaRnIt"),
+            Err(AppError::InvalidName)
+        ));
+        assert!(filter.validate("alice").is_ok());
+    }
+}
@@ -0,0 +1,54 @@
+//! Room join passwords, stored only as Argon2id PHC strings.
+
+use argon2::{
+    Argon2,
+    password_hash::{PasswordHasher, PasswordVerifier, phc::PasswordHash},
+};
+
+use crate::errors::AppError;
+
+pub const MAX_PASSWORD_CHARS: usize = 128;
+
+/// Hashes a room password with a fresh random salt.
+pub fn hash_password(password: &str) -> Result<String, AppError> {
+    if password.is_empty() || password.chars().count() > MAX_PASSWORD_CHARS {
+        return Err(AppError::InvalidPassword);
+    }
+    Argon2::default()
+        .hash_password(password.as_bytes())
+        .map(|hash| hash.to_string())
+        .map_err(|_| AppError::Internal)
+}
+
+/// Argon2 compares the derived hashes in constant time.
+pub fn verify_password(hash: &str, password: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|parsed| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_verifies_only_the_original_password() {
+        let hash = hash_password("open sesame").unwrap();
+        assert!(!hash.contains("open sesame"));
+        assert!(verify_password(&hash, "open sesame"));
+        assert!(!verify_password(&hash, "open sesame "));
+        assert!(!verify_password("not a phc string", "open sesame"));
+    }
+
+    #[test]
+    fn empty_or_oversized_passwords_are_rejected() {
+        assert!(matches!(hash_password(""), Err(AppError::InvalidPassword)));
+        let long = "x".repeat(MAX_PASSWORD_CHARS + 1);
+        assert!(matches!(
+            hash_password(&long),
+            Err(AppError::InvalidPassword)
+        ));
+    }
+}
@@ -0,0 +1,59 @@
+//! Helpers shared by the server's unit tests.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use core::game::PlayerId;
+
+use crate::adapter::{DEFAULT_OUTBOUND_CAPACITY, Outbound};
+use crate::state::room_state::RoomState;
+
+/// Drive an async test body to completion. `#[tokio::test]` is unusable here
+/// because the workspace's `core` crate shadows the sysroot `core` that the
+/// macro expansion references. Rooms spawn tasks, so they need a runtime.
+pub fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("build test runtime")
+        .block_on(fut)
+}
+
+/// A socket's outbound channel, as `handle_socket` makes it by default.
+pub fn outbound_channel() -> (mpsc::Sender<Outbound>, mpsc::Receiver<Outbound>) {
+    mpsc::channel(DEFAULT_OUTBOUND_CAPACITY)
+}
+
+/// Skip messages on a player's route until one with the given `type` arrives.
+pub async fn next_of_type(rx: &mut mpsc::Receiver<Outbound>, kind: &str) -> Value {
+    let wait = async {
+        loop {
+            let text = rx.recv().await.expect("route closed");
+            let msg: Value = serde_json::from_str(&text).expect("server message is json");
+            if msg["type"] == kind {
+                return msg;
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(2), wait)
+        .await
+        .unwrap_or_else(|_| panic!("timed out waiting for {kind}"))
+}
+
+/// Does for a test's attached route what the websocket does for a real client:
+/// copies every room broadcast onto it. Call right after attaching.
+pub fn forward_broadcasts(room: &Arc<RoomState>, player_id: PlayerId) {
+    let mut broadcasts = room.subscribe();
+    let room = Arc::downgrade(room);
+    tokio::spawn(async move {
+        while let Ok(broadcast) = broadcasts.recv().await {
+            let Some(room) = room.upgrade() else {
+                break;
+            };
+            room.deliver_broadcast(player_id, &broadcast);
+        }
+    });
+}
@@ -0,0 +1,182 @@
+//! Optional HTTP callbacks for integrators on room lifecycle events.
+//!
+//! Events go onto a bounded queue drained by one background task, so a slow or
+//! dead endpoint never holds up a room: when the queue is full, new events are
+//! dropped with a warning. Each body is signed with HMAC-SHA256 under the shared
+//! secret and the signature sent as `x-buzzer-signature: sha256=<hex>`.
+
+use std::fmt;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::sync::mpsc;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::utils::time::now_millis;
+
+pub const SIGNATURE_HEADER: &str = "x-buzzer-signature";
+/// Events waiting for delivery before new ones are dropped.
+const QUEUE_LEN: usize = 256;
+const MAX_ATTEMPTS: u32 = 4;
+/// Doubled after every failed attempt.
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(500);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct WebhookSettings {
+    /// `http://` or `https://` endpoint that receives a `POST` per event.
+    pub url: String,
+    pub secret: String,
+}
+
+/// Keeps the secret out of logs.
+impl fmt::Debug for WebhookSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebhookSettings")
+            .field("url", &self.url)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    RoomCreated {
+        room_id: String,
+    },
+    /// The host marked an answer correct, ending the round.
+    RoundResolved {
+        room_id: String,
+        round: u64,
+        winner: String,
+        /// The winner's score including this round.
+        score: u32,
+    },
+    RoomClosed {
+        room_id: String,
+        reason: String,
+    },
+}
+
+/// The JSON body of a delivery: the event's fields next to these.
+#[derive(Serialize)]
+struct Delivery<'a> {
+    /// Unchanged across retries, so receivers can drop duplicates.
+    id: String,
+    ts_ms: u64,
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+}
+
+#[derive(Clone)]
+pub struct Webhook {
+    tx: mpsc::Sender<WebhookEvent>,
+}
+
+impl Webhook {
+    /// Starts the delivery task; call from within the runtime.
+    pub fn spawn(settings: WebhookSettings) -> Self {
+        let (tx, rx) = mpsc::channel(QUEUE_LEN);
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("build webhook client");
+        tokio::spawn(deliver_all(client, settings, rx));
+        Self { tx }
+    }
+
+    pub fn send(&self, event: WebhookEvent) {
+        if let Err(err) = self.tx.try_send(event) {
+            warn!("Dropping webhook event, delivery is behind: {err}");
+        }
+    }
+
+    pub fn for_room(&self, room_id: &str) -> RoomWebhook {
+        RoomWebhook {
+            webhook: self.clone(),
+            room_id: room_id.to_string(),
+        }
+    }
+}
+
+/// A [`Webhook`] for the events of one room's game loop.
+#[derive(Clone)]
+pub struct RoomWebhook {
+    webhook: Webhook,
+    room_id: String,
+}
+
+impl RoomWebhook {
+    pub fn round_resolved(&self, round: u64, winner: String, score: u32) {
+        self.webhook.send(WebhookEvent::RoundResolved {
+            room_id: self.room_id.clone(),
+            round,
+            winner,
+            score,
+        });
+    }
+}
+
+async fn deliver_all(
+    client: reqwest::Client,
+    settings: WebhookSettings,
+    mut rx: mpsc::Receiver<WebhookEvent>,
+) {
+    while let Some(event) = rx.recv().await {
+        let delivery = Delivery {
+            id: Uuid::new_v4().to_string(),
+            ts_ms: now_millis(),
+            event: &event,
+        };
+        let body = serde_json::to_vec(&delivery).expect("webhook events serialize");
+        deliver(&client, &settings, body).await;
+    }
+}
+
+async fn deliver(client: &reqwest::Client, settings: &WebhookSettings, body: Vec<u8>) {
+    let signature = sign(settings.secret.as_bytes(), &body);
+    let mut delay = FIRST_RETRY_DELAY;
+    for attempt in 1..=MAX_ATTEMPTS {
+        let sent = client
+            .post(&settings.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .body(body.clone())
+            .send()
+            .await;
+        match sent {
+            Ok(resp) if resp.status().is_success() => return,
+            // The receiver refused this event; sending it again won't help.
+            Ok(resp)
+                if resp.status().is_client_error()
+                    && resp.status() != reqwest::StatusCode::TOO_MANY_REQUESTS =>
+            {
+                warn!("Webhook rejected with {}", resp.status());
+                return;
+            }
+            Ok(resp) => warn!("Webhook attempt {attempt} failed with {}", resp.status()),
+            Err(err) => warn!("Webhook attempt {attempt} failed: {err}"),
+        }
+        if attempt < MAX_ATTEMPTS {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+    warn!("Giving up on webhook event after {MAX_ATTEMPTS} attempts");
+}
+
+/// `sha256=<hex HMAC of body>`, as sent in [`SIGNATURE_HEADER`].
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes keys of any size");
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("sha256={hex}")
+}
@@ -16,17 +16,31 @@ type ParticipantInfo = {
     name: string
     role: Role
     locked_out: boolean
+    color: number
+    connected: boolean
 }
 
 type ServerMessage =
-    | { type: 'accepted'; name: string }
-    | { type: 'rejected' }
+    | { type: 'accepted'; name: string; deadline_ms: number; remaining_ms: number }
+    | { type: 'rejected'; id?: string }
     | { type: 'timed_out'; name: string }
     | { type: 'round_started' }
     | { type: 'round_continued' }
     | { type: 'participants'; participants: ParticipantInfo[] }
-    | { type: 'action_denied'; reason: string }
+    | {
+          type: 'welcome'
+          you: ParticipantInfo
+          participants: ParticipantInfo[]
+          game: {
+              phase: 'idle' | 'countdown' | 'answering' | 'resolved'
+              answering: string | null
+          }
+      }
+    | { type: 'action_denied'; reason: string; id?: string }
+    | { type: 'action_ok'; id: string }
+    | { type: 'protocol_error'; detail: string; supported_versions: number[] }
     | { type: 'kicked' }
+    | { type: 'replaced' }
 
 type Notice = {
     id: string
@@ -34,8 +48,33 @@ type Notice = {
     tone?: 'ok' | 'warn' | 'bad'
 }
 
-const REFRESH_THRESHOLD_SECS = 60 * 60
-const REFRESH_CHECK_INTERVAL_SECS = 15 * 60
+// Indexed by the server's per-player `color`; keep its length in sync with PLAYER_COLORS.
+const PLAYER_COLORS = [
+    '#f94144',
+    '#f3722c',
+    '#f8961e',
+    '#f9c74f',
+    '#90be6d',
+    '#43aa8b',
+    '#4d908e',
+    '#577590',
+    '#277da1',
+    '#9b5de5',
+    '#f15bb5',
+    '#00bbf9',
+]
+
+function participantColor(color: number): string {
+    return PLAYER_COLORS[color % PLAYER_COLORS.length]
+}
+
+// Greys out players whose socket dropped; they keep their seat until they return.
+function connectionClass(connected: boolean): string | undefined {
+    return connected ? undefined : 'disconnected'
+}
+
+const REFRESH_THRESHOLD_SECS = 10 * 60
+const REFRESH_CHECK_INTERVAL_SECS = 60
 
 // WebSocket reconnect backoff: 0.5s, 1s, 2s, 4s, 8s, capped at 10s, plus jitter.
 // This is what keeps a flaky connection (or a server hiccup) from turning into a
@@ -83,9 +122,9 @@ function App() {
     const [view, setView] = useState<'landing' | 'room'>('landing')
     const [error, setError] = useState<string | null>(null)
     const [retryDeadline, setRetryDeadline] = useState<number | null>(null)
-    const [wsState, setWsState] = useState<'disconnected' | 'connecting' | 'connected'>(
-        'disconnected'
-    )
+    const [wsState, setWsState] = useState<
+        'disconnected' | 'connecting' | 'connected' | 'replaced'
+    >('disconnected')
     const [result, setResult] = useState<'idle' | 'won' | 'lost' | 'rejected'>('idle')
     const [winnerName, setWinnerName] = useState('')
     const [myName, setMyName] = useState('')
@@ -112,6 +151,8 @@ function App() {
     const soundBoardRef = useRef<ReturnType<typeof useSoundBoard> | null>(null)
     const reconnectAttemptsRef = useRef(0)
     const reconnectTimerRef = useRef<number | null>(null)
+    // Last `seq` seen on the current socket; each socket starts counting at 0.
+    const lastSeqRef = useRef<number | null>(null)
     const answeringPlayerRef = useRef<string | null>(null)
     const myNameRef = useRef('')
     const createRoomMutation = useCreateRoom()
@@ -129,7 +170,7 @@ function App() {
 
     useEffect(() => {
         const params = new URLSearchParams(window.location.search)
-        const roomParam = params.get('room')
+        const roomParam = params.get('room')?.trim().toUpperCase()
         const activeRoomId = getActiveRoomId()
 
         if (roomParam) {
@@ -277,6 +318,9 @@ function App() {
             if (nextToken !== currentToken) {
                 setToken(nextToken)
                 persistAuth(roomId, nextToken)
+                if (wsRef.current?.readyState === WebSocket.OPEN) {
+                    wsRef.current.send(JSON.stringify({ type: 'update_token', token: nextToken }))
+                }
             }
             return nextToken
         } catch (error) {
@@ -359,6 +403,8 @@ function App() {
         try {
             const data = await joinRoomMutation.mutateAsync({ roomId, name })
             const nextRole = data.role
+            // The server answers with the canonical code ("abc123 " -> "ABC123").
+            setRoomId(data.room_id)
             setRole(nextRole)
             setToken(data.token)
             setAnswerWindowInMs(String(data.answer_window_in_ms))
@@ -366,7 +412,7 @@ function App() {
             if (name.trim()) {
                 setMyName(name.trim())
             }
-            persistAuth(roomId, data.token ?? undefined, name, nextRole)
+            persistAuth(data.room_id, data.token ?? undefined, name, nextRole)
         } catch (err) {
             if (err instanceof ApiError && err.status === 429 && err.retryAfter) {
                 setRetryDeadline(Date.now() + err.retryAfter * 1000)
@@ -403,6 +449,7 @@ function App() {
         const wsUrl = `${window.location.origin.replace('http', 'ws')}/ws/${roomId}?token=${encodeURIComponent(freshToken)}`
         const ws = new WebSocket(wsUrl)
         wsRef.current = ws
+        lastSeqRef.current = null
 
         ws.onopen = () => {
             reconnectAttemptsRef.current = 0
@@ -414,8 +461,29 @@ function App() {
         }
         ws.onmessage = (event) => {
             try {
-                const msg = JSON.parse(event.data) as ServerMessage
+                const msg = JSON.parse(event.data) as ServerMessage & { seq?: number }
+                if (msg.seq !== undefined) {
+                    // A jump means messages went missing; ask for the room again.
+                    const lastSeq = lastSeqRef.current
+                    lastSeqRef.current = msg.seq
+                    if (lastSeq !== null && msg.seq > lastSeq + 1) {
+                        ws.send(JSON.stringify({ type: 'resync', last_seq: lastSeq }))
+                    }
+                }
                 switch (msg.type) {
+                    case 'welcome': {
+                        // Restores an answer already in flight, without replaying its effects.
+                        const answering = msg.game.answering
+                        setParticipants(msg.participants)
+                        setAnsweringPlayer(answering)
+                        setWinnerName(answering ?? '')
+                        setRoundLocked(msg.game.phase !== 'idle')
+                        setHasBuzzedThisRound(msg.you.locked_out || answering === msg.you.name)
+                        if (answering) {
+                            setResult(answering === msg.you.name ? 'won' : 'lost')
+                        }
+                        break
+                    }
                     case 'accepted':
                         setRoundLocked(true)
                         setWinnerName(msg.name)
@@ -480,23 +548,41 @@ function App() {
                         break
                     }
                     case 'action_denied':
+                    case 'action_ok':
+                        break
+                    case 'protocol_error':
+                        // Only a client bug sends unreadable messages; log it for whoever is debugging.
+                        console.warn(`Server could not read a message: ${msg.detail}`)
                         break
                     case 'kicked':
                         showNotice('You were kicked from the room.', 'bad', 6000)
                         setResult('idle')
                         resetSession()
                         break
+                    case 'replaced':
+                        // Another tab took over this seat. Reconnecting would just
+                        // take it back, so stay offline until the user reloads.
+                        wsRef.current = null
+                        setWsState('replaced')
+                        showNotice('This room is open in another tab.', 'warn', 6000)
+                        break
                     default:
                 }
             } catch {
                 // ignore
             }
         }
-        ws.onclose = () => {
+        ws.onclose = (event) => {
             // Only react to the socket we currently own; a stale socket closing
             // after we've already moved on must not disturb a newer connection.
             if (wsRef.current !== ws) return
             wsRef.current = null
+            if (event.code === 4403 || event.code === 4410) {
+                // Kicked, otherwise removed, or the room closed; the server won't
+                // take this session back, so there is nothing to reconnect to.
+                resetSession()
+                return
+            }
             reconnectAttemptsRef.current += 1
             setWsState('disconnected')
             // The reconnect effect below schedules the next attempt with backoff.
@@ -553,7 +639,7 @@ function App() {
 
     useEffect(() => {
         if (view !== 'room') return
-        if (wsState === 'connected' || wsState === 'connecting') return
+        if (wsState !== 'disconnected') return
         const attempt = reconnectAttemptsRef.current
         const delay =
             attempt === 0
@@ -576,7 +662,7 @@ function App() {
         if (view !== 'room' || !token || !roomId) return
         const interval = setInterval(() => {
             void refreshTokenIfNeeded(token, roomId)
-        }, REFRESH_CHECK_INTERVAL_SECS)
+        }, REFRESH_CHECK_INTERVAL_SECS * 1000)
         return () => clearInterval(interval)
     }, [view, token, roomId])
 
@@ -911,8 +997,18 @@ function App() {
                                 <ul>
                                     {participants.length === 0 && <li className="muted">No players yet.</li>}
                                     {participants.map((participant) => (
-                                        <li key={participant.name}>
+                                        <li
+                                            key={participant.name}
+                                            className={connectionClass(participant.connected)}
+                                        >
                                             <div className="participant-info">
+                                                <span
+                                                    className="participant-color"
+                                                    style={{
+                                                        background: participantColor(participant.color),
+                                                    }}
+                                                    aria-hidden="true"
+                                                />
                                                 <span>{participant.name}</span>
                                                 {participant.locked_out && (
                                                     <span className="badge">Locked</span>
@@ -14,7 +14,8 @@ export function useJoinRoom() {
     return useMutation({
         mutationKey: qk.rooms.joinMutation,
         mutationFn: async (payload: { roomId: string; name: string }) => {
-            const token = getStoredToken(payload.roomId)
+            // Stored under the canonical code, whatever case the user typed.
+            const token = getStoredToken(payload.roomId.trim().toUpperCase())
             return roomsApi.joinRoom({
                 roomId: payload.roomId,
                 name: payload.name,
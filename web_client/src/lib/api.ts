@@ -7,6 +7,7 @@ export type CreateRoomResponse = {
 }
 
 export type JoinRoomResponse = {
+    room_id: string
     token: string | null
     answer_window_in_ms: number
     role: Role